@@ -0,0 +1,309 @@
+mod tests;
+
+use crate::compiler::Compiler;
+use crate::diagnostics::Diagnostic;
+use crate::evaluator::environment::Environment;
+use crate::evaluator::eval;
+use crate::evaluator::macros::Macros;
+use crate::module::{LoadError, Loader};
+use crate::object::builtins::Builtins;
+use crate::object::Object;
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+use crate::span::Sources;
+use crate::symtab::SymbolTable;
+use crate::vm::{VirtualMachine, VmError, GLOBAL_SIZE};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+
+pub const USAGE: &str = "usage:
+    rust-monkey [options]                        start the REPL (or run stdin if it is not a terminal)
+    rust-monkey [options] run <file> [args...]   run a script, '-' reads it from stdin
+    rust-monkey [options] -e <code> [args...]    run <code> and print its value
+    rust-monkey --help                           show this message
+
+options:
+    --engine=<vm|eval>   run on the bytecode VM (default) or the tree-walking evaluator
+    --lib=<dir>          look for imported modules in <dir> too; may be repeated
+
+script arguments are available to the program as the array 'args'";
+
+/// The name under which script arguments are visible to Monkey code.
+const ARGS_NAME: &str = "args";
+
+/// Which interpreter programs are run with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Engine {
+    /// The tree-walking evaluator.
+    Eval,
+    /// The bytecode compiler and virtual machine.
+    #[default]
+    Vm,
+}
+
+impl TryFrom<&str> for Engine {
+    type Error = UsageError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "eval" => Ok(Engine::Eval),
+            "vm" => Ok(Engine::Vm),
+            _ => Err(UsageError::UnknownEngine(name.to_string())),
+        }
+    }
+}
+
+/// The parsed command line.
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub engine: Engine,
+    /// The directories modules are looked for in, after the importing file's.
+    pub lib: Vec<PathBuf>,
+    pub command: Command,
+}
+
+/// What the command line asked for.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Repl,
+    Help,
+    Run { source: Source, args: Vec<String> },
+}
+
+/// Where the program to run comes from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    File(String),
+    Expression(String),
+    Stdin,
+}
+
+impl Source {
+    /// The name used for the source in diagnostics.
+    pub fn name(&self) -> &str {
+        match self {
+            Source::File(path) => path,
+            Source::Expression(_) => "<expression>",
+            Source::Stdin => "<stdin>",
+        }
+    }
+
+    /// The file the source is read from, if it is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) => Some(Path::new(path)),
+            Source::Expression(_) | Source::Stdin => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UsageError {
+    MissingExpression,
+    MissingEngine,
+    UnknownEngine(String),
+    MissingLib,
+    UnknownOption(String),
+    UnknownCommand(String),
+}
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsageError::MissingExpression => write!(f, "'-e' needs code to run"),
+            UsageError::MissingEngine => write!(f, "'--engine' needs 'vm' or 'eval'"),
+            UsageError::UnknownEngine(name) => {
+                write!(f, "unknown engine '{name}', expected 'vm' or 'eval'")
+            }
+            UsageError::MissingLib => write!(f, "'--lib' needs a directory"),
+            UsageError::UnknownOption(option) => write!(f, "unknown option '{option}'"),
+            UsageError::UnknownCommand(command) => write!(f, "unknown command '{command}'"),
+        }
+    }
+}
+
+/// Why a run failed. Each class of failure exits with its own status.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    Runtime = 1,
+    Usage = 2,
+    Io = 3,
+    Syntax = 4,
+    Compile = 5,
+    Macro = 6,
+    Import = 7,
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+/// Parses the command line arguments, excluding the program name. Options must
+/// come before the command, as everything after a script belongs to the script.
+/// With no command a program piped into stdin is run rather than starting the
+/// REPL.
+pub fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    stdin_is_terminal: bool,
+) -> Result<Options, UsageError> {
+    let mut args = args.into_iter().peekable();
+
+    let mut engine = Engine::default();
+    let mut lib = vec![];
+    while let Some(arg) =
+        args.next_if(|arg| arg.starts_with("--engine") || arg.starts_with("--lib"))
+    {
+        if let Some(name) = arg.strip_prefix("--engine") {
+            engine = match name {
+                "" => Engine::try_from(args.next().ok_or(UsageError::MissingEngine)?.as_str())?,
+                _ if name.starts_with('=') => Engine::try_from(&name[1..])?,
+                _ => return Err(UsageError::UnknownOption(arg)),
+            };
+        } else if let Some(dir) = arg.strip_prefix("--lib") {
+            lib.push(match dir {
+                "" => PathBuf::from(args.next().ok_or(UsageError::MissingLib)?),
+                _ if dir.starts_with('=') => PathBuf::from(&dir[1..]),
+                _ => return Err(UsageError::UnknownOption(arg)),
+            });
+        }
+    }
+
+    let command = parse_command(args, stdin_is_terminal)?;
+    Ok(Options {
+        engine,
+        lib,
+        command,
+    })
+}
+
+fn parse_command(
+    mut args: impl Iterator<Item = String>,
+    stdin_is_terminal: bool,
+) -> Result<Command, UsageError> {
+    let source = match args.next() {
+        None if stdin_is_terminal => return Ok(Command::Repl),
+        None => Source::Stdin,
+        Some(arg) => match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-e" => Source::Expression(args.next().ok_or(UsageError::MissingExpression)?),
+            "run" => match args.next() {
+                None => Source::Stdin,
+                Some(path) if path == "-" => Source::Stdin,
+                Some(path) => Source::File(path),
+            },
+            _ if arg.starts_with('-') => return Err(UsageError::UnknownOption(arg)),
+            _ => return Err(UsageError::UnknownCommand(arg)),
+        },
+    };
+
+    Ok(Command::Run {
+        source,
+        args: args.collect(),
+    })
+}
+
+/// Runs `input`, read from `source`, on `engine`, with `args` bound to the
+/// global `args`, modules looked for in the directories of `lib`, and the
+/// builtins of `builtins`, which macros call too. Errors are reported on
+/// stderr as diagnostics. Returns the value of the last expression statement,
+/// if there was one.
+pub fn run(
+    source: &Source,
+    input: &str,
+    args: &[String],
+    engine: Engine,
+    lib: &[PathBuf],
+    builtins: Rc<Builtins>,
+) -> Result<Option<Rc<Object>>, Failure> {
+    let mut loader = Loader::new(source.name(), input, lib.to_vec(), Rc::clone(&builtins));
+    let sources = &loader.sources;
+    let program = Parser::parse_program(input).map_err(|errors| {
+        errors
+            .iter()
+            .for_each(|e| eprint!("{}", Diagnostic::from(e).render_in(sources)));
+        Failure::Syntax
+    })?;
+    let program = Macros::new_with_builtins(Rc::clone(&builtins))
+        .expand(program)
+        .map_err(|e| {
+            eprint!("{}", Diagnostic::from(&e).render_in(sources));
+            Failure::Macro
+        })?;
+    let program = loader.load(program, source.path()).map_err(|e| {
+        eprint!("{}", e.render(&loader.sources));
+        match e {
+            LoadError::Syntax(_) => Failure::Syntax,
+            LoadError::Macro(_) => Failure::Macro,
+            LoadError::Import(_) => Failure::Import,
+        }
+    })?;
+
+    let args = Rc::new(Object::Array(
+        args.iter()
+            .map(|arg| Rc::new(Object::String(arg.clone())))
+            .collect(),
+    ));
+
+    match engine {
+        Engine::Eval => run_eval(&loader.sources, program, args, builtins),
+        Engine::Vm => run_vm(&loader.sources, program, args, builtins),
+    }
+}
+
+fn run_eval(
+    sources: &Sources,
+    program: Program,
+    args: Rc<Object>,
+    builtins: Rc<Builtins>,
+) -> Result<Option<Rc<Object>>, Failure> {
+    let env = Rc::new(RefCell::new(Environment::new_with_builtins(builtins)));
+    env.borrow_mut().set(ARGS_NAME, args);
+
+    match eval(program, env) {
+        Ok(object) => Ok(Some(object)),
+        Err(e) => {
+            eprint!("{}", Diagnostic::from(&e).render_in(sources));
+            Err(Failure::Runtime)
+        }
+    }
+}
+
+fn run_vm(
+    sources: &Sources,
+    program: Program,
+    args: Rc<Object>,
+    builtins: Rc<Builtins>,
+) -> Result<Option<Rc<Object>>, Failure> {
+    let mut symtab = SymbolTable::new();
+    symtab.define_builtins(&builtins);
+    let args_symbol = symtab.define(ARGS_NAME.to_string());
+    let null = Rc::new(Object::Null);
+    let mut globals = vec![null; GLOBAL_SIZE];
+    globals[args_symbol.index as usize] = args;
+
+    let mut compiler = Compiler::new_with_state(symtab, vec![], Rc::clone(&builtins));
+    let byte_code = compiler.compile(program).map_err(|e| {
+        eprint!("{}", Diagnostic::from(&e).render_in(sources));
+        Failure::Compile
+    })?;
+    compiler
+        .warnings
+        .iter()
+        .for_each(|w| eprint!("{}", Diagnostic::from(w).render_in(sources)));
+
+    let mut vm = VirtualMachine::new_with_global_state(byte_code, globals, builtins);
+    match vm.run() {
+        Ok(object) => Ok(Some(object)),
+        // the program ended without an expression statement to take a value from
+        Err(e) if e.error == VmError::EmptyStack => Ok(None),
+        Err(e) => {
+            eprint!("{}", Diagnostic::from(&e).render_in(sources));
+            Err(Failure::Runtime)
+        }
+    }
+}
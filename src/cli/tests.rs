@@ -0,0 +1,317 @@
+#![cfg(test)]
+
+use crate::cli::{parse_args, run, Command, Engine, Failure, Options, Source, UsageError};
+use crate::module::TestDir;
+use crate::object::builtins::{capture_output, Arity, Builtins};
+use crate::object::Object;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn builtins() -> Rc<Builtins> {
+    Rc::new(Builtins::new())
+}
+
+fn test_source() -> Source {
+    Source::File("test.mk".to_string())
+}
+
+fn parse_command(input: &[&str], stdin_is_terminal: bool) -> Result<Command, UsageError> {
+    parse_args(args(input), stdin_is_terminal).map(|options| options.command)
+}
+
+#[test]
+fn test_parse_args_no_arguments_starts_repl() {
+    assert_eq!(parse_command(&[], true), Ok(Command::Repl));
+}
+
+#[test]
+fn test_parse_args_no_arguments_runs_piped_stdin() {
+    let expected = Command::Run {
+        source: Source::Stdin,
+        args: vec![],
+    };
+    assert_eq!(parse_command(&[], false), Ok(expected));
+}
+
+#[test]
+fn test_parse_args_run_file() {
+    let expected = Command::Run {
+        source: Source::File("file.mk".to_string()),
+        args: args(&["a", "-b"]),
+    };
+    assert_eq!(
+        parse_command(&["run", "file.mk", "a", "-b"], true),
+        Ok(expected)
+    );
+}
+
+#[test]
+fn test_parse_args_run_stdin() {
+    let expected = Command::Run {
+        source: Source::Stdin,
+        args: args(&["a"]),
+    };
+    assert_eq!(parse_command(&["run", "-", "a"], true), Ok(expected));
+}
+
+#[test]
+fn test_parse_args_expression() {
+    let expected = Command::Run {
+        source: Source::Expression("1 + 2".to_string()),
+        args: vec![],
+    };
+    assert_eq!(parse_command(&["-e", "1 + 2"], true), Ok(expected));
+}
+
+#[test]
+fn test_parse_args_errors() {
+    assert_eq!(
+        parse_command(&["-e"], true),
+        Err(UsageError::MissingExpression)
+    );
+    assert_eq!(
+        parse_command(&["--fast"], true),
+        Err(UsageError::UnknownOption("--fast".to_string()))
+    );
+    assert_eq!(
+        parse_command(&["file.mk"], true),
+        Err(UsageError::UnknownCommand("file.mk".to_string()))
+    );
+}
+
+#[test]
+fn test_run_reads_script_args() {
+    let input = "len(args); args[1]";
+    let result = run(
+        &test_source(),
+        input,
+        &args(&["one", "two"]),
+        Engine::Vm,
+        &[],
+        builtins(),
+    );
+    assert_eq!(result, Ok(Some(Rc::new(Object::String("two".to_string())))));
+}
+
+#[test]
+fn test_run_without_value() {
+    let input = "let x = 1;";
+    assert_eq!(
+        run(&test_source(), input, &[], Engine::Vm, &[], builtins()),
+        Ok(None)
+    );
+}
+
+#[test]
+fn test_run_failure_classes() {
+    assert_eq!(
+        run(&test_source(), "let = 1;", &[], Engine::Vm, &[], builtins()),
+        Err(Failure::Syntax)
+    );
+    assert_eq!(
+        run(&test_source(), "x = 1;", &[], Engine::Vm, &[], builtins()),
+        Err(Failure::Compile)
+    );
+    assert_eq!(
+        run(&test_source(), "1 + true", &[], Engine::Vm, &[], builtins()),
+        Err(Failure::Runtime)
+    );
+    assert_eq!(
+        run(
+            &test_source(),
+            "quote(1)",
+            &[],
+            Engine::Eval,
+            &[],
+            builtins()
+        ),
+        Err(Failure::Macro)
+    );
+}
+
+#[test]
+fn test_parse_args_engine() {
+    let expected = Options {
+        engine: Engine::Eval,
+        lib: vec![],
+        command: Command::Run {
+            source: Source::File("file.mk".to_string()),
+            args: args(&["--engine=vm"]),
+        },
+    };
+    let input = args(&["--engine=eval", "run", "file.mk", "--engine=vm"]);
+    assert_eq!(parse_args(input, true), Ok(expected));
+
+    let expected = Options {
+        engine: Engine::Eval,
+        lib: vec![],
+        command: Command::Repl,
+    };
+    assert_eq!(parse_args(args(&["--engine", "eval"]), true), Ok(expected));
+}
+
+#[test]
+fn test_parse_args_engine_defaults_to_vm() {
+    let options = parse_args(args(&[]), true).unwrap();
+    assert_eq!(options.engine, Engine::Vm);
+}
+
+#[test]
+fn test_parse_args_engine_errors() {
+    assert_eq!(
+        parse_command(&["--engine=jit"], true),
+        Err(UsageError::UnknownEngine("jit".to_string()))
+    );
+    assert_eq!(
+        parse_command(&["--engine"], true),
+        Err(UsageError::MissingEngine)
+    );
+    assert_eq!(
+        parse_command(&["--engines"], true),
+        Err(UsageError::UnknownOption("--engines".to_string()))
+    );
+}
+
+#[test]
+fn test_run_with_evaluator() {
+    let input = "let x = 2; x * len(args)";
+    let result = run(
+        &test_source(),
+        input,
+        &args(&["a", "b"]),
+        Engine::Eval,
+        &[],
+        builtins(),
+    );
+    assert_eq!(result, Ok(Some(Rc::new(Object::Integer(4)))));
+}
+
+#[test]
+fn test_run_failure_classes_with_evaluator() {
+    assert_eq!(
+        run(
+            &test_source(),
+            "let = 1;",
+            &[],
+            Engine::Eval,
+            &[],
+            builtins()
+        ),
+        Err(Failure::Syntax)
+    );
+    assert_eq!(
+        run(
+            &test_source(),
+            "1 + true",
+            &[],
+            Engine::Eval,
+            &[],
+            builtins()
+        ),
+        Err(Failure::Runtime)
+    );
+}
+
+#[test]
+fn test_run_imports_modules() {
+    let dir = TestDir::new(
+        "run",
+        &[
+            (
+                "lib/counter.mk",
+                "import \"step.mk\" as step;
+                 puts(\"loaded\");
+                 let count = 0;
+                 export let next = fn() { count += step[\"size\"]; count };
+                 if (count == 0) { return 1; }
+                 export let unreachable = 1;",
+            ),
+            ("lib/step.mk", "export let size = 2; let count = 100;"),
+        ],
+    );
+    let input = "import \"lib/counter.mk\" as a;
+                 let count = 10;
+                 import \"counter.mk\" as b;
+                 [a[\"next\"](), b[\"next\"](), count, a[\"unreachable\"]]";
+    let lib = [dir.0.join("lib")];
+    let source = Source::File(dir.0.join("main.mk").display().to_string());
+    for engine in [Engine::Vm, Engine::Eval] {
+        let (result, output) =
+            capture_output(|| run(&source, input, &[], engine, &lib, builtins()));
+        let expected = Object::Array(vec![
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(4)),
+            Rc::new(Object::Integer(10)),
+            Rc::new(Object::Null),
+        ]);
+        assert_eq!(result, Ok(Some(Rc::new(expected))), "{engine:?}");
+        assert_eq!(output, "loaded\n", "{engine:?}");
+    }
+}
+
+#[test]
+fn test_run_with_host_builtins() {
+    // the host's builtins are the ones macros call as they are expanded, in
+    // the program and in the modules it imports, as well as the program's
+    let dir = TestDir::new(
+        "host",
+        &[(
+            "lib.mk",
+            "let answer = macro() { quote(unquote(version())) };
+             export let version = answer();",
+        )],
+    );
+    let mut builtins = Builtins::new();
+    builtins
+        .register("version", Arity::Exactly(0), |_| {
+            Ok(Rc::new(Object::Integer(7)))
+        })
+        .unwrap();
+    let builtins = Rc::new(builtins);
+    let input = "import \"lib.mk\" as lib;
+                 let twice = macro() { let v = version(); quote(unquote(v) * 2) };
+                 [lib[\"version\"], twice(), version()]";
+    let source = Source::File(dir.0.join("main.mk").display().to_string());
+    for engine in [Engine::Vm, Engine::Eval] {
+        let result = run(&source, input, &[], engine, &[], Rc::clone(&builtins));
+        let expected = Object::Array(vec![
+            Rc::new(Object::Integer(7)),
+            Rc::new(Object::Integer(14)),
+            Rc::new(Object::Integer(7)),
+        ]);
+        assert_eq!(result, Ok(Some(Rc::new(expected))), "{engine:?}");
+    }
+}
+
+#[test]
+fn test_run_import_failures() {
+    let dir = TestDir::new(
+        "failures",
+        &[
+            ("bad.mk", "let = 1;"),
+            ("cycle.mk", "import \"cycle.mk\" as c;"),
+        ],
+    );
+    let source = Source::File(dir.0.join("main.mk").display().to_string());
+    for engine in [Engine::Vm, Engine::Eval] {
+        let failure = |input| run(&source, input, &[], engine, &[], builtins());
+        assert_eq!(failure("import \"missing.mk\" as m;"), Err(Failure::Import));
+        assert_eq!(failure("import \"cycle.mk\" as c;"), Err(Failure::Import));
+        assert_eq!(failure("import \"bad.mk\" as b;"), Err(Failure::Syntax));
+    }
+}
+
+#[test]
+fn test_parse_args_lib() {
+    let options = parse_args(args(&["--lib=a", "--lib", "b", "--engine=eval"]), true).unwrap();
+    assert_eq!(options.lib, vec![PathBuf::from("a"), PathBuf::from("b")]);
+    assert_eq!(parse_command(&["--lib"], true), Err(UsageError::MissingLib));
+    assert_eq!(
+        parse_command(&["--library"], true),
+        Err(UsageError::UnknownOption("--library".to_string()))
+    );
+}
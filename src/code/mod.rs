@@ -1,11 +1,42 @@
 mod tests;
 
+use crate::span::Span;
 use std::fmt::{Display, Formatter};
 
 pub const WORD_SIZE: usize = 4;
 
 pub type Instructions = Vec<u8>;
 
+/// Maps instruction addresses to the span of the statement they were compiled
+/// from. Each entry covers the instructions from its address up to the next
+/// entry's address.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineTable(Vec<(usize, Span)>);
+
+impl LineTable {
+    pub fn add(&mut self, address: usize, span: Span) {
+        // entries at or after `address` describe instructions that have since
+        // been overwritten or removed
+        while let Some((last, _)) = self.0.last() {
+            if *last >= address {
+                self.0.pop();
+            } else {
+                break;
+            }
+        }
+        self.0.push((address, span));
+    }
+
+    pub fn lookup(&self, address: usize) -> Option<Span> {
+        let idx = self.0.partition_point(|(start, _)| *start <= address);
+        if idx == 0 {
+            None
+        } else {
+            Some(self.0[idx - 1].1)
+        }
+    }
+}
+
 #[derive(Debug, PartialOrd, PartialEq)]
 pub enum OpCode {
     Constant = 0,
@@ -38,8 +69,39 @@ pub enum OpCode {
     Closure,
     GetFree,
     CurrentClosure,
-    And,
-    Or,
+    SetFree,
+    CaptureLocal,
+    CaptureFree,
+    LessThan,
+    GreaterEqual,
+    LessEqual,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Stringify,
+    Range,
+    IterStart,
+    IterNext,
+    GetIndexPath,
+    SetIndexPath,
+    UnpackArray,
+    UnpackHash,
+    JumpIfArgGiven,
+    Match,
+    NoMatch,
+    SetupTry,
+    PopTry,
+    Throw,
+    /// A call whose value the function returns as soon as it is made, which
+    /// reuses the caller's frame.
+    TailCall,
+    /// Raises the error a `finally` block was run for again once it ends,
+    /// from where the error was first raised.
+    Rethrow,
 }
 
 impl Display for OpCode {
@@ -78,8 +140,35 @@ impl Display for OpCode {
                 OpCode::Closure => "OpClosure",
                 OpCode::GetFree => "OpGetFree",
                 OpCode::CurrentClosure => "OpCurrentClosure",
-                OpCode::And => "OpAnd",
-                OpCode::Or => "OpOr",
+                OpCode::SetFree => "OpSetFree",
+                OpCode::CaptureLocal => "OpCaptureLocal",
+                OpCode::CaptureFree => "OpCaptureFree",
+                OpCode::LessThan => "OpLessThan",
+                OpCode::GreaterEqual => "OpGreaterEqual",
+                OpCode::LessEqual => "OpLessEqual",
+                OpCode::Modulo => "OpModulo",
+                OpCode::Power => "OpPower",
+                OpCode::BitAnd => "OpBitAnd",
+                OpCode::BitOr => "OpBitOr",
+                OpCode::BitXor => "OpBitXor",
+                OpCode::ShiftLeft => "OpShiftLeft",
+                OpCode::ShiftRight => "OpShiftRight",
+                OpCode::Stringify => "OpStringify",
+                OpCode::Range => "OpRange",
+                OpCode::IterStart => "OpIterStart",
+                OpCode::IterNext => "OpIterNext",
+                OpCode::GetIndexPath => "OpGetIndexPath",
+                OpCode::SetIndexPath => "OpSetIndexPath",
+                OpCode::UnpackArray => "OpUnpackArray",
+                OpCode::UnpackHash => "OpUnpackHash",
+                OpCode::JumpIfArgGiven => "OpJumpIfArgGiven",
+                OpCode::Match => "OpMatch",
+                OpCode::NoMatch => "OpNoMatch",
+                OpCode::SetupTry => "OpSetupTry",
+                OpCode::PopTry => "OpPopTry",
+                OpCode::Throw => "OpThrow",
+                OpCode::TailCall => "OpTailCall",
+                OpCode::Rethrow => "OpRethrow",
             }
         )
     }
@@ -120,8 +209,35 @@ impl TryFrom<u8> for OpCode {
             0x1b => Ok(OpCode::Closure),
             0x1c => Ok(OpCode::GetFree),
             0x1d => Ok(OpCode::CurrentClosure),
-            0x1e => Ok(OpCode::And),
-            0x1f => Ok(OpCode::Or),
+            0x1e => Ok(OpCode::SetFree),
+            0x1f => Ok(OpCode::CaptureLocal),
+            0x20 => Ok(OpCode::CaptureFree),
+            0x21 => Ok(OpCode::LessThan),
+            0x22 => Ok(OpCode::GreaterEqual),
+            0x23 => Ok(OpCode::LessEqual),
+            0x24 => Ok(OpCode::Modulo),
+            0x25 => Ok(OpCode::Power),
+            0x26 => Ok(OpCode::BitAnd),
+            0x27 => Ok(OpCode::BitOr),
+            0x28 => Ok(OpCode::BitXor),
+            0x29 => Ok(OpCode::ShiftLeft),
+            0x2a => Ok(OpCode::ShiftRight),
+            0x2b => Ok(OpCode::Stringify),
+            0x2c => Ok(OpCode::Range),
+            0x2d => Ok(OpCode::IterStart),
+            0x2e => Ok(OpCode::IterNext),
+            0x2f => Ok(OpCode::GetIndexPath),
+            0x30 => Ok(OpCode::SetIndexPath),
+            0x31 => Ok(OpCode::UnpackArray),
+            0x32 => Ok(OpCode::UnpackHash),
+            0x33 => Ok(OpCode::JumpIfArgGiven),
+            0x34 => Ok(OpCode::Match),
+            0x35 => Ok(OpCode::NoMatch),
+            0x36 => Ok(OpCode::SetupTry),
+            0x37 => Ok(OpCode::PopTry),
+            0x38 => Ok(OpCode::Throw),
+            0x39 => Ok(OpCode::TailCall),
+            0x3a => Ok(OpCode::Rethrow),
             _ => Err("Invalid OpCode"),
         }
     }
@@ -160,8 +276,35 @@ impl From<OpCode> for u8 {
             OpCode::Closure => 0x1b,
             OpCode::GetFree => 0x1c,
             OpCode::CurrentClosure => 0x1d,
-            OpCode::And => 0x1e,
-            OpCode::Or => 0x1f,
+            OpCode::SetFree => 0x1e,
+            OpCode::CaptureLocal => 0x1f,
+            OpCode::CaptureFree => 0x20,
+            OpCode::LessThan => 0x21,
+            OpCode::GreaterEqual => 0x22,
+            OpCode::LessEqual => 0x23,
+            OpCode::Modulo => 0x24,
+            OpCode::Power => 0x25,
+            OpCode::BitAnd => 0x26,
+            OpCode::BitOr => 0x27,
+            OpCode::BitXor => 0x28,
+            OpCode::ShiftLeft => 0x29,
+            OpCode::ShiftRight => 0x2a,
+            OpCode::Stringify => 0x2b,
+            OpCode::Range => 0x2c,
+            OpCode::IterStart => 0x2d,
+            OpCode::IterNext => 0x2e,
+            OpCode::GetIndexPath => 0x2f,
+            OpCode::SetIndexPath => 0x30,
+            OpCode::UnpackArray => 0x31,
+            OpCode::UnpackHash => 0x32,
+            OpCode::JumpIfArgGiven => 0x33,
+            OpCode::Match => 0x34,
+            OpCode::NoMatch => 0x35,
+            OpCode::SetupTry => 0x36,
+            OpCode::PopTry => 0x37,
+            OpCode::Throw => 0x38,
+            OpCode::TailCall => 0x39,
+            OpCode::Rethrow => 0x3a,
         }
     }
 }
@@ -169,7 +312,16 @@ impl From<OpCode> for u8 {
 pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
     let mut instruction = [0x00; 4];
     match op {
-        OpCode::Closure => {
+        // the jump target when the iterator is exhausted, and the number of
+        // loop variables; the number of elements, and whether there is a rest;
+        // the jump target when the parameter is given an argument, and its index;
+        // the handler's position, and whether it runs a `finally` block and
+        // raises the error again
+        OpCode::Closure
+        | OpCode::IterNext
+        | OpCode::UnpackArray
+        | OpCode::JumpIfArgGiven
+        | OpCode::SetupTry => {
             instruction[0] = u8::from(op);
             let operand = (operands[0] as u16).to_be_bytes();
             instruction[1] = operand[0];
@@ -179,8 +331,14 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         OpCode::SetLocal
         | OpCode::GetLocal
         | OpCode::Call
+        | OpCode::TailCall
         | OpCode::GetBuiltin
-        | OpCode::GetFree => {
+        | OpCode::GetFree
+        | OpCode::SetFree
+        | OpCode::CaptureLocal
+        | OpCode::CaptureFree
+        | OpCode::GetIndexPath
+        | OpCode::SetIndexPath => {
             instruction[0] = u8::from(op);
             instruction[1] = operands[0] as u8;
         }
@@ -190,7 +348,9 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         | OpCode::SetGlobal
         | OpCode::GetGlobal
         | OpCode::Array
-        | OpCode::Hash => {
+        | OpCode::Hash
+        | OpCode::UnpackHash
+        | OpCode::Match => {
             instruction[0] = u8::from(op);
             let operand = (operands[0] as u16).to_be_bytes();
             instruction[1] = operand[0];
@@ -206,29 +366,47 @@ pub fn make(op: OpCode, operands: &[u32]) -> [u8; 4] {
         | OpCode::Equal
         | OpCode::NotEqual
         | OpCode::GreaterThan
+        | OpCode::LessThan
+        | OpCode::GreaterEqual
+        | OpCode::LessEqual
+        | OpCode::Modulo
+        | OpCode::Power
+        | OpCode::BitAnd
+        | OpCode::BitOr
+        | OpCode::BitXor
+        | OpCode::ShiftLeft
+        | OpCode::ShiftRight
+        | OpCode::Stringify
+        | OpCode::Range
+        | OpCode::IterStart
+        | OpCode::NoMatch
+        | OpCode::PopTry
+        | OpCode::Throw
+        | OpCode::Rethrow
         | OpCode::Minus
         | OpCode::Bang
         | OpCode::Null
         | OpCode::Index
         | OpCode::ReturnValue
         | OpCode::Return
-        | OpCode::CurrentClosure
-        | OpCode::And
-        | OpCode::Or => {
+        | OpCode::CurrentClosure => {
             instruction[0] = u8::from(op);
         }
     }
     instruction
 }
 
-#[allow(unused)]
 pub fn disassemble(instructions: &Instructions) -> String {
     let mut assembly = String::from("");
     let mut address: u32 = 0;
     instructions.chunks_exact(WORD_SIZE).for_each(|word| {
         let op: OpCode = OpCode::try_from(word[0]).expect("Invalid OpCode");
         match op {
-            OpCode::Closure => {
+            OpCode::Closure
+            | OpCode::IterNext
+            | OpCode::UnpackArray
+            | OpCode::JumpIfArgGiven
+            | OpCode::SetupTry => {
                 let operand = read_u16(&word[1..=2]);
                 assembly.push_str(&format!(
                     "{:04x} {} {} {}\n",
@@ -238,8 +416,14 @@ pub fn disassemble(instructions: &Instructions) -> String {
             OpCode::SetLocal
             | OpCode::GetLocal
             | OpCode::Call
+            | OpCode::TailCall
             | OpCode::GetBuiltin
-            | OpCode::GetFree => {
+            | OpCode::GetFree
+            | OpCode::SetFree
+            | OpCode::CaptureLocal
+            | OpCode::CaptureFree
+            | OpCode::GetIndexPath
+            | OpCode::SetIndexPath => {
                 assembly.push_str(&format!("{:04x} {} {}\n", address, op, &word[1]))
             }
             OpCode::Constant
@@ -248,7 +432,9 @@ pub fn disassemble(instructions: &Instructions) -> String {
             | OpCode::SetGlobal
             | OpCode::GetGlobal
             | OpCode::Array
-            | OpCode::Hash => {
+            | OpCode::Hash
+            | OpCode::UnpackHash
+            | OpCode::Match => {
                 let operand = read_u16(&word[1..=2]);
                 assembly.push_str(&format!("{:04x} {} {}\n", address, op, operand))
             }
@@ -262,15 +448,30 @@ pub fn disassemble(instructions: &Instructions) -> String {
             | OpCode::Equal
             | OpCode::NotEqual
             | OpCode::GreaterThan
+            | OpCode::LessThan
+            | OpCode::GreaterEqual
+            | OpCode::LessEqual
+            | OpCode::Modulo
+            | OpCode::Power
+            | OpCode::BitAnd
+            | OpCode::BitOr
+            | OpCode::BitXor
+            | OpCode::ShiftLeft
+            | OpCode::ShiftRight
+            | OpCode::Stringify
+            | OpCode::Range
+            | OpCode::IterStart
+            | OpCode::NoMatch
+            | OpCode::PopTry
+            | OpCode::Throw
+            | OpCode::Rethrow
             | OpCode::Minus
             | OpCode::Bang
             | OpCode::Null
             | OpCode::Index
             | OpCode::ReturnValue
             | OpCode::Return
-            | OpCode::CurrentClosure
-            | OpCode::And
-            | OpCode::Or => assembly.push_str(&format!("{:04x} {}\n", address, op)),
+            | OpCode::CurrentClosure => assembly.push_str(&format!("{:04x} {}\n", address, op)),
         }
         address += 4;
     });
@@ -1,6 +1,7 @@
 #![cfg(test)]
 
-use crate::code::{disassemble, make, Instructions, OpCode};
+use crate::code::{disassemble, make, Instructions, LineTable, OpCode};
+use crate::span::{Position, Span};
 
 #[test]
 fn test_make_op_constant() {
@@ -243,17 +244,73 @@ fn test_make_op_current_closure() {
 }
 
 #[test]
-fn test_make_op_and() {
-    let (op, operands) = (OpCode::And, []);
-    let expected: [u8; 4] = [0x1e, 0x00, 0x00, 0x00];
+fn test_make_op_set_free() {
+    let (op, operands) = (OpCode::SetFree, [0xFE]);
+    let expected: [u8; 4] = [0x1e, 0xFE, 0x00, 0x00];
     let result = make(op, &operands);
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_make_op_or() {
-    let (op, operands) = (OpCode::Or, []);
-    let expected: [u8; 4] = [0x1f, 0x00, 0x00, 0x00];
+fn test_make_op_capture_local() {
+    let (op, operands) = (OpCode::CaptureLocal, [0xFE]);
+    let expected: [u8; 4] = [0x1f, 0xFE, 0x00, 0x00];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_capture_free() {
+    let (op, operands) = (OpCode::CaptureFree, [0xFE]);
+    let expected: [u8; 4] = [0x20, 0xFE, 0x00, 0x00];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_iter_next() {
+    let (op, operands) = (OpCode::IterNext, [0xFFFE, 0x02]);
+    let expected: [u8; 4] = [0x2e, 0xFF, 0xFE, 0x02];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_unpack_array() {
+    let (op, operands) = (OpCode::UnpackArray, [0x0102, 0x01]);
+    let expected: [u8; 4] = [0x31, 0x01, 0x02, 0x01];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_jump_if_arg_given() {
+    let (op, operands) = (OpCode::JumpIfArgGiven, [0x0010, 0x03]);
+    let expected: [u8; 4] = [0x33, 0x00, 0x10, 0x03];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_setup_try() {
+    let (op, operands) = (OpCode::SetupTry, [0x0102, 0x01]);
+    let expected: [u8; 4] = [0x36, 0x01, 0x02, 0x01];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_match() {
+    let (op, operands) = (OpCode::Match, [0x0102]);
+    let expected: [u8; 4] = [0x34, 0x01, 0x02, 0x00];
+    let result = make(op, &operands);
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_make_op_tail_call() {
+    let (op, operands) = (OpCode::TailCall, [0xFE]);
+    let expected: [u8; 4] = [0x39, 0xFE, 0x00, 0x00];
     let result = make(op, &operands);
     assert_eq!(result, expected);
 }
@@ -269,7 +326,8 @@ fn test_disassemble() {
         0x00, 0x12, 0xFF, 0xFE, 0x00, 0x13, 0xFF, 0xFE, 0x00, 0x14, 0x00, 0x00, 0x00, 0x15, 0xFE,
         0x00, 0x00, 0x16, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00, 0x18, 0xFE, 0x00, 0x00, 0x19,
         0xFE, 0x00, 0x00, 0x1a, 0xFE, 0x00, 0x00, 0x1b, 0xFF, 0xFE, 0xFE, 0x1c, 0xFE, 0x00, 0x00,
-        0x1d, 0x00, 0x00, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+        0x1d, 0x00, 0x00, 0x00, 0x1e, 0xFE, 0x00, 0x00, 0x1f, 0xFE, 0x00, 0x00, 0x20, 0xFE, 0x00,
+        0x00,
     ];
     let expected = String::from(
         "\
@@ -304,10 +362,29 @@ fn test_disassemble() {
 0070 OpClosure 65534 254\n\
 0074 OpGetFree 254\n\
 0078 OpCurrentClosure\n\
-007c OpAnd\n\
-0080 OpOr\n\
+007c OpSetFree 254\n\
+0080 OpCaptureLocal 254\n\
+0084 OpCaptureFree 254\n\
 ",
     );
     let result = disassemble(&input);
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_line_table_lookup() {
+    let span = |line| Span::new(Position::new(line, 1), Position::new(line, 2));
+    let mut lines = LineTable::default();
+    lines.add(0, span(1));
+    lines.add(8, span(2));
+    lines.add(16, span(3));
+    // an entry at an earlier address replaces everything after it
+    lines.add(12, span(4));
+
+    let line_at = |address| lines.lookup(address).map(|span| span.start.line);
+    assert_eq!(line_at(0), Some(1));
+    assert_eq!(line_at(4), Some(1));
+    assert_eq!(line_at(8), Some(2));
+    assert_eq!(line_at(12), Some(4));
+    assert_eq!(line_at(16), Some(4));
+}
@@ -1,47 +1,121 @@
-use crate::code::{make, Instructions, OpCode, WORD_SIZE};
-use crate::object::{CompiledFunction, Object};
-use crate::parser::ast::{Expression, Infix, Prefix, Program, Statement};
+use crate::code::{make, read_u16, Instructions, LineTable, OpCode, WORD_SIZE};
+use crate::evaluator::macros::source_name;
+use crate::object::builtins::Builtins;
+use crate::object::{CompiledFunction, MatchPattern, Object};
+use crate::parser::ast::{Expression, Import, Infix, MatchArm, Module, Prefix, Program, Statement};
+use crate::span::{Located, Span};
 use crate::symtab::{Symbol, SymbolScope, SymbolTable};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
 mod tests;
 
+/// The most arguments a call can pass, as `OpCall` has a one-byte operand.
+const MAX_ARGUMENTS: usize = u8::MAX as usize;
+/// The most locals a function can have, which are numbered from 0 by a byte.
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+/// The most free variables a function can use, as `OpClosure` counts them in
+/// a byte.
+const MAX_FREE_VARIABLES: usize = u8::MAX as usize;
+
 #[derive(Debug, PartialEq)]
-pub struct ByteCode(pub Instructions, pub Vec<Rc<Object>>);
+pub struct ByteCode(pub Instructions, pub Vec<Rc<Object>>, pub LineTable);
 
 #[derive(Debug, PartialEq)]
 pub struct Compiler {
     pub constants: Vec<Rc<Object>>,
     pub symbol_table: SymbolTable,
     scopes: Vec<Instructions>,
+    lines: Vec<LineTable>,
     scope_idx: usize,
+    // the loops around the code being compiled, within the innermost function
+    loops: Vec<Loop>,
+    // the `try` blocks around the code being compiled, within the innermost
+    // function
+    tries: Vec<TryBlock>,
+    // span of the statement currently being compiled
+    span: Span,
+    /// Problems found in the program that do not stop it from compiling.
+    pub warnings: Vec<Located<CompilerWarning>>,
+    /// The global each module compiled so far keeps its exports in, by the
+    /// number of the module's source.
+    pub modules: HashMap<usize, u32>,
+    // the addresses of the jumps made by the top-level `return`s of the module
+    // being compiled, if it is a module, patched once its end is known
+    module_returns: Option<Vec<u32>>,
+    // the builtins defined in the global scope of each module
+    builtins: Rc<Builtins>,
+}
+
+/// A loop being compiled, for `break` and `continue` to jump out of.
+#[derive(Debug, PartialEq)]
+struct Loop {
+    // where `continue` jumps to
+    start: u32,
+    // the addresses of the jumps made by `break`, patched once the end of the
+    // loop is known
+    breaks: Vec<u32>,
+    // whether an iterator is kept on the stack while the body runs
+    has_iterator: bool,
+}
+
+/// A `try` block, or a `catch` block followed by `finally`, being compiled.
+/// `return`, `break` and `continue` leaving it remove its handler and run the
+/// `finally` block first.
+#[derive(Debug, PartialEq)]
+struct TryBlock {
+    finally: Option<Statement>,
+    // the number of loops around the block, within the innermost function
+    loop_depth: usize,
 }
 
 impl Compiler {
     pub fn new() -> Self {
+        Self::new_with_builtins(Rc::new(Builtins::new()))
+    }
+
+    /// A compiler for programs calling the builtins of `builtins`, which the
+    /// VM running them must be given too.
+    pub fn new_with_builtins(builtins: Rc<Builtins>) -> Self {
         let mut symbol_table = SymbolTable::new();
-        symbol_table.define_all_builtins();
+        symbol_table.define_builtins(&builtins);
         Compiler {
             constants: vec![],
             symbol_table,
             scopes: vec![Instructions::new()],
+            lines: vec![LineTable::default()],
             scope_idx: 0,
+            loops: vec![],
+            tries: vec![],
+            span: Span::default(),
+            warnings: vec![],
+            modules: HashMap::new(),
+            module_returns: None,
+            builtins,
         }
     }
 
-    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Rc<Object>>) -> Self {
-        let mut compiler = Self::new();
+    pub fn new_with_state(
+        symbol_table: SymbolTable,
+        constants: Vec<Rc<Object>>,
+        builtins: Rc<Builtins>,
+    ) -> Self {
+        let mut compiler = Self::new_with_builtins(builtins);
         compiler.symbol_table = symbol_table;
         compiler.constants = constants;
         compiler
     }
 
-    pub fn compile(&mut self, program: Program) -> Result<ByteCode, CompilerError> {
+    pub fn compile(&mut self, program: Program) -> Result<ByteCode, Located<CompilerError>> {
         let Program(statements) = program;
-        self.compile_statements(&statements)?;
+        // on error `span` is left at the innermost statement being compiled
+        self.compile_statements(&statements)
+            .map_err(|e| Located::new(e, self.span))?;
         Ok(ByteCode(
             self.scopes[self.scope_idx].clone(),
             self.constants.clone(),
+            self.lines[self.scope_idx].clone(),
         ))
     }
 
@@ -53,31 +127,318 @@ impl Compiler {
     }
 
     fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompilerError> {
+        let outer_span = self.span;
+        self.set_span(statement.span());
         match statement {
-            Statement::Let(id, val) => self.compile_let(id, val)?,
-            Statement::Return(val) => {
+            Statement::Let(id, val, _) => self.compile_let(id, val)?,
+            Statement::Return(val, _) if self.scope_idx == 0 && self.module_returns.is_some() => {
+                self.compile_module_return(val)?
+            }
+            Statement::Return(val, _) => {
                 self.compile_expression(val)?;
+                self.compile_leave_tries(0)?;
                 self.emit(OpCode::ReturnValue, &[]);
             }
-            Statement::Expression(expression) => {
+            Statement::Expression(expression, _) => {
                 self.compile_expression(expression)?;
                 self.emit(OpCode::Pop, &[]);
             }
-            Statement::BlockStatement(statements) => self.compile_block_statement(statements)?,
-            Statement::Assignment(_, _) => todo!(),
+            Statement::BlockStatement(statements, _) => self.compile_block_statement(statements)?,
+            Statement::Assignment(target, val, _) => self.compile_assignment(target, None, val)?,
+            Statement::CompoundAssignment(target, infix, val, _) => {
+                self.compile_assignment(target, Some(infix), val)?
+            }
+            Statement::Break(_) => self.compile_break()?,
+            Statement::Continue(_) => self.compile_continue()?,
+            Statement::Throw(val, _) => {
+                self.compile_expression(val)?;
+                self.emit(OpCode::Throw, &[]);
+            }
+            Statement::Import(import, _) => self.compile_import(import)?,
+            Statement::Export(statement, _) => self.compile_statement(statement)?,
+        }
+        // any instructions emitted after a nested statement belong to the
+        // enclosing one again
+        self.set_span(outer_span);
+        Ok(())
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+        let address = self.scopes[self.scope_idx].len();
+        self.lines[self.scope_idx].add(address, span);
+    }
+
+    /// Emits an instruction that can raise an error, so that the error is
+    /// reported at the expression `span` rather than at the whole statement.
+    fn emit_at(&mut self, span: Span, op: OpCode, operands: &[u32]) {
+        let outer_span = self.span;
+        self.set_span(span);
+        self.emit(op, operands);
+        self.set_span(outer_span);
+    }
+
+    /// Binds the exports of the module `import` loaded. A module is compiled
+    /// where it is first imported, with globals of its own, and leaves its
+    /// exports in a global that later imports of it read.
+    fn compile_import(&mut self, import: &Import) -> Result<(), CompilerError> {
+        let module = import
+            .module
+            .as_ref()
+            .expect("modules are loaded before the program is compiled");
+        let exports = match self.modules.get(&module.source) {
+            Some(&exports) => exports,
+            None => self.compile_module(module)?,
+        };
+        self.emit(OpCode::GetGlobal, &[exports]);
+        let symbol = self.symbol_table.define(import.name.clone());
+        self.store_symbol(symbol)
+    }
+
+    /// Compiles `module` in a global scope of its own, whose globals follow
+    /// the importer's, and stores a hash of its exports in a global with a
+    /// name no variable can have. Returns the index of that global.
+    fn compile_module(&mut self, module: &Module) -> Result<u32, CompilerError> {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.define_builtins(&self.builtins);
+        symbol_table.num_definitions = self.symbol_table.num_definitions;
+        let importer = std::mem::replace(&mut self.symbol_table, symbol_table);
+        let outer_returns = self.module_returns.replace(vec![]);
+
+        let Program(statements) = &module.program;
+        let compiled = self.compile_statements(statements).and_then(|()| {
+            let end = self.scopes[self.scope_idx].len() as u32;
+            let returns = self.module_returns.take().unwrap_or_default();
+            for pos in returns {
+                self.change_operand(pos as usize, end)?;
+            }
+            for name in &module.exports {
+                let key = self.add_constant(Object::String(name.clone()));
+                self.emit(OpCode::Constant, &[key]);
+                let symbol = self.symbol_table.resolve(name.clone());
+                self.load_symbol(symbol.expect("an export is defined by its 'let'"));
+            }
+            self.emit(OpCode::Hash, &[(module.exports.len() * 2) as u32]);
+            Ok(())
+        });
+
+        let module_table = std::mem::replace(&mut self.symbol_table, importer);
+        self.module_returns = outer_returns;
+        compiled?;
+
+        self.symbol_table.num_definitions = module_table.num_definitions;
+        let exports = self
+            .symbol_table
+            .define(format!("module {}", module.source));
+        self.store_symbol(Rc::clone(&exports))?;
+        self.modules.insert(module.source, exports.index);
+        Ok(exports.index)
+    }
+
+    /// Compiles a `return` at the top level of a module, which ends the module
+    /// rather than the program. The value returned is discarded.
+    fn compile_module_return(&mut self, val: &Expression) -> Result<(), CompilerError> {
+        self.compile_expression(val)?;
+        self.compile_leave_tries(0)?;
+        self.emit(OpCode::Pop, &[]);
+        let iterators = self.loops.iter().filter(|l| l.has_iterator).count();
+        for _ in 0..iterators {
+            self.emit(OpCode::Pop, &[]);
         }
+        let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
+        self.module_returns
+            .as_mut()
+            .expect("a module is being compiled")
+            .push(jump_pos);
         Ok(())
     }
 
     fn compile_let(&mut self, id: &Expression, val: &Expression) -> Result<(), CompilerError> {
         if let Expression::Identifier(id) = id {
+            // defined first, so that a function can call itself
             let symbol = self.symbol_table.define(id.to_string());
             self.compile_expression(val)?;
-            match symbol.scope {
-                SymbolScope::Global => self.emit(OpCode::SetGlobal, &[symbol.index]),
-                SymbolScope::Local => self.emit(OpCode::SetLocal, &[symbol.index]),
-                _ => todo!(),
-            };
+            self.store_symbol(symbol)?;
+        } else {
+            self.compile_expression(val)?;
+            self.compile_pattern(id)?;
+        }
+        Ok(())
+    }
+
+    /// Defines the parameters, in the order of the arguments' slots, and emits
+    /// what runs on entry: each default is stored if its parameter has no
+    /// argument, then the argument is unpacked if matched against a pattern.
+    fn compile_parameters(&mut self, parameters: &[Expression]) -> Result<(), CompilerError> {
+        // an argument matched against a pattern is kept in a slot with a name
+        // no variable can have
+        let slots = parameters
+            .iter()
+            .enumerate()
+            .map(|(idx, parameter)| match parameter.parameter_parts().0 {
+                Expression::Identifier(id) | Expression::Rest(id) => {
+                    self.symbol_table.define(id.clone())
+                }
+                _ => self.symbol_table.define(idx.to_string()),
+            })
+            .collect::<Vec<_>>();
+
+        for (idx, (parameter, slot)) in parameters.iter().zip(slots).enumerate() {
+            let (pattern, default) = parameter.parameter_parts();
+            if let Some(default) = default {
+                let jump_pos = self.emit(OpCode::JumpIfArgGiven, &[9999, idx as u32]);
+                self.compile_expression(default)?;
+                self.store_symbol(Rc::clone(&slot))?;
+                let after_default_pos = self.scopes[self.scope_idx].len() as u32;
+                self.replace_instruction(
+                    jump_pos as usize,
+                    &make(OpCode::JumpIfArgGiven, &[after_default_pos, idx as u32]),
+                )?;
+            }
+            if matches!(pattern, Expression::Array(_) | Expression::Hash(_)) {
+                self.load_symbol(slot);
+                self.compile_pattern(pattern)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds the variables of `pattern` to the value on top of the stack,
+    /// which is consumed. Arrays and hashes are unpacked onto the stack with
+    /// their first part on top, so that the patterns of the parts are bound
+    /// in order.
+    fn compile_pattern(&mut self, pattern: &Expression) -> Result<(), CompilerError> {
+        match pattern {
+            Expression::Identifier(id) | Expression::Rest(id) => {
+                let symbol = self.symbol_table.define(id.to_string());
+                self.store_symbol(symbol)?;
+            }
+            Expression::Array(patterns) => {
+                let has_rest = matches!(patterns.last(), Some(Expression::Rest(_)));
+                let count = patterns.len() - has_rest as usize;
+                self.emit(OpCode::UnpackArray, &[count as u32, has_rest as u32]);
+                for pattern in patterns {
+                    self.compile_pattern(pattern)?;
+                }
+            }
+            Expression::Hash(pairs) => {
+                for (key, _) in pairs {
+                    self.compile_expression(key)?;
+                }
+                self.emit(OpCode::UnpackHash, &[pairs.len() as u32]);
+                for (_, pattern) in pairs {
+                    self.compile_pattern(pattern)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn compile_assignment(
+        &mut self,
+        target: &Expression,
+        infix: Option<&Infix>,
+        val: &Expression,
+    ) -> Result<(), CompilerError> {
+        let Some((id, indices)) = target.as_place() else {
+            return Ok(());
+        };
+        let symbol = match self.symbol_table.resolve(id.to_string()) {
+            Some(symbol) => symbol,
+            None => {
+                return Err(CompilerError::UndefinedVariable(
+                    source_name(id).to_string(),
+                ))
+            }
+        };
+
+        // an element is assigned to by building an updated copy of the
+        // variable's collection, from the collection and the indices of the
+        // element, which are left on the stack to read the current value from
+        let depth = indices.len() as u32;
+        if infix.is_some() || depth > 0 {
+            self.load_symbol(Rc::clone(&symbol));
+        }
+        for index in indices {
+            self.compile_expression(index)?;
+        }
+        if infix.is_some() && depth > 0 {
+            self.emit(OpCode::GetIndexPath, &[depth]);
+        }
+        self.compile_expression(val)?;
+        if let Some(infix) = infix {
+            self.emit(infix_op_code(infix), &[]);
+        }
+        if depth > 0 {
+            self.emit(OpCode::SetIndexPath, &[depth]);
+        }
+        self.store_symbol(symbol)
+    }
+
+    fn compile_break(&mut self) -> Result<(), CompilerError> {
+        self.compile_leave_tries(self.tries_outside_loop())?;
+        // the value of a loop ended by `break` is Null
+        let has_iterator = self.innermost_loop().has_iterator;
+        if has_iterator {
+            self.emit(OpCode::Pop, &[]);
+        }
+        self.emit(OpCode::Null, &[]);
+        let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
+        self.innermost_loop().breaks.push(jump_pos);
+        Ok(())
+    }
+
+    fn compile_continue(&mut self) -> Result<(), CompilerError> {
+        self.compile_leave_tries(self.tries_outside_loop())?;
+        // the skipped iteration's value is Null
+        let start = self.innermost_loop().start;
+        self.emit(OpCode::Null, &[]);
+        self.emit(OpCode::Jump, &[start]);
+        Ok(())
+    }
+
+    /// The number of `try` blocks around the innermost loop.
+    fn tries_outside_loop(&self) -> usize {
+        self.tries
+            .iter()
+            .take_while(|block| block.loop_depth < self.loops.len())
+            .count()
+    }
+
+    /// Emits what leaving all but the outermost `depth` of the `try` blocks
+    /// being compiled runs: from the innermost out, each block's handler is
+    /// removed and its `finally` block, if it has one, run.
+    fn compile_leave_tries(&mut self, depth: usize) -> Result<(), CompilerError> {
+        // each `finally` block is compiled again where it runs normally, which
+        // reports any warnings in it
+        let num_warnings = self.warnings.len();
+        let mut left = vec![];
+        while self.tries.len() > depth {
+            let block = self.tries.pop().expect("there are more than depth blocks");
+            self.emit(OpCode::PopTry, &[]);
+            if let Some(finally) = &block.finally {
+                self.compile_statement(finally)?;
+            }
+            left.push(block);
+        }
+        self.tries.extend(left.into_iter().rev());
+        self.warnings.truncate(num_warnings);
+        Ok(())
+    }
+
+    fn innermost_loop(&mut self) -> &mut Loop {
+        self.loops
+            .last_mut()
+            .expect("the parser only allows 'break' and 'continue' inside a loop")
+    }
+
+    /// Ends the innermost loop, whose `break`s jump to the current address.
+    fn end_loop(&mut self) -> Result<(), CompilerError> {
+        let after_loop_pos = self.scopes[self.scope_idx].len() as u32;
+        let Loop { breaks, .. } = self.loops.pop().expect("a loop is being compiled");
+        for pos in breaks {
+            self.change_operand(pos as usize, after_loop_pos)?;
         }
         Ok(())
     }
@@ -94,13 +455,24 @@ impl Compiler {
             Expression::Identifier(id) => match self.symbol_table.resolve(id.to_string()) {
                 Some(binding) => self.load_symbol(binding),
                 None => {
-                    return Err(CompilerError::UndefinedVariable);
+                    return Err(CompilerError::UndefinedVariable(
+                        source_name(id).to_string(),
+                    ));
                 }
             },
             Expression::Integer(integer) => self.compile_integer_expression(*integer)?,
-            Expression::Prefix(prefix, right) => self.compile_prefix_expression(prefix, right)?,
-            Expression::Infix(left, infix, right) => {
-                self.compile_infix_expression(left, infix, right)?
+            Expression::Float(float) => {
+                let constant_address = self.add_constant(Object::Float(*float));
+                self.emit(OpCode::Constant, &[constant_address]);
+            }
+            Expression::Prefix(prefix, right, span) => {
+                self.compile_prefix_expression(prefix, right, *span)?
+            }
+            Expression::Infix(left, infix @ (Infix::And | Infix::Or), right, _) => {
+                self.compile_logical_expression(left, infix, right)?
+            }
+            Expression::Infix(left, infix, right, span) => {
+                self.compile_infix_expression(left, infix, right, *span)?
             }
             Expression::Boolean(value) => {
                 if *value {
@@ -113,14 +485,15 @@ impl Compiler {
                 self.compile_if_expression(condition, consequence, alternative)?
             }
             Expression::Function(args, body, name) => {
+                let loops = std::mem::take(&mut self.loops);
+                let tries = std::mem::take(&mut self.tries);
                 self.enter_scope();
-                self.symbol_table.define_function_name(name.clone());
-                for arg in args {
-                    match arg {
-                        Expression::Identifier(id) => self.symbol_table.define(id.clone()),
-                        _ => todo!(),
-                    };
+                // a function that assigns to the variable it is bound to refers
+                // to that variable by its name, rather than to itself
+                if !body.assigns_to(name) {
+                    self.symbol_table.define_function_name(name.clone());
                 }
+                self.compile_parameters(args)?;
                 self.compile_statement(body)?;
                 if self.last_instruction_is(OpCode::Pop) {
                     let address = self.scopes[self.scope_idx].len() - WORD_SIZE;
@@ -129,42 +502,64 @@ impl Compiler {
                 if !self.last_instruction_is(OpCode::ReturnValue) {
                     self.emit(OpCode::Return, &[]);
                 }
+                self.mark_tail_calls()?;
                 let free_symbols = self.symbol_table.free_symbols.clone();
                 let num_locals = self.symbol_table.num_definitions;
-                let instructions = self.leave_scope();
+                // locals and free variables are referred to by one-byte indices
+                if num_locals as usize > MAX_LOCALS {
+                    return Err(CompilerError::TooManyLocals);
+                }
+                if free_symbols.len() > MAX_FREE_VARIABLES {
+                    return Err(CompilerError::TooManyFreeVariables);
+                }
+                let (instructions, lines) = self.leave_scope();
+                self.loops = loops;
+                self.tries = tries;
                 free_symbols
                     .iter()
-                    .for_each(|binding| self.load_symbol(Rc::clone(binding)));
-                let compilted_fn = Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                    instructions,
-                    num_locals,
-                    args.len() as u32,
-                )));
+                    .for_each(|binding| self.capture_symbol(Rc::clone(binding)));
+                let has_rest = matches!(args.last(), Some(Expression::Rest(_)));
+                let num_defaults = args
+                    .iter()
+                    .filter(|arg| matches!(arg, Expression::Default(..)))
+                    .count();
+                let num_params = args.len() - has_rest as usize;
+                let compilted_fn = Object::CompiledFunc(Rc::new(
+                    CompiledFunction::new(instructions, num_locals, num_params as u32)
+                        .with_defaults(num_defaults as u32)
+                        .with_rest(has_rest)
+                        .with_lines(lines)
+                        .with_name(name),
+                ));
                 let address = self.add_constant(compilted_fn);
                 self.emit(OpCode::Closure, &[address, free_symbols.len() as u32]);
             }
-            Expression::Call(func, args) => {
+            Expression::Call(func, args, span) => {
                 self.compile_expression(func)?;
+                if args.len() > MAX_ARGUMENTS {
+                    return Err(CompilerError::TooManyArguments);
+                }
                 for arg in args {
                     self.compile_expression(arg)?;
                 }
-                self.emit(OpCode::Call, &[args.len() as u32]);
+                self.emit_at(*span, OpCode::Call, &[args.len() as u32]);
             }
             Expression::String(val) => {
                 let str = Object::String(val.clone());
                 let address = self.add_constant(str);
                 self.emit(OpCode::Constant, &[address]);
             }
+            Expression::Interpolation(parts) => self.compile_interpolation(parts)?,
             Expression::Array(val) => {
-                for exp in val.into_iter() {
+                for exp in val.iter() {
                     self.compile_expression(exp)?;
                 }
                 self.emit(OpCode::Array, &[val.len() as u32]);
             }
-            Expression::Index(store, i) => {
+            Expression::Index(store, i, span) => {
                 self.compile_expression(store)?;
                 self.compile_expression(i)?;
-                self.emit(OpCode::Index, &[]);
+                self.emit_at(*span, OpCode::Index, &[]);
             }
             Expression::Hash(val) => {
                 for (k, v) in val {
@@ -173,7 +568,25 @@ impl Compiler {
                 }
                 self.emit(OpCode::Hash, &[(val.len() * 2) as u32]);
             }
-            Expression::While(_, _) => todo!(),
+            Expression::While(condition, loop_block) => {
+                self.compile_while_expression(condition, loop_block)?
+            }
+            Expression::For(variables, iterable, loop_block) => {
+                self.compile_for_expression(variables, iterable, loop_block)?
+            }
+            Expression::Range(start, end) => {
+                self.compile_expression(start)?;
+                self.compile_expression(end)?;
+                self.emit(OpCode::Range, &[]);
+            }
+            Expression::Match(value, arms) => self.compile_match_expression(value, arms)?,
+            Expression::Try(block, catch, finally) => {
+                self.compile_try_expression(block, catch, finally.as_deref())?
+            }
+            Expression::Rest(_) | Expression::Default(..) => {
+                unreachable!("only parsed in patterns")
+            }
+            Expression::Macro(..) => unreachable!("removed by macro expansion"),
         }
         Ok(())
     }
@@ -192,6 +605,9 @@ impl Compiler {
 
         if self.last_instruction_is(OpCode::Pop) {
             self.remove_last_instruction();
+        } else {
+            // blocks that end without a value (e.g. in a `let`) evaluate to Null
+            self.emit(OpCode::Null, &[]);
         }
 
         let jump_pos = self.emit(OpCode::Jump, &[9999_u32]);
@@ -203,10 +619,12 @@ impl Compiler {
             self.emit(OpCode::Null, &[]);
         } else {
             let else_block = alternative.as_ref().unwrap();
-            self.compile_statement(&else_block)?;
+            self.compile_statement(else_block)?;
 
             if self.last_instruction_is(OpCode::Pop) {
                 self.remove_last_instruction();
+            } else {
+                self.emit(OpCode::Null, &[]);
             }
         }
 
@@ -216,18 +634,286 @@ impl Compiler {
         Ok(())
     }
 
+    fn compile_match_expression(
+        &mut self,
+        value: &Expression,
+        arms: &[MatchArm],
+    ) -> Result<(), CompilerError> {
+        // the value is kept on the stack while the arms are tried, and popped
+        // by the arm that matches it
+        self.compile_expression(value)?;
+
+        let mut end_jump_positions = vec![];
+        // the patterns of the arms before without guards, which always match
+        // any value their pattern does
+        let mut unguarded: Vec<MatchPattern> = vec![];
+        for arm in arms {
+            let pattern = MatchPattern::new(&arm.pattern).expect("parsed as a match pattern");
+            if unguarded
+                .iter()
+                .any(|earlier| pattern.is_covered_by(earlier))
+            {
+                self.warnings
+                    .push(Located::new(CompilerWarning::UnreachableArm, arm.span));
+            }
+
+            let names = pattern
+                .names()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let pattern = Rc::new(pattern);
+            let address = self.add_constant(Object::Pattern(Rc::clone(&pattern)));
+            self.emit(OpCode::Match, &[address]);
+            let mut next_arm_jump_positions = vec![self.emit(OpCode::JumpNotTruthy, &[9999_u32])];
+
+            // the bound values are pushed in order, so are stored in reverse
+            for name in names.into_iter().rev() {
+                let symbol = self.symbol_table.define(name);
+                self.store_symbol(symbol)?;
+            }
+            match &arm.guard {
+                Some(guard) => {
+                    self.compile_expression(guard)?;
+                    next_arm_jump_positions.push(self.emit(OpCode::JumpNotTruthy, &[9999_u32]));
+                }
+                None => unguarded.push(pattern.as_ref().clone()),
+            }
+
+            self.emit(OpCode::Pop, &[]);
+            let body_start_pos = self.scopes[self.scope_idx].len();
+            self.compile_statement(&arm.body)?;
+            if self.scopes[self.scope_idx].len() > body_start_pos
+                && self.last_instruction_is(OpCode::Pop)
+            {
+                self.remove_last_instruction();
+            } else {
+                self.emit(OpCode::Null, &[]);
+            }
+            end_jump_positions.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+            let next_arm_pos = self.scopes[self.scope_idx].len() as u32;
+            for position in next_arm_jump_positions {
+                self.change_operand(position as usize, next_arm_pos)?;
+            }
+        }
+        self.emit(OpCode::NoMatch, &[]);
+
+        let after_match_pos = self.scopes[self.scope_idx].len() as u32;
+        for position in end_jump_positions {
+            self.change_operand(position as usize, after_match_pos)?;
+        }
+        Ok(())
+    }
+
+    fn compile_try_expression(
+        &mut self,
+        block: &Statement,
+        catch: &Option<(String, Box<Statement>)>,
+        finally: Option<&Statement>,
+    ) -> Result<(), CompilerError> {
+        // OpSetupTry installs a handler, which an error raised before OpPopTry
+        // removes it jumps to, with the error on top of the stack; a handler
+        // that only runs the `finally` block raises the error again with
+        // OpRethrow, from where it was first raised
+        let rethrows = catch.is_none() && finally.is_some();
+        let setup_pos = self.emit(OpCode::SetupTry, &[9999_u32, rethrows as u32]);
+        self.compile_protected_block(block, finally)?;
+        if let Some(finally) = finally {
+            self.compile_statement(finally)?;
+        }
+        let mut end_jump_positions = vec![self.emit(OpCode::Jump, &[9999_u32])];
+
+        let handler_pos = self.scopes[self.scope_idx].len() as u32;
+        self.replace_instruction(
+            setup_pos as usize,
+            &make(OpCode::SetupTry, &[handler_pos, rethrows as u32]),
+        )?;
+        if let Some((name, handler)) = catch {
+            let symbol = self.symbol_table.define(name.clone());
+            self.store_symbol(symbol)?;
+            match finally {
+                Some(finally) => {
+                    let setup_pos = self.emit(OpCode::SetupTry, &[9999_u32, 1]);
+                    self.compile_protected_block(handler, Some(finally))?;
+                    self.compile_statement(finally)?;
+                    end_jump_positions.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+                    let handler_pos = self.scopes[self.scope_idx].len() as u32;
+                    self.replace_instruction(
+                        setup_pos as usize,
+                        &make(OpCode::SetupTry, &[handler_pos, 1]),
+                    )?;
+                }
+                None => self.compile_block_value(handler)?,
+            }
+        }
+        if let Some(finally) = finally {
+            // an error no `catch` block handled is raised again afterwards
+            self.compile_statement(finally)?;
+            self.emit(OpCode::Rethrow, &[]);
+        }
+
+        let after_try_pos = self.scopes[self.scope_idx].len() as u32;
+        for position in end_jump_positions {
+            self.change_operand(position as usize, after_try_pos)?;
+        }
+        Ok(())
+    }
+
+    /// Compiles a block run with a handler installed, leaving its value on
+    /// the stack, then removes the handler.
+    fn compile_protected_block(
+        &mut self,
+        block: &Statement,
+        finally: Option<&Statement>,
+    ) -> Result<(), CompilerError> {
+        self.tries.push(TryBlock {
+            finally: finally.cloned(),
+            loop_depth: self.loops.len(),
+        });
+        self.compile_block_value(block)?;
+        self.tries.pop();
+        self.emit(OpCode::PopTry, &[]);
+        Ok(())
+    }
+
+    /// Compiles a block whose value is used, which is left on the stack.
+    fn compile_block_value(&mut self, block: &Statement) -> Result<(), CompilerError> {
+        let block_start_pos = self.scopes[self.scope_idx].len();
+        self.compile_statement(block)?;
+        if self.scopes[self.scope_idx].len() > block_start_pos
+            && self.last_instruction_is(OpCode::Pop)
+        {
+            self.remove_last_instruction();
+        } else {
+            // blocks that end without a value evaluate to Null
+            self.emit(OpCode::Null, &[]);
+        }
+        Ok(())
+    }
+
+    fn compile_while_expression(
+        &mut self,
+        condition: &Expression,
+        loop_block: &Statement,
+    ) -> Result<(), CompilerError> {
+        // the value of the loop is the value of the last iteration of its block,
+        // or Null if the block never runs; it is kept on top of the stack and
+        // replaced at the start of every iteration
+        self.emit(OpCode::Null, &[]);
+
+        let loop_start_pos = self.scopes[self.scope_idx].len() as u32;
+        self.compile_expression(condition)?;
+
+        let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+
+        self.emit(OpCode::Pop, &[]);
+        self.loops.push(Loop {
+            start: loop_start_pos,
+            breaks: vec![],
+            has_iterator: false,
+        });
+        let block_start_pos = self.scopes[self.scope_idx].len();
+        self.compile_statement(loop_block)?;
+
+        if self.scopes[self.scope_idx].len() > block_start_pos
+            && self.last_instruction_is(OpCode::Pop)
+        {
+            self.remove_last_instruction();
+        } else {
+            self.emit(OpCode::Null, &[]);
+        }
+
+        self.emit(OpCode::Jump, &[loop_start_pos]);
+
+        let after_loop_pos = self.scopes[self.scope_idx].len() as u32;
+        self.change_operand(jump_not_truthy_pos as usize, after_loop_pos)?;
+        self.end_loop()?;
+
+        Ok(())
+    }
+
+    fn compile_for_expression(
+        &mut self,
+        variables: &[Expression],
+        iterable: &Expression,
+        loop_block: &Statement,
+    ) -> Result<(), CompilerError> {
+        // the iterator sits below the value of the loop, which is handled as in
+        // a while loop; OpIterNext pops the value and pushes the loop variables
+        // unless the iterator is exhausted, in which case it removes the
+        // iterator instead and jumps past the loop
+        self.compile_expression(iterable)?;
+        self.emit(OpCode::IterStart, &[]);
+        self.emit(OpCode::Null, &[]);
+
+        let count = variables.len() as u32;
+        let loop_start_pos = self.scopes[self.scope_idx].len() as u32;
+        let iter_next_pos = self.emit(OpCode::IterNext, &[9999_u32, count]);
+
+        // the variables are pushed in order, so are stored in reverse
+        for variable in variables.iter().rev() {
+            if let Expression::Identifier(id) = variable {
+                let symbol = self.symbol_table.define(id.to_string());
+                self.store_symbol(symbol)?;
+            }
+        }
+
+        self.loops.push(Loop {
+            start: loop_start_pos,
+            breaks: vec![],
+            has_iterator: true,
+        });
+        let block_start_pos = self.scopes[self.scope_idx].len();
+        self.compile_statement(loop_block)?;
+
+        if self.scopes[self.scope_idx].len() > block_start_pos
+            && self.last_instruction_is(OpCode::Pop)
+        {
+            self.remove_last_instruction();
+        } else {
+            self.emit(OpCode::Null, &[]);
+        }
+
+        self.emit(OpCode::Jump, &[loop_start_pos]);
+
+        let after_loop_pos = self.scopes[self.scope_idx].len() as u32;
+        self.replace_instruction(
+            iter_next_pos as usize,
+            &make(OpCode::IterNext, &[after_loop_pos, count]),
+        )?;
+        self.end_loop()?;
+
+        Ok(())
+    }
+
     fn compile_prefix_expression(
         &mut self,
         prefix: &Prefix,
         right: &Expression,
+        span: Span,
     ) -> Result<(), CompilerError> {
         self.compile_expression(right)?;
         match prefix {
             Prefix::Minus => {
-                self.emit(OpCode::Minus, &[]);
+                self.emit_at(span, OpCode::Minus, &[]);
             }
             Prefix::Bang => {
-                self.emit(OpCode::Bang, &[]);
+                self.emit_at(span, OpCode::Bang, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_interpolation(&mut self, parts: &[Expression]) -> Result<(), CompilerError> {
+        for (i, part) in parts.iter().enumerate() {
+            self.compile_expression(part)?;
+            if !matches!(part, Expression::String(_)) {
+                self.emit(OpCode::Stringify, &[]);
+            }
+            if i > 0 {
+                self.emit(OpCode::Add, &[]);
             }
         }
         Ok(())
@@ -244,46 +930,53 @@ impl Compiler {
         left: &Expression,
         infix: &Infix,
         right: &Expression,
+        span: Span,
     ) -> Result<(), CompilerError> {
-        if *infix == Infix::LessThan {
-            self.compile_expression(right)?;
-            self.compile_expression(left)?;
-        } else {
-            self.compile_expression(left)?;
-            self.compile_expression(right)?;
+        self.compile_expression(left)?;
+        self.compile_expression(right)?;
+        self.emit_at(span, infix_op_code(infix), &[]);
+        Ok(())
+    }
+
+    fn compile_logical_expression(
+        &mut self,
+        left: &Expression,
+        infix: &Infix,
+        right: &Expression,
+    ) -> Result<(), CompilerError> {
+        // the right operand is only evaluated if the left operand does not
+        // already decide the result; either way the result is a boolean
+        self.compile_expression(left)?;
+
+        let mut jump_to_end_positions = vec![];
+        let jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+
+        if *infix == Infix::Or {
+            self.emit(OpCode::True, &[]);
+            jump_to_end_positions.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+            let right_pos = self.scopes[self.scope_idx].len() as u32;
+            self.change_operand(jump_not_truthy_pos as usize, right_pos)?;
         }
-        match infix {
-            Infix::Plus => {
-                self.emit(OpCode::Add, &[]);
-            }
-            Infix::Minus => {
-                self.emit(OpCode::Subtract, &[]);
-            }
-            Infix::Multiply => {
-                self.emit(OpCode::Multiply, &[]);
-            }
-            Infix::Divide => {
-                self.emit(OpCode::Divide, &[]);
-            }
-            Infix::GreaterThan => {
-                self.emit(OpCode::GreaterThan, &[]);
-            }
-            Infix::LessThan => {
-                self.emit(OpCode::GreaterThan, &[]);
-            }
-            Infix::Equal => {
-                self.emit(OpCode::Equal, &[]);
-            }
-            Infix::NotEqual => {
-                self.emit(OpCode::NotEqual, &[]);
-            }
-            Infix::And => {
-                self.emit(OpCode::And, &[]);
-            }
-            Infix::Or => {
-                self.emit(OpCode::Or, &[]);
-            }
+
+        self.compile_expression(right)?;
+        let right_jump_not_truthy_pos = self.emit(OpCode::JumpNotTruthy, &[9999_u32]);
+
+        self.emit(OpCode::True, &[]);
+        jump_to_end_positions.push(self.emit(OpCode::Jump, &[9999_u32]));
+
+        let false_pos = self.scopes[self.scope_idx].len() as u32;
+        if *infix == Infix::And {
+            self.change_operand(jump_not_truthy_pos as usize, false_pos)?;
+        }
+        self.change_operand(right_jump_not_truthy_pos as usize, false_pos)?;
+        self.emit(OpCode::False, &[]);
+
+        let end_pos = self.scopes[self.scope_idx].len() as u32;
+        for pos in jump_to_end_positions {
+            self.change_operand(pos as usize, end_pos)?;
         }
+
         Ok(())
     }
 
@@ -293,7 +986,7 @@ impl Compiler {
     }
 
     fn last_instruction_is(&self, target_opcode: OpCode) -> bool {
-        if self.scopes[self.scope_idx].len() == 0 {
+        if self.scopes[self.scope_idx].is_empty() {
             return false;
         }
         let last_op_code = self.get_instruction_at(self.scopes[self.scope_idx].len() - WORD_SIZE);
@@ -321,17 +1014,51 @@ impl Compiler {
         address: usize,
         new_instruction: &[u8],
     ) -> Result<(), CompilerError> {
-        let mut i = 0;
-        for _ in 0..WORD_SIZE {
-            self.scopes[self.scope_idx][address + i] = new_instruction[i];
-            i += 1;
+        for (i, byte) in new_instruction.iter().take(WORD_SIZE).enumerate() {
+            self.scopes[self.scope_idx][address + i] = *byte;
         }
         Ok(())
     }
 
+    /// Turns each call in the function being compiled whose value is returned
+    /// as soon as it is made, e.g. `return f(x);` or a call that is the last
+    /// expression of a branch of the `if` the function ends with, into a tail
+    /// call.
+    fn mark_tail_calls(&mut self) -> Result<(), CompilerError> {
+        let len = self.scopes[self.scope_idx].len();
+        for address in (0..len).step_by(WORD_SIZE) {
+            if self.get_instruction_at(address)? == OpCode::Call
+                && self.returns_at(address + WORD_SIZE)?
+            {
+                let num_args = self.scopes[self.scope_idx][address + 1] as u32;
+                self.replace_instruction(address, &make(OpCode::TailCall, &[num_args]))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the instruction at `address`, after any unconditional jumps,
+    /// returns the value on top of the stack.
+    fn returns_at(&self, mut address: usize) -> Result<bool, CompilerError> {
+        let instructions = &self.scopes[self.scope_idx];
+        // a chain of jumps is never longer than the function, unless it loops
+        for _ in 0..instructions.len() / WORD_SIZE {
+            if address >= instructions.len() {
+                return Ok(false);
+            }
+            match self.get_instruction_at(address)? {
+                OpCode::ReturnValue => return Ok(true),
+                OpCode::Jump => {
+                    address = read_u16(&instructions[address + 1..=address + 2]) as usize
+                }
+                _ => return Ok(false),
+            }
+        }
+        Ok(false)
+    }
+
     fn get_instruction_at(&self, idx: usize) -> Result<OpCode, CompilerError> {
-        OpCode::try_from(self.scopes[self.scope_idx][idx] as u8)
-            .map_err(|_| CompilerError::InvalidOpCode)
+        OpCode::try_from(self.scopes[self.scope_idx][idx]).map_err(|_| CompilerError::InvalidOpCode)
     }
 
     fn emit(&mut self, op: OpCode, operands: &[u32]) -> u32 {
@@ -344,13 +1071,14 @@ impl Compiler {
     fn enter_scope(&mut self) {
         self.symbol_table = SymbolTable::new_enclosed(self.symbol_table.clone());
         self.scopes.push(Instructions::new());
+        self.lines.push(LineTable::default());
         self.scope_idx += 1;
     }
 
-    fn leave_scope(&mut self) -> Instructions {
+    fn leave_scope(&mut self) -> (Instructions, LineTable) {
         self.symbol_table = self.symbol_table.outer.as_ref().unwrap().as_ref().clone();
         self.scope_idx -= 1;
-        self.scopes.pop().unwrap()
+        (self.scopes.pop().unwrap(), self.lines.pop().unwrap())
     }
 
     fn load_symbol(&mut self, binding: Rc<Symbol>) {
@@ -372,10 +1100,118 @@ impl Compiler {
             }
         }
     }
+
+    fn store_symbol(&mut self, binding: Rc<Symbol>) -> Result<(), CompilerError> {
+        match binding.scope {
+            SymbolScope::Global => {
+                self.emit(OpCode::SetGlobal, &[binding.index]);
+            }
+            SymbolScope::Local => {
+                self.emit(OpCode::SetLocal, &[binding.index]);
+            }
+            SymbolScope::Free => {
+                self.emit(OpCode::SetFree, &[binding.index]);
+            }
+            SymbolScope::Builtin | SymbolScope::Function => {
+                return Err(CompilerError::InvalidAssignment(
+                    source_name(&binding.name).to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn capture_symbol(&mut self, binding: Rc<Symbol>) {
+        // locals and free variables are captured by reference, so that the new
+        // closure shares them with the enclosing scope
+        match binding.scope {
+            SymbolScope::Local => {
+                self.emit(OpCode::CaptureLocal, &[binding.index]);
+            }
+            SymbolScope::Free => {
+                self.emit(OpCode::CaptureFree, &[binding.index]);
+            }
+            _ => self.load_symbol(binding),
+        }
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The opcode of a binary operator, other than the logical ones.
+fn infix_op_code(infix: &Infix) -> OpCode {
+    match infix {
+        Infix::Plus => OpCode::Add,
+        Infix::Minus => OpCode::Subtract,
+        Infix::Multiply => OpCode::Multiply,
+        Infix::Divide => OpCode::Divide,
+        Infix::Modulo => OpCode::Modulo,
+        Infix::Power => OpCode::Power,
+        Infix::GreaterThan => OpCode::GreaterThan,
+        Infix::LessThan => OpCode::LessThan,
+        Infix::GreaterEqual => OpCode::GreaterEqual,
+        Infix::LessEqual => OpCode::LessEqual,
+        Infix::Equal => OpCode::Equal,
+        Infix::NotEqual => OpCode::NotEqual,
+        Infix::BitAnd => OpCode::BitAnd,
+        Infix::BitOr => OpCode::BitOr,
+        Infix::BitXor => OpCode::BitXor,
+        Infix::ShiftLeft => OpCode::ShiftLeft,
+        Infix::ShiftRight => OpCode::ShiftRight,
+        Infix::And | Infix::Or => unreachable!("compiled by compile_logical_expression"),
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum CompilerError {
     InvalidOpCode,
-    UndefinedVariable,
+    UndefinedVariable(String),
+    InvalidAssignment(String),
+    /// A call with more arguments than `OpCall` can count.
+    TooManyArguments,
+    /// A function with more parameters and variables than `OpGetLocal` can
+    /// refer to.
+    TooManyLocals,
+    /// A function using more variables of the functions around it than
+    /// `OpGetFree` can refer to.
+    TooManyFreeVariables,
+}
+
+/// A problem found while compiling that does not stop the program from running.
+#[derive(Debug, PartialEq)]
+pub enum CompilerWarning {
+    UnreachableArm,
+}
+
+impl Display for CompilerWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompilerWarning::UnreachableArm => write!(f, "unreachable match arm"),
+        }
+    }
+}
+
+impl Display for CompilerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompilerError::InvalidOpCode => write!(f, "invalid opcode"),
+            CompilerError::UndefinedVariable(name) => write!(f, "undefined variable '{name}'"),
+            CompilerError::InvalidAssignment(name) => write!(f, "cannot assign to '{name}'"),
+            CompilerError::TooManyArguments => {
+                write!(f, "a call can pass at most {MAX_ARGUMENTS} arguments")
+            }
+            CompilerError::TooManyLocals => write!(
+                f,
+                "a function can have at most {MAX_LOCALS} parameters and variables"
+            ),
+            CompilerError::TooManyFreeVariables => write!(
+                f,
+                "a function can use at most {MAX_FREE_VARIABLES} variables of the functions around it"
+            ),
+        }
+    }
 }
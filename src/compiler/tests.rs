@@ -1,9 +1,11 @@
 #![cfg(test)]
 
-use crate::code::{make, OpCode};
-use crate::compiler::{ByteCode, Compiler, CompilerError};
-use crate::object::{CompiledFunction, Object};
+use crate::code::{make, LineTable, OpCode, WORD_SIZE};
+use crate::compiler::{ByteCode, Compiler, CompilerError, CompilerWarning};
+use crate::evaluator::macros::Macros;
+use crate::object::{CompiledFunction, MatchPattern, Object};
 use crate::parser::Parser;
+use crate::span::Position;
 use std::rc::Rc;
 
 fn parse_and_compile(input: &str) -> (Option<ByteCode>, Option<CompilerError>) {
@@ -12,12 +14,27 @@ fn parse_and_compile(input: &str) -> (Option<ByteCode>, Option<CompilerError>) {
     let ast = Parser::parse_program(input).expect("got a parsing error");
     let mut compiler = Compiler::new();
     match compiler.compile(ast) {
-        Ok(result) => byte_code = Some(result),
-        Err(err) => error = Some(err),
+        Ok(result) => byte_code = Some(without_lines(result)),
+        Err(err) => error = Some(err.error),
     }
     (byte_code, error)
 }
 
+/// Line tables depend on the layout of the source, so tests compare bytecode
+/// with its line tables left empty.
+fn without_lines(ByteCode(instructions, constants, _): ByteCode) -> ByteCode {
+    let constants = constants
+        .into_iter()
+        .map(|constant| match constant.as_ref() {
+            Object::CompiledFunc(function) => Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::clone(function).with_lines(LineTable::default()),
+            ))),
+            _ => constant,
+        })
+        .collect();
+    ByteCode(instructions, constants, LineTable::default())
+}
+
 #[test]
 fn test_compile_integer_object() {
     let input = "1096";
@@ -27,6 +44,54 @@ fn test_compile_integer_object() {
             .flatten()
             .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1096))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_float_object() {
+    let input = "1.5";
+    let expected = ByteCode(
+        vec![make(OpCode::Constant, &[0_u32]), make(OpCode::Pop, &[])]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Float(1.5))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_string_interpolation() {
+    let input = r#""a${1}b${"c"}""#;
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Stringify, &[]),
+            make(OpCode::Add, &[]),
+            make(OpCode::Constant, &[2_u32]),
+            make(OpCode::Add, &[]),
+            make(OpCode::Constant, &[3_u32]),
+            make(OpCode::Add, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::String(String::from("a"))),
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::String(String::from("b"))),
+            Rc::new(Object::String(String::from("c"))),
+        ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -47,6 +112,7 @@ fn test_compile_integer_addition() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -67,6 +133,7 @@ fn test_compile_integer_subtraction() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(4)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -87,6 +154,7 @@ fn test_compile_integer_multiplication() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(3)), Rc::new(Object::Integer(5))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -107,6 +175,133 @@ fn test_compile_integer_division() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_modulo() {
+    let input = "10 % 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Modulo, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_power() {
+    let input = "10 ** 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::Power, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_less_equal() {
+    let input = "10 <= 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::LessEqual, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_greater_equal() {
+    let input = "10 >= 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::GreaterEqual, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_bitwise_and() {
+    let input = "10 & 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::BitAnd, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_shift_right() {
+    let input = "10 >> 2";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::ShiftRight, &[]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -133,6 +328,7 @@ fn test_compile_nested_integer_expression() {
             Rc::new(Object::Integer(10)),
             Rc::new(Object::Integer(2)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -153,6 +349,7 @@ fn test_compile_boolean_literals() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -173,6 +370,7 @@ fn test_compile_boolean_expression_one() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -186,13 +384,14 @@ fn test_compile_boolean_expression_two() {
         vec![
             make(OpCode::Constant, &[0_u32]),
             make(OpCode::Constant, &[1_u32]),
-            make(OpCode::GreaterThan, &[]),
+            make(OpCode::LessThan, &[]),
             make(OpCode::Pop, &[]),
         ]
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
-        vec![Rc::new(Object::Integer(2)), Rc::new(Object::Integer(1))],
+        vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -213,6 +412,7 @@ fn test_compile_boolean_expression() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -233,6 +433,7 @@ fn test_compile_boolean_expression_three() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -253,6 +454,7 @@ fn test_compile_boolean_expression_four() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -273,6 +475,7 @@ fn test_compile_boolean_expression_five() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -284,15 +487,20 @@ fn test_compile_boolean_expression_six() {
     let input = "true && false;";
     let expected = ByteCode(
         vec![
-            make(OpCode::True, &[]),
-            make(OpCode::False, &[]),
-            make(OpCode::And, &[]),
-            make(OpCode::Pop, &[]),
+            make(OpCode::True, &[]),                // 0000
+            make(OpCode::JumpNotTruthy, &[24_u32]), // 0004
+            make(OpCode::False, &[]),               // 0008
+            make(OpCode::JumpNotTruthy, &[24_u32]), // 0012
+            make(OpCode::True, &[]),                // 0016
+            make(OpCode::Jump, &[28_u32]),          // 0020
+            make(OpCode::False, &[]),               // 0024
+            make(OpCode::Pop, &[]),                 // 0028
         ]
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -304,15 +512,22 @@ fn test_compile_boolean_expression_seven() {
     let input = "true || false;";
     let expected = ByteCode(
         vec![
-            make(OpCode::True, &[]),
-            make(OpCode::False, &[]),
-            make(OpCode::Or, &[]),
-            make(OpCode::Pop, &[]),
+            make(OpCode::True, &[]),                // 0000
+            make(OpCode::JumpNotTruthy, &[16_u32]), // 0004
+            make(OpCode::True, &[]),                // 0008
+            make(OpCode::Jump, &[36_u32]),          // 0012
+            make(OpCode::False, &[]),               // 0016
+            make(OpCode::JumpNotTruthy, &[32_u32]), // 0020
+            make(OpCode::True, &[]),                // 0024
+            make(OpCode::Jump, &[36_u32]),          // 0028
+            make(OpCode::False, &[]),               // 0032
+            make(OpCode::Pop, &[]),                 // 0036
         ]
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -332,6 +547,7 @@ fn test_compile_minus_expressions() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -351,6 +567,7 @@ fn test_compile_bang_expression() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -375,6 +592,7 @@ fn test_compile_conditional_no_else() {
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(1024))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -403,6 +621,100 @@ fn test_compile_conditional_with_else() {
             Rc::new(Object::Integer(20)),
             Rc::new(Object::Integer(1024)),
         ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_while_loop() {
+    let input = "while (false) { 10 }; 1024";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Null, &[]),                // 0000
+            make(OpCode::False, &[]),               // 0004
+            make(OpCode::JumpNotTruthy, &[24_u32]), // 0008
+            make(OpCode::Pop, &[]),                 // 0012
+            make(OpCode::Constant, &[0_u32]),       // 0016
+            make(OpCode::Jump, &[4_u32]),           // 0020
+            make(OpCode::Pop, &[]),                 // 0024
+            make(OpCode::Constant, &[1_u32]),       // 0028
+            make(OpCode::Pop, &[]),                 // 0032
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(10)), Rc::new(Object::Integer(1024))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_for_loop() {
+    let input = "for (k, v in 0..2) { v }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),     // 0000
+            make(OpCode::Constant, &[1_u32]),     // 0004
+            make(OpCode::Range, &[]),             // 0008
+            make(OpCode::IterStart, &[]),         // 0012
+            make(OpCode::Null, &[]),              // 0016
+            make(OpCode::IterNext, &[40_u32, 2]), // 0020
+            make(OpCode::SetGlobal, &[0_u32]),    // 0024
+            make(OpCode::SetGlobal, &[1_u32]),    // 0028
+            make(OpCode::GetGlobal, &[0_u32]),    // 0032
+            make(OpCode::Jump, &[20_u32]),        // 0036
+            make(OpCode::Pop, &[]),               // 0040
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(0)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_break_and_continue() {
+    let input = "while (true) { continue; break; }; for (x in []) { break; }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Null, &[]),                // 0000
+            make(OpCode::True, &[]),                // 0004
+            make(OpCode::JumpNotTruthy, &[40_u32]), // 0008
+            make(OpCode::Pop, &[]),                 // 0012
+            make(OpCode::Null, &[]),                // 0016
+            make(OpCode::Jump, &[4_u32]),           // 0020
+            make(OpCode::Null, &[]),                // 0024
+            make(OpCode::Jump, &[40_u32]),          // 0028
+            make(OpCode::Null, &[]),                // 0032
+            make(OpCode::Jump, &[4_u32]),           // 0036
+            make(OpCode::Pop, &[]),                 // 0040
+            make(OpCode::Array, &[0_u32]),          // 0044
+            make(OpCode::IterStart, &[]),           // 0048
+            make(OpCode::Null, &[]),                // 0052
+            make(OpCode::IterNext, &[84_u32, 1]),   // 0056
+            make(OpCode::SetGlobal, &[0_u32]),      // 0060
+            make(OpCode::Pop, &[]),                 // 0064
+            make(OpCode::Null, &[]),                // 0068
+            make(OpCode::Jump, &[84_u32]),          // 0072
+            make(OpCode::Null, &[]),                // 0076
+            make(OpCode::Jump, &[56_u32]),          // 0080
+            make(OpCode::Pop, &[]),                 // 0084
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -426,6 +738,7 @@ let two = 2;
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -449,6 +762,7 @@ one;
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -475,6 +789,7 @@ two;
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -490,6 +805,7 @@ fn test_compile_string_literal() {
             .flatten()
             .collect::<Vec<u8>>(),
         vec![Rc::new(Object::String("monkey".to_string()))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -513,6 +829,7 @@ fn test_compile_string_literal_addition() {
             Rc::new(Object::String("mon".to_string())),
             Rc::new(Object::String("key".to_string())),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -528,6 +845,7 @@ fn test_compile_array_literal_one() {
             .flatten()
             .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -553,6 +871,7 @@ fn test_compile_array_literal_two() {
             Rc::new(Object::Integer(2)),
             Rc::new(Object::Integer(3)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -587,6 +906,7 @@ fn test_compile_array_literal_three() {
             Rc::new(Object::Integer(5)),
             Rc::new(Object::Integer(6)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -602,6 +922,7 @@ fn test_compile_hash_literal_one() {
             .flatten()
             .collect::<Vec<u8>>(),
         vec![],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -633,6 +954,7 @@ fn test_compile_hash_literal_two() {
             Rc::new(Object::Integer(5)),
             Rc::new(Object::Integer(6)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -666,6 +988,7 @@ fn test_compile_hash_literal_three() {
             Rc::new(Object::Integer(5)),
             Rc::new(Object::Integer(6)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -697,6 +1020,7 @@ fn test_compile_index_expression_one() {
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(1)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -723,15 +1047,312 @@ fn test_compile_index_expression_three() {
         vec![
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
-            Rc::new(Object::Integer(2)),
-            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(1)),
+        ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_let_destructuring() {
+    let input = "let [a, {\"k\": b}, ...c] = [];";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Array, &[0_u32]),
+            make(OpCode::UnpackArray, &[2_u32, 1_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::UnpackHash, &[1_u32]),
+            make(OpCode::SetGlobal, &[1_u32]),
+            make(OpCode::SetGlobal, &[2_u32]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::String(String::from("k")))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_function_parameter_pattern() {
+    let input = "fn([a], b) { a + b }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Closure, &[0_u32, 0_u32]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::CompiledFunc(Rc::new(
+            CompiledFunction::new(
+                vec![
+                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::UnpackArray, &[1_u32, 0_u32]),
+                    make(OpCode::SetLocal, &[2_u32]),
+                    make(OpCode::GetLocal, &[2_u32]),
+                    make(OpCode::GetLocal, &[1_u32]),
+                    make(OpCode::Add, &[]),
+                    make(OpCode::ReturnValue, &[]),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<u8>>(),
+                3,
+                2,
+            ),
+        )))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_default_and_rest_parameters() {
+    let input = "fn(a, b = 1, ...c) { b }";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Closure, &[1_u32, 0_u32]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::JumpIfArgGiven, &[12_u32, 1_u32]),
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::SetLocal, &[1_u32]),
+                        make(OpCode::GetLocal, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    3,
+                    2,
+                )
+                .with_defaults(1)
+                .with_rest(true),
+            ))),
+        ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compile_match() {
+    let input = "match (1) { x if x => x, _ => 2 }";
+    let expected = ByteCode(
+        vec![
+            // 0000
+            make(OpCode::Constant, &[0_u32]),
+            // 0004
+            make(OpCode::Match, &[1_u32]),
+            // 0008
+            make(OpCode::JumpNotTruthy, &[36_u32]),
+            // 0012
+            make(OpCode::SetGlobal, &[0_u32]),
+            // 0016
+            make(OpCode::GetGlobal, &[0_u32]),
+            // 0020
+            make(OpCode::JumpNotTruthy, &[36_u32]),
+            // 0024
+            make(OpCode::Pop, &[]),
+            // 0028
+            make(OpCode::GetGlobal, &[0_u32]),
+            // 0032
+            make(OpCode::Jump, &[60_u32]),
+            // 0036
+            make(OpCode::Match, &[2_u32]),
+            // 0040
+            make(OpCode::JumpNotTruthy, &[56_u32]),
+            // 0044
+            make(OpCode::Pop, &[]),
+            // 0048
+            make(OpCode::Constant, &[3_u32]),
+            // 0052
+            make(OpCode::Jump, &[60_u32]),
+            // 0056
+            make(OpCode::NoMatch, &[]),
+            // 0060
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Pattern(Rc::new(MatchPattern::Binding(
+                String::from("x"),
+            )))),
+            Rc::new(Object::Pattern(Rc::new(MatchPattern::Wildcard))),
+            Rc::new(Object::Integer(2)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
     assert_eq!(byte_code, Some(expected));
 }
 
+#[test]
+fn test_compile_try_expressions() {
+    let tests = vec![
+        (
+            "try { 1 } catch (e) { e }",
+            vec![
+                // 0000
+                make(OpCode::SetupTry, &[16_u32, 0]),
+                // 0004
+                make(OpCode::Constant, &[0_u32]),
+                // 0008
+                make(OpCode::PopTry, &[]),
+                // 0012
+                make(OpCode::Jump, &[24_u32]),
+                // 0016
+                make(OpCode::SetGlobal, &[0_u32]),
+                // 0020
+                make(OpCode::GetGlobal, &[0_u32]),
+                // 0024
+                make(OpCode::Pop, &[]),
+            ],
+            vec![Rc::new(Object::Integer(1))],
+        ),
+        (
+            "try { 1 } finally { 2 }",
+            vec![
+                // 0000
+                make(OpCode::SetupTry, &[24_u32, 1]),
+                // 0004
+                make(OpCode::Constant, &[0_u32]),
+                // 0008
+                make(OpCode::PopTry, &[]),
+                // 0012
+                make(OpCode::Constant, &[1_u32]),
+                // 0016
+                make(OpCode::Pop, &[]),
+                // 0020
+                make(OpCode::Jump, &[36_u32]),
+                // 0024
+                make(OpCode::Constant, &[2_u32]),
+                // 0028
+                make(OpCode::Pop, &[]),
+                // 0032
+                make(OpCode::Rethrow, &[]),
+                // 0036
+                make(OpCode::Pop, &[]),
+            ],
+            vec![
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::Integer(2)),
+            ],
+        ),
+        (
+            "while (true) { try { break; } finally { 1 } }",
+            vec![
+                // 0000
+                make(OpCode::Null, &[]),
+                // 0004
+                make(OpCode::True, &[]),
+                // 0008
+                make(OpCode::JumpNotTruthy, &[76_u32]),
+                // 0012
+                make(OpCode::Pop, &[]),
+                // 0016
+                make(OpCode::SetupTry, &[60_u32, 1]),
+                // 0020
+                make(OpCode::PopTry, &[]),
+                // 0024
+                make(OpCode::Constant, &[0_u32]),
+                // 0028
+                make(OpCode::Pop, &[]),
+                // 0032
+                make(OpCode::Null, &[]),
+                // 0036
+                make(OpCode::Jump, &[76_u32]),
+                // 0040
+                make(OpCode::Null, &[]),
+                // 0044
+                make(OpCode::PopTry, &[]),
+                // 0048
+                make(OpCode::Constant, &[1_u32]),
+                // 0052
+                make(OpCode::Pop, &[]),
+                // 0056
+                make(OpCode::Jump, &[72_u32]),
+                // 0060
+                make(OpCode::Constant, &[2_u32]),
+                // 0064
+                make(OpCode::Pop, &[]),
+                // 0068
+                make(OpCode::Rethrow, &[]),
+                // 0072
+                make(OpCode::Jump, &[4_u32]),
+                // 0076
+                make(OpCode::Pop, &[]),
+            ],
+            vec![
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(1)),
+            ],
+        ),
+    ];
+    for (input, instructions, constants) in tests {
+        let expected = ByteCode(
+            instructions.into_iter().flatten().collect::<Vec<u8>>(),
+            constants,
+            LineTable::default(),
+        );
+        let (byte_code, error) = parse_and_compile(input);
+        assert_eq!(error, None);
+        assert_eq!(byte_code, Some(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_unreachable_match_arm_warnings() {
+    let tests = vec![
+        ("match (1) { _ => 1, 2 => 2 }", 1),
+        ("match (1) { x => 1, [y] => 2, {} => 3 }", 2),
+        ("match (1) { x if x => 1, 2 => 2 }", 0),
+        ("match (1) { [a, ...b] => 1, [1, 2] => 2, [] => 3 }", 1),
+        (
+            "match (1) { {\"a\": 1} => 1, {\"a\": 1, \"b\": c} => 2, {} => 3 }",
+            1,
+        ),
+        ("match (1) { 1 => 1, 1.0 => 2, \"1\" => 3 }", 1),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).ok().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(program).ok().unwrap();
+        assert_eq!(compiler.warnings.len(), expected, "{input}");
+        assert!(compiler
+            .warnings
+            .iter()
+            .all(|warning| warning.error == CompilerWarning::UnreachableArm));
+    }
+}
+
 #[test]
 fn test_compile_function_one() {
     let input = "fn() { return 5 + 10; }";
@@ -760,6 +1381,7 @@ fn test_compile_function_one() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -794,6 +1416,7 @@ fn test_compile_function_two() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -828,6 +1451,7 @@ fn test_compile_function_three() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -848,6 +1472,7 @@ fn test_compile_function_four() {
         vec![Rc::new(Object::CompiledFunc(Rc::new(
             CompiledFunction::new(make(OpCode::Return, &[]).to_vec(), 0, 0),
         )))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -880,6 +1505,7 @@ fn test_compile_function_call_one() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -902,18 +1528,22 @@ fn test_compile_function_call_two() {
         .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(24)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                0,
-                0,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    0,
+                    0,
+                )
+                .with_name("noArg"),
+            ))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -950,6 +1580,7 @@ fn() { num };
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -988,6 +1619,7 @@ fn() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1032,6 +1664,7 @@ fn() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1057,16 +1690,20 @@ arg(24);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![make(OpCode::Return, &[])]
-                    .into_iter()
-                    .flatten()
-                    .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![make(OpCode::Return, &[])]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+                .with_name("arg"),
+            ))),
             Rc::new(Object::Integer(24)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1094,18 +1731,22 @@ arg(1, 2, 3);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![make(OpCode::Return, &[])]
-                    .into_iter()
-                    .flatten()
-                    .collect::<Vec<u8>>(),
-                3,
-                3,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![make(OpCode::Return, &[])]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<u8>>(),
+                    3,
+                    3,
+                )
+                .with_name("arg"),
+            ))),
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
             Rc::new(Object::Integer(3)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1131,19 +1772,23 @@ arg(24);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                1,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+                .with_name("arg"),
+            ))),
             Rc::new(Object::Integer(24)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1171,25 +1816,29 @@ arg(1, 2, 3);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Pop, &[]),
-                    make(OpCode::GetLocal, &[1_u32]),
-                    make(OpCode::Pop, &[]),
-                    make(OpCode::GetLocal, &[2_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                3,
-                3,
-            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Pop, &[]),
+                        make(OpCode::GetLocal, &[1_u32]),
+                        make(OpCode::Pop, &[]),
+                        make(OpCode::GetLocal, &[2_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    3,
+                    3,
+                )
+                .with_name("arg"),
+            ))),
             Rc::new(Object::Integer(1)),
             Rc::new(Object::Integer(2)),
             Rc::new(Object::Integer(3)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1218,6 +1867,7 @@ push([], 1);
         .flatten()
         .collect::<Vec<u8>>(),
         vec![Rc::new(Object::Integer(1))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1242,7 +1892,7 @@ fn() { len([]) };
                 vec![
                     make(OpCode::GetBuiltin, &[0_u32]),
                     make(OpCode::Array, &[0_u32]),
-                    make(OpCode::Call, &[1_u32]),
+                    make(OpCode::TailCall, &[1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
                 .into_iter()
@@ -1252,6 +1902,7 @@ fn() { len([]) };
                 0,
             ),
         )))],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1291,7 +1942,7 @@ fn(a) {
             )))),
             Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
                 vec![
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
                     make(OpCode::Closure, &[0_u32, 1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
@@ -1302,6 +1953,7 @@ fn(a) {
                 1,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1345,8 +1997,8 @@ fn(a) {
             )))),
             Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
                 vec![
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::CaptureFree, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
                     make(OpCode::Closure, &[0_u32, 2_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
@@ -1358,7 +2010,7 @@ fn(a) {
             )))),
             Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
                 vec![
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
                     make(OpCode::Closure, &[1_u32, 1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
@@ -1369,6 +2021,7 @@ fn(a) {
                 1,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1428,8 +2081,8 @@ fn() {
                 vec![
                     make(OpCode::Constant, &[2_u32]),
                     make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetFree, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::CaptureFree, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
                     make(OpCode::Closure, &[4_u32, 2_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
@@ -1443,7 +2096,7 @@ fn() {
                 vec![
                     make(OpCode::Constant, &[1_u32]),
                     make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
                     make(OpCode::Closure, &[5_u32, 1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
@@ -1454,6 +2107,7 @@ fn() {
                 0,
             )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1461,42 +2115,181 @@ fn() {
 }
 
 #[test]
-fn test_recursive_function_one() {
+fn test_assignment_global() {
     let input = "
-let countdown = fn(x) { countdown(x-1); };
-countdown(1);
+let a = 1;
+a = 2;
 ";
     let expected = ByteCode(
         vec![
-            make(OpCode::Closure, &[1_u32, 0_u32]),
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![Rc::new(Object::Integer(1)), Rc::new(Object::Integer(2))],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_compound_index_assignment() {
+    let input = "
+let a = [1];
+a[0] += 2;
+";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Constant, &[0_u32]),
+            make(OpCode::Array, &[1_u32]),
             make(OpCode::SetGlobal, &[0_u32]),
             make(OpCode::GetGlobal, &[0_u32]),
+            make(OpCode::Constant, &[1_u32]),
+            make(OpCode::GetIndexPath, &[1_u32]),
             make(OpCode::Constant, &[2_u32]),
-            make(OpCode::Call, &[1_u32]),
+            make(OpCode::Add, &[]),
+            make(OpCode::SetIndexPath, &[1_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(2)),
+        ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_assignment_free() {
+    let input = "
+fn() {
+    let count = 0;
+    fn() { count = count + 1; };
+};
+";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Closure, &[3_u32, 0_u32]),
             make(OpCode::Pop, &[]),
         ]
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
         vec![
+            Rc::new(Object::Integer(0)),
             Rc::new(Object::Integer(1)),
             Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
                 vec![
-                    make(OpCode::CurrentClosure, &[]),
-                    make(OpCode::GetLocal, &[0_u32]),
+                    make(OpCode::GetFree, &[0_u32]),
+                    make(OpCode::Constant, &[1_u32]),
+                    make(OpCode::Add, &[]),
+                    make(OpCode::SetFree, &[0_u32]),
+                    make(OpCode::Return, &[]),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<u8>>(),
+                0,
+                0,
+            )))),
+            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
+                vec![
                     make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Subtract, &[]),
-                    make(OpCode::Call, &[1_u32]),
+                    make(OpCode::SetLocal, &[0_u32]),
+                    make(OpCode::CaptureLocal, &[0_u32]),
+                    make(OpCode::Closure, &[2_u32, 1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
                 .into_iter()
                 .flatten()
                 .collect::<Vec<u8>>(),
                 1,
-                1,
+                0,
             )))),
+        ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_assignment_undefined_variable() {
+    let input = "a = 1;";
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(
+        error,
+        Some(CompilerError::UndefinedVariable("a".to_string()))
+    );
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_assignment_to_builtin() {
+    let input = "len = 1;";
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(
+        error,
+        Some(CompilerError::InvalidAssignment("len".to_string()))
+    );
+    assert_eq!(byte_code, None);
+}
+
+#[test]
+fn test_recursive_function_one() {
+    let input = "
+let countdown = fn(x) { countdown(x-1); };
+countdown(1);
+";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Closure, &[1_u32, 0_u32]),
+            make(OpCode::SetGlobal, &[0_u32]),
+            make(OpCode::GetGlobal, &[0_u32]),
+            make(OpCode::Constant, &[2_u32]),
+            make(OpCode::Call, &[1_u32]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::CurrentClosure, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Subtract, &[]),
+                        make(OpCode::TailCall, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+                .with_name("countdown"),
+            ))),
             Rc::new(Object::Integer(1)),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
@@ -1523,15 +2316,168 @@ wrapper();
         .into_iter()
         .flatten()
         .collect::<Vec<u8>>(),
+        vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::CurrentClosure, &[]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[0_u32]),
+                        make(OpCode::Subtract, &[]),
+                        make(OpCode::TailCall, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    1,
+                )
+                .with_name("countdown"),
+            ))),
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::CompiledFunc(Rc::new(
+                CompiledFunction::new(
+                    vec![
+                        make(OpCode::Closure, &[1_u32, 0_u32]),
+                        make(OpCode::SetLocal, &[0_u32]),
+                        make(OpCode::GetLocal, &[0_u32]),
+                        make(OpCode::Constant, &[2_u32]),
+                        make(OpCode::TailCall, &[1_u32]),
+                        make(OpCode::ReturnValue, &[]),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u8>>(),
+                    1,
+                    0,
+                )
+                .with_name("wrapper"),
+            ))),
+        ],
+        LineTable::default(),
+    );
+    let (byte_code, error) = parse_and_compile(input);
+    assert_eq!(error, None);
+    assert_eq!(byte_code, Some(expected));
+}
+
+#[test]
+fn test_undefined_variable_renamed_by_macro() {
+    let input =
+        "let m = macro() { quote(fn() { let f = fn() { g() }; let g = fn() { 1 }; f() }()) };
+m()";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let expanded = Macros::new().expand(ast).expect("got a macro error");
+    let error = Compiler::new().compile(expanded).err().unwrap();
+    assert_eq!(
+        error.error,
+        CompilerError::UndefinedVariable("g".to_string())
+    );
+}
+
+#[test]
+fn test_operand_limits() {
+    // identifiers cannot contain digits, so the index is written in letters
+    let name = |idx: usize| {
+        format!(
+            "v{}{}",
+            (b'a' + (idx / 26) as u8) as char,
+            (b'a' + (idx % 26) as u8) as char
+        )
+    };
+    let names = |n: usize| (0..n).map(name).collect::<Vec<_>>();
+    let call = |n: usize| format!("len({});", vec!["1"; n].join(", "));
+    let params = |n: usize| format!("fn({}) {{ 1 }};", names(n).join(", "));
+    // the inner function uses each variable of the outer one
+    let free = |n: usize| {
+        let names = names(n);
+        format!(
+            "fn() {{ {} fn() {{ [{}] }} }};",
+            names
+                .iter()
+                .map(|name| format!("let {name} = 1;"))
+                .collect::<String>(),
+            names.join(", ")
+        )
+    };
+    let tests = [
+        (call(255), None),
+        (call(256), Some(CompilerError::TooManyArguments)),
+        (params(256), None),
+        (params(257), Some(CompilerError::TooManyLocals)),
+        (free(255), None),
+        (free(256), Some(CompilerError::TooManyFreeVariables)),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_and_compile(&input).1, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_compile_error_position() {
+    let input = "let a = 1;
+let f = fn() {
+    a + b
+};";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let error = Compiler::new().compile(ast).err().unwrap();
+    assert_eq!(
+        error.error,
+        CompilerError::UndefinedVariable("b".to_string())
+    );
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(3, 5)));
+}
+
+#[test]
+fn test_compile_line_table() {
+    let input = "let a = 1;
+let f = fn() {
+    a;
+    a + 1
+};
+f();";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let ByteCode(_, constants, lines) = Compiler::new().compile(ast).unwrap();
+    let line_at = |lines: &LineTable, address| lines.lookup(address).map(|span| span.start.line);
+
+    // main: constant 0, set global 0 | closure, set global 1 | get global 1, call, pop
+    assert_eq!(line_at(&lines, 0), Some(1));
+    assert_eq!(line_at(&lines, 4), Some(1));
+    assert_eq!(line_at(&lines, 8), Some(2));
+    assert_eq!(line_at(&lines, 16), Some(6));
+    assert_eq!(line_at(&lines, 24), Some(6));
+
+    // f: get global 0, pop | get global 0, constant, add, return value
+    let Object::CompiledFunc(f) = &*constants[2] else {
+        panic!("expected a compiled function");
+    };
+    assert_eq!(line_at(&f.lines, 0), Some(3));
+    assert_eq!(line_at(&f.lines, 8), Some(4));
+    assert_eq!(line_at(&f.lines, 20), Some(4));
+}
+
+#[test]
+fn test_tail_calls() {
+    let input = "
+fn(f) { return f(1); };
+";
+    let expected = ByteCode(
+        vec![
+            make(OpCode::Closure, &[1_u32, 0_u32]),
+            make(OpCode::Pop, &[]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<u8>>(),
         vec![
             Rc::new(Object::Integer(1)),
             Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
                 vec![
-                    make(OpCode::CurrentClosure, &[]),
                     make(OpCode::GetLocal, &[0_u32]),
                     make(OpCode::Constant, &[0_u32]),
-                    make(OpCode::Subtract, &[]),
-                    make(OpCode::Call, &[1_u32]),
+                    make(OpCode::TailCall, &[1_u32]),
                     make(OpCode::ReturnValue, &[]),
                 ]
                 .into_iter()
@@ -1540,25 +2486,56 @@ wrapper();
                 1,
                 1,
             )))),
-            Rc::new(Object::Integer(1)),
-            Rc::new(Object::CompiledFunc(Rc::new(CompiledFunction::new(
-                vec![
-                    make(OpCode::Closure, &[1_u32, 0_u32]),
-                    make(OpCode::SetLocal, &[0_u32]),
-                    make(OpCode::GetLocal, &[0_u32]),
-                    make(OpCode::Constant, &[2_u32]),
-                    make(OpCode::Call, &[1_u32]),
-                    make(OpCode::ReturnValue, &[]),
-                ]
-                .into_iter()
-                .flatten()
-                .collect::<Vec<u8>>(),
-                1,
-                0,
-            )))),
         ],
+        LineTable::default(),
     );
     let (byte_code, error) = parse_and_compile(input);
     assert_eq!(error, None);
     assert_eq!(byte_code, Some(expected));
 }
+
+#[test]
+fn test_tail_calls_only_in_tail_position() {
+    let tests = [
+        (
+            "fn(f) { if (f) { f(1) } else { f(2) } }",
+            vec![OpCode::TailCall, OpCode::TailCall],
+        ),
+        (
+            "fn(f) { match (f) { 1 => f(1), _ => 2 } }",
+            vec![OpCode::TailCall],
+        ),
+        (
+            "fn(f) { let x = f(1); f(x) }",
+            vec![OpCode::Call, OpCode::TailCall],
+        ),
+        ("fn(f) { 1 + f(1) }", vec![OpCode::Call]),
+        ("fn(f) { f(f(1)) }", vec![OpCode::Call, OpCode::TailCall]),
+        ("fn(f) { f(1); }; 2", vec![OpCode::TailCall]),
+        ("fn(f) { f(1); 2 }", vec![OpCode::Call]),
+        (
+            "fn(f) { try { return f(1); } catch (e) { 2 } }",
+            vec![OpCode::Call],
+        ),
+        ("fn(f) { while (true) { f(1) } }", vec![OpCode::Call]),
+    ];
+    for (input, expected) in tests {
+        let (byte_code, error) = parse_and_compile(input);
+        assert_eq!(error, None, "{input}");
+        let ByteCode(_, constants, _) = byte_code.unwrap();
+        let function = constants
+            .iter()
+            .find_map(|constant| match &**constant {
+                Object::CompiledFunc(function) => Some(Rc::clone(function)),
+                _ => None,
+            })
+            .unwrap();
+        let calls = function
+            .instructions
+            .chunks_exact(WORD_SIZE)
+            .map(|word| OpCode::try_from(word[0]).unwrap())
+            .filter(|op| matches!(op, OpCode::Call | OpCode::TailCall))
+            .collect::<Vec<_>>();
+        assert_eq!(calls, expected, "{input}");
+    }
+}
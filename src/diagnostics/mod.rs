@@ -0,0 +1,389 @@
+mod tests;
+
+use crate::compiler::{CompilerError, CompilerWarning};
+use crate::evaluator::macros::MacroError;
+use crate::evaluator::EvalError;
+use crate::lexer::LexerError;
+use crate::module::ImportError;
+use crate::object::Object;
+use crate::parser::ParsingError;
+use crate::span::{Located, Sources, Span};
+use crate::vm::{RuntimeError, TraceFrame, VmError};
+use std::fmt::{Display, Write};
+
+/// An error that can be reported to the user as a [`Diagnostic`].
+pub trait Diagnose: Display {
+    /// What went wrong, in broad terms, e.g. "syntax error".
+    const KIND: &'static str;
+
+    /// A suggestion on how to fix the error, if there is an obvious one.
+    fn hint(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A located error, ready to be rendered against the source it came from.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub kind: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub hint: Option<String>,
+    /// The calls in progress when a runtime error was raised, innermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+impl<E: Diagnose> From<&Located<E>> for Diagnostic {
+    fn from(located: &Located<E>) -> Self {
+        Diagnostic {
+            kind: E::KIND,
+            message: located.error.to_string(),
+            span: located.span,
+            hint: located.error.hint(),
+            trace: vec![],
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(runtime_error: &RuntimeError) -> Self {
+        Diagnostic {
+            kind: VmError::KIND,
+            message: runtime_error.error.to_string(),
+            span: runtime_error.span,
+            hint: runtime_error.error.hint(),
+            trace: runtime_error.trace.clone(),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic, quoting the offending line of `source` and
+    /// underlining the span with carets. `name` identifies the source, e.g. a
+    /// file name.
+    ///
+    /// ```text
+    /// runtime error: cannot add INTEGER and STRING
+    ///  --> <repl>:1:9
+    ///   |
+    /// 1 | let x = 1 + "one";
+    ///   |         ^^^^^^^^^
+    /// ```
+    pub fn render(&self, name: &str, source: &str) -> String {
+        self.render_in(&Sources::new(name, source))
+    }
+
+    /// Renders the diagnostic as [`Diagnostic::render`] does, against the
+    /// source each span refers to, which may be a module the program imports.
+    pub fn render_in(&self, sources: &Sources) -> String {
+        let mut out = format!("{}: {}\n", self.kind, self.message);
+
+        if let Some(span) = self.span {
+            let (name, source) = sources.get(span.source);
+            let line_number = span.start.line.to_string();
+            let gutter = " ".repeat(line_number.len());
+            // writing to a String cannot fail
+            let _ = writeln!(out, "{gutter}--> {name}:{}", span.start);
+
+            if let Some(line) = source.lines().nth(span.start.line as usize - 1) {
+                let line = line.trim_end_matches('\r');
+                let _ = writeln!(out, "{gutter} |");
+                let _ = writeln!(out, "{line_number} | {line}");
+                let _ = writeln!(out, "{gutter} | {}", underline(line, span));
+            }
+
+            if let Some(hint) = &self.hint {
+                let _ = writeln!(out, "{gutter} = hint: {hint}");
+            }
+        } else if let Some(hint) = &self.hint {
+            let _ = writeln!(out, "  = hint: {hint}");
+        }
+
+        // a trace of just the main program adds nothing to the location above
+        if self.trace.len() > 1 {
+            out.push_str("stack trace (most recent call first):\n");
+            let mut frames = self.trace.iter().peekable();
+            while let Some(frame) = frames.next() {
+                let _ = write!(out, "  at {}", frame.function);
+                match frame.span {
+                    Some(span) => {
+                        let (name, _) = sources.get(span.source);
+                        let _ = writeln!(out, " ({name}:{}, offset {})", span.start, frame.offset);
+                    }
+                    None => {
+                        let _ = writeln!(out, " (offset {})", frame.offset);
+                    }
+                }
+
+                // collapse runs of identical frames, as left by deep recursion
+                let mut repeated = 0;
+                while frames.next_if_eq(&frame).is_some() {
+                    repeated += 1;
+                }
+                if repeated > 0 {
+                    let _ = writeln!(out, "  ... {repeated} more identical calls");
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Builds the caret line for `span` within `line`. Tabs before the span are
+/// kept so that the carets line up with the source however tabs are shown.
+fn underline(line: &str, span: Span) -> String {
+    let start = span.start.column as usize - 1;
+    let end = if span.end.line == span.start.line {
+        (span.end.column as usize - 1).max(start + 1)
+    } else {
+        line.chars().count().max(start + 1)
+    };
+
+    let mut carets: String = line
+        .chars()
+        .take(start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    carets.extend(std::iter::repeat_n('^', end - start));
+    carets
+}
+
+/// Describes a binary operator applied to operands of unsupported types.
+pub fn describe_operands(op: &str, left: &str, right: &str) -> String {
+    match op {
+        "+" => format!("cannot add {left} and {right}"),
+        "-" => format!("cannot subtract {right} from {left}"),
+        "*" => format!("cannot multiply {left} by {right}"),
+        "/" => format!("cannot divide {left} by {right}"),
+        "%" => format!("cannot take the remainder of {left} divided by {right}"),
+        "**" => format!("cannot raise {left} to the power of {right}"),
+        ".." => format!("cannot make a range from {left} to {right}"),
+        "&" | "|" | "^" | "<<" | ">>" => {
+            format!("cannot apply '{op}' to {left} and {right}")
+        }
+        _ => format!("cannot compare {left} and {right} with '{op}'"),
+    }
+}
+
+/// Describes a prefix operator applied to an operand of an unsupported type.
+pub fn describe_operand(op: &str, operand: &str) -> String {
+    match op {
+        "-" => format!("cannot negate {operand}"),
+        _ => format!("cannot apply '{op}' to {operand}"),
+    }
+}
+
+/// Describes a thrown value that was not caught.
+pub fn describe_thrown(object: &Object) -> String {
+    match object {
+        Object::Error(_) => object.to_string(),
+        _ => format!("value {object}"),
+    }
+}
+
+fn concatenation_hint(op: &str, left: &str, right: &str) -> Option<String> {
+    (op == "+" && left != right && (left == "STRING" || right == "STRING"))
+        .then(|| "strings can only be concatenated with other strings".to_string())
+}
+
+fn integer_range_hint() -> String {
+    format!("integers range from {} to {}", i64::MIN, i64::MAX)
+}
+
+fn recursion_hint() -> String {
+    "this is usually caused by unbounded recursion".to_string()
+}
+
+fn iterable_hint() -> String {
+    "loops can iterate over arrays, hashes, strings and ranges such as '0..10'".to_string()
+}
+
+fn pattern_hint() -> String {
+    "'[a, b]' unpacks an array and '{\"key\": a}' a hash".to_string()
+}
+
+fn match_hint() -> String {
+    "add an arm with the pattern '_' to handle any other value".to_string()
+}
+
+fn catch_hint() -> String {
+    "catch it with 'try { ... } catch (e) { ... }'".to_string()
+}
+
+fn shift_range_hint() -> String {
+    format!("shift amounts range from 0 to {}", i64::BITS - 1)
+}
+
+impl Diagnose for ParsingError {
+    const KIND: &'static str = "syntax error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            ParsingError::UnexpectedEof => {
+                Some("check for a missing ';', closing ')' or closing '}'".to_string())
+            }
+            ParsingError::OutsideLoop(_) => Some(
+                "'break' and 'continue' can only be used inside a 'while' or 'for' loop, \
+                 and not in a function defined in one"
+                    .to_string(),
+            ),
+            ParsingError::LoopControlInExpression(_) => Some(
+                "move it out of the expression, e.g. into an 'if (done) { break; }' \
+                 before it"
+                    .to_string(),
+            ),
+            ParsingError::InvalidAssignmentTarget => Some(
+                "only a variable or an element of one, such as 'a[0]' or 'h[\"key\"]', \
+                 can be assigned to"
+                    .to_string(),
+            ),
+            ParsingError::RequiredAfterDefault => Some(
+                "give it a default too, or move it before the parameters with defaults".to_string(),
+            ),
+            ParsingError::TryWithoutHandler => Some(
+                "add 'catch (e) { ... }' to handle errors raised in the block, \
+                 or 'finally { ... }' to run code however it ends"
+                    .to_string(),
+            ),
+            ParsingError::NotTopLevel(_) => {
+                Some("move it out of any block or function".to_string())
+            }
+            ParsingError::ExportPattern => {
+                Some("export each name with its own 'export let name = ...;'".to_string())
+            }
+            ParsingError::DuplicateBinding(_) => {
+                Some("give each variable it binds a name of its own".to_string())
+            }
+            ParsingError::UnboundParameter(_) => {
+                Some("a default can only use the parameters before it".to_string())
+            }
+            ParsingError::Lexer(LexerError::UnterminatedString) => Some(
+                r#"end the string with a closing '"', or write a quote inside it as '\"'"#
+                    .to_string(),
+            ),
+            ParsingError::Lexer(LexerError::UnterminatedComment) => Some(
+                "close it with '*/'; block comments nest, so each '/*' needs its own '*/'"
+                    .to_string(),
+            ),
+            ParsingError::Lexer(
+                LexerError::InvalidEscape(_) | LexerError::InvalidUnicodeEscape(_),
+            ) => Some(
+                r#"the escapes are \n, \t, \", \\, \$ and \u{...} with 1 to 6 hex digits"#
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnose for CompilerError {
+    const KIND: &'static str = "compile error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            CompilerError::UndefinedVariable(name) => {
+                Some(format!("declare it first with 'let {name} = ...;'"))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Diagnose for CompilerWarning {
+    const KIND: &'static str = "warning";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            CompilerWarning::UnreachableArm => {
+                Some("an earlier arm without a guard matches every value this one does".to_string())
+            }
+        }
+    }
+}
+
+impl Diagnose for EvalError {
+    const KIND: &'static str = "runtime error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            EvalError::InvalidOperands(op, left, right) => concatenation_hint(op, left, right),
+            EvalError::UnrecognisedIdentifier(name) => {
+                Some(format!("declare it first with 'let {name} = ...;'"))
+            }
+            EvalError::IncorrectNumberOfArgs => {
+                Some("check the parameters in the function's definition".to_string())
+            }
+            EvalError::IndexOutOfBounds => Some("array indices start at 0".to_string()),
+            EvalError::IntegerOverflow => Some(integer_range_hint()),
+            EvalError::InvalidShiftAmount(_) => Some(shift_range_hint()),
+            EvalError::NotIterable(_) => Some(iterable_hint()),
+            EvalError::CannotDestructure(..) => Some(pattern_hint()),
+            EvalError::NoMatchingArm(_) => Some(match_hint()),
+            EvalError::Thrown(_) => Some(catch_hint()),
+            EvalError::StackOverflow => Some(recursion_hint()),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnose for MacroError {
+    const KIND: &'static str = "macro error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            MacroError::Eval(error) => error.hint(),
+            MacroError::NotQuoted(_) => {
+                Some("return the code to expand to as 'quote(...)'".to_string())
+            }
+            MacroError::MisplacedMacro => {
+                Some("define it as 'let name = macro(...) { ... };' outside any block".to_string())
+            }
+            MacroError::OutsideMacro(_) => {
+                Some("'quote' and 'unquote' build the code a macro expands to".to_string())
+            }
+            MacroError::TooDeep => {
+                Some("check for a macro that expands to a call to itself".to_string())
+            }
+        }
+    }
+}
+
+impl Diagnose for ImportError {
+    const KIND: &'static str = "import error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            ImportError::NotFound(_) => Some(
+                "paths are relative to the importing file, then to each '--lib' directory"
+                    .to_string(),
+            ),
+            ImportError::Cycle(_) => {
+                Some("move what the modules share into a module of their own".to_string())
+            }
+            ImportError::Unreadable(..) => None,
+        }
+    }
+}
+
+impl Diagnose for VmError {
+    const KIND: &'static str = "runtime error";
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            VmError::InvalidOperands(op, left, right) => concatenation_hint(op, left, right),
+            VmError::IndexOutOfBounds => Some("array indices start at 0".to_string()),
+            VmError::IntegerOverflow => Some(integer_range_hint()),
+            VmError::InvalidShiftAmount(_) => Some(shift_range_hint()),
+            VmError::NotIterable(_) => Some(iterable_hint()),
+            VmError::CannotDestructure(..) => Some(pattern_hint()),
+            VmError::NoMatchingArm(_) => Some(match_hint()),
+            VmError::Thrown(_) => Some(catch_hint()),
+            VmError::WrongArguments => {
+                Some("check the parameters in the function's definition".to_string())
+            }
+            VmError::StackOverflow | VmError::FrameStackOverflow => Some(recursion_hint()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,250 @@
+#![cfg(test)]
+
+use crate::compiler::Compiler;
+use crate::diagnostics::Diagnostic;
+use crate::evaluator::macros::Macros;
+use crate::module::{Loader, TestDir};
+use crate::object::builtins::Builtins;
+use crate::parser::Parser;
+use crate::span::{Located, Position, Span};
+use crate::vm::{VirtualMachine, VmError};
+use std::rc::Rc;
+
+fn run_and_render(input: &str) -> String {
+    // PRE: `input` is a well-formed program that fails at runtime
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    Diagnostic::from(&error).render("test.mk", input)
+}
+
+#[test]
+fn test_render_runtime_error() {
+    let input = "let x = 1;\nlet y = x + \"one\";";
+    let expected = "runtime error: cannot add INTEGER and STRING
+ --> test.mk:2:9
+  |
+2 | let y = x + \"one\";
+  |         ^^^^^^^^^
+  = hint: strings can only be concatenated with other strings
+";
+    assert_eq!(run_and_render(input), expected);
+}
+
+#[test]
+fn test_render_prefix_error() {
+    let input = "-true";
+    let expected = "runtime error: cannot negate BOOLEAN
+ --> test.mk:1:1
+  |
+1 | -true
+  | ^^^^^
+";
+    assert_eq!(run_and_render(input), expected);
+}
+
+#[test]
+fn test_render_shift_error() {
+    let input = "1 << 64";
+    let expected = "runtime error: cannot shift by 64 bits
+ --> test.mk:1:1
+  |
+1 | 1 << 64
+  | ^^^^^^^
+  = hint: shift amounts range from 0 to 63
+";
+    assert_eq!(run_and_render(input), expected);
+}
+
+#[test]
+fn test_render_parse_error() {
+    let input = "let x = (1 + 2;";
+    let errors = Parser::parse_program(input).err().unwrap();
+    let expected = "syntax error: unexpected token: ';'
+ --> test.mk:1:15
+  |
+1 | let x = (1 + 2;
+  |               ^
+";
+    assert_eq!(
+        Diagnostic::from(&errors[0]).render("test.mk", input),
+        expected
+    );
+}
+
+#[test]
+fn test_render_illegal_character() {
+    for input in ["let price = 1 $ 2;", "let price = $2;"] {
+        let errors = Parser::parse_program(input).err().unwrap();
+        let expected = format!(
+            "syntax error: unexpected character: '$'
+ --> test.mk:1:{}
+  |
+1 | {input}
+  | {}^
+",
+            input.find('$').unwrap() + 1,
+            " ".repeat(input.find('$').unwrap()),
+        );
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render("test.mk", input),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_render_error_inside_interpolation() {
+    let input = "puts(\"total: ${1 +}\");";
+    let errors = Parser::parse_program(input).err().unwrap();
+    let expected = "syntax error: unexpected end of input
+ --> test.mk:1:19
+  |
+1 | puts(\"total: ${1 +}\");
+  |                   ^
+  = hint: check for a missing ';', closing ')' or closing '}'
+";
+    assert_eq!(
+        Diagnostic::from(&errors[0]).render("test.mk", input),
+        expected
+    );
+}
+
+#[test]
+fn test_render_unterminated_string() {
+    let input = "let x = \"abc;\nputs(x);";
+    let errors = Parser::parse_program(input).err().unwrap();
+    let expected = "syntax error: unterminated string
+ --> test.mk:1:9
+  |
+1 | let x = \"abc;
+  |         ^^^^^
+  = hint: end the string with a closing '\"', or write a quote inside it as '\\\"'
+";
+    assert_eq!(
+        Diagnostic::from(&errors[0]).render("test.mk", input),
+        expected
+    );
+}
+
+#[test]
+fn test_render_compile_error() {
+    let input = "x = 1;";
+    let ast = Parser::parse_program(input).unwrap();
+    let error = Compiler::new().compile(ast).err().unwrap();
+    let expected = "compile error: undefined variable 'x'
+ --> test.mk:1:1
+  |
+1 | x = 1;
+  | ^^^^^
+  = hint: declare it first with 'let x = ...;'
+";
+    assert_eq!(Diagnostic::from(&error).render("test.mk", input), expected);
+}
+
+#[test]
+fn test_render_macro_error() {
+    let input = "let twice = macro(x) { [x, x] };\nputs(twice(1));";
+    let ast = Parser::parse_program(input).unwrap();
+    let error = Macros::new().expand(ast).err().unwrap();
+    let expected = "macro error: a macro must return a QUOTE, not ARRAY
+ --> test.mk:2:1
+  |
+2 | puts(twice(1));
+  | ^^^^^^^^^^^^^^
+  = hint: return the code to expand to as 'quote(...)'
+";
+    assert_eq!(Diagnostic::from(&error).render("test.mk", input), expected);
+}
+
+#[test]
+fn test_render_keeps_tabs_in_underline() {
+    let span = Span::new(Position::new(1, 3), Position::new(1, 4));
+    let error = Located::new(VmError::CallingNonFunction, span);
+    let expected = "runtime error: not a function
+ --> test.mk:1:3
+  |
+1 | \t\tx
+  | \t\t^
+";
+    assert_eq!(
+        Diagnostic::from(&error).render("test.mk", "\t\tx"),
+        expected
+    );
+}
+
+#[test]
+fn test_render_without_span() {
+    let error = Located::from(VmError::FrameStackOverflow);
+    let expected = "runtime error: maximum call depth exceeded
+  = hint: this is usually caused by unbounded recursion
+";
+    assert_eq!(Diagnostic::from(&error).render("test.mk", ""), expected);
+}
+
+#[test]
+fn test_render_stack_trace_collapses_recursion() {
+    let input = "let count = fn(n) { if (n == 0) { -true } else { 1 + count(n - 1) } };
+count(3);";
+    let expected = "runtime error: cannot negate BOOLEAN
+ --> test.mk:1:35
+  |
+1 | let count = fn(n) { if (n == 0) { -true } else { 1 + count(n - 1) } };
+  |                                   ^^^^^
+stack trace (most recent call first):
+  at count (test.mk:1:35, offset 20)
+  at count (test.mk:1:54, offset 48)
+  ... 2 more identical calls
+  at <main> (test.mk:2:1, offset 16)
+";
+    assert_eq!(run_and_render(input), expected);
+}
+
+#[test]
+fn test_render_uncaught_throw() {
+    let input = "let check = fn(x) { if (x < 0) { throw error(\"negative\", \"RangeError\"); }; x };\ncheck(-1);";
+    let expected = "runtime error: uncaught RangeError: negative
+ --> test.mk:1:34
+  |
+1 | let check = fn(x) { if (x < 0) { throw error(\"negative\", \"RangeError\"); }; x };
+  |                                  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+  = hint: catch it with 'try { ... } catch (e) { ... }'
+stack trace (most recent call first):
+  at check (test.mk:1:34, offset 32)
+  at <main> (test.mk:2:1, offset 20)
+";
+    assert_eq!(run_and_render(input), expected);
+}
+
+#[test]
+fn test_render_error_in_module() {
+    let dir = TestDir::new(
+        "render",
+        &[("lib.mk", "export let check = fn(x) {\n  x + true\n};")],
+    );
+    let input = "import \"lib.mk\" as lib;\nlib[\"check\"](1);";
+    let file = dir.0.join("main.mk");
+    let mut loader = Loader::new("main.mk", input, vec![], Rc::new(Builtins::new()));
+    let ast = Parser::parse_program(input).unwrap();
+    let ast = loader.load(ast, Some(&file)).unwrap();
+    let byte_code = Compiler::new().compile(ast).unwrap();
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+
+    let lib = dir.0.join("lib.mk").display().to_string();
+    let expected = format!(
+        "runtime error: cannot add INTEGER and BOOLEAN
+ --> {lib}:2:3
+  |
+2 |   x + true
+  |   ^^^^^^^^
+stack trace (most recent call first):
+  at check ({lib}:2:3, offset 8)
+  at <main> (main.mk:2:1, offset 48)
+"
+    );
+    assert_eq!(
+        Diagnostic::from(&error).render_in(&loader.sources),
+        expected
+    );
+}
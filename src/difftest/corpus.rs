@@ -0,0 +1,167 @@
+/// Programs both engines are expected to agree on. Each ends in an expression
+/// so that its value is compared as well as its output.
+pub const CORPUS: &[&str] = &[
+    // arithmetic and comparison
+    "1 + 2 * 3 - 4 / 2",
+    "(5 + 10 * 2 + 15 / 3) * 2 + -10",
+    "-(-7)",
+    "1 < 2 == true",
+    "3 > 4 != false",
+    "\"mon\" + \"key\"",
+    // truthiness and logic
+    "if (0) { 1 } else { 2 }",
+    "if (1) { 1 } else { 2 }",
+    "if (false) { 1 }",
+    "if (if (false) { 1 }) { 1 } else { 2 }",
+    "!true",
+    "!!false",
+    "0 && 1",
+    "1 || 0",
+    "let calls = 0; let f = fn() { calls = calls + 1; true }; false && f(); true || f(); calls",
+    // bindings, assignment and loops
+    "let a = 5; let b = a * 2; a + b",
+    "let x = 1; x = x + 1; x",
+    "let i = 0; let sum = 0; while (i < 10) { sum = sum + i; i = i + 1; } sum",
+    "let i = 0; while (i < 3) { let i = i + 1; } i",
+    "let sum = 0; for (x in [1, 2, 3]) { sum = sum + x; }; sum",
+    "for (k, v in {\"b\": 2, 1: [3], true: 0.5, \"a\": 1}) { puts(k, v); }",
+    "for (i, c in \"héllo\") { puts(\"${i}:${c}\"); }",
+    "[for (i in 0..4) { i * i }, for (i in 4..0) { i }, 2..5]",
+    "let f = fn(n) { for (i in 0..n) { if (i * i > n) { return i; } }; -1 }; [f(10), f(0)]",
+    "for (x in 5) { x }",
+    "0..\"a\"",
+    "let i = 0; let r = while (true) { i = i + 1; if (i == 3) { break; } i }; [i, r]",
+    "let n = []; for (x in 0..6) { if (x % 2 == 0) { continue; } if (x > 4) { break; } n = push(n, x); }; n",
+    "let s = 0; for (a in 0..4) { for (b in 0..4) { if (b > a) { break; } s = s + b; } }; s",
+    "for (x in [1, 2]) { let f = fn() { break; }; }",
+    "let r = []; let v = for (x in 0..6) { match (x % 3) { 0 => { continue; }, _ => { if (x > 4) { break; } r = push(r, x); } } }; [r, v]",
+    // functions and closures
+    "let add = fn(a, b) { a + b }; add(2, add(3, 4))",
+    "let early = fn(x) { if (x > 0) { return 1; } return -1; }; early(5) + early(-5)",
+    "fn() { }()",
+    "let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)",
+    "let counter = fn() { let n = 0; fn() { n = n + 1; n } }; let c = counter(); c(); c(); c()",
+    "let f = fn() { f = 1; }; f(); f",
+    "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+    "let twice = fn(f, x) { f(f(x)) }; twice(fn(x) { x * 3 }, 2)",
+    "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * x })",
+    "let f = fn() { 1 }; f",
+    "return 3; 4",
+    // arrays, hashes and builtins
+    "[1, 2 * 2, 3 + 3][1]",
+    "let a = [1, 2, 3]; a[0] + a[2]",
+    "{\"one\": 1, \"two\": 2}[\"two\"]",
+    "{1: true, false: \"no\", \"k\": [1]}",
+    "{\"a\": 1}[\"b\"]",
+    "len(\"hello\") + len([1, 2])",
+    "first([4, 5]) + last([4, 5])",
+    "rest(rest([1, 2, 3]))",
+    "first([])",
+    "push([], [1])",
+    "puts(\"hello\", 1, [true]); 5",
+    "len",
+    // errors
+    "1 + true",
+    "-\"a\"",
+    "\"a\" - \"b\"",
+    "true + false",
+    "undefined_name",
+    "let f = fn(a) { a }; f(1, 2)",
+    "len(1, 2)",
+    "len(5)",
+    "5()",
+    "{[1]: 2}",
+    "[1][\"a\"]",
+    "1[0]",
+    "let zero = 0; 10 / zero",
+    "let max = 9223372036854775807; max + 1",
+    "let min = -9223372036854775807 - 1; min - 1",
+    "let big = 3037000500; big * big",
+    "let min = -9223372036854775807 - 1; min / -1",
+    "let min = -9223372036854775807 - 1; -min",
+    "-9223372036854775807 - 1",
+    "1.5 + 2.25 * 2.0 - 1.0 / 4.0",
+    "[1 + 0.5, 0.1 + 0.2, 3.0, -2.5, 7 / 2.0]",
+    "[1e3, 2.5E-3, 1e11 * 1e11, 2.0 ** 63, {2.0 ** 63: 1}[9223372036854775807]]",
+    "[1 == 1.0, 0.1 + 0.2 > 0.3, 2.5 > 2, if (0.0) { 1 } else { 2 }]",
+    "{1: \"one\", 2.5: \"two and a half\"}[1.0]",
+    "let h = {1.0: 1, 1: 2}; h[1]",
+    "1.5 / 0",
+    "1.5 + \"a\"",
+    "puts(0.5, 100.0)",
+    "let log = fn(x) { puts(x); x }; log(1) < log(2)",
+    "let log = fn(x) { puts(x); x }; [log(1) <= log(2), log(3) >= log(4)]",
+    "[7 % 3, -7 % 3, 7.5 % 2, 2 ** 10, 2 ** 3 ** 2, -2 ** 2, 2 ** -1, 2.0 ** 0.5]",
+    "[6 & 3, 6 | 3, 6 ^ 3, 1 << 62, -16 >> 2, 1 + 2 << 3 & 15]",
+    "let zero = 0; 10 % zero",
+    "2 ** 63",
+    "1 << 64",
+    "1 >> -1",
+    "1.5 & 1",
+    r#""tab\tquote\"backslash\\dollar\$ \u{1F600}""#,
+    r#"let name = "Monkey"; "Hello ${name}, ${[1, 2.5, "x"]} ${1 + 1 == 2}""#,
+    r#"let log = fn(x) { puts(x); x }; "${log(1)}-${log("two")}-${ "${log(3)}" }""#,
+    r#""${len(1)}""#,
+    r###"let x = 1; [r"a\n${x}", r#"say "hi""#, len(r"\t") + len(r"")]"###,
+    "let x = 10 /* ten */ / 2; // five\n/* nested /* block */ comment */ x",
+    "let x = 1; let f = fn() { x + true }; puts(\"before\"); f()",
+    "let a = [1, [2], {}]; let b = a; a[0] += 1; a[1][0] *= 3; a[2][\"k\"] = 4; [a, b]",
+    "let x = 7; x -= 2; x /= 2; x",
+    "let a = [1]; a[1] = 2;",
+    "let h = {}; h[\"a\"] += 1;",
+    "let f = fn(a) { a[0] = 9; a }; let a = [1]; [f(a), a]",
+    "let [a, [b], ...c] = [1, [], 3, 4]; let {\"k\": k, 2: m} = {\"k\": a}; [a, b, c, k, m]",
+    "let f = fn([x, ...xs], {\"n\": n}) { [x * n, xs] }; f([1, 2], {\"n\": 3})",
+    "let [a] = {};",
+    "let f = fn(a, b = a + 1, ...c) { [a, b, c] }; [f(1), f(1, 5), f(1, 5, 6, 7)]",
+    "let log = fn(x) { puts(x); x }; let f = fn(a = log(1), b = log(2)) { a + b }; [f(), f(10), f(10, 20)]",
+    "let f = fn(a, b = 1) { a }; f()",
+    "let f = fn(a, b = 1) { a }; f(1, 2, 3)",
+    "fn(a = 1, b = a + 1, [c, d] = [b, a]) { [a, b, c, d] }()",
+    "let y = 5; fn(x = y, y = 2) { x }()",
+    // match
+    "let f = fn(v) { match (v) { 0 => \"zero\", [x, ...r] if x > 1 => r, [x] => x, {\"k\": k} => k, _ => v } }; [f(0.0), f([2, 3]), f([1]), f({\"k\": \"v\"}), f(\"s\")]",
+    "let x = 1; let y = match ([5, 6]) { [x, y] => { puts(x); x + y } }; [x, y]",
+    "match ([1, 2]) { [a, a] => a }",
+    "let log = fn(v) { puts(v); v }; match (3) { n if log(n > 5) => 1, n if log(n > 1) => 2, _ => 3 }",
+    "match ([1, 2]) { [a] => a, {\"a\": a} => a }",
+    "match (1) { _ => 1, 2 => 2 }",
+    // exceptions
+    "let f = fn(x) { if (x > 1) { throw error(\"big\", \"RangeError\"); }; x }; [try { f(2) } catch (e) { [e[\"kind\"], e[\"message\"], e] }, try { f(1) } catch (e) { e }]",
+    "let kind = fn(f) { try { f() } catch (e) { e[\"kind\"] } }; [kind(fn() { 1 + true }), kind(fn() { 1 % 0 }), kind(fn() { len(1, 2) }), kind(fn() { match (1) { 2 => 2 } }), kind(fn() { let [a] = 1; a })]",
+    "let f = fn() { try { return 1; } finally { puts(\"finally\"); } }; let s = 0; for (i in 0..5) { try { if (i == 1) { continue; }; if (i == 3) { break; }; s += i; } finally { puts(i); } }; [f(), s]",
+    "let deep = fn(n) { if (n == 0) { throw [n]; }; deep(n - 1) + 1 }; [1, try { deep(20) } catch (e) { e }, 2]",
+    "try { try { 1 / 0 } finally { puts(\"inner\"); } } catch (e) { puts(e); throw e; }",
+    "try { throw 1; } catch (e) { puts(e); } finally { puts(2); }; try { 1 } finally { throw \"late\"; }",
+    "let unless = macro(c, a, b) { quote(if (!(unquote(c))) { unquote(a) } else { unquote(b) }) }; unless(1 > 2, puts(\"less\"), puts(\"greater\")); unless(true, 1, 2)",
+    "let sum = macro(a, b) { quote(fn() { let x = unquote(a); let y = unquote(b); x + y }()) }; let x = 10; let y = 20; [sum(y, x), sum(sum(1, x), y)]",
+    "let twice = macro(x) { quote([unquote(x), unquote(x)]) }; let n = 0; let next = fn() { n += 1; n }; twice(next())",
+    "let quoted = macro() { quote(unquote(quote(1 + 2)) * unquote([3, \"four\"])[0]) }; quoted()",
+    "let m = macro() { quote(len([fn(len) { len }(1)])) }; m()",
+    "let x = 5; let m = macro() { quote([fn(x) { x }(1), x]) }; m()",
+    "let m = macro(x) { x + 1 }; m(1)",
+    "error(1)",
+    "let log = fn(x) { puts(x); x }; let {log(\"a\"): a, log(\"b\"): {log(\"c\"): b}} = log({});",
+];
+
+/// Programs the engines are known to disagree on, and how. Fixing one of these
+/// makes `test_known_divergences_still_diverge` fail, so that the program can
+/// be moved to [`CORPUS`].
+pub const KNOWN_DIVERGENCES: &[(&str, &str)] = &[
+    (
+        "!5",
+        "the evaluator negates the truthiness of any value, the VM only negates booleans",
+    ),
+    (
+        "[1, 2][2]",
+        "indexing past the end of an array is an error in the evaluator, null in the VM",
+    ),
+    (
+        "len = 1;",
+        "assigning to a builtin is an undefined name in the evaluator, an invalid assignment in the compiler",
+    ),
+    (
+        "true > false",
+        "the VM orders booleans, the evaluator only compares them for equality",
+    ),
+];
@@ -0,0 +1,453 @@
+/// A xorshift generator: fuzzing needs no extra dependencies, and every
+/// generated program can be reproduced from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero, and nearby seeds should not produce
+        // similar sequences
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Type {
+    Integer,
+    Boolean,
+    String,
+    Array,
+}
+
+const TYPES: [Type; 4] = [Type::Integer, Type::Boolean, Type::String, Type::Array];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Binding {
+    Variable(Type),
+    /// A function taking this many integers and returning an integer, the
+    /// last of which has a default if the flag is set.
+    Function(usize, bool),
+}
+
+const MAX_DEPTH: usize = 3;
+
+/// Generates random, mostly well-typed Monkey programs. A small share of
+/// expressions is deliberately given the wrong type, so that the errors the
+/// engines raise are compared too.
+pub struct ProgramGenerator {
+    rng: Rng,
+    /// Names in scope, innermost scope last.
+    scopes: Vec<Vec<(String, Binding)>>,
+    next_name: usize,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        ProgramGenerator {
+            rng: Rng::new(seed),
+            scopes: vec![vec![]],
+            next_name: 0,
+        }
+    }
+
+    pub fn program(&mut self) -> String {
+        let mut program = String::new();
+        for _ in 0..=self.rng.below(6) {
+            program.push_str(&self.statement());
+            program.push('\n');
+        }
+        let ty = *self.rng.pick(&TYPES);
+        program.push_str(&self.expression(ty, 0));
+        program
+    }
+
+    fn fresh_name(&mut self, prefix: &str) -> String {
+        // identifiers cannot contain digits, so the counter is spelled in letters
+        self.next_name += 1;
+        let mut name = prefix.to_string();
+        let mut n = self.next_name;
+        while n > 0 {
+            name.push((b'a' + (n % 26) as u8) as char);
+            n /= 26;
+        }
+        name
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .push((name.to_string(), binding));
+    }
+
+    fn variables(&self, ty: Type) -> Vec<String> {
+        self.scopes
+            .iter()
+            .flatten()
+            .filter(|(_, binding)| *binding == Binding::Variable(ty))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn functions(&self) -> Vec<(String, usize, bool)> {
+        self.scopes
+            .iter()
+            .flatten()
+            .filter_map(|(name, binding)| match binding {
+                Binding::Function(arity, has_default) => Some((name.clone(), *arity, *has_default)),
+                Binding::Variable(_) => None,
+            })
+            .collect()
+    }
+
+    fn statement(&mut self) -> String {
+        match self.rng.below(10) {
+            0 if self.rng.chance(50) => self.destructuring_let(),
+            0..=3 => {
+                let ty = *self.rng.pick(&TYPES);
+                let value = self.expression(ty, 0);
+                let name = self.fresh_name("var");
+                self.bind(&name, Binding::Variable(ty));
+                format!("let {name} = {value};")
+            }
+            4 => {
+                let ty = *self.rng.pick(&TYPES);
+                match self.variables(ty).as_slice() {
+                    [] => format!("puts({});", self.expression(ty, 0)),
+                    names => {
+                        let name = self.rng.pick(names).clone();
+                        match ty {
+                            Type::Integer if self.rng.chance(50) => {
+                                let op = self.rng.pick(&["+=", "-=", "*=", "/="]);
+                                format!("{name} {op} {};", self.expression(ty, 0))
+                            }
+                            Type::Array if self.rng.chance(50) => {
+                                let element_ty = *self.rng.pick(&TYPES);
+                                let value = self.expression(element_ty, 0);
+                                format!("if (len({name}) > 0) {{ {name}[0] = {value}; }}")
+                            }
+                            _ => format!("{name} = {};", self.expression(ty, 0)),
+                        }
+                    }
+                }
+            }
+            5 | 6 => self.function(),
+            7 if self.rng.chance(50) => self.while_loop(),
+            7 => self.for_loop(),
+            8 if self.rng.chance(40) => self.try_statement(),
+            _ => {
+                let ty = *self.rng.pick(&TYPES);
+                format!("puts({});", self.expression(ty, 0))
+            }
+        }
+    }
+
+    /// A `let` unpacking an array literal, with a rest for some of its elements.
+    fn destructuring_let(&mut self) -> String {
+        let types = (0..=self.rng.below(3))
+            .map(|_| *self.rng.pick(&TYPES))
+            .collect::<Vec<_>>();
+        let values = types
+            .iter()
+            .map(|ty| self.expression(*ty, 1))
+            .collect::<Vec<_>>();
+        let bound = self.rng.below(types.len() + 1);
+        let mut names = vec![];
+        for ty in &types[..bound] {
+            let name = self.fresh_name("var");
+            self.bind(&name, Binding::Variable(*ty));
+            names.push(name);
+        }
+        if bound < types.len() || self.rng.chance(50) {
+            let name = self.fresh_name("var");
+            self.bind(&name, Binding::Variable(Type::Array));
+            names.push(format!("...{name}"));
+        }
+        format!("let [{}] = [{}];", names.join(", "), values.join(", "))
+    }
+
+    /// A statement in a `try` block, with a `catch` block printing the kind of
+    /// any error, a `finally` block, or both.
+    fn try_statement(&mut self) -> String {
+        // the statements' variables may never have been bound, so are not
+        // made visible after them
+        self.scopes.push(vec![]);
+        let body = self.statement();
+        self.scopes.pop();
+        let mut handlers = String::new();
+        if self.rng.chance(80) {
+            handlers.push_str(" catch (e) { puts(e[\"kind\"]); }");
+        }
+        if handlers.is_empty() || self.rng.chance(30) {
+            self.scopes.push(vec![]);
+            handlers.push_str(&format!(" finally {{ {} }}", self.statement()));
+            self.scopes.pop();
+        }
+        format!("try {{ {body} }}{handlers};")
+    }
+
+    fn function(&mut self) -> String {
+        let arity = self.rng.below(3);
+        let name = self.fresh_name("fun");
+        let params = (0..arity)
+            .map(|_| (self.fresh_name("arg"), Binding::Variable(Type::Integer)))
+            .collect::<Vec<_>>();
+        let mut param_list = params
+            .iter()
+            .map(|(param, _)| param.clone())
+            .collect::<Vec<_>>();
+
+        let has_default = arity > 0 && self.rng.chance(30);
+        if has_default {
+            // a default can refer to the parameters before it
+            self.scopes.push(params[..arity - 1].to_vec());
+            let default = self.expression(Type::Integer, 2);
+            self.scopes.pop();
+            param_list[arity - 1].push_str(&format!(" = {default}"));
+        }
+        let param_list = param_list.join(", ");
+
+        self.scopes.push(params);
+        let mut body = String::new();
+        if self.rng.chance(50) {
+            body.push_str(&self.statement());
+            body.push(' ');
+        }
+        body.push_str(&self.expression(Type::Integer, 1));
+        self.scopes.pop();
+
+        self.bind(&name, Binding::Function(arity, has_default));
+        format!("let {name} = fn({param_list}) {{ {body} }};")
+    }
+
+    fn while_loop(&mut self) -> String {
+        let counter = self.fresh_name("idx");
+        let bound = self.rng.below(5);
+        // the counter is not made visible to the body, so it cannot be
+        // reassigned and the loop always ends
+        self.scopes.push(vec![]);
+        // `continue` would skip the counter's increment
+        let body = self.loop_body(&["break"]);
+        self.scopes.pop();
+        format!("let {counter} = 0; while ({counter} < {bound}) {{ {body} {counter} = {counter} + 1; }};")
+    }
+
+    fn for_loop(&mut self) -> String {
+        let variable = self.fresh_name("item");
+        // ranges are kept short; the elements of an array may be of any type,
+        // so the body cannot see them
+        let (iterable, ty) = match self.rng.below(3) {
+            0 => (format!("0..{}", self.rng.below(5)), Some(Type::Integer)),
+            1 => (self.expression(Type::Array, 1), None),
+            _ => (self.expression(Type::String, 1), Some(Type::String)),
+        };
+        self.scopes.push(
+            ty.map(|ty| (variable.clone(), Binding::Variable(ty)))
+                .into_iter()
+                .collect(),
+        );
+        let body = self.loop_body(&["break", "continue"]);
+        self.scopes.pop();
+        format!("for ({variable} in {iterable}) {{ {body} }};")
+    }
+
+    /// A statement, sometimes preceded by one of `controls` under a condition.
+    fn loop_body(&mut self, controls: &[&str]) -> String {
+        let mut body = String::new();
+        if self.rng.chance(30) {
+            let control = self.rng.pick(controls);
+            let condition = self.expression(Type::Boolean, 1);
+            body.push_str(&format!("if ({condition}) {{ {control}; }} "));
+        }
+        body.push_str(&self.statement());
+        body
+    }
+
+    fn expression(&mut self, ty: Type, depth: usize) -> String {
+        // occasionally produce an operand of the wrong type
+        let ty = if depth > 0 && self.rng.chance(3) {
+            *self.rng.pick(&TYPES)
+        } else {
+            ty
+        };
+        let leaf = depth >= MAX_DEPTH || self.rng.chance(30);
+
+        let variables = self.variables(ty);
+        if !variables.is_empty() && self.rng.chance(30) {
+            return self.rng.pick(&variables).clone();
+        }
+
+        match ty {
+            Type::Integer => self.integer(depth, leaf),
+            Type::Boolean => self.boolean(depth, leaf),
+            Type::String => self.string(depth, leaf),
+            Type::Array => self.array(depth, leaf),
+        }
+    }
+
+    fn integer(&mut self, depth: usize, leaf: bool) -> String {
+        if leaf {
+            // the largest integer now and then, to provoke overflows
+            if self.rng.chance(3) {
+                return i64::MAX.to_string();
+            }
+            // and a float, so that arithmetic mixes the two
+            if self.rng.chance(5) {
+                return self.rng.pick(&["0.0", "0.5", "1.0", "2.5"]).to_string();
+            }
+            return self.rng.below(10).to_string();
+        }
+        let depth = depth + 1;
+        match self.rng.below(10) {
+            0 | 1 => {
+                let op = self
+                    .rng
+                    .pick(&["+", "-", "*", "/", "%", "**", "&", "|", "^", "<<", ">>"]);
+                let left = self.expression(Type::Integer, depth);
+                let right = self.expression(Type::Integer, depth);
+                format!("({left} {op} {right})")
+            }
+            2 => format!("-{}", self.expression(Type::Integer, depth)),
+            3 => format!("len({})", self.expression(Type::String, depth)),
+            4 => format!("len({})", self.expression(Type::Array, depth)),
+            5 => format!(
+                "if ({}) {{ {} }} else {{ {} }}",
+                self.expression(Type::Boolean, depth),
+                self.expression(Type::Integer, depth),
+                self.expression(Type::Integer, depth)
+            ),
+            6 if !self.functions().is_empty() => {
+                let (name, arity, has_default) = self.rng.pick(&self.functions()).clone();
+                // occasionally call with the wrong number of arguments
+                let arity = if self.rng.chance(5) {
+                    arity + 1
+                } else if has_default && self.rng.chance(50) {
+                    arity - 1
+                } else {
+                    arity
+                };
+                let args = (0..arity)
+                    .map(|_| self.expression(Type::Integer, depth))
+                    .collect::<Vec<_>>();
+                format!("{name}({})", args.join(", "))
+            }
+            7 => {
+                let len = self.rng.below(3) + 1;
+                let elements = (0..len)
+                    .map(|_| self.expression(Type::Integer, depth))
+                    .collect::<Vec<_>>();
+                format!("[{}][{}]", elements.join(", "), self.rng.below(len))
+            }
+            8 => {
+                let value = self.expression(Type::Array, depth);
+                let literal = self.rng.below(10);
+                let guard = self.expression(Type::Boolean, depth);
+                let first = self.expression(Type::Integer, depth);
+                let fallback = self.expression(Type::Integer, depth);
+                // arm bodies are parenthesized, as one starting with '{' would be
+                // a block; the wildcard arm is sometimes left out so that
+                // nothing matches
+                let wildcard = if self.rng.chance(80) {
+                    format!(", _ => ({fallback})")
+                } else {
+                    String::new()
+                };
+                format!(
+                    "match ({value}) {{ [{literal}] => ({first}), [x, ...xs] if {guard} => x + len(xs), [] => 0{wildcard} }}"
+                )
+            }
+            _ => format!(
+                "{{\"k\": {}}}[\"k\"]",
+                self.expression(Type::Integer, depth)
+            ),
+        }
+    }
+
+    fn boolean(&mut self, depth: usize, leaf: bool) -> String {
+        if leaf {
+            return self.rng.pick(&["true", "false"]).to_string();
+        }
+        let depth = depth + 1;
+        match self.rng.below(4) {
+            0 | 1 => self.boolean_operation(depth),
+            // the engines are known to disagree on `!` applied to anything
+            // but a boolean, so its operand is an operation that can only
+            // produce a boolean or an error
+            2 => format!("!{}", self.boolean_operation(depth)),
+            _ => format!(
+                "if ({}) {{ {} }} else {{ {} }}",
+                self.expression(Type::Boolean, depth),
+                self.expression(Type::Boolean, depth),
+                self.expression(Type::Boolean, depth)
+            ),
+        }
+    }
+
+    fn boolean_operation(&mut self, depth: usize) -> String {
+        let (ops, ty): (&[&str], _) = if self.rng.chance(50) {
+            (&["<", ">", "<=", ">=", "==", "!="], Type::Integer)
+        } else {
+            (&["&&", "||", "==", "!="], Type::Boolean)
+        };
+        let op = self.rng.pick(ops);
+        let left = self.expression(ty, depth);
+        let right = self.expression(ty, depth);
+        format!("({left} {op} {right})")
+    }
+
+    fn string(&mut self, depth: usize, leaf: bool) -> String {
+        if leaf {
+            return format!("\"{}\"", self.rng.pick(&["", "a", "monkey", "b c"]));
+        }
+        if self.rng.chance(30) {
+            // interpolate a value of any type
+            let ty = *self.rng.pick(&TYPES);
+            return format!("\"<${{{}}}>\"", self.expression(ty, depth + 1));
+        }
+        let left = self.expression(Type::String, depth + 1);
+        let right = self.expression(Type::String, depth + 1);
+        format!("({left} + {right})")
+    }
+
+    fn array(&mut self, depth: usize, leaf: bool) -> String {
+        if leaf {
+            return "[]".to_string();
+        }
+        let depth = depth + 1;
+        match self.rng.below(3) {
+            0 => format!(
+                "push({}, {})",
+                self.expression(Type::Array, depth),
+                self.expression(Type::Integer, depth)
+            ),
+            1 => format!("rest({})", self.expression(Type::Array, depth)),
+            _ => {
+                let elements = (0..self.rng.below(4))
+                    .map(|_| {
+                        let ty = *self.rng.pick(&TYPES);
+                        self.expression(ty, depth)
+                    })
+                    .collect::<Vec<_>>();
+                format!("[{}]", elements.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Differential testing of the two engines: the same program is run through
+//! the evaluator and through the compiler and VM, and any difference in the
+//! value produced, the output printed or the class of error raised is reported.
+#![cfg(test)]
+
+mod corpus;
+mod fuzz;
+
+mod tests;
+
+use crate::compiler::{Compiler, CompilerError};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::macros::Macros;
+use crate::evaluator::{eval, EvalError};
+use crate::object::builtins::capture_output;
+use crate::object::{format_float, Hashable, Object};
+use crate::parser::Parser;
+use crate::vm::{VirtualMachine, VmError};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// The kind of error a program stopped with, in terms both engines share.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorClass {
+    Syntax,
+    UndefinedName,
+    InvalidAssignment,
+    Type,
+    NotAFunction,
+    WrongArguments,
+    IndexOutOfBounds,
+    StackOverflow,
+    DivisionByZero,
+    IntegerOverflow,
+    InvalidShiftAmount,
+    NoMatchingArm,
+    /// A value was thrown and not caught.
+    Uncaught,
+    /// Expanding the program's macros failed, which it does the same way for
+    /// both engines.
+    Macro,
+    /// Code beyond what the VM's instructions can encode, e.g. a call with
+    /// too many arguments.
+    Limit,
+    /// An error that points to a bug in the engine itself.
+    Internal,
+    /// The engine panicked rather than raising a Monkey error.
+    Panic,
+}
+
+impl From<&EvalError> for ErrorClass {
+    fn from(error: &EvalError) -> Self {
+        match error {
+            EvalError::IncompatibleTypes
+            | EvalError::InvalidOperands(..)
+            | EvalError::InvalidOperand(..)
+            | EvalError::NotIterable(_)
+            | EvalError::CannotDestructure(..)
+            | EvalError::CannotUnquote(_) => ErrorClass::Type,
+            EvalError::UnrecognisedIdentifier(_) => ErrorClass::UndefinedName,
+            EvalError::NotAFunction => ErrorClass::NotAFunction,
+            EvalError::IncorrectNumberOfArgs => ErrorClass::WrongArguments,
+            EvalError::IndexOutOfBounds => ErrorClass::IndexOutOfBounds,
+            EvalError::DivisionByZero => ErrorClass::DivisionByZero,
+            EvalError::IntegerOverflow => ErrorClass::IntegerOverflow,
+            EvalError::InvalidShiftAmount(_) => ErrorClass::InvalidShiftAmount,
+            EvalError::NoMatchingArm(_) => ErrorClass::NoMatchingArm,
+            EvalError::Thrown(_) => ErrorClass::Uncaught,
+            EvalError::StackOverflow => ErrorClass::StackOverflow,
+        }
+    }
+}
+
+impl From<&CompilerError> for ErrorClass {
+    fn from(error: &CompilerError) -> Self {
+        match error {
+            CompilerError::UndefinedVariable(_) => ErrorClass::UndefinedName,
+            CompilerError::InvalidAssignment(_) => ErrorClass::InvalidAssignment,
+            CompilerError::TooManyArguments
+            | CompilerError::TooManyLocals
+            | CompilerError::TooManyFreeVariables => ErrorClass::Limit,
+            CompilerError::InvalidOpCode => ErrorClass::Internal,
+        }
+    }
+}
+
+impl From<&VmError> for ErrorClass {
+    fn from(error: &VmError) -> Self {
+        match error {
+            VmError::IncompatibleTypes
+            | VmError::InvalidOperands(..)
+            | VmError::InvalidOperand(..)
+            | VmError::UnhashableKey
+            | VmError::IndexNotSupported
+            | VmError::NotIterable(_)
+            | VmError::CannotDestructure(..) => ErrorClass::Type,
+            VmError::CallingNonFunction => ErrorClass::NotAFunction,
+            VmError::WrongArguments => ErrorClass::WrongArguments,
+            VmError::IndexOutOfBounds => ErrorClass::IndexOutOfBounds,
+            VmError::StackOverflow | VmError::FrameStackOverflow => ErrorClass::StackOverflow,
+            VmError::DivisionByZero => ErrorClass::DivisionByZero,
+            VmError::IntegerOverflow => ErrorClass::IntegerOverflow,
+            VmError::InvalidShiftAmount(_) => ErrorClass::InvalidShiftAmount,
+            VmError::NoMatchingArm(_) => ErrorClass::NoMatchingArm,
+            VmError::Thrown(_) => ErrorClass::Uncaught,
+            VmError::UnknownOpCode
+            | VmError::StackUnderflow
+            | VmError::EmptyStack
+            | VmError::FrameStackUnderflow
+            | VmError::UnknownBuiltin(_) => ErrorClass::Internal,
+        }
+    }
+}
+
+/// What running a program observably did.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    /// The program's value, normalised by [`normalise`], or why it failed.
+    pub result: Result<String, ErrorClass>,
+    /// Everything the program printed, or `None` if it was rejected before
+    /// it started running.
+    pub output: Option<String>,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(value) => write!(f, "value {value}")?,
+            Err(class) => write!(f, "error {class:?}")?,
+        }
+        match &self.output {
+            Some(output) => write!(f, ", output {output:?}"),
+            None => write!(f, ", did not run"),
+        }
+    }
+}
+
+/// A program the engines disagree on.
+#[derive(Debug)]
+pub struct Divergence {
+    pub program: String,
+    pub eval: Outcome,
+    pub vm: Outcome,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "engines disagree on:\n{}", self.program)?;
+        writeln!(f, "  eval: {}", self.eval)?;
+        write!(f, "  vm:   {}", self.vm)
+    }
+}
+
+/// Renders `object` so that values the engines represent differently but
+/// which are equal to a Monkey program, e.g. functions and closures, compare
+/// equal. Hash entries are sorted so their order does not matter.
+pub fn normalise(object: &Object) -> String {
+    match object {
+        Object::Function(_) | Object::CompiledFunc(_) | Object::Closure(_) => "<function>".into(),
+        Object::Builtin(_) => "<builtin>".into(),
+        Object::Return(object) => normalise(object),
+        Object::Upvalue(cell) => normalise(&cell.borrow()),
+        Object::String(string) => format!("{string:?}"),
+        Object::Array(array) => {
+            let elements = array.iter().map(|e| normalise(e)).collect::<Vec<_>>();
+            format!("[{}]", elements.join(", "))
+        }
+        Object::Hash(map) => {
+            let mut pairs = map
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Hashable::String(string) => format!("{string:?}"),
+                        Hashable::Integer(int) => int.to_string(),
+                        Hashable::Boolean(bool) => bool.to_string(),
+                        Hashable::Float(bits) => format_float(f64::from_bits(*bits)),
+                    };
+                    format!("{key}: {}", normalise(value))
+                })
+                .collect::<Vec<_>>();
+            pairs.sort();
+            format!("{{{}}}", pairs.join(", "))
+        }
+        _ => object.to_string(),
+    }
+}
+
+fn rejected(class: ErrorClass) -> Outcome {
+    Outcome {
+        result: Err(class),
+        output: None,
+    }
+}
+
+pub fn run_eval(input: &str) -> Outcome {
+    let Ok(program) = Parser::parse_program(input) else {
+        return rejected(ErrorClass::Syntax);
+    };
+    let Ok(program) = Macros::new().expand(program) else {
+        return rejected(ErrorClass::Macro);
+    };
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let (result, output) = capture_output(|| catch_unwind(AssertUnwindSafe(|| eval(program, env))));
+    Outcome {
+        result: match result {
+            Ok(Ok(object)) => Ok(normalise(&object)),
+            Ok(Err(e)) => Err(ErrorClass::from(&e.error)),
+            Err(_) => Err(ErrorClass::Panic),
+        },
+        output: Some(output),
+    }
+}
+
+pub fn run_vm(input: &str) -> Outcome {
+    let Ok(program) = Parser::parse_program(input) else {
+        return rejected(ErrorClass::Syntax);
+    };
+    let Ok(program) = Macros::new().expand(program) else {
+        return rejected(ErrorClass::Macro);
+    };
+    let byte_code = match Compiler::new().compile(program) {
+        Ok(byte_code) => byte_code,
+        Err(e) => return rejected(ErrorClass::from(&e.error)),
+    };
+    let (result, output) =
+        capture_output(|| catch_unwind(AssertUnwindSafe(|| VirtualMachine::new(byte_code).run())));
+    Outcome {
+        result: match result {
+            Ok(Ok(object)) => Ok(normalise(&object)),
+            // a program ending in a statement without a value evaluates to null
+            Ok(Err(e)) if e.error == VmError::EmptyStack => Ok(normalise(&Object::Null)),
+            Ok(Err(e)) => Err(ErrorClass::from(&e.error)),
+            Err(_) => Err(ErrorClass::Panic),
+        },
+        output: Some(output),
+    }
+}
+
+/// Runs `program` on both engines, returning how they differ, if they do.
+///
+/// The compiler rejects some programs, e.g. ones using an undefined name,
+/// that the evaluator only fails on once it reaches the error. So output is
+/// only compared if both engines ran the program.
+pub fn compare(program: &str) -> Option<Divergence> {
+    let eval = run_eval(program);
+    let vm = run_vm(program);
+    let same_output = match (&eval.output, &vm.output) {
+        (Some(eval_output), Some(vm_output)) => eval_output == vm_output,
+        _ => true,
+    };
+    (eval.result != vm.result || !same_output).then(|| Divergence {
+        program: program.to_string(),
+        eval,
+        vm,
+    })
+}
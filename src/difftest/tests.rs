@@ -0,0 +1,103 @@
+#![cfg(test)]
+
+use crate::difftest::corpus::{CORPUS, KNOWN_DIVERGENCES};
+use crate::difftest::fuzz::ProgramGenerator;
+use crate::difftest::{compare, run_eval, run_vm, ErrorClass};
+
+fn divergences(programs: impl Iterator<Item = String>) -> Vec<String> {
+    programs
+        .filter_map(|program| compare(&program))
+        .map(|divergence| divergence.to_string())
+        .collect()
+}
+
+#[test]
+fn test_corpus_engines_agree() {
+    let found = divergences(CORPUS.iter().map(|program| program.to_string()));
+    assert!(found.is_empty(), "{}", found.join("\n\n"));
+}
+
+#[test]
+fn test_known_divergences_still_diverge() {
+    for (program, reason) in KNOWN_DIVERGENCES {
+        assert!(
+            compare(program).is_some(),
+            "engines now agree on `{program}` ({reason}), move it to the corpus"
+        );
+    }
+}
+
+#[test]
+fn test_compare_reports_output_differences() {
+    let divergence = compare("puts([1, 2][2])").unwrap();
+    assert_eq!(divergence.eval.output, Some("".to_string()));
+    assert_eq!(divergence.vm.output, Some("Null\n".to_string()));
+}
+
+#[test]
+fn test_compare_ignores_output_of_programs_the_compiler_rejects() {
+    let program = "puts(1); undefined_name";
+    assert_eq!(run_eval(program).output, Some("1\n".to_string()));
+    assert_eq!(run_vm(program).output, None);
+    assert!(compare(program).is_none());
+}
+
+#[test]
+fn test_error_classes() {
+    assert_eq!(run_vm("1 +").result, Err(ErrorClass::Syntax));
+    assert_eq!(run_eval("5()").result, Err(ErrorClass::NotAFunction));
+    assert_eq!(run_vm("5()").result, Err(ErrorClass::NotAFunction));
+}
+
+#[test]
+fn test_generator_is_deterministic() {
+    assert_eq!(
+        ProgramGenerator::new(7).program(),
+        ProgramGenerator::new(7).program()
+    );
+    assert_ne!(
+        ProgramGenerator::new(7).program(),
+        ProgramGenerator::new(8).program()
+    );
+}
+
+#[test]
+fn test_generated_programs_parse() {
+    for seed in 0..100 {
+        let program = ProgramGenerator::new(seed).program();
+        assert_ne!(
+            run_eval(&program).result,
+            Err(ErrorClass::Syntax),
+            "{program}"
+        );
+    }
+}
+
+#[test]
+fn test_fuzz_engines_agree() {
+    let found = divergences((0..300).map(|seed| ProgramGenerator::new(seed).program()));
+    assert!(found.is_empty(), "{}", found.join("\n\n"));
+}
+
+/// A longer fuzzing run to look for new divergences, e.g.
+/// `MONKEY_FUZZ_SEEDS=100000 cargo test fuzz_search -- --ignored`.
+#[test]
+#[ignore]
+fn test_fuzz_search() {
+    let seeds = std::env::var("MONKEY_FUZZ_SEEDS")
+        .ok()
+        .and_then(|seeds| seeds.parse().ok())
+        .unwrap_or(10_000);
+    let found = (0..seeds)
+        .filter_map(|seed| {
+            let divergence = compare(&ProgramGenerator::new(seed).program())?;
+            Some(format!("seed {seed}: {divergence}"))
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        found.is_empty(),
+        "{} divergences:\n\n{}",
+        found.len(),
+        found.join("\n\n")
+    );
+}
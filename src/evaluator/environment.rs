@@ -1,3 +1,4 @@
+use crate::object::builtins::Builtins;
 use crate::object::Object;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -7,24 +8,64 @@ use std::rc::Rc;
 pub struct Environment {
     store: HashMap<String, Rc<Object>>,
     outer: Option<Rc<RefCell<Environment>>>,
+    /// The exports of the modules run so far, by the number of the module's
+    /// source, shared by every environment of a program.
+    modules: Rc<RefCell<HashMap<usize, Rc<Object>>>>,
+    /// The builtins names not bound by any scope refer to, shared by every
+    /// environment of a program.
+    builtins: Rc<Builtins>,
 }
 
-#[allow(unused)]
 impl Environment {
     pub fn new() -> Environment {
+        Environment::new_with_builtins(Rc::new(Builtins::new()))
+    }
+
+    pub fn new_with_builtins(builtins: Rc<Builtins>) -> Environment {
         Environment {
             store: HashMap::new(),
             outer: None,
+            modules: Rc::new(RefCell::new(HashMap::new())),
+            builtins,
         }
     }
 
     pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let modules = Rc::clone(&outer.borrow().modules);
+        let builtins = Rc::clone(&outer.borrow().builtins);
         Rc::new(RefCell::new(Environment {
             store: HashMap::new(),
             outer: Some(outer),
+            modules,
+            builtins,
+        }))
+    }
+
+    /// A new top-level environment for a module to run in, which shares the
+    /// modules run so far and the builtins.
+    pub fn new_module(&self) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: None,
+            modules: Rc::clone(&self.modules),
+            builtins: Rc::clone(&self.builtins),
         }))
     }
 
+    /// The builtin or namespace of builtins named `name`.
+    pub fn get_builtin(&self, name: &str) -> Option<Rc<Object>> {
+        self.builtins.get(name)
+    }
+
+    /// The exports of the module numbered `source`, if it has been run.
+    pub fn get_module(&self, source: usize) -> Option<Rc<Object>> {
+        self.modules.borrow().get(&source).cloned()
+    }
+
+    pub fn set_module(&mut self, source: usize, exports: Rc<Object>) {
+        self.modules.borrow_mut().insert(source, exports);
+    }
+
     pub fn get(&self, key: &str) -> Option<Rc<Object>> {
         // checking `store` before `outer` means a variable in the inner scope with
         // the same name as a variable in the outer scope will SHADOW that variable
@@ -40,8 +81,15 @@ impl Environment {
         result
     }
 
+    /// Binds `key` in this scope, shadowing any binding of it in outer scopes.
+    pub fn define(&mut self, key: &str, val: Rc<Object>) {
+        self.store.insert(key.to_string(), val);
+    }
+
+    /// Updates the innermost binding of `key`, or binds it in this scope if
+    /// there is none.
     pub fn set(&mut self, key: &str, val: Rc<Object>) {
-        if self.store.get(key).is_some() {
+        if self.store.contains_key(key) {
             self.store.insert(key.to_string(), Rc::clone(&val));
             return;
         } else if let Some(outer) = &self.outer {
@@ -53,3 +101,9 @@ impl Environment {
         self.store.insert(key.to_string(), Rc::clone(&val));
     }
 }
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
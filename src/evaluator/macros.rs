@@ -0,0 +1,469 @@
+use crate::evaluator::environment::Environment;
+use crate::evaluator::{apply_function, EvalError};
+use crate::object::builtins::Builtins;
+use crate::object::{Function, Object};
+use crate::parser::ast::{Expression, MatchArm, Program, Rewrite, Statement};
+use crate::span::Located;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+pub const QUOTE: &str = "quote";
+pub const UNQUOTE: &str = "unquote";
+
+/// How deeply macro calls in the code macros expand to may be nested before
+/// expansion is taken to never end.
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+/// The macros defined so far, which a REPL session keeps from line to line.
+///
+/// A macro is bound by a top-level `let`, e.g.
+/// `let unless = macro(cond, body) { quote(if (!unquote(cond)) { unquote(body) }) };`.
+/// Its arguments are the code it is called with, quoted, and the quote it
+/// returns is the code the call is replaced with. Variables bound in the
+/// quotes of its body are renamed on each expansion, so that they cannot be
+/// confused with the caller's.
+pub struct Macros {
+    env: Rc<RefCell<Environment>>,
+    /// The number of macro calls expanded so far, which makes the names
+    /// variables are renamed to unique.
+    expansions: usize,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Macros::new_with_builtins(Rc::new(Builtins::new()))
+    }
+
+    /// Macros whose bodies, run as they are expanded, call the builtins of
+    /// `builtins`.
+    pub fn new_with_builtins(builtins: Rc<Builtins>) -> Self {
+        Macros {
+            env: Rc::new(RefCell::new(Environment::new_with_builtins(builtins))),
+            expansions: 0,
+        }
+    }
+
+    /// Defines the macros bound in `program`, removing their `let` statements,
+    /// and then replaces each call to a macro in the rest of it with the code
+    /// the macro expands to. Any `quote`, `unquote` or macro literal left after
+    /// that is an error.
+    pub fn expand(&mut self, program: Program) -> Result<Program, Located<MacroError>> {
+        let Program(statements) = program;
+
+        let mut remaining = vec![];
+        for statement in statements {
+            match statement {
+                Statement::Let(
+                    Expression::Identifier(name),
+                    Expression::Macro(parameters, body),
+                    _,
+                ) => {
+                    let function = Function {
+                        parameters,
+                        body: *body,
+                        env: Rc::clone(&self.env),
+                    };
+                    self.env
+                        .borrow_mut()
+                        .define(&name, Rc::new(Object::Macro(function)));
+                }
+                statement => remaining.push(statement),
+            }
+        }
+
+        let mut expanded = vec![];
+        for statement in remaining {
+            let span = statement.span();
+            let statement = statement
+                .modify(&mut |expression| self.expand_expression(expression, 0))
+                .and_then(check_expanded)
+                .map_err(|e| e.or_at(span))?;
+            expanded.push(statement);
+        }
+
+        Ok(Program(expanded))
+    }
+
+    /// Expands `expression` if it is a call to a macro, and then the calls to
+    /// macros in the code it expands to, which are `depth + 1` deep.
+    fn expand_expression(
+        &mut self,
+        expression: Expression,
+        depth: usize,
+    ) -> Result<Rewrite, Located<MacroError>> {
+        let Expression::Call(func, args, span) = expression else {
+            return Ok(Rewrite::Continue(expression));
+        };
+        let Some(function) = self.get_macro(&func) else {
+            return Ok(Rewrite::Continue(Expression::Call(func, args, span)));
+        };
+
+        if depth == MAX_EXPANSION_DEPTH {
+            return Err(MacroError::TooDeep.into());
+        }
+        self.expansions += 1;
+
+        let args: Vec<_> = args
+            .into_iter()
+            .map(|arg| Rc::new(Object::Quote(arg)))
+            .collect();
+        let function = Function {
+            body: rename_quoted_bindings(function.body, self.expansions),
+            ..function
+        };
+        let expanded = apply_function(Rc::new(Object::Function(function)), &args).map_err(
+            |Located { error, span }| Located {
+                error: MacroError::Eval(error),
+                span,
+            },
+        )?;
+
+        let Object::Quote(expansion) = &*expanded else {
+            return Err(MacroError::NotQuoted(expanded.type_name()).into());
+        };
+        let expansion = expansion
+            .clone()
+            .modify(&mut |expression| self.expand_expression(expression, depth + 1))?;
+
+        Ok(Rewrite::Break(expansion))
+    }
+
+    fn get_macro(&self, func: &Expression) -> Option<Function> {
+        let Expression::Identifier(name) = func else {
+            return None;
+        };
+        match self.env.borrow().get(name).as_deref() {
+            Some(Object::Macro(function)) => Some(function.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Macros {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `func` is the name `name`, as the function of a call.
+pub fn is_call_to(func: &Expression, name: &str) -> bool {
+    matches!(func, Expression::Identifier(id) if id == name)
+}
+
+/// Checks that only code that can be run is left in an expanded statement.
+fn check_expanded(statement: Statement) -> Result<Statement, Located<MacroError>> {
+    statement.modify(&mut |expression| match expression {
+        Expression::Macro(..) => Err(MacroError::MisplacedMacro.into()),
+        Expression::Call(func, _, _) if is_call_to(&func, QUOTE) || is_call_to(&func, UNQUOTE) => {
+            let Expression::Identifier(name) = *func else {
+                unreachable!("checked to be an identifier")
+            };
+            Err(MacroError::OutsideMacro(name).into())
+        }
+        expression => Ok(Rewrite::Continue(expression)),
+    })
+}
+
+/// Renames the variables bound by the code in each `quote(...)` in `body`
+/// after the expansion numbered `expansion`, leaving the code in `unquote(...)`
+/// as it is. Generated names cannot be written in programs, as identifiers
+/// cannot contain '@'.
+fn rename_quoted_bindings(body: Statement, expansion: usize) -> Statement {
+    let renamed = body.modify(&mut |expression| match expression {
+        Expression::Call(func, args, span) if is_call_to(&func, QUOTE) => {
+            let mut renamer = Renamer {
+                expansion,
+                bound: HashSet::new(),
+                enclosing: HashSet::new(),
+            };
+            for arg in args.iter() {
+                let statement = Statement::Expression(arg.clone(), span);
+                collect_bindings(&statement, &mut renamer.enclosing);
+            }
+            let args = args
+                .into_iter()
+                .map(|arg| renamer.expression(arg))
+                .collect();
+            Ok::<_, Infallible>(Rewrite::Break(Expression::Call(func, args, span)))
+        }
+        expression => Ok(Rewrite::Continue(expression)),
+    });
+    let Ok(renamed) = renamed;
+    renamed
+}
+
+/// The name a variable renamed by macro expansion has in the source.
+pub fn source_name(name: &str) -> &str {
+    name.split_once('@').map_or(name, |(name, _)| name)
+}
+
+/// Adds the names of the variables bound by quoted code to `names`, leaving
+/// out those bound inside the functions in it.
+fn collect_bindings(quoted: &Statement, names: &mut HashSet<String>) {
+    let_names(quoted, names);
+    let _ = quoted.clone().modify(&mut |expression| {
+        match &expression {
+            Expression::Call(func, _, _) if is_call_to(func, UNQUOTE) => {
+                return Ok::<_, Infallible>(Rewrite::Break(expression));
+            }
+            Expression::Function(..) | Expression::Macro(..) => {
+                return Ok(Rewrite::Break(expression));
+            }
+            Expression::If(_, consequence, alternative) => {
+                let_names(consequence, names);
+                alternative.iter().for_each(|block| let_names(block, names));
+            }
+            Expression::While(_, body) => let_names(body, names),
+            Expression::For(variables, _, body) => {
+                variables
+                    .iter()
+                    .for_each(|variable| pattern_names(variable, names));
+                let_names(body, names);
+            }
+            Expression::Match(_, arms) => {
+                for arm in arms {
+                    pattern_names(&arm.pattern, names);
+                    let_names(&arm.body, names);
+                }
+            }
+            Expression::Try(block, catch, finally) => {
+                let_names(block, names);
+                if let Some((name, handler)) = catch {
+                    names.insert(name.clone());
+                    let_names(handler, names);
+                }
+                finally.iter().for_each(|block| let_names(block, names));
+            }
+            _ => {}
+        }
+        Ok(Rewrite::Continue(expression))
+    });
+}
+
+/// Adds the names bound by the `let` statements of a block to `names`.
+fn let_names(block: &Statement, names: &mut HashSet<String>) {
+    match block {
+        Statement::Let(pattern, ..) => pattern_names(pattern, names),
+        Statement::BlockStatement(statements, _) => {
+            statements
+                .iter()
+                .for_each(|statement| let_names(statement, names));
+        }
+        _ => {}
+    }
+}
+
+fn pattern_names(pattern: &Expression, names: &mut HashSet<String>) {
+    names.extend(pattern.bound_names().into_iter().map(String::from));
+}
+
+/// Renames the variables quoted code binds, and each use of one that its
+/// binding reaches: one before the use in the same function, or any in the
+/// functions around it, which have usually bound their variables by the time
+/// a function in them is called.
+struct Renamer {
+    expansion: usize,
+    /// The names bound so far in the function being renamed.
+    bound: HashSet<String>,
+    /// The names bound anywhere in the function being renamed, outside the
+    /// functions in it, and in the functions around it.
+    enclosing: HashSet<String>,
+}
+
+impl Renamer {
+    fn expression(&mut self, quoted: Expression) -> Expression {
+        let renamed = quoted.modify(&mut |expression| {
+            Ok::<_, Infallible>(Rewrite::Break(match expression {
+                Expression::Call(func, args, span) if is_call_to(&func, UNQUOTE) => {
+                    Expression::Call(func, args, span)
+                }
+                Expression::Identifier(name) => Expression::Identifier(self.fresh(name)),
+                Expression::If(condition, consequence, alternative) => Expression::If(
+                    Box::new(self.expression(*condition)),
+                    Box::new(self.statement(*consequence)),
+                    alternative.map(|block| Box::new(self.statement(*block))),
+                ),
+                Expression::While(condition, body) => Expression::While(
+                    Box::new(self.expression(*condition)),
+                    Box::new(self.statement(*body)),
+                ),
+                Expression::For(variables, iterable, body) => {
+                    let iterable = self.expression(*iterable);
+                    let variables = variables
+                        .into_iter()
+                        .map(|variable| self.pattern(variable))
+                        .collect();
+                    let body = self.statement(*body);
+                    Expression::For(variables, Box::new(iterable), Box::new(body))
+                }
+                Expression::Match(value, arms) => {
+                    let value = self.expression(*value);
+                    let arms = arms
+                        .into_iter()
+                        .map(|arm| MatchArm {
+                            pattern: self.pattern(arm.pattern),
+                            guard: arm.guard.map(|guard| self.expression(guard)),
+                            body: self.statement(arm.body),
+                            span: arm.span,
+                        })
+                        .collect();
+                    Expression::Match(Box::new(value), arms)
+                }
+                Expression::Try(block, catch, finally) => Expression::Try(
+                    Box::new(self.statement(*block)),
+                    catch.map(|(name, handler)| {
+                        let name = self.bind(name);
+                        (name, Box::new(self.statement(*handler)))
+                    }),
+                    finally.map(|block| Box::new(self.statement(*block))),
+                ),
+                Expression::Function(parameters, body, name) => {
+                    let (parameters, body, name) = self.function(parameters, *body, name);
+                    Expression::Function(parameters, Box::new(body), name)
+                }
+                Expression::Macro(parameters, body) => {
+                    let (parameters, body, _) = self.function(parameters, *body, String::new());
+                    Expression::Macro(parameters, Box::new(body))
+                }
+                expression => return Ok(Rewrite::Continue(expression)),
+            }))
+        });
+        let Ok(renamed) = renamed;
+        renamed
+    }
+
+    fn statement(&mut self, quoted: Statement) -> Statement {
+        match quoted {
+            // the variables are bound after the value is evaluated
+            Statement::Let(pattern, value, span) => {
+                let value = self.expression(value);
+                Statement::Let(self.pattern(pattern), value, span)
+            }
+            Statement::Return(value, span) => Statement::Return(self.expression(value), span),
+            Statement::Expression(value, span) => {
+                Statement::Expression(self.expression(value), span)
+            }
+            Statement::Throw(value, span) => Statement::Throw(self.expression(value), span),
+            Statement::BlockStatement(statements, span) => Statement::BlockStatement(
+                statements
+                    .into_iter()
+                    .map(|statement| self.statement(statement))
+                    .collect(),
+                span,
+            ),
+            Statement::Assignment(target, value, span) => {
+                Statement::Assignment(self.expression(target), self.expression(value), span)
+            }
+            Statement::CompoundAssignment(target, infix, value, span) => {
+                let target = self.expression(target);
+                Statement::CompoundAssignment(target, infix, self.expression(value), span)
+            }
+            Statement::Export(statement, span) => {
+                Statement::Export(Box::new(self.statement(*statement)), span)
+            }
+            statement => statement,
+        }
+    }
+
+    /// Binds the variables of a pattern, with its defaults and hash keys
+    /// renamed as the code they are.
+    fn pattern(&mut self, pattern: Expression) -> Expression {
+        match pattern {
+            Expression::Identifier(name) => Expression::Identifier(self.bind(name)),
+            Expression::Rest(name) => Expression::Rest(self.bind(name)),
+            Expression::Array(patterns) => Expression::Array(
+                patterns
+                    .into_iter()
+                    .map(|pattern| self.pattern(pattern))
+                    .collect(),
+            ),
+            Expression::Hash(pairs) => Expression::Hash(
+                pairs
+                    .into_iter()
+                    .map(|(key, pattern)| (self.expression(key), self.pattern(pattern)))
+                    .collect(),
+            ),
+            Expression::Default(pattern, default) => {
+                let default = self.expression(*default);
+                Expression::Default(Box::new(self.pattern(*pattern)), Box::new(default))
+            }
+            literal => literal,
+        }
+    }
+
+    /// Renames a function, whose body can use all the variables bound around
+    /// it, including the one it is bound to, and its own.
+    fn function(
+        &mut self,
+        parameters: Vec<Expression>,
+        body: Statement,
+        name: String,
+    ) -> (Vec<Expression>, Statement, String) {
+        let mut names = self.enclosing.clone();
+        parameters
+            .iter()
+            .for_each(|parameter| pattern_names(parameter, &mut names));
+        collect_bindings(&body, &mut names);
+        let bound = std::mem::replace(&mut self.bound, self.enclosing.clone());
+        let enclosing = std::mem::replace(&mut self.enclosing, names);
+
+        let name = self.fresh(name);
+        let parameters = parameters
+            .into_iter()
+            .map(|parameter| self.pattern(parameter))
+            .collect();
+        let body = self.statement(body);
+
+        self.bound = bound;
+        self.enclosing = enclosing;
+        (parameters, body, name)
+    }
+
+    fn bind(&mut self, name: String) -> String {
+        if name != "_" {
+            self.bound.insert(name.clone());
+        }
+        self.fresh(name)
+    }
+
+    fn fresh(&self, name: String) -> String {
+        match self.bound.contains(&name) {
+            true => format!("{name}@{}", self.expansion),
+            false => name,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MacroError {
+    /// An error raised while running a macro's body.
+    Eval(EvalError),
+    /// The type of the value a macro returned instead of a quote.
+    NotQuoted(&'static str),
+    MisplacedMacro,
+    /// `quote` or `unquote`, used outside the body of a macro.
+    OutsideMacro(String),
+    TooDeep,
+}
+
+impl Display for MacroError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacroError::Eval(error) => write!(f, "{error}"),
+            MacroError::NotQuoted(type_name) => {
+                write!(f, "a macro must return a QUOTE, not {type_name}")
+            }
+            MacroError::MisplacedMacro => {
+                write!(f, "a macro can only be bound by a top-level 'let'")
+            }
+            MacroError::OutsideMacro(name) => write!(f, "'{name}' outside of a macro"),
+            MacroError::TooDeep => write!(
+                f,
+                "macro expansion nested more than {MAX_EXPANSION_DEPTH} levels deep"
+            ),
+        }
+    }
+}
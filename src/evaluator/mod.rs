@@ -1,16 +1,37 @@
+use crate::diagnostics::{describe_operand, describe_operands, describe_thrown};
 use crate::evaluator::environment::Environment;
-use crate::object::builtins::{Builtin, BuiltinError};
-use crate::object::{Function, Hashable, Object};
-use crate::parser::ast::{Expression, Infix, Prefix, Program, Statement};
-use std::cell::RefCell;
+use crate::evaluator::macros::{is_call_to, source_name, QUOTE, UNQUOTE};
+use crate::object::builtins::BuiltinError;
+use crate::object::{ErrorObject, ForIterator, Function, Hashable, MatchPattern, Object};
+use crate::parser::ast::{
+    Expression, Import, Infix, MatchArm, Prefix, Program, Rewrite, Statement,
+};
+use crate::span::Located;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
 pub mod environment;
+pub mod macros;
 mod tests;
 
-#[allow(dead_code)]
-pub fn eval(program: Program, env: Rc<RefCell<Environment>>) -> Result<Rc<Object>, EvalError> {
+/// How deeply calls to functions may be nested, as in the VM.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// The size of the stack the evaluator needs to reach `MAX_CALL_DEPTH` nested
+/// calls, which is larger than a thread is given by default.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+thread_local! {
+    /// How many calls to functions the evaluator is inside of.
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn eval(
+    program: Program,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
     let Program(statements) = program;
     eval_statements(&statements, env)
 }
@@ -18,7 +39,7 @@ pub fn eval(program: Program, env: Rc<RefCell<Environment>>) -> Result<Rc<Object
 fn eval_statements(
     statements: &[Statement],
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let mut result = Rc::new(Object::Null);
 
     for statement in statements.iter() {
@@ -35,34 +56,140 @@ fn eval_statements(
 fn eval_statement(
     statement: &Statement,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
-    Ok(match statement {
-        Statement::Let(id, val) => {
-            eval_let_statement(id, val, env)?;
-            Rc::new(Object::Null)
-        }
-        Statement::Return(exp) => Rc::new(Object::Return(Rc::clone(&eval_expression(exp, env)?))),
-        Statement::Expression(exp) => eval_expression(exp, env)?,
-        Statement::BlockStatement(statements) => eval_block_statement(statements, env)?,
-        Statement::Assignment(id, val) => {
-            eval_assignment_statement(id, val, env)?;
-            Rc::new(Object::Null)
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let result = match statement {
+        Statement::Let(id, val, _) => {
+            eval_let_statement(id, val, env).map(|_| Rc::new(Object::Null))
         }
-    })
+        Statement::Return(exp, _) => {
+            eval_expression(exp, env).map(|object| Rc::new(Object::Return(object)))
+        }
+        Statement::Expression(exp, _) => eval_expression(exp, env),
+        Statement::BlockStatement(statements, _) => eval_block_statement(statements, env),
+        Statement::Assignment(target, val, _) => {
+            eval_assignment_statement(target, None, val, env).map(|_| Rc::new(Object::Null))
+        }
+        Statement::CompoundAssignment(target, infix, val, _) => {
+            eval_assignment_statement(target, Some(infix), val, env).map(|_| Rc::new(Object::Null))
+        }
+        Statement::Break(_) => Ok(Rc::new(Object::Break)),
+        Statement::Continue(_) => Ok(Rc::new(Object::Continue)),
+        Statement::Throw(exp, _) => {
+            eval_expression(exp, env).and_then(|object| Err(EvalError::Thrown(object).into()))
+        }
+        Statement::Import(import, _) => eval_import(import, env).map(|_| Rc::new(Object::Null)),
+        Statement::Export(statement, _) => eval_statement(statement, env),
+    };
+
+    // errors are reported at the innermost statement or expression with a
+    // span they were raised in
+    result.map_err(|e| e.or_at(statement.span()))
 }
 
+/// Assigns to a variable or an element of one, applying `infix` to the current
+/// value first if there is one. Arrays and hashes are values, so assigning to
+/// an element rebinds the variable to an updated copy of its collection.
 fn eval_assignment_statement(
-    id: &Expression,
+    target: &Expression,
+    infix: Option<&Infix>,
     val: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<(), EvalError> {
-    if let Expression::Identifier(key) = id {
-        if env.borrow().get(key).is_none() {
-            return Err(EvalError::UnrecognisedIdentifier);
+) -> Result<(), Located<EvalError>> {
+    let Some((key, index_expressions)) = target.as_place() else {
+        return Ok(());
+    };
+    let Some(collection) = env.borrow().get(key) else {
+        return Err(EvalError::UnrecognisedIdentifier(source_name(key).to_string()).into());
+    };
+    let indices = index_expressions
+        .into_iter()
+        .map(|exp| eval_expression(exp, Rc::clone(&env)))
+        .collect::<Result<Vec<Rc<Object>>, Located<EvalError>>>()?;
+
+    let mut value = match infix {
+        Some(infix) => {
+            let mut current = Rc::clone(&collection);
+            for index in indices.iter() {
+                current = index_object(&current, index)?;
+            }
+            let operand = eval_expression(val, Rc::clone(&env))?;
+            apply_infix(&current, infix, &operand)?
+        }
+        None => eval_expression(val, Rc::clone(&env))?,
+    };
+    if !indices.is_empty() {
+        value = set_index_path(&collection, &indices, value)?;
+    }
+
+    env.borrow_mut().set(key, value);
+    Ok(())
+}
+
+/// A copy of `collection` with the element at the path of `indices` replaced by
+/// `value`. The elements along the path are copied; all others are shared.
+fn set_index_path(
+    collection: &Object,
+    indices: &[Rc<Object>],
+    value: Rc<Object>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let Some((index, rest)) = indices.split_first() else {
+        return Ok(value);
+    };
+
+    match (collection, &**index) {
+        (Object::Array(array), Object::Integer(idx)) => {
+            if *idx < 0 || *idx as usize >= array.len() {
+                return Err(EvalError::IndexOutOfBounds.into());
+            }
+            let mut array = array.clone();
+            let idx = *idx as usize;
+            array[idx] = set_index_path(&array[idx], rest, value)?;
+            Ok(Rc::new(Object::Array(array)))
+        }
+        (Object::Hash(map), index) => {
+            let key = Hashable::from_object(index).ok_or(EvalError::IncompatibleTypes)?;
+            let mut map = map.clone();
+            let element = match map.get(&key) {
+                Some(element) => set_index_path(element, rest, value)?,
+                None => set_index_path(&Object::Null, rest, value)?,
+            };
+            map.insert(key, element);
+            Ok(Rc::new(Object::Hash(map)))
         }
-        let value = eval_expression(val, Rc::clone(&env))?;
-        env.borrow_mut().set(key, value);
+        _ => Err(EvalError::IncompatibleTypes.into()),
     }
+}
+
+/// Binds the exports of the module `import` loaded, running the module first
+/// unless an earlier import already has. A module runs in its own top-level
+/// environment, so it sees none of the importer's variables.
+fn eval_import(import: &Import, env: Rc<RefCell<Environment>>) -> Result<(), Located<EvalError>> {
+    let module = import
+        .module
+        .as_ref()
+        .expect("modules are loaded before the program runs");
+    let ran = env.borrow().get_module(module.source);
+    let exports = match ran {
+        Some(exports) => exports,
+        None => {
+            let module_env = env.borrow().new_module();
+            eval_statements(&module.program.0, Rc::clone(&module_env))?;
+            let exports = module
+                .exports
+                .iter()
+                .map(|name| {
+                    let value = module_env.borrow().get(name);
+                    let value = value.unwrap_or_else(|| Rc::new(Object::Null));
+                    (Hashable::String(name.clone()), value)
+                })
+                .collect();
+            let exports = Rc::new(Object::Hash(exports));
+            env.borrow_mut()
+                .set_module(module.source, Rc::clone(&exports));
+            exports
+        }
+    };
+    env.borrow_mut().define(&import.name, exports);
     Ok(())
 }
 
@@ -70,10 +197,51 @@ fn eval_let_statement(
     id: &Expression,
     val: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<(), EvalError> {
-    if let Expression::Identifier(key) = id {
-        let value = eval_expression(val, Rc::clone(&env))?;
-        env.borrow_mut().set(key, value);
+) -> Result<(), Located<EvalError>> {
+    let value = eval_expression(val, Rc::clone(&env))?;
+    bind_pattern(id, value, &env)
+}
+
+/// Binds the variables of `pattern` to the parts of `value` they match. Array
+/// elements and hash keys that are missing are bound to `Null`.
+fn bind_pattern(
+    pattern: &Expression,
+    value: Rc<Object>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Located<EvalError>> {
+    match pattern {
+        Expression::Identifier(name) => env.borrow_mut().define(name, value),
+        Expression::Array(patterns) => {
+            let Object::Array(elements) = &*value else {
+                return Err(EvalError::CannotDestructure("an array", value.type_name()).into());
+            };
+            for (idx, pattern) in patterns.iter().enumerate() {
+                match pattern {
+                    Expression::Rest(name) => {
+                        let rest = elements.get(idx..).unwrap_or_default().to_vec();
+                        env.borrow_mut().define(name, Rc::new(Object::Array(rest)));
+                    }
+                    pattern => {
+                        let element = elements.get(idx).cloned();
+                        bind_pattern(pattern, element.unwrap_or(Rc::new(Object::Null)), env)?;
+                    }
+                }
+            }
+        }
+        Expression::Hash(pairs) => {
+            // every key is evaluated before anything is bound
+            let keys = pairs
+                .iter()
+                .map(|(key, _)| eval_expression(key, Rc::clone(env)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !matches!(&*value, Object::Hash(_)) {
+                return Err(EvalError::CannotDestructure("a hash", value.type_name()).into());
+            }
+            for (key, (_, pattern)) in keys.iter().zip(pairs.iter()) {
+                bind_pattern(pattern, index_object(&value, key)?, env)?;
+            }
+        }
+        _ => {}
     }
     Ok(())
 }
@@ -81,12 +249,12 @@ fn eval_let_statement(
 fn eval_block_statement(
     statements: &[Statement],
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let mut result = Rc::new(Object::Null);
 
     for statement in statements.iter() {
         result = eval_statement(statement, Rc::clone(&env))?;
-        if let Object::Return(_) = *result {
+        if let Object::Return(_) | Object::Break | Object::Continue = *result {
             break;
         }
     }
@@ -97,12 +265,20 @@ fn eval_block_statement(
 fn eval_expression(
     expression: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     match expression {
         Expression::Identifier(id) => eval_identifier_expression(id, env),
         Expression::Integer(int) => Ok(Rc::new(Object::Integer(*int))),
-        Expression::Prefix(operator, operand) => eval_prefix_expressions(operator, operand, env),
-        Expression::Infix(left, infix, right) => eval_infix_expression(left, infix, right, env),
+        Expression::Float(float) => Ok(Rc::new(Object::Float(*float))),
+        Expression::Prefix(operator, operand, span) => {
+            eval_prefix_expressions(operator, operand, env).map_err(|e| e.or_at(*span))
+        }
+        Expression::Infix(left, infix @ (Infix::And | Infix::Or), right, _) => {
+            eval_logical_expression(left, infix, right, env)
+        }
+        Expression::Infix(left, infix, right, span) => {
+            eval_infix_expression(left, infix, right, env).map_err(|e| e.or_at(*span))
+        }
         Expression::Boolean(val) => Ok(Rc::new(Object::Boolean(*val))),
         Expression::If(condition, if_block, else_block) => {
             eval_if_expression(condition, if_block, else_block, env)
@@ -110,22 +286,95 @@ fn eval_expression(
         Expression::Function(parameters, body, _) => {
             eval_function_expression(parameters, body, env)
         }
-        Expression::Call(func, args) => eval_function_call_expression(func, args, env),
+        Expression::Call(func, args, span) => {
+            eval_function_call_expression(func, args, env).map_err(|e| e.or_at(*span))
+        }
         Expression::String(string) => Ok(Rc::new(Object::String(string.clone()))),
+        Expression::Interpolation(parts) => eval_interpolation(parts, env),
         Expression::Array(elements) => eval_array_literal(elements, env),
-        Expression::Index(exp, index) => eval_index_expression(exp, index, env),
+        Expression::Index(exp, index, span) => {
+            eval_index_expression(exp, index, env).map_err(|e| e.or_at(*span))
+        }
         Expression::Hash(pairs) => eval_hash_literal(pairs, env),
         Expression::While(condition, loop_block) => {
             eval_while_expression(condition, loop_block, env)
         }
+        Expression::For(variables, iterable, loop_block) => {
+            eval_for_expression(variables, iterable, loop_block, env)
+        }
+        Expression::Range(start, end) => eval_range_expression(start, end, env),
+        Expression::Match(value, arms) => eval_match_expression(value, arms, env),
+        Expression::Try(block, catch, finally) => eval_try_expression(block, catch, finally, env),
+        Expression::Rest(_) | Expression::Default(..) => {
+            unreachable!("only parsed in patterns")
+        }
+        Expression::Macro(..) => unreachable!("removed by macro expansion"),
     }
 }
 
+fn eval_match_expression(
+    value: &Expression,
+    arms: &[MatchArm],
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let value = eval_expression(value, Rc::clone(&env))?;
+
+    for arm in arms {
+        let pattern = MatchPattern::new(&arm.pattern).expect("parsed as a match pattern");
+        let Some(bound) = pattern.bind(&value) else {
+            continue;
+        };
+        // the variables are bound before the guard is checked, even if it fails
+        for (name, object) in pattern.names().into_iter().zip(bound) {
+            env.borrow_mut().define(name, object);
+        }
+        if let Some(guard) = &arm.guard {
+            if !is_truthy(&*eval_expression(guard, Rc::clone(&env))?) {
+                continue;
+            }
+        }
+        return eval_statement(&arm.body, env);
+    }
+
+    Err(EvalError::NoMatchingArm(value.type_name()).into())
+}
+
+fn eval_try_expression(
+    block: &Statement,
+    catch: &Option<(String, Box<Statement>)>,
+    finally: &Option<Box<Statement>>,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let mut result = eval_statement(block, Rc::clone(&env));
+
+    if let (Err(e), Some((name, handler))) = (&result, catch) {
+        env.borrow_mut().define(name, e.error.to_object());
+        result = eval_statement(handler, Rc::clone(&env));
+    }
+
+    // the value, error, `return`, `break` or `continue` the other blocks
+    // ended with stands unless the `finally` block ends with one of its own
+    if let Some(finally) = finally {
+        let ending = eval_statement(finally, env)?;
+        if let Object::Return(_) | Object::Break | Object::Continue = *ending {
+            return Ok(ending);
+        }
+        // an error is raised again afterwards as the value a `catch` block
+        // would have received
+        return result.map_err(|e| Located {
+            error: EvalError::Thrown(e.error.to_object()),
+            span: e.span,
+        });
+    }
+
+    result
+}
+
 fn eval_while_expression(
     condition: &Expression,
     loop_block: &Statement,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let mut result = Rc::new(Object::Null);
 
     loop {
@@ -135,30 +384,86 @@ fn eval_while_expression(
         }
 
         result = eval_statement(loop_block, Rc::clone(&env))?;
-        if let Object::Return(_) = &*result {
-            break;
+        match &*result {
+            Object::Return(_) => break,
+            Object::Break => {
+                result = Rc::new(Object::Null);
+                break;
+            }
+            Object::Continue => result = Rc::new(Object::Null),
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+fn eval_for_expression(
+    variables: &[Expression],
+    iterable: &Expression,
+    loop_block: &Statement,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let iterable = eval_expression(iterable, Rc::clone(&env))?;
+    let mut iterator =
+        ForIterator::new(&iterable).ok_or(EvalError::NotIterable(iterable.type_name()))?;
+    let mut result = Rc::new(Object::Null);
+
+    while let Some((key, value)) = iterator.next() {
+        match variables {
+            [Expression::Identifier(name)] => {
+                let object = iterator.single((key, value));
+                env.borrow_mut().define(name, object);
+            }
+            [Expression::Identifier(key_name), Expression::Identifier(value_name)] => {
+                env.borrow_mut().define(key_name, key);
+                env.borrow_mut().define(value_name, value);
+            }
+            _ => {}
+        }
+
+        result = eval_statement(loop_block, Rc::clone(&env))?;
+        match &*result {
+            Object::Return(_) => break,
+            Object::Break => {
+                result = Rc::new(Object::Null);
+                break;
+            }
+            Object::Continue => result = Rc::new(Object::Null),
+            _ => {}
         }
     }
 
     Ok(result)
 }
 
+fn eval_range_expression(
+    start: &Expression,
+    end: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let start = eval_expression(start, Rc::clone(&env))?;
+    let end = eval_expression(end, env)?;
+
+    match (&*start, &*end) {
+        (Object::Integer(start), Object::Integer(end)) => Ok(Rc::new(Object::Range(*start, *end))),
+        (start, end) => {
+            Err(EvalError::InvalidOperands("..", start.type_name(), end.type_name()).into())
+        }
+    }
+}
+
 fn eval_hash_literal(
     pairs: &[(Expression, Expression)],
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let mut map = HashMap::new();
 
     for (k, v) in pairs.iter() {
         let key = eval_expression(k, Rc::clone(&env))?;
         let value = eval_expression(v, Rc::clone(&env))?;
 
-        let key = match &*key {
-            Object::String(key) => Hashable::String(key.clone()),
-            Object::Integer(key) => Hashable::Integer(*key),
-            Object::Boolean(key) => Hashable::Boolean(*key),
-            _ => return Err(EvalError::IncompatibleTypes),
-        };
+        let key = Hashable::from_object(&key).ok_or(EvalError::IncompatibleTypes)?;
 
         map.insert(key, value);
     }
@@ -170,45 +475,56 @@ fn eval_index_expression(
     exp: &Expression,
     index: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let collection = eval_expression(exp, Rc::clone(&env))?;
     let index = eval_expression(index, Rc::clone(&env))?;
 
-    match &*collection {
-        Object::Array(array) => match &*index {
+    index_object(&collection, &index)
+}
+
+fn index_object(collection: &Object, index: &Object) -> Result<Rc<Object>, Located<EvalError>> {
+    match collection {
+        Object::Array(array) => match index {
             Object::Integer(idx) => {
                 if *idx < 0 || *idx as usize >= array.len() {
-                    return Err(EvalError::IndexOutOfBounds);
+                    return Err(EvalError::IndexOutOfBounds.into());
                 }
                 // safe to unwrap due to bound check
                 let result = array.get(*idx as usize).unwrap();
                 Ok(Rc::clone(result))
             }
-            _ => Err(EvalError::IncompatibleTypes),
+            _ => Err(EvalError::IncompatibleTypes.into()),
         },
-        Object::Hash(map) => Ok(match &*index {
-            Object::String(key) => match map.get(&Hashable::String(key.clone())) {
-                Some(object) => Rc::clone(object),
-                None => Rc::new(Object::Null),
-            },
-            Object::Integer(key) => match map.get(&Hashable::Integer(*key)) {
+        Object::Hash(map) => {
+            let key = Hashable::from_object(index).ok_or(EvalError::IncompatibleTypes)?;
+            Ok(match map.get(&key) {
                 Some(object) => Rc::clone(object),
                 None => Rc::new(Object::Null),
-            },
-            Object::Boolean(key) => match map.get(&Hashable::Boolean(*key)) {
-                Some(object) => Rc::clone(object),
-                None => Rc::new(Object::Null),
-            },
-            _ => return Err(EvalError::IncompatibleTypes),
-        }),
-        _ => Err(EvalError::IncompatibleTypes),
+            })
+        }
+        Object::Error(error) => Ok(error.field(index)),
+        _ => Err(EvalError::IncompatibleTypes.into()),
     }
 }
 
+fn eval_interpolation(
+    parts: &[Expression],
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let mut string = String::new();
+
+    for part in parts.iter() {
+        let object = eval_expression(part, Rc::clone(&env))?;
+        string.push_str(&object.to_string());
+    }
+
+    Ok(Rc::new(Object::String(string)))
+}
+
 fn eval_array_literal(
     expressions: &[Expression],
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let mut array = vec![];
 
     for exp in expressions.iter() {
@@ -223,61 +539,144 @@ fn eval_function_call_expression(
     func: &Expression,
     args: &[Expression],
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
+    if let [argument] = args {
+        if is_call_to(func, QUOTE) {
+            return eval_quote(argument, env);
+        }
+    }
+
     let function = eval_expression(func, Rc::clone(&env))?;
     let arguments: Vec<Rc<Object>> = args
         .iter()
         .map(|exp| eval_expression(exp, Rc::clone(&env)))
-        .collect::<Result<Vec<Rc<Object>>, EvalError>>()?;
+        .collect::<Result<Vec<Rc<Object>>, Located<EvalError>>>()?;
 
     apply_function(function, &arguments)
 }
 
-fn apply_function(func: Rc<Object>, args: &[Rc<Object>]) -> Result<Rc<Object>, EvalError> {
-    match &*func {
-        Object::Function(function) => {
-            let extended_env = Environment::new_enclosed(Rc::clone(&function.env));
-
-            if function.parameters.len() != args.len() {
-                return Err(EvalError::IncorrectNumberOfArgs);
+/// Quotes `expression`, replacing each `unquote(...)` in it with the code for
+/// the value of its argument.
+fn eval_quote(
+    expression: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let mut unquote = |expression| -> Result<Rewrite, Located<EvalError>> {
+        match expression {
+            Expression::Call(func, args, _) if is_call_to(&func, UNQUOTE) && args.len() == 1 => {
+                let object = eval_expression(&args[0], Rc::clone(&env))?;
+                Ok(Rewrite::Break(unquoted(&object)?))
             }
+            expression => Ok(Rewrite::Continue(expression)),
+        }
+    };
+    let quoted = expression.clone().modify(&mut unquote)?;
 
-            function
-                .parameters
-                .iter()
-                .zip(args.iter())
-                .for_each(|(p, a)| extended_env.borrow_mut().set(p, Rc::clone(a)));
+    Ok(Rc::new(Object::Quote(quoted)))
+}
 
-            let mut result = eval_statement(&function.body, extended_env)?;
+/// The code for a value unquoted into a quote: a literal, or the quoted code
+/// itself for a quote.
+fn unquoted(object: &Object) -> Result<Expression, Located<EvalError>> {
+    Ok(match object {
+        Object::Integer(int) => Expression::Integer(*int),
+        Object::Float(float) => Expression::Float(*float),
+        Object::Boolean(bool) => Expression::Boolean(*bool),
+        Object::String(string) => Expression::String(string.clone()),
+        Object::Array(elements) => Expression::Array(
+            elements
+                .iter()
+                .map(|element| unquoted(element))
+                .collect::<Result<_, _>>()?,
+        ),
+        Object::Hash(pairs) => Expression::Hash(
+            pairs
+                .iter()
+                .map(|(key, value)| Ok((unquoted(&key.to_object())?, unquoted(value)?)))
+                .collect::<Result<_, Located<EvalError>>>()?,
+        ),
+        Object::Quote(expression) => expression.clone(),
+        _ => return Err(EvalError::CannotUnquote(object.type_name()).into()),
+    })
+}
 
-            if let Object::Return(object) = &*result {
-                result = Rc::clone(object);
+fn apply_function(func: Rc<Object>, args: &[Rc<Object>]) -> Result<Rc<Object>, Located<EvalError>> {
+    match &*func {
+        Object::Function(function) => {
+            // each call nests the evaluation of the body in Rust calls, so
+            // the depth is limited before the native stack runs out
+            let depth = CALL_DEPTH.get();
+            if depth == MAX_CALL_DEPTH {
+                return Err(EvalError::StackOverflow.into());
             }
-
-            Ok(result)
+            CALL_DEPTH.set(depth + 1);
+            let result = call_function(function, args);
+            CALL_DEPTH.set(depth);
+            result
         }
         Object::Builtin(builtin) => builtin.apply(args).map_err(|e| match e {
-            BuiltinError::IncompatibleTypes => EvalError::IncompatibleTypes,
-            BuiltinError::IncorrectNumberOfArgs => EvalError::IncorrectNumberOfArgs,
+            BuiltinError::IncompatibleTypes => EvalError::IncompatibleTypes.into(),
+            BuiltinError::IncorrectNumberOfArgs => EvalError::IncorrectNumberOfArgs.into(),
+            BuiltinError::Thrown(object) => EvalError::Thrown(object).into(),
         }),
-        _ => Err(EvalError::NotAFunction),
+        _ => Err(EvalError::NotAFunction.into()),
     }
 }
 
+/// Binds the arguments of a call to `function`'s parameters, and evaluates its
+/// body.
+fn call_function(
+    function: &Function,
+    args: &[Rc<Object>],
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let extended_env = Environment::new_enclosed(Rc::clone(&function.env));
+
+    let parameters = &function.parameters;
+    let has_rest = matches!(parameters.last(), Some(Expression::Rest(_)));
+    let num_params = parameters.len() - has_rest as usize;
+    let num_required = parameters
+        .iter()
+        .take_while(|parameter| !matches!(parameter, Expression::Default(..) | Expression::Rest(_)))
+        .count();
+    if args.len() < num_required || (args.len() > num_params && !has_rest) {
+        return Err(EvalError::IncorrectNumberOfArgs.into());
+    }
+
+    // defaults are evaluated after the parameters before them are bound,
+    // so can refer to them
+    for (idx, parameter) in parameters.iter().enumerate() {
+        if let Expression::Rest(name) = parameter {
+            let rest = args.get(idx..).unwrap_or_default().to_vec();
+            extended_env
+                .borrow_mut()
+                .define(name, Rc::new(Object::Array(rest)));
+            continue;
+        }
+        let (pattern, default) = parameter.parameter_parts();
+        let value = match (args.get(idx), default) {
+            (Some(arg), _) => Rc::clone(arg),
+            (None, Some(default)) => eval_expression(default, Rc::clone(&extended_env))?,
+            (None, None) => unreachable!("the number of arguments was checked"),
+        };
+        bind_pattern(pattern, value, &extended_env)?;
+    }
+
+    let mut result = eval_statement(&function.body, extended_env)?;
+
+    if let Object::Return(object) = &*result {
+        result = Rc::clone(object);
+    }
+
+    Ok(result)
+}
+
 fn eval_function_expression(
     parameters: &[Expression],
     body: &Statement,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
-    let mut params = vec![];
-    parameters.iter().for_each(|exp| {
-        if let Expression::Identifier(id) = exp {
-            params.push(id.to_string());
-        }
-    });
-
+) -> Result<Rc<Object>, Located<EvalError>> {
     Ok(Rc::new(Object::Function(Function {
-        parameters: params,
+        parameters: parameters.to_vec(),
         body: body.clone(),
         env,
     })))
@@ -286,11 +685,11 @@ fn eval_function_expression(
 fn eval_identifier_expression(
     id: &str,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     match env.borrow().get(id) {
         Some(object) => Ok(object),
-        None => match Builtin::get(id) {
-            None => Err(EvalError::UnrecognisedIdentifier),
+        None => match env.borrow().get_builtin(id) {
+            None => Err(EvalError::UnrecognisedIdentifier(source_name(id).to_string()).into()),
             Some(object) => Ok(object),
         },
     }
@@ -301,7 +700,7 @@ fn eval_if_expression(
     if_block: &Statement,
     maybe_else_block: &Option<Box<Statement>>,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let condition = eval_expression(condition, Rc::clone(&env))?;
 
     if is_truthy(&condition) {
@@ -317,7 +716,26 @@ fn is_truthy(object: &Object) -> bool {
     !matches!(
         object,
         Object::Boolean(false) | Object::Integer(0) | Object::Null
-    )
+    ) && !matches!(object, Object::Float(float) if *float == 0.0)
+}
+
+fn eval_logical_expression(
+    left: &Expression,
+    infix: &Infix,
+    right: &Expression,
+    env: Rc<RefCell<Environment>>,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let left_truthy = is_truthy(&*eval_expression(left, Rc::clone(&env))?);
+
+    // only evaluate the right operand if the left operand does not already
+    // decide the result
+    let result = match (infix, left_truthy) {
+        (Infix::And, false) => false,
+        (Infix::Or, true) => true,
+        _ => is_truthy(&*eval_expression(right, env)?),
+    };
+
+    Ok(Rc::new(Object::Boolean(result)))
 }
 
 fn eval_infix_expression(
@@ -325,13 +743,21 @@ fn eval_infix_expression(
     infix: &Infix,
     right: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let left_object = eval_expression(left, Rc::clone(&env))?;
     let right_object = eval_expression(right, Rc::clone(&env))?;
 
-    Ok(match (&*left_object, infix, &*right_object) {
+    apply_infix(&left_object, infix, &right_object)
+}
+
+fn apply_infix(
+    left_object: &Object,
+    infix: &Infix,
+    right_object: &Object,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    Ok(match (left_object, infix, right_object) {
         (Object::Integer(left_int), _, Object::Integer(right_int)) => {
-            eval_integer_infix_expression(*left_int, infix, *right_int)
+            eval_integer_infix_expression(*left_int, infix, *right_int)?
         }
         (Object::Boolean(left_bool), Infix::Equal, Object::Boolean(right_bool)) => {
             Rc::new(Object::Boolean(left_bool == right_bool))
@@ -339,47 +765,104 @@ fn eval_infix_expression(
         (Object::Boolean(left_bool), Infix::NotEqual, Object::Boolean(right_bool)) => {
             Rc::new(Object::Boolean(left_bool != right_bool))
         }
-        (Object::Boolean(left_bool), Infix::And, Object::Boolean(right_bool)) => {
-            Rc::new(Object::Boolean(*left_bool && *right_bool))
-        }
-        (Object::Boolean(left_bool), Infix::Or, Object::Boolean(right_bool)) => {
-            Rc::new(Object::Boolean(*left_bool || *right_bool))
-        }
-        (Object::Boolean(_), _, Object::Boolean(_)) => return Err(EvalError::UnknownOperator),
         (Object::String(s1), Infix::Plus, Object::String(s2)) => {
             Rc::new(Object::String(format!("{s1}{s2}")))
         }
-        (Object::String(_), _, Object::String(_)) => return Err(EvalError::UnknownOperator),
-        _ => return Err(EvalError::IncompatibleTypes),
+        (left, _, right) => match (left.as_float(), right.as_float()) {
+            (Some(left_float), Some(right_float)) if !infix.is_bitwise() => {
+                eval_float_infix_expression(left_float, infix, right_float)?
+            }
+            _ => {
+                return Err(EvalError::InvalidOperands(
+                    infix.symbol(),
+                    left.type_name(),
+                    right.type_name(),
+                )
+                .into())
+            }
+        },
     })
 }
 
-fn eval_integer_infix_expression(left: i64, infix: &Infix, right: i64) -> Rc<Object> {
+fn eval_integer_infix_expression(
+    left: i64,
+    infix: &Infix,
+    right: i64,
+) -> Result<Rc<Object>, Located<EvalError>> {
     let result = match infix {
-        Infix::Plus => Object::Integer(left + right),
-        Infix::Minus => Object::Integer(left - right),
-        Infix::Multiply => Object::Integer(left * right),
-        Infix::Divide => Object::Integer(left / right),
+        Infix::Plus => Object::Integer(left.checked_add(right).ok_or(EvalError::IntegerOverflow)?),
+        Infix::Minus => Object::Integer(left.checked_sub(right).ok_or(EvalError::IntegerOverflow)?),
+        Infix::Multiply => {
+            Object::Integer(left.checked_mul(right).ok_or(EvalError::IntegerOverflow)?)
+        }
+        Infix::Divide if right == 0 => return Err(EvalError::DivisionByZero.into()),
+        Infix::Divide => {
+            Object::Integer(left.checked_div(right).ok_or(EvalError::IntegerOverflow)?)
+        }
+        Infix::Modulo if right == 0 => return Err(EvalError::DivisionByZero.into()),
+        // `i64::MIN % -1` overflows in Rust, but the remainder is 0
+        Infix::Modulo => Object::Integer(left.wrapping_rem(right)),
+        Infix::Power if right < 0 => Object::Float((left as f64).powf(right as f64)),
+        Infix::Power => Object::Integer(
+            u32::try_from(right)
+                .ok()
+                .and_then(|exponent| left.checked_pow(exponent))
+                .ok_or(EvalError::IntegerOverflow)?,
+        ),
         Infix::GreaterThan => Object::Boolean(left > right),
         Infix::LessThan => Object::Boolean(left < right),
+        Infix::GreaterEqual => Object::Boolean(left >= right),
+        Infix::LessEqual => Object::Boolean(left <= right),
         Infix::Equal => Object::Boolean(left == right),
         Infix::NotEqual => Object::Boolean(left != right),
-        Infix::And => {
-            Object::Boolean(is_truthy(&Object::Integer(left)) && is_truthy(&Object::Integer(right)))
+        Infix::BitAnd => Object::Integer(left & right),
+        Infix::BitOr => Object::Integer(left | right),
+        Infix::BitXor => Object::Integer(left ^ right),
+        Infix::ShiftLeft | Infix::ShiftRight if !(0..64).contains(&right) => {
+            return Err(EvalError::InvalidShiftAmount(right).into())
         }
-        Infix::Or => {
-            Object::Boolean(is_truthy(&Object::Integer(left)) || is_truthy(&Object::Integer(right)))
+        Infix::ShiftLeft => Object::Integer(left << right),
+        Infix::ShiftRight => Object::Integer(left >> right),
+        Infix::And | Infix::Or => unreachable!("evaluated by eval_logical_expression"),
+    };
+
+    Ok(Rc::new(result))
+}
+
+fn eval_float_infix_expression(
+    left: f64,
+    infix: &Infix,
+    right: f64,
+) -> Result<Rc<Object>, Located<EvalError>> {
+    let result = match infix {
+        Infix::Plus => Object::Float(left + right),
+        Infix::Minus => Object::Float(left - right),
+        Infix::Multiply => Object::Float(left * right),
+        Infix::Divide if right == 0.0 => return Err(EvalError::DivisionByZero.into()),
+        Infix::Divide => Object::Float(left / right),
+        Infix::Modulo if right == 0.0 => return Err(EvalError::DivisionByZero.into()),
+        Infix::Modulo => Object::Float(left % right),
+        Infix::Power => Object::Float(left.powf(right)),
+        Infix::GreaterThan => Object::Boolean(left > right),
+        Infix::LessThan => Object::Boolean(left < right),
+        Infix::GreaterEqual => Object::Boolean(left >= right),
+        Infix::LessEqual => Object::Boolean(left <= right),
+        Infix::Equal => Object::Boolean(left == right),
+        Infix::NotEqual => Object::Boolean(left != right),
+        Infix::BitAnd | Infix::BitOr | Infix::BitXor | Infix::ShiftLeft | Infix::ShiftRight => {
+            unreachable!("{infix} is not defined on floats")
         }
+        Infix::And | Infix::Or => unreachable!("evaluated by eval_logical_expression"),
     };
 
-    Rc::new(result)
+    Ok(Rc::new(result))
 }
 
 fn eval_prefix_expressions(
     operator: &Prefix,
     operand: &Expression,
     env: Rc<RefCell<Environment>>,
-) -> Result<Rc<Object>, EvalError> {
+) -> Result<Rc<Object>, Located<EvalError>> {
     let right = eval_expression(operand, env)?;
     match operator {
         Prefix::Minus => eval_minus_operator_expression(&right),
@@ -387,18 +870,22 @@ fn eval_prefix_expressions(
     }
 }
 
-fn eval_minus_operator_expression(object: &Object) -> Result<Rc<Object>, EvalError> {
+fn eval_minus_operator_expression(object: &Object) -> Result<Rc<Object>, Located<EvalError>> {
     match object {
-        Object::Integer(int) => Ok(Rc::new(Object::Integer(-int))),
-        _ => Err(EvalError::UnknownOperator),
+        Object::Integer(int) => Ok(Rc::new(Object::Integer(
+            int.checked_neg().ok_or(EvalError::IntegerOverflow)?,
+        ))),
+        Object::Float(float) => Ok(Rc::new(Object::Float(-float))),
+        _ => Err(EvalError::InvalidOperand(Prefix::Minus.symbol(), object.type_name()).into()),
     }
 }
 
 fn eval_bang_operator_expression(object: &Object) -> Rc<Object> {
-    // false, Null, and 0 are falsy; everything else is truthy
+    // false, Null, 0 and 0.0 are falsy; everything else is truthy
     let result = match object {
         Object::Null => true,
         Object::Integer(int) => *int == 0,
+        Object::Float(float) => *float == 0.0,
         Object::Boolean(val) => !val,
         _ => false,
     };
@@ -409,9 +896,81 @@ fn eval_bang_operator_expression(object: &Object) -> Rc<Object> {
 #[derive(Debug, PartialEq)]
 pub enum EvalError {
     IncompatibleTypes,
-    UnknownOperator,
-    UnrecognisedIdentifier,
+    InvalidOperands(&'static str, &'static str, &'static str),
+    InvalidOperand(&'static str, &'static str),
+    UnrecognisedIdentifier(String),
     NotAFunction,
     IncorrectNumberOfArgs,
     IndexOutOfBounds,
+    DivisionByZero,
+    IntegerOverflow,
+    InvalidShiftAmount(i64),
+    NotIterable(&'static str),
+    /// The kind of pattern, and the type of the value it did not match.
+    CannotDestructure(&'static str, &'static str),
+    /// The type of the value no arm of a `match` matched.
+    NoMatchingArm(&'static str),
+    /// A value raised by `throw` that no `catch` block handled.
+    Thrown(Rc<Object>),
+    /// The type of a value that has no code to put in a quote.
+    CannotUnquote(&'static str),
+    /// Calls nested more than `MAX_CALL_DEPTH` deep.
+    StackOverflow,
+}
+
+impl EvalError {
+    /// The value a `catch` block receives for this error: the value thrown,
+    /// or an error of the kind matching the runtime error.
+    fn to_object(&self) -> Rc<Object> {
+        let kind = match self {
+            EvalError::Thrown(object) => return Rc::clone(object),
+            EvalError::IncompatibleTypes
+            | EvalError::InvalidOperands(..)
+            | EvalError::InvalidOperand(..)
+            | EvalError::NotAFunction
+            | EvalError::NotIterable(_)
+            | EvalError::CannotDestructure(..)
+            | EvalError::CannotUnquote(_) => "TypeError",
+            EvalError::UnrecognisedIdentifier(_) => "NameError",
+            EvalError::IncorrectNumberOfArgs => "ArgumentError",
+            EvalError::IndexOutOfBounds => "IndexError",
+            EvalError::DivisionByZero
+            | EvalError::IntegerOverflow
+            | EvalError::InvalidShiftAmount(_) => "ArithmeticError",
+            EvalError::NoMatchingArm(_) => "MatchError",
+            EvalError::StackOverflow => "StackOverflowError",
+        };
+        Rc::new(Object::Error(ErrorObject::new(kind, &self.to_string())))
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::IncompatibleTypes => write!(f, "incompatible types"),
+            EvalError::InvalidOperands(op, left, right) => {
+                write!(f, "{}", describe_operands(op, left, right))
+            }
+            EvalError::InvalidOperand(op, operand) => {
+                write!(f, "{}", describe_operand(op, operand))
+            }
+            EvalError::UnrecognisedIdentifier(name) => write!(f, "unknown identifier '{name}'"),
+            EvalError::NotAFunction => write!(f, "not a function"),
+            EvalError::IncorrectNumberOfArgs => write!(f, "wrong number of arguments"),
+            EvalError::IndexOutOfBounds => write!(f, "index out of bounds"),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::IntegerOverflow => write!(f, "integer overflow"),
+            EvalError::InvalidShiftAmount(amount) => write!(f, "cannot shift by {amount} bits"),
+            EvalError::NotIterable(type_name) => write!(f, "cannot iterate over {type_name}"),
+            EvalError::CannotDestructure(pattern, type_name) => {
+                write!(f, "cannot destructure {type_name} with {pattern} pattern")
+            }
+            EvalError::NoMatchingArm(type_name) => {
+                write!(f, "no arm of the match matches the {type_name}")
+            }
+            EvalError::Thrown(object) => write!(f, "uncaught {}", describe_thrown(object)),
+            EvalError::CannotUnquote(type_name) => write!(f, "cannot unquote {type_name}"),
+            EvalError::StackOverflow => write!(f, "maximum call depth exceeded"),
+        }
+    }
 }
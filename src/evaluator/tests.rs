@@ -1,11 +1,14 @@
 #![cfg(test)]
 
 use crate::evaluator::environment::Environment;
-use crate::evaluator::{eval, EvalError};
-use crate::object::{Function, Hashable, Object};
-use crate::parser::ast::{Expression, Infix, Statement};
+use crate::evaluator::macros::{MacroError, Macros};
+use crate::evaluator::{eval, EvalError, STACK_SIZE};
+use crate::object::builtins::{capture_output, Arity, BuiltinError, Builtins};
+use crate::object::{ErrorObject, Function, Hashable, Object};
+use crate::parser::ast::{Expression, Infix, Program, Statement};
 use crate::parser::Parser;
-use std::cell::RefCell;
+use crate::span::{Position, Span};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -13,7 +16,22 @@ fn parse_and_eval(input: &str) -> Result<Rc<Object>, EvalError> {
     // PRE: `input` is a well-formed (i.e. parsable) program
     let program = Parser::parse_program(input).unwrap();
     let env = Rc::new(RefCell::new(Environment::new()));
-    eval(program, env)
+    eval(program, env).map_err(|e| e.error)
+}
+
+fn parse_and_expand(input: &str) -> Result<Program, MacroError> {
+    let program = Parser::parse_program(input).unwrap();
+    Macros::new()
+        .expand(program)
+        .map(Program::without_spans)
+        .map_err(|e| e.error)
+}
+
+fn parse_expression(input: &str) -> Expression {
+    match Parser::parse_program(input).unwrap().0.remove(0) {
+        Statement::Expression(expression, _) => expression,
+        statement => panic!("expected an expression, got {statement:?}"),
+    }
 }
 
 #[test]
@@ -115,7 +133,287 @@ fn test_eval_minus_operator_three() {
 #[test]
 fn test_eval_minus_operator_error_if_not_integer() {
     let input = "-true";
-    let expected_error = EvalError::UnknownOperator;
+    let expected_error = EvalError::InvalidOperand("-", "BOOLEAN");
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_division_by_zero() {
+    let input = "let zero = 0; 1 / zero";
+    let expected_error = EvalError::DivisionByZero;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_addition_overflow() {
+    let input = "9223372036854775807 + 1";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_subtraction_overflow() {
+    let input = "-9223372036854775807 - 2";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_multiplication_overflow() {
+    let input = "4611686018427387904 * 2";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_division_overflow() {
+    let input = "let min = -9223372036854775807 - 1; min / -1";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_negation_overflow() {
+    let input = "let min = -9223372036854775807 - 1; -min";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_integer_arithmetic_at_bounds() {
+    let input = "(-9223372036854775807 - 1) + 9223372036854775807";
+    let expected = Rc::new(Object::Integer(-1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_arithmetic() {
+    let input = "1.5 + 2.25 * 2.0 - 1.0 / 4.0";
+    let expected = Rc::new(Object::Float(5.75));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_mixed_integer_and_float_arithmetic() {
+    let input = "1 + 0.5 * 3";
+    let expected = Rc::new(Object::Float(2.5));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_comparison() {
+    let input = "0.1 + 0.2 > 0.3";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_integer_equals_integral_float() {
+    let input = "1 == 1.0";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_negation() {
+    let input = "-(2.5 - 5)";
+    let expected = Rc::new(Object::Float(2.5));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_zero_float_is_falsy() {
+    let input = "if (0.0) { 1 } else { 2 }";
+    let expected = Rc::new(Object::Integer(2));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_integral_float_is_integer_hash_key() {
+    let input = "{1: \"one\", 2.5: \"two and a half\"}[1.0]";
+    let expected = Rc::new(Object::String(String::from("one")));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_float_division_by_zero() {
+    let input = "1.5 / 0";
+    let expected_error = EvalError::DivisionByZero;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_modulo() {
+    let input = "[7 % 3, -7 % 3, 7.5 % 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(-1)),
+        Rc::new(Object::Float(1.5)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_modulo_of_minimum_integer() {
+    let input = "let min = -9223372036854775807 - 1; min % -1";
+    let expected = Rc::new(Object::Integer(0));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_power() {
+    let input = "[2 ** 10, 2 ** 3 ** 2, -2 ** 2, 2 ** -1, 4.0 ** 0.5]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1024)),
+        Rc::new(Object::Integer(512)),
+        Rc::new(Object::Integer(-4)),
+        Rc::new(Object::Float(0.5)),
+        Rc::new(Object::Float(2.0)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_less_and_greater_or_equal() {
+    let input = "[1 <= 1, 2 <= 1, 1 >= 1, 1 >= 2, 1.5 <= 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_bitwise_operators() {
+    let input = "[6 & 3, 6 | 3, 6 ^ 3, 1 << 62, -16 >> 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(7)),
+        Rc::new(Object::Integer(5)),
+        Rc::new(Object::Integer(4611686018427387904)),
+        Rc::new(Object::Integer(-4)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_less_than_evaluates_left_operand_first() {
+    let input =
+        "let x = 1; let f = fn() { x = x * 10; x }; let g = fn() { x = x + 1; x }; f() < g()";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_modulo_by_zero() {
+    let input = "let zero = 0; 1 % zero";
+    let expected_error = EvalError::DivisionByZero;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_power_overflow() {
+    let input = "2 ** 63";
+    let expected_error = EvalError::IntegerOverflow;
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_shift_amount_out_of_range() {
+    let input = "1 << 64";
+    let expected_error = EvalError::InvalidShiftAmount(64);
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_negative_shift_amount() {
+    let input = "1 >> -1";
+    let expected_error = EvalError::InvalidShiftAmount(-1);
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_bitwise_operator_on_float() {
+    let input = "1.5 & 1";
+    let expected_error = EvalError::InvalidOperands("&", "FLOAT", "INTEGER");
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_string_escapes() {
+    let input = r#""\"a\"\tb\\\u{e9}""#;
+    let expected = Rc::new(Object::String(String::from("\"a\"\tb\\é")));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_raw_strings() {
+    let input = r###"let x = 1; [r"a\n${x}", r#"say "hi""#, len(r"\t")]"###;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from(r"a\n${x}"))),
+        Rc::new(Object::String(String::from(r#"say "hi""#))),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_string_interpolation() {
+    let input = r#"let name = "Monkey"; let xs = [1, 2.5];
+"${name} has ${len(xs)} items: ${xs}, ${ "n${1 + 1}" }""#;
+    let expected = Rc::new(Object::String(String::from(
+        "Monkey has 2 items: [1, 2.5], n2",
+    )));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_string_interpolation_evaluates_left_to_right() {
+    let input = r#"let log = fn(x) { puts(x); x }; "${log(1)}${log(2)}""#;
+    let (result, output) = capture_output(|| parse_and_eval(input));
+    assert_eq!(result, Ok(Rc::new(Object::String(String::from("12")))));
+    assert_eq!(output, "1\n2\n");
+}
+
+#[test]
+fn test_eval_nan_hash_key_error() {
+    let input = "let big = 1000000000000000000000000000000000000000000000000000.0;
+let inf = big * big * big * big * big * big * big;
+{inf - inf: 1}";
+    let expected_error = EvalError::IncompatibleTypes;
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -171,7 +469,7 @@ fn test_eval_infix_operators_six() {
 #[test]
 fn test_eval_infix_error_if_boolean_passed_to_arithmetic_operator() {
     let input = "2 + true";
-    let expected_error = EvalError::IncompatibleTypes;
+    let expected_error = EvalError::InvalidOperands("+", "INTEGER", "BOOLEAN");
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -179,7 +477,7 @@ fn test_eval_infix_error_if_boolean_passed_to_arithmetic_operator() {
 #[test]
 fn test_eval_infix_error_if_integer_compared_to_boolean() {
     let input = "2 == true";
-    let expected_error = EvalError::IncompatibleTypes;
+    let expected_error = EvalError::InvalidOperands("==", "INTEGER", "BOOLEAN");
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -187,7 +485,7 @@ fn test_eval_infix_error_if_integer_compared_to_boolean() {
 #[test]
 fn test_eval_infix_error_if_invalid_infix_with_bools() {
     let input = "true + false";
-    let expected_error = EvalError::UnknownOperator;
+    let expected_error = EvalError::InvalidOperands("+", "BOOLEAN", "BOOLEAN");
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -338,7 +636,7 @@ fn test_eval_let_statement_binding_four() {
 #[test]
 fn test_eval_let_statement_error_if_identifier_unbound() {
     let input = "foo";
-    let expected_error = EvalError::UnrecognisedIdentifier;
+    let expected_error = EvalError::UnrecognisedIdentifier("foo".to_string());
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -346,13 +644,21 @@ fn test_eval_let_statement_error_if_identifier_unbound() {
 #[test]
 fn test_eval_function_definition() {
     let input = "fn(x) { x + 2 }";
+    let span = |start, end| Span::new(Position::new(1, start), Position::new(1, end));
     let expected = Rc::new(Object::Function(Function {
-        parameters: vec![String::from("x")],
-        body: Statement::BlockStatement(vec![Statement::Expression(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("x"))),
-            Infix::Plus,
-            Box::new(Expression::Integer(2)),
-        ))]),
+        parameters: vec![Expression::Identifier(String::from("x"))],
+        body: Statement::BlockStatement(
+            vec![Statement::Expression(
+                Expression::Infix(
+                    Box::new(Expression::Identifier(String::from("x"))),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(2)),
+                    span(9, 14),
+                ),
+                span(9, 14),
+            )],
+            span(7, 16),
+        ),
         env: Rc::new(RefCell::new(Environment::new())),
     }));
     let result = parse_and_eval(input).ok().unwrap();
@@ -496,7 +802,7 @@ fn test_eval_string_concatenation() {
 #[test]
 fn test_eval_string_concatenation_error_if_unsupported_infix() {
     let input = "\"hello\" * \"world\"";
-    let expected_error = EvalError::UnknownOperator;
+    let expected_error = EvalError::InvalidOperands("*", "STRING", "STRING");
     let error = parse_and_eval(input).err().unwrap();
     assert_eq!(error, expected_error);
 }
@@ -814,6 +1120,57 @@ fn test_eval_and_operator_with_truthy_and_falsy_values() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn test_eval_and_operator_short_circuits() {
+    let input = "false && (1 + true)";
+    let expected = Rc::new(Object::Boolean(false));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_or_operator_short_circuits() {
+    let input = "true || (1 + true)";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_logical_operator_guard() {
+    let input = "
+let arr = [1, 2];
+let i = 2;
+i < len(arr) && arr[i] == 1
+";
+    let expected = Rc::new(Object::Boolean(false));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_logical_operator_skips_side_effects() {
+    let input = "
+let x = 0;
+let f = fn() { x = x + 1; true };
+false && f();
+true || f();
+true && f();
+x
+";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_logical_operator_with_any_type() {
+    let input = "\"a\" && [1] && (0 || fn() {})";
+    let expected = Rc::new(Object::Boolean(true));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn test_eval_while_loop() {
     let input = "
@@ -845,40 +1202,974 @@ while (true) {
 }
 
 #[test]
-fn test_eval_assignment_expression() {
+fn test_eval_for_loop_over_array() {
     let input = "
-let i = 0;
-i = i + 1;
-i
+let sum = 0;
+for (x in [1, 2, 3]) {
+    sum = sum + x;
+}
+sum
 ";
-    let expected = Rc::new(Object::Integer(1));
+    let expected = Rc::new(Object::Integer(6));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_assignment_expression_closure() {
+fn test_eval_for_loop_over_hash_in_key_order() {
     let input = "
-let count = 0;
-let counter = fn() { count = count + 1; count };
-counter();
-counter();
+let keys = \"\";
+let total = 0;
+for (k, v in {\"b\": 2, 10: 3, \"a\": 1, 2.5: 4, true: 5}) {
+    keys = \"${keys}${k} \";
+    total = total + v;
+};
+[keys, total]
 ";
-    let expected = Rc::new(Object::Integer(2));
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from("true 2.5 10 a b "))),
+        Rc::new(Object::Integer(15)),
+    ]));
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
 
 #[test]
-fn test_eval_assignment_expression_closure_and_hof() {
+fn test_eval_for_loop_over_string_and_range_with_index() {
     let input = "
-let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
-let counter = makeCounter();
-counter();
-counter();
-counter();
+let pairs = [];
+for (i, c in \"ab\") { pairs = push(pairs, [i, c]); }
+for (i, n in 5..7) { pairs = push(pairs, [i, n]); }
+pairs
 ";
-    let expected = Rc::new(Object::Integer(3));
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::String(String::from("a"))),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::String(String::from("b"))),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(5)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(6)),
+        ])),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_value() {
+    let input = "for (i in 0..3) { i * 10 }";
+    let expected = Rc::new(Object::Integer(20));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_over_empty_range() {
+    let input = "for (i in 3..0) { i }";
+    let expected = Rc::new(Object::Null);
     let result = parse_and_eval(input).ok().unwrap();
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_eval_for_loop_early_return_in_function() {
+    let input = "
+let find = fn(xs, target) {
+    for (i, x in xs) {
+        if (x == target) {
+            return i;
+        }
+    };
+    -1
+};
+[find([4, 5, 6], 6), find([4, 5, 6], 7)]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(-1)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_for_loop_error_if_not_iterable() {
+    let input = "for (x in 5) { x }";
+    let expected_error = EvalError::NotIterable("INTEGER");
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_range_error_if_bounds_not_integers() {
+    let input = "0..1.5";
+    let expected_error = EvalError::InvalidOperands("..", "INTEGER", "FLOAT");
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, expected_error);
+}
+
+#[test]
+fn test_eval_break_ends_loop_with_null() {
+    let input = "
+let i = 0;
+let value = while (true) {
+    i = i + 1;
+    if (i == 3) { break; }
+    i
+};
+[i, value]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Null),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_continue_skips_rest_of_iteration() {
+    let input = "
+let odd = [];
+for (x in 0..6) {
+    if (x % 2 == 0) { continue; }
+    odd = push(odd, x);
+};
+odd
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(5)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_break_and_continue_in_nested_loops() {
+    let input = "
+let pairs = 0;
+let i = 0;
+while (i < 4) {
+    i = i + 1;
+    for (j in 0..4) {
+        if (j == 1) { continue; }
+        if (j >= i) { break; }
+        pairs = pairs + 1;
+    }
+    if (i == 3) { break; }
+};
+[i, pairs]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(4)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_break_in_loop_in_function() {
+    let input = "
+let first_over = fn(xs, limit) {
+    let found = -1;
+    for (x in xs) {
+        if (x > limit) {
+            found = x;
+            break;
+        }
+    };
+    found
+};
+[first_over([1, 5, 9], 4), first_over([1], 4)]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(5)),
+        Rc::new(Object::Integer(-1)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_break_and_continue_in_branches() {
+    let input = "
+let evens = [];
+let last = for (x in 0..10) {
+    match (x % 2) {
+        1 => { continue; },
+        _ => {
+            try {
+                if (x > 6) { break; }
+                evens = push(evens, x);
+            } finally {
+                evens = evens;
+            }
+        }
+    }
+};
+[evens, last]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(4)),
+            Rc::new(Object::Integer(6)),
+        ])),
+        Rc::new(Object::Null),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_index_assignment_nested() {
+    let input = "
+let a = [1, [2, 3], {\"k\": 1}];
+a[0] = 10;
+a[1][0] = 20;
+a[2][\"k\"] = 30;
+a[2][\"new\"] = 40;
+[a[0], a[1], a[2][\"k\"], a[2][\"new\"]]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(10)),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(20)),
+            Rc::new(Object::Integer(3)),
+        ])),
+        Rc::new(Object::Integer(30)),
+        Rc::new(Object::Integer(40)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_index_assignment_copies_collection() {
+    let input = "
+let a = [[1], 2];
+let b = a;
+let inner = a[0];
+a[0][0] = 5;
+let set = fn(arr) { arr[1] = 6; arr };
+[a, b, inner, set(a), a]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(6)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_compound_assignment() {
+    let input = "
+let x = 10;
+x += 5;
+x -= 3;
+x *= 2;
+x /= 4;
+let h = {\"n\": [1]};
+h[\"n\"][0] += 41;
+let counter = fn() {
+    let c = [0];
+    fn() { c[0] += 1; c[0] }
+};
+let next = counter();
+next();
+[x, h[\"n\"][0], next()]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_index_assignment_errors() {
+    let tests = vec![
+        ("let a = [1]; a[1] = 2;", EvalError::IndexOutOfBounds),
+        ("let a = [1]; a[-1] += 2;", EvalError::IndexOutOfBounds),
+        ("let h = {}; h[[1]] = 2;", EvalError::IncompatibleTypes),
+        (
+            "let h = {}; h[\"a\"][\"b\"] = 2;",
+            EvalError::IncompatibleTypes,
+        ),
+        ("let n = 1; n[0] = 2;", EvalError::IncompatibleTypes),
+        (
+            "m[0] = 2;",
+            EvalError::UnrecognisedIdentifier(String::from("m")),
+        ),
+        (
+            "let h = {}; h[\"a\"] += 1;",
+            EvalError::InvalidOperands("+", "NULL", "INTEGER"),
+        ),
+    ];
+    for (input, expected) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_let_destructuring() {
+    let input = "
+let [a, [b, c], ...rest] = [1, [2], 3, 4];
+let {\"name\": name, 1 + 1: two, \"missing\": missing} = {\"name\": \"n\", 2: [a]};
+[a, b, c, rest, name, two, missing]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Null),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(3)),
+            Rc::new(Object::Integer(4)),
+        ])),
+        Rc::new(Object::String(String::from("n"))),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+        Rc::new(Object::Null),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_destructuring_parameters() {
+    let input = "
+let sum = fn([first, ...others], {\"scale\": scale}) {
+    let total = first;
+    for (x in others) { total += x; };
+    let inner = fn() { total * scale };
+    inner()
+};
+let [x, ...empty] = [5];
+[sum([1, 2, 3], {\"scale\": 10}), sum([x], {\"scale\": 2}), empty]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(60)),
+        Rc::new(Object::Integer(10)),
+        Rc::new(Object::Array(vec![])),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_destructuring_errors() {
+    let tests = vec![
+        (
+            "let [a] = 1;",
+            EvalError::CannotDestructure("an array", "INTEGER"),
+        ),
+        (
+            "let {\"a\": a} = [1];",
+            EvalError::CannotDestructure("a hash", "ARRAY"),
+        ),
+        (
+            "let f = fn([a]) { a }; f({});",
+            EvalError::CannotDestructure("an array", "HASH"),
+        ),
+        (
+            "let [[a]] = [\"s\"];",
+            EvalError::CannotDestructure("an array", "STRING"),
+        ),
+    ];
+    for (input, expected) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_default_and_rest_parameters() {
+    let input = "
+let f = fn(a, b = a * 10, ...rest) { [a, b, rest] };
+let calls = 0;
+let count = fn() { calls += 1; calls };
+let g = fn(n = count()) { n };
+[f(1), f(1, 2), f(1, 2, 3, 4), g(), g(), g(7), calls]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(10)),
+            Rc::new(Object::Array(vec![])),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Array(vec![])),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Array(vec![
+                Rc::new(Object::Integer(3)),
+                Rc::new(Object::Integer(4)),
+            ])),
+        ])),
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(7)),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_variadic_function() {
+    let input = "
+let sum = fn(...xs) { let total = 0; for (x in xs) { total += x; }; total };
+let apply = fn(f, ...args) { f(args[0], args[1]) };
+[sum(), sum(1, 2, 3), apply(fn(a, [b] = [5]) { a + b }, 1, [2])]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(0)),
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(3)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_default_parameter_arity() {
+    let tests = vec![
+        "let f = fn(a, b = 1) { a }; f();",
+        "let f = fn(a, b = 1) { a }; f(1, 2, 3);",
+        "let f = fn(a, ...b) { a }; f();",
+    ];
+    for input in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::IncorrectNumberOfArgs, "{input}");
+    }
+}
+
+#[test]
+fn test_eval_match_expression() {
+    let input = "
+let describe = fn(v) {
+    match (v) {
+        0 => \"zero\",
+        -1.5 => \"minus one and a half\",
+        \"hi\" => \"greeting\",
+        false => \"no\",
+        [] => \"empty\",
+        [x] => \"one ${x}\",
+        [x, ...xs] if len(xs) > 1 => \"many\",
+        [x, y] => x + y,
+        {\"type\": \"add\", \"lhs\": l, \"rhs\": r} => l + r,
+        {\"type\": t} => { let s = \"type \"; s + t },
+        _ => \"other\",
+    }
+};
+[describe(0), describe(0.0), describe(-1.5), describe(\"hi\"), describe(false), describe([]),
+ describe([1]), describe([1, 2, 3]), describe([1, 2]), describe({\"type\": \"add\", \"lhs\": 2, \"rhs\": 3}),
+ describe({\"type\": \"t\"}), describe({}), describe(true)]
+";
+    let expected = Rc::new(Object::Array(
+        vec![
+            "zero",
+            "zero",
+            "minus one and a half",
+            "greeting",
+            "no",
+            "empty",
+            "one 1",
+            "many",
+        ]
+        .into_iter()
+        .map(|string| Rc::new(Object::String(string.to_string())))
+        .chain([Rc::new(Object::Integer(3)), Rc::new(Object::Integer(5))])
+        .chain(
+            ["type t", "other", "other"]
+                .into_iter()
+                .map(|string| Rc::new(Object::String(string.to_string()))),
+        )
+        .collect(),
+    ));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_match_bindings_and_guards() {
+    let input = "
+let classify = fn(pair) {
+    match (pair) {
+        [a, b] if a > b => \"desc\",
+        [a, b] if a == b => \"same\",
+        _ => \"asc\",
+    }
+};
+let total = 0;
+for (i in 0..6) {
+    match (i % 3) {
+        0 => { continue; },
+        n => { total += n * i; },
+    };
+};
+let last = match ([7, 8]) { [x, ...rest] => rest };
+[classify([2, 1]), classify([1, 1]), classify([1, 2]), total, last]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from("desc"))),
+        Rc::new(Object::String(String::from("same"))),
+        Rc::new(Object::String(String::from("asc"))),
+        Rc::new(Object::Integer(1 + 4 + 4 + 10)),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(8))])),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_match_without_matching_arm() {
+    let input = "match ([1, 2]) { [a] => a, {\"k\": v} => v, x if false => x }";
+    let error = parse_and_eval(input).err().unwrap();
+    assert_eq!(error, EvalError::NoMatchingArm("ARRAY"));
+}
+
+#[test]
+fn test_eval_try_catch_runtime_errors() {
+    let input = r#"let kind = fn(f) { try { f(); "none" } catch (e) { e["kind"] } };
+[
+    kind(fn() { 1 + true }),
+    kind(fn() { 5() }),
+    kind(fn() { let g = fn(a) { a }; g() }),
+    kind(fn() { 1 / 0 }),
+    kind(fn() { match (1) { 2 => 2 } }),
+    kind(fn() { throw error("out of range", "RangeError"); }),
+    kind(fn() { throw {"kind": "thrown hash"}; }),
+    kind(fn() { 1 })
+]"#;
+    let expected = Rc::new(Object::Array(
+        [
+            "TypeError",
+            "TypeError",
+            "ArgumentError",
+            "ArithmeticError",
+            "MatchError",
+            "RangeError",
+            "thrown hash",
+            "none",
+        ]
+        .into_iter()
+        .map(|kind| Rc::new(Object::String(String::from(kind))))
+        .collect(),
+    ));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_stack_overflow() {
+    let input = r#"let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };
+[try { f(5000) } catch (e) { e["kind"] }, f(1000)]"#;
+    // the evaluator needs a larger stack than a test is run with
+    let test = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let expected = Rc::new(Object::Array(vec![
+                Rc::new(Object::String(String::from("StackOverflowError"))),
+                Rc::new(Object::Integer(1000)),
+            ]));
+            assert_eq!(parse_and_eval(input), Ok(expected));
+            assert_eq!(
+                parse_and_eval("let f = fn() { f() }; f()"),
+                Err(EvalError::StackOverflow)
+            );
+        })
+        .unwrap();
+    test.join().unwrap();
+}
+
+#[test]
+fn test_eval_try_finally_control_flow() {
+    let input = r#"let log = fn(x) { puts(x); x };
+let f = fn() { try { return log(1); } finally { log(2); } };
+let g = fn() { try { throw "oops"; } catch (e) { return e; } finally { log(3); } };
+let total = 0;
+for (i in 0..4) {
+    try {
+        if (i == 1) { continue; };
+        if (i == 3) { break; };
+        total += i;
+    } finally { log(i); }
+};
+let nested = try { try { throw "inner"; } finally { log(4); } } catch (e) { e };
+[f(), g(), total, nested]"#;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::String(String::from("oops"))),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::String(String::from("inner"))),
+    ]));
+    let (result, output) = capture_output(|| parse_and_eval(input));
+    assert_eq!(result, Ok(expected));
+    assert_eq!(output, "0\n1\n2\n3\n4\n1\n2\n3\n");
+}
+
+#[test]
+fn test_eval_uncaught_throw() {
+    let tests = vec![
+        (
+            r#"throw error("bye");"#,
+            Object::Error(ErrorObject::new("Error", "bye")),
+        ),
+        (r#"try { throw 1; } finally { 2 }"#, Object::Integer(1)),
+        (
+            r#"try { 1 } catch (e) { 2 } finally { throw "late"; }"#,
+            Object::String(String::from("late")),
+        ),
+        (
+            r#"try { 1 + true } finally { 2 }"#,
+            Object::Error(ErrorObject::new(
+                "TypeError",
+                "cannot add INTEGER and BOOLEAN",
+            )),
+        ),
+        (
+            r#"try { 1 + true } catch (e) { throw e; }"#,
+            Object::Error(ErrorObject::new(
+                "TypeError",
+                "cannot add INTEGER and BOOLEAN",
+            )),
+        ),
+    ];
+    for (input, expected) in tests {
+        let error = parse_and_eval(input).err().unwrap();
+        assert_eq!(error, EvalError::Thrown(Rc::new(expected)), "{input}");
+    }
+}
+
+#[test]
+fn test_eval_quote_and_unquote() {
+    let tests = vec![
+        ("quote(5 + x)", "5 + x"),
+        ("quote(unquote(4 + 4))", "8"),
+        ("let foo = 8; quote(foo + unquote(foo * 2))", "foo + 16"),
+        ("quote(unquote(true == false) && b)", "false && b"),
+        ("let q = quote(4 + 4); quote(unquote(q) * 2)", "(4 + 4) * 2"),
+        (
+            r#"quote(unquote([1, "two", 3.5])[0])"#,
+            r#"[1, "two", 3.5][0]"#,
+        ),
+        ("quote(fn(x) { unquote(1) + x })", "fn(x) { 1 + x }"),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        let Object::Quote(quoted) = result.as_ref() else {
+            panic!("expected a quote, got {result}");
+        };
+        let expected = parse_expression(expected).without_spans();
+        assert_eq!(quoted.clone().without_spans(), expected, "{input}");
+    }
+
+    let error = parse_and_eval("quote(unquote(fn() { 1 }))").err().unwrap();
+    assert_eq!(error, EvalError::CannotUnquote("FUNCTION"));
+}
+
+#[test]
+fn test_expand_macros() {
+    let tests = vec![
+        (
+            "let infix = macro() { quote(1 + 2) }; let reverse = macro(a, b) { quote(unquote(b) - unquote(a)) }; infix(); reverse(2 + 2, 10 - 5);",
+            "1 + 2; (10 - 5) - (2 + 2);",
+        ),
+        (
+            r#"let unless = macro(c, a, b) { quote(if (!(unquote(c))) { unquote(a) } else { unquote(b) }) };
+let f = fn() { unless(10 > 5, puts("not greater"), puts("greater")) };"#,
+            r#"let f = fn() { if (!(10 > 5)) { puts("not greater") } else { puts("greater") } };"#,
+        ),
+        (
+            // macros are expanded outside in, so an argument is only expanded
+            // where the macro puts it
+            "let inc = macro(x) { quote(unquote(x) + 1) }; let both = macro(x) { quote([unquote(x), inc(0)]) }; both(inc(inc(1)));",
+            "[((1 + 1) + 1), (0 + 1)];",
+        ),
+        (
+            "let later = macro(x) { quote(unquote(x)) }; later(later);",
+            "later;",
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = parse_and_expand(input).unwrap();
+        let expected = Parser::parse_program(expected).unwrap().without_spans();
+        assert_eq!(program, expected, "{input}");
+    }
+}
+
+#[test]
+fn test_expand_macros_across_programs() {
+    let mut macros = Macros::new();
+    let define = Parser::parse_program("let id = macro(x) { quote(unquote(x)) };").unwrap();
+    let call = Parser::parse_program("id(1 + 2);").unwrap();
+    assert_eq!(macros.expand(define), Ok(Program(vec![])));
+    assert_eq!(
+        macros.expand(call).map(Program::without_spans),
+        Ok(Parser::parse_program("1 + 2;").unwrap().without_spans())
+    );
+}
+
+#[test]
+fn test_macro_hygiene() {
+    // the variables bound in a macro's quote are renamed, so cannot capture
+    // the variables in the code a macro is called with
+    let input =
+        "let sum = macro(a, b) { quote(fn() { let x = unquote(a); let y = unquote(b); x + y }()) };
+let x = 10;
+let y = 20;
+let f = fn(xs, ...ys) { sum(len(ys), xs[0]) };
+[sum(y, x), sum(sum(1, x), y), f([5], 1, 2)]";
+    let program = Macros::new()
+        .expand(Parser::parse_program(input).unwrap())
+        .unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(30)),
+        Rc::new(Object::Integer(31)),
+        Rc::new(Object::Integer(7)),
+    ]));
+    assert_eq!(eval(program, env), Ok(expected));
+}
+
+#[test]
+fn test_macro_hygiene_scope() {
+    // only the uses a quoted binding reaches are renamed, so the free `len`
+    // and `x` are the caller's, and the error names `g` as it is written
+    let tests = vec![
+        (
+            "let m = macro() { quote(len([fn(len) { len }(1)])) }; m()",
+            Ok(Rc::new(Object::Integer(1))),
+        ),
+        (
+            "let x = 5; let m = macro() { quote([fn(x) { x }(1), x]) }; m()",
+            Ok(Rc::new(Object::Array(vec![
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(5)),
+            ]))),
+        ),
+        (
+            "let m = macro() { quote(fn() { let f = fn() { g }; f(); let g = 1; }()) }; m()",
+            Err(EvalError::UnrecognisedIdentifier(String::from("g"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Macros::new()
+            .expand(Parser::parse_program(input).unwrap())
+            .unwrap();
+        let env = Rc::new(RefCell::new(Environment::new()));
+        assert_eq!(eval(program, env).map_err(|e| e.error), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_macro_errors() {
+    let tests = vec![
+        (
+            "let m = macro(x) { 1 }; m(2)",
+            MacroError::NotQuoted("INTEGER"),
+        ),
+        (
+            "let f = fn() { macro(x) { x } }; f",
+            MacroError::MisplacedMacro,
+        ),
+        ("let [m] = [macro(x) { x }]; m", MacroError::MisplacedMacro),
+        ("quote(1)", MacroError::OutsideMacro(String::from("quote"))),
+        (
+            "let f = fn(x) { unquote(x) }; f",
+            MacroError::OutsideMacro(String::from("unquote")),
+        ),
+        (
+            "let m = macro(x) { quote(unquote(x)) }; m()",
+            MacroError::Eval(EvalError::IncorrectNumberOfArgs),
+        ),
+        (
+            "let m = macro(x) { quote(unquote(y)) }; m(1)",
+            MacroError::Eval(EvalError::UnrecognisedIdentifier(String::from("y"))),
+        ),
+        (
+            "let m = macro(x) { quote(1 + m(unquote(x))) }; m(1)",
+            MacroError::TooDeep,
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_and_expand(input), Err(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_eval_assignment_expression() {
+    let input = "
+let i = 0;
+i = i + 1;
+i
+";
+    let expected = Rc::new(Object::Integer(1));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_expression_closure() {
+    let input = "
+let count = 0;
+let counter = fn() { count = count + 1; count };
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(2));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_expression_closure_and_hof() {
+    let input = "
+let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
+let counter = makeCounter();
+counter();
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(3));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_assignment_to_own_binding() {
+    let tests = vec![
+        ("let f = fn() { f = 1; }; f(); f", 1),
+        ("let g = fn() { let f = fn() { f = 2; }; f(); f }; g()", 2),
+    ];
+    for (input, expected) in tests {
+        let result = parse_and_eval(input).ok().unwrap();
+        assert_eq!(result, Rc::new(Object::Integer(expected)));
+    }
+}
+
+#[test]
+fn test_eval_let_and_parameters_shadow_outer_variables() {
+    let input = "
+let x = 1;
+let a = [1];
+let f = fn(a) { let x = 2; a[0] = x; a };
+[f(a), a, x]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(2))])),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+        Rc::new(Object::Integer(1)),
+    ]));
+    let result = parse_and_eval(input).ok().unwrap();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_eval_error_position() {
+    let input = "let f = fn(x) {
+    let y = 1;
+    x + true
+};
+f(1);";
+    let program = Parser::parse_program(input).unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let error = eval(program, env).err().unwrap();
+    assert_eq!(
+        error.error,
+        EvalError::InvalidOperands("+", "INTEGER", "BOOLEAN")
+    );
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(3, 5)));
+}
+
+#[test]
+fn test_eval_error_position_in_expression() {
+    let input = "let a = 1;
+let b = [a,
+    a + \"x\"];";
+    let program = Parser::parse_program(input).unwrap();
+    let env = Rc::new(RefCell::new(Environment::new()));
+    let error = eval(program, env).err().unwrap();
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(3, 5)));
+}
+
+#[test]
+fn test_eval_host_builtins() {
+    let calls = Rc::new(Cell::new(0));
+    let mut builtins = Builtins::new();
+    let counter = Rc::clone(&calls);
+    builtins
+        .register("count", Arity::Exactly(0), move |_| {
+            counter.set(counter.get() + 1);
+            Ok(Rc::new(Object::Integer(counter.get())))
+        })
+        .unwrap();
+    builtins
+        .register_in("host", "fail", Arity::Exactly(0), |_| {
+            let error = ErrorObject::new("HostError", "failed");
+            Err(BuiltinError::Thrown(Rc::new(Object::Error(error))))
+        })
+        .unwrap();
+    let builtins = Rc::new(builtins);
+
+    let tests = [
+        (
+            r#"let f = fn() { count() }; f(); [f(), try { host["fail"]() } catch (e) { e["kind"] }]"#,
+            Ok(Rc::new(Object::Array(vec![
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::String(String::from("HostError"))),
+            ]))),
+        ),
+        ("count(1)", Err(EvalError::IncorrectNumberOfArgs)),
+        (
+            r#"host["fail"]()"#,
+            Err(EvalError::Thrown(Rc::new(Object::Error(ErrorObject::new(
+                "HostError",
+                "failed",
+            ))))),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).unwrap();
+        let env = Environment::new_with_builtins(Rc::clone(&builtins));
+        let result = eval(program, Rc::new(RefCell::new(env))).map_err(|e| e.error);
+        assert_eq!(result, expected, "{input}");
+    }
+    assert_eq!(calls.get(), 2);
+}
@@ -1,6 +1,8 @@
+use std::fmt::{Display, Formatter};
 use std::{iter::Peekable, str::Chars};
 
-use token::Token;
+use crate::span::{Position, Span};
+use token::{TemplatePart, Token};
 
 mod tests;
 pub mod token;
@@ -8,17 +10,122 @@ pub mod token;
 #[derive(Debug)]
 pub struct LexerIter<'a> {
     iter: Peekable<Chars<'a>>,
+    position: Position,
+    trivia: Vec<Comment>,
+}
+
+/// A comment. The lexer skips comments, but keeps them for tools that need
+/// them, such as formatters and documentation generators.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    pub kind: CommentKind,
+    /// The source text of the comment, including its delimiters.
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommentKind {
+    /// From `//` to the end of the line.
+    Line,
+    /// Between `/*` and `*/`, which may nest.
+    Block,
 }
 
 impl<'a> LexerIter<'a> {
-    fn skip_whitespace(&mut self) {
+    /// Turns this iterator into one that also yields the span of each token.
+    pub fn spanned(self) -> SpannedLexerIter<'a> {
+        SpannedLexerIter { iter: self }
+    }
+
+    /// The position of the next character to be read.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The comments skipped so far, in source order.
+    pub fn trivia(&self) -> &[Comment] {
+        &self.trivia
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.iter.next()?;
+        if ch == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(ch)
+    }
+
+    /// Skips whitespace and comments, keeping the comments as trivia. A block
+    /// comment that is never closed is an error, reported with its start.
+    fn skip_trivia(&mut self) -> Result<(), Position> {
+        loop {
+            let mut ahead = self.iter.clone();
+            match (ahead.next(), ahead.next()) {
+                (Some(c), _) if c.is_ascii_whitespace() => {
+                    self.advance();
+                }
+                (Some('/'), Some('/')) => self.skip_line_comment(),
+                (Some('/'), Some('*')) => self.skip_block_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_line_comment(&mut self) {
+        let start = self.position;
+        let mut text = String::new();
         while let Some(c) = self.iter.peek() {
-            if c.is_ascii_whitespace() {
-                self.iter.next();
-            } else {
+            if *c == '\n' {
                 break;
             }
+            // unwrap safe here since already peeked
+            text.push(self.advance().unwrap());
+        }
+        self.push_comment(CommentKind::Line, text, start);
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), Position> {
+        let start = self.position;
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            let mut ahead = self.iter.clone();
+            let delimiter = match (ahead.next(), ahead.next()) {
+                (None, _) => {
+                    self.push_comment(CommentKind::Block, text, start);
+                    return Err(start);
+                }
+                (Some('/'), Some('*')) => Some(1),
+                (Some('*'), Some('/')) => Some(-1),
+                _ => None,
+            };
+            match delimiter {
+                Some(change) => {
+                    // unwraps safe here since both characters were looked at
+                    text.push(self.advance().unwrap());
+                    text.push(self.advance().unwrap());
+                    depth += change;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                None => text.push(self.advance().unwrap()),
+            }
         }
+        self.push_comment(CommentKind::Block, text, start);
+        Ok(())
+    }
+
+    fn push_comment(&mut self, kind: CommentKind, text: String, start: Position) {
+        self.trivia.push(Comment {
+            kind,
+            text,
+            span: Span::new(start, self.position),
+        });
     }
 
     fn get_rest_of_word(&mut self, ch: char) -> String {
@@ -26,7 +133,7 @@ impl<'a> LexerIter<'a> {
         while let Some(c) = self.iter.peek() {
             if c.is_ascii_alphabetic() || *c == '_' {
                 // unwrap safe here since already peeked
-                word.push(self.iter.next().unwrap());
+                word.push(self.advance().unwrap());
             } else {
                 break;
             }
@@ -34,30 +141,230 @@ impl<'a> LexerIter<'a> {
         word
     }
 
-    fn get_rest_of_number(&mut self, ch: char) -> String {
+    fn get_rest_of_number(&mut self, ch: char) -> Token {
         let mut num = String::from(ch);
+        self.push_digits(&mut num);
+        let mut is_float = false;
+
+        // a '.' is only part of the number if a digit follows it
+        let mut ahead = self.iter.clone();
+        if ahead.next() == Some('.') && ahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+            // unwrap safe here since already peeked
+            num.push(self.advance().unwrap());
+            self.push_digits(&mut num);
+            is_float = true;
+        }
+
+        // and an exponent, e.g. the `e-3` of `2.5e-3`, only if a digit follows
+        // the 'e' and its sign
+        let mut ahead = self.iter.clone();
+        if matches!(ahead.next(), Some('e' | 'E')) {
+            let sign = ahead.next_if(|c| *c == '+' || *c == '-');
+            if ahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+                // unwrap safe here since already peeked
+                num.push(self.advance().unwrap());
+                if sign.is_some() {
+                    num.push(self.advance().unwrap());
+                }
+                self.push_digits(&mut num);
+                is_float = true;
+            }
+        }
+
+        if is_float {
+            Token::Float(num)
+        } else {
+            Token::Int(num)
+        }
+    }
+
+    fn push_digits(&mut self, num: &mut String) {
         while let Some(c) = self.iter.peek() {
             if c.is_ascii_digit() {
                 // unwrap safe here since already peeked
-                num.push(self.iter.next().unwrap());
+                num.push(self.advance().unwrap());
             } else {
                 break;
             }
         }
-        num
     }
 
-    fn get_string(&mut self) -> String {
+    /// Reads a string after its opening '"'. A string containing `${...}` is
+    /// read as a template, with the tokens of each embedded expression.
+    fn get_string(&mut self) -> Token {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        // the first bad escape is reported once the whole string is read
+        let mut error = None;
+
+        loop {
+            match self.advance() {
+                None => return Token::Error(LexerError::UnterminatedString),
+                Some('"') => break,
+                Some('\\') => match self.get_escape() {
+                    Ok(ch) => literal.push(ch),
+                    Err(LexerError::UnterminatedString) => {
+                        return Token::Error(LexerError::UnterminatedString)
+                    }
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                },
+                Some('$') if self.iter.peek() == Some(&'{') => {
+                    self.advance();
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    match self.get_interpolation() {
+                        Ok((tokens, end)) => parts.push(TemplatePart::Expression(tokens, end)),
+                        Err(e) => return Token::Error(e),
+                    }
+                }
+                Some(ch) => literal.push(ch),
+            }
+        }
+
+        if let Some(e) = error {
+            return Token::Error(e);
+        }
+        if parts.is_empty() {
+            return Token::String(literal);
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Token::Template(parts)
+    }
+
+    /// Whether a raw string starts after an `r`: some '#'s, if any, then a '"'.
+    fn at_raw_string(&self) -> bool {
+        let mut ahead = self.iter.clone();
+        ahead.find(|c| *c != '#') == Some('"')
+    }
+
+    /// Reads a raw string after its `r`: any number of '#'s and a '"', then
+    /// everything up to a '"' followed by as many '#'s. It has no escapes or
+    /// `${...}`, e.g. `r"C:\dir"`, and the '#'s let it hold quotes, as in
+    /// `r#"say "hi""#`.
+    fn get_raw_string(&mut self) -> Token {
+        let mut hashes = 0;
+        while self.iter.peek() == Some(&'#') {
+            self.advance();
+            hashes += 1;
+        }
+        // the opening '"'
+        self.advance();
+
         let mut string = String::new();
-        while let Some(c) = self.iter.peek() {
-            if *c == '"' {
-                self.iter.next();
-                break;
+        loop {
+            match self.advance() {
+                None => return Token::Error(LexerError::UnterminatedString),
+                Some('"') => {
+                    let mut ahead = self.iter.clone();
+                    if (0..hashes).all(|_| ahead.next() == Some('#')) {
+                        for _ in 0..hashes {
+                            self.advance();
+                        }
+                        return Token::String(string);
+                    }
+                    string.push('"');
+                }
+                Some(ch) => string.push(ch),
             }
-            string.push(*c);
-            self.iter.next();
         }
-        string
+    }
+
+    /// Reads an escape sequence after its '\\'.
+    fn get_escape(&mut self) -> Result<char, LexerError> {
+        match self.advance() {
+            None => Err(LexerError::UnterminatedString),
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('$') => Ok('$'),
+            Some('u') => self.get_unicode_escape(),
+            Some(ch) => Err(LexerError::InvalidEscape(ch)),
+        }
+    }
+
+    /// Reads the `{...}` of a `\u{...}` escape, holding 1 to 6 hex digits.
+    fn get_unicode_escape(&mut self) -> Result<char, LexerError> {
+        if self.iter.peek() != Some(&'{') {
+            return Err(LexerError::InvalidUnicodeEscape(String::new()));
+        }
+        self.advance();
+
+        let mut digits = String::new();
+        loop {
+            match self.iter.peek() {
+                None => return Err(LexerError::UnterminatedString),
+                Some('}') => {
+                    self.advance();
+                    break;
+                }
+                // leave the '"' to end the string
+                Some('"') => return Err(LexerError::InvalidUnicodeEscape(digits)),
+                // unwrap safe here since already peeked
+                Some(_) => digits.push(self.advance().unwrap()),
+            }
+        }
+
+        if !(1..=6).contains(&digits.len()) {
+            return Err(LexerError::InvalidUnicodeEscape(digits));
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(LexerError::InvalidUnicodeEscape(digits))
+    }
+
+    /// Reads the tokens of an embedded expression after its `${`, up to the
+    /// matching '}', whose span is returned with them.
+    fn get_interpolation(&mut self) -> Result<(Vec<(Token, Span)>, Span), LexerError> {
+        let mut tokens = vec![];
+        let mut depth = 0;
+
+        loop {
+            if self.skip_trivia().is_err() {
+                return Err(LexerError::UnterminatedComment);
+            }
+            let start = self.position;
+            let token = self.next().ok_or(LexerError::UnterminatedString)?;
+            let span = Span::new(start, self.position);
+            match token {
+                Token::Lbrace => depth += 1,
+                Token::Rbrace if depth == 0 => return Ok((tokens, span)),
+                Token::Rbrace => depth -= 1,
+                // a string left open inside the expression ran to the end
+                Token::Error(LexerError::UnterminatedString) => {
+                    return Err(LexerError::UnterminatedString)
+                }
+                _ => {}
+            }
+            tokens.push((token, span));
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexerError {
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidUnicodeEscape(String),
+    UnterminatedComment,
+}
+
+impl Display for LexerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexerError::UnterminatedString => write!(f, "unterminated string"),
+            LexerError::InvalidEscape(ch) => write!(f, "invalid escape sequence '\\{ch}'"),
+            LexerError::InvalidUnicodeEscape(digits) => {
+                write!(f, "invalid unicode escape '\\u{{{digits}}}'")
+            }
+            LexerError::UnterminatedComment => write!(f, "unterminated block comment"),
+        }
     }
 }
 
@@ -65,15 +372,67 @@ impl<'a> Iterator for LexerIter<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
-        let ch = self.iter.next()?;
+        if self.skip_trivia().is_err() {
+            return Some(Token::Error(LexerError::UnterminatedComment));
+        }
+        let ch = self.advance()?;
         match ch {
-            '+' => Some(Token::Plus),
-            '-' => Some(Token::Minus),
-            '*' => Some(Token::Asterisk),
-            '/' => Some(Token::Slash),
-            '<' => Some(Token::Lt),
-            '>' => Some(Token::Gt),
+            '+' => match self.iter.peek() {
+                Some('=') => {
+                    self.advance();
+                    Some(Token::PlusAssign)
+                }
+                _ => Some(Token::Plus),
+            },
+            '-' => match self.iter.peek() {
+                Some('=') => {
+                    self.advance();
+                    Some(Token::MinusAssign)
+                }
+                _ => Some(Token::Minus),
+            },
+            '*' => match self.iter.peek() {
+                Some('*') => {
+                    self.advance();
+                    Some(Token::Power)
+                }
+                Some('=') => {
+                    self.advance();
+                    Some(Token::AsteriskAssign)
+                }
+                _ => Some(Token::Asterisk),
+            },
+            '/' => match self.iter.peek() {
+                Some('=') => {
+                    self.advance();
+                    Some(Token::SlashAssign)
+                }
+                _ => Some(Token::Slash),
+            },
+            '%' => Some(Token::Percent),
+            '^' => Some(Token::Caret),
+            '<' => match self.iter.peek() {
+                Some('=') => {
+                    self.advance();
+                    Some(Token::LtEq)
+                }
+                Some('<') => {
+                    self.advance();
+                    Some(Token::ShiftLeft)
+                }
+                _ => Some(Token::Lt),
+            },
+            '>' => match self.iter.peek() {
+                Some('=') => {
+                    self.advance();
+                    Some(Token::GtEq)
+                }
+                Some('>') => {
+                    self.advance();
+                    Some(Token::ShiftRight)
+                }
+                _ => Some(Token::Gt),
+            },
             ';' => Some(Token::Semicolon),
             ':' => Some(Token::Colon),
             '(' => Some(Token::Lparen),
@@ -86,26 +445,29 @@ impl<'a> Iterator for LexerIter<'a> {
             '&' => {
                 if let Some(c) = self.iter.peek() {
                     if *c == '&' {
-                        self.iter.next();
+                        self.advance();
                         return Some(Token::And);
                     }
                 }
-                Some(Token::Illegal)
+                Some(Token::Ampersand)
             }
             '|' => {
                 if let Some(c) = self.iter.peek() {
                     if *c == '|' {
-                        self.iter.next();
+                        self.advance();
                         return Some(Token::Or);
                     }
                 }
-                Some(Token::Illegal)
+                Some(Token::Pipe)
             }
             '=' => {
                 if let Some(c) = self.iter.peek() {
                     if *c == '=' {
-                        self.iter.next();
+                        self.advance();
                         return Some(Token::Eq);
+                    } else if *c == '>' {
+                        self.advance();
+                        return Some(Token::FatArrow);
                     }
                 }
                 Some(Token::Assign)
@@ -113,17 +475,27 @@ impl<'a> Iterator for LexerIter<'a> {
             '!' => {
                 if let Some(c) = self.iter.peek() {
                     if *c == '=' {
-                        self.iter.next();
+                        self.advance();
                         return Some(Token::Noteq);
                     }
                 }
                 Some(Token::Bang)
             }
-            '"' => Some(Token::String(self.get_string())),
+            '.' if self.iter.peek() == Some(&'.') => {
+                self.advance();
+                if self.iter.peek() == Some(&'.') {
+                    self.advance();
+                    Some(Token::Ellipsis)
+                } else {
+                    Some(Token::DotDot)
+                }
+            }
+            '"' => Some(self.get_string()),
             _ => {
                 if ch.is_ascii_alphabetic() || ch == '_' {
                     let word = self.get_rest_of_word(ch);
                     match word.as_str() {
+                        "r" if self.at_raw_string() => Some(self.get_raw_string()),
                         "let" => Some(Token::Let),
                         "fn" => Some(Token::Function),
                         "true" => Some(Token::True),
@@ -132,26 +504,70 @@ impl<'a> Iterator for LexerIter<'a> {
                         "else" => Some(Token::Else),
                         "return" => Some(Token::Return),
                         "while" => Some(Token::While),
+                        "for" => Some(Token::For),
+                        "in" => Some(Token::In),
+                        "break" => Some(Token::Break),
+                        "continue" => Some(Token::Continue),
+                        "match" => Some(Token::Match),
+                        "throw" => Some(Token::Throw),
+                        "try" => Some(Token::Try),
+                        "catch" => Some(Token::Catch),
+                        "finally" => Some(Token::Finally),
+                        "macro" => Some(Token::Macro),
+                        "import" => Some(Token::Import),
+                        "export" => Some(Token::Export),
+                        "as" => Some(Token::As),
                         _ => Some(Token::Identifier(word)),
                     }
                 } else if ch.is_ascii_digit() {
-                    Some(Token::Int(self.get_rest_of_number(ch)))
+                    Some(self.get_rest_of_number(ch))
                 } else {
-                    Some(Token::Illegal)
+                    Some(Token::Illegal(ch))
                 }
             }
         }
     }
 }
 
+/// Yields each token together with the span of source it was read from.
+#[derive(Debug)]
+pub struct SpannedLexerIter<'a> {
+    iter: LexerIter<'a>,
+}
+
+impl SpannedLexerIter<'_> {
+    /// The comments skipped so far, in source order.
+    pub fn trivia(&self) -> &[Comment] {
+        self.iter.trivia()
+    }
+}
+
+impl<'a> Iterator for SpannedLexerIter<'a> {
+    type Item = (Token, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = match self.iter.skip_trivia() {
+            Ok(()) => self.iter.position(),
+            Err(start) => {
+                let span = Span::new(start, self.iter.position());
+                return Some((Token::Error(LexerError::UnterminatedComment), span));
+            }
+        };
+        let token = self.iter.next()?;
+        Some((token, Span::new(start, self.iter.position())))
+    }
+}
+
 pub trait Lexer {
-    fn tokens(&self) -> LexerIter;
+    fn tokens(&self) -> LexerIter<'_>;
 }
 
 impl Lexer for str {
-    fn tokens(&self) -> LexerIter {
+    fn tokens(&self) -> LexerIter<'_> {
         LexerIter {
             iter: self.chars().peekable(),
+            position: Position::default(),
+            trivia: vec![],
         }
     }
 }
@@ -1,6 +1,8 @@
 #![cfg(test)]
 
-use crate::lexer::{token::Token, Lexer};
+use crate::lexer::token::{TemplatePart, Token};
+use crate::lexer::{CommentKind, Lexer, LexerError};
+use crate::span::{Position, Span};
 
 #[test]
 fn test_lexer_one() {
@@ -74,7 +76,7 @@ let result = add(five, ten);
 
 #[test]
 fn test_lexer_three() {
-    let input = "!-/*5;
+    let input = "!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -172,3 +174,449 @@ while (x < 0) { x + x; }
     let tokens: Vec<_> = input.tokens().collect();
     assert_eq!(tests, tokens);
 }
+
+#[test]
+fn test_lexer_floats() {
+    let input = "3.14 0.5 10 1.x 1e3 2.5E-4 6e+2 1e x";
+    let tests = vec![
+        Token::Float(String::from("3.14")),
+        Token::Float(String::from("0.5")),
+        Token::Int(String::from("10")),
+        // a '.' only starts a fraction if a digit follows it
+        Token::Int(String::from("1")),
+        Token::Illegal('.'),
+        Token::Identifier(String::from("x")),
+        Token::Float(String::from("1e3")),
+        Token::Float(String::from("2.5E-4")),
+        Token::Float(String::from("6e+2")),
+        // and an 'e' only starts an exponent if a digit follows it
+        Token::Int(String::from("1")),
+        Token::Identifier(String::from("e")),
+        Token::Identifier(String::from("x")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_arithmetic_and_bitwise_operators() {
+    let input = "<= >= < > % ** * & && | || ^ << >>";
+    let tests = vec![
+        Token::LtEq,
+        Token::GtEq,
+        Token::Lt,
+        Token::Gt,
+        Token::Percent,
+        Token::Power,
+        Token::Asterisk,
+        Token::Ampersand,
+        Token::And,
+        Token::Pipe,
+        Token::Or,
+        Token::Caret,
+        Token::ShiftLeft,
+        Token::ShiftRight,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_string_escapes() {
+    let input = r#""a\nb\tc" "\"quoted\" \\ \$" "\u{41}\u{1F600}""#;
+    let tests = vec![
+        Token::String(String::from("a\nb\tc")),
+        Token::String(String::from("\"quoted\" \\ $")),
+        Token::String(String::from("A\u{1F600}")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_raw_strings() {
+    let input = r###"r"a\nb ${x}" r#"say "hi""# r##"a "# b"## r"" r (r)"###;
+    let tests = vec![
+        Token::String(String::from(r"a\nb ${x}")),
+        Token::String(String::from(r#"say "hi""#)),
+        Token::String(String::from(r##"a "# b"##)),
+        Token::String(String::new()),
+        // an `r` not followed by a quote is a name
+        Token::Identifier(String::from("r")),
+        Token::Lparen,
+        Token::Identifier(String::from("r")),
+        Token::Rparen,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+
+    for input in [r#"r"abc"#, r##"r#"abc""##] {
+        let tokens: Vec<_> = input.tokens().collect();
+        assert_eq!(
+            tokens,
+            vec![Token::Error(LexerError::UnterminatedString)],
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_lexer_string_errors() {
+    let tests = vec![
+        (r#""abc"#, LexerError::UnterminatedString),
+        (r#""abc\"#, LexerError::UnterminatedString),
+        (r#""a\qb""#, LexerError::InvalidEscape('q')),
+        (r#""\u41""#, LexerError::InvalidUnicodeEscape(String::new())),
+        (
+            r#""\u{110000}""#,
+            LexerError::InvalidUnicodeEscape(String::from("110000")),
+        ),
+        (
+            r#""\u{zz}""#,
+            LexerError::InvalidUnicodeEscape(String::from("zz")),
+        ),
+        (r#""${1 + ""#, LexerError::UnterminatedString),
+    ];
+
+    for (input, expected) in tests {
+        let tokens: Vec<_> = input.tokens().collect();
+        assert_eq!(tokens, vec![Token::Error(expected)], "{input}");
+    }
+}
+
+#[test]
+fn test_lexer_string_error_does_not_swallow_later_tokens() {
+    let input = r#""a\qb"; 1"#;
+    let tests = vec![
+        Token::Error(LexerError::InvalidEscape('q')),
+        Token::Semicolon,
+        Token::Int(String::from("1")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_template() {
+    let input = r#""a ${x + "${y}"} b""#;
+    let span = |start, end| Span::new(Position::new(1, start), Position::new(1, end));
+    let tests = vec![Token::Template(vec![
+        TemplatePart::Literal(String::from("a ")),
+        TemplatePart::Expression(
+            vec![
+                (Token::Identifier(String::from("x")), span(6, 7)),
+                (Token::Plus, span(8, 9)),
+                (
+                    Token::Template(vec![TemplatePart::Expression(
+                        vec![(Token::Identifier(String::from("y")), span(13, 14))],
+                        span(14, 15),
+                    )]),
+                    span(10, 16),
+                ),
+            ],
+            span(16, 17),
+        ),
+        TemplatePart::Literal(String::from(" b")),
+    ])];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_for_loops_and_ranges() {
+    let input = "for (i in 0..n) { 1.5..2 }";
+    let tests = vec![
+        Token::For,
+        Token::Lparen,
+        Token::Identifier(String::from("i")),
+        Token::In,
+        Token::Int(String::from("0")),
+        Token::DotDot,
+        Token::Identifier(String::from("n")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Float(String::from("1.5")),
+        Token::DotDot,
+        Token::Int(String::from("2")),
+        Token::Rbrace,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_loop_control_keywords() {
+    let input = "break; continue; breaks";
+    let tests = vec![
+        Token::Break,
+        Token::Semicolon,
+        Token::Continue,
+        Token::Semicolon,
+        Token::Identifier(String::from("breaks")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_illegal_characters() {
+    let input = "a $ b@ ~";
+    let tests = vec![
+        Token::Identifier(String::from("a")),
+        Token::Illegal('$'),
+        Token::Identifier(String::from("b")),
+        Token::Illegal('@'),
+        Token::Illegal('~'),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+    assert_eq!(Token::Illegal('$').to_string(), "$");
+}
+
+#[test]
+fn test_lexer_ellipsis() {
+    let input = "[a, ...b] 0..1 .... .";
+    let tests = vec![
+        Token::Lbracket,
+        Token::Identifier(String::from("a")),
+        Token::Comma,
+        Token::Ellipsis,
+        Token::Identifier(String::from("b")),
+        Token::Rbracket,
+        Token::Int(String::from("0")),
+        Token::DotDot,
+        Token::Int(String::from("1")),
+        Token::Ellipsis,
+        Token::Illegal('.'),
+        Token::Illegal('.'),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_exceptions() {
+    let input = "try {} catch (e) {} finally {} throw e;";
+    let tests = vec![
+        Token::Try,
+        Token::Lbrace,
+        Token::Rbrace,
+        Token::Catch,
+        Token::Lparen,
+        Token::Identifier(String::from("e")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Rbrace,
+        Token::Finally,
+        Token::Lbrace,
+        Token::Rbrace,
+        Token::Throw,
+        Token::Identifier(String::from("e")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_match() {
+    let input = "match (x) { _ => 1 } == =>";
+    let tests = vec![
+        Token::Match,
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Identifier(String::from("_")),
+        Token::FatArrow,
+        Token::Int(String::from("1")),
+        Token::Rbrace,
+        Token::Eq,
+        Token::FatArrow,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_compound_assignment_operators() {
+    let input = "+= -= *= /= **= - =";
+    let tests = vec![
+        Token::PlusAssign,
+        Token::MinusAssign,
+        Token::AsteriskAssign,
+        Token::SlashAssign,
+        Token::Power,
+        Token::Assign,
+        Token::Minus,
+        Token::Assign,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_skips_comments() {
+    let input = "counter(); // 1
+/* a block
+   comment */ 10 / 2 /* nested /* comments */ are skipped */ ;
+//";
+    let tests = vec![
+        Token::Identifier(String::from("counter")),
+        Token::Lparen,
+        Token::Rparen,
+        Token::Semicolon,
+        Token::Int(String::from("10")),
+        Token::Slash,
+        Token::Int(String::from("2")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_keeps_comments_as_trivia() {
+    let input = "let x = 1; // one
+/* two /* three */ */ x";
+    let mut lexer = input.tokens().spanned();
+    lexer.by_ref().for_each(drop);
+    let trivia: Vec<_> = lexer
+        .trivia()
+        .iter()
+        .map(|comment| {
+            (
+                comment.kind,
+                comment.text.as_str(),
+                (comment.span.start.line, comment.span.start.column),
+                (comment.span.end.line, comment.span.end.column),
+            )
+        })
+        .collect();
+    let expected = vec![
+        (CommentKind::Line, "// one", (1, 12), (1, 18)),
+        (CommentKind::Block, "/* two /* three */ */", (2, 1), (2, 22)),
+    ];
+    assert_eq!(trivia, expected);
+}
+
+#[test]
+fn test_lexer_unterminated_block_comment() {
+    let input = "1 /* open /* nested */";
+    let tests = vec![
+        (Token::Int(String::from("1")), (1, 1), (1, 2)),
+        (
+            Token::Error(LexerError::UnterminatedComment),
+            (1, 3),
+            (1, 23),
+        ),
+    ];
+    let tokens: Vec<_> = input
+        .tokens()
+        .spanned()
+        .map(|(token, span)| {
+            (
+                token,
+                (span.start.line, span.start.column),
+                (span.end.line, span.end.column),
+            )
+        })
+        .collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_comments_inside_interpolation() {
+    let input = r#""${ 1 /* one */ }" "// not a comment""#;
+    let span = |start, end| Span::new(Position::new(1, start), Position::new(1, end));
+    let tests = vec![
+        Token::Template(vec![TemplatePart::Expression(
+            vec![(Token::Int(String::from("1")), span(5, 6))],
+            span(17, 18),
+        )]),
+        Token::String(String::from("// not a comment")),
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_spans() {
+    let input = "let x = 10;
+  x == \"ab\"";
+    let tests = vec![
+        (Token::Let, (1, 1), (1, 4)),
+        (Token::Identifier(String::from("x")), (1, 5), (1, 6)),
+        (Token::Assign, (1, 7), (1, 8)),
+        (Token::Int(String::from("10")), (1, 9), (1, 11)),
+        (Token::Semicolon, (1, 11), (1, 12)),
+        (Token::Identifier(String::from("x")), (2, 3), (2, 4)),
+        (Token::Eq, (2, 5), (2, 7)),
+        (Token::String(String::from("ab")), (2, 8), (2, 12)),
+    ];
+    let tokens: Vec<_> = input
+        .tokens()
+        .spanned()
+        .map(|(token, span)| {
+            (
+                token,
+                (span.start.line, span.start.column),
+                (span.end.line, span.end.column),
+            )
+        })
+        .collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_position_at_end_of_input() {
+    let mut lexer = "a\nbc ".tokens();
+    lexer.by_ref().for_each(drop);
+    assert_eq!(lexer.position(), Position::new(2, 4));
+}
+
+#[test]
+fn test_lexer_macro() {
+    let input = "let unless = macro(x) { quote(unquote(x)) };";
+    let tests = vec![
+        Token::Let,
+        Token::Identifier(String::from("unless")),
+        Token::Assign,
+        Token::Macro,
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::Rparen,
+        Token::Lbrace,
+        Token::Identifier(String::from("quote")),
+        Token::Lparen,
+        Token::Identifier(String::from("unquote")),
+        Token::Lparen,
+        Token::Identifier(String::from("x")),
+        Token::Rparen,
+        Token::Rparen,
+        Token::Rbrace,
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
+
+#[test]
+fn test_lexer_import_export() {
+    let input = "import \"lib/strings.mk\" as strings; export let x = 1;";
+    let tests = vec![
+        Token::Import,
+        Token::String(String::from("lib/strings.mk")),
+        Token::As,
+        Token::Identifier(String::from("strings")),
+        Token::Semicolon,
+        Token::Export,
+        Token::Let,
+        Token::Identifier(String::from("x")),
+        Token::Assign,
+        Token::Int(String::from("1")),
+        Token::Semicolon,
+    ];
+    let tokens: Vec<_> = input.tokens().collect();
+    assert_eq!(tests, tokens);
+}
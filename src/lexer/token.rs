@@ -1,25 +1,46 @@
 use std::fmt::Display;
 
+use crate::lexer::LexerError;
+use crate::span::Span;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     // identifiers and literals
     Identifier(String),
     Int(String),
+    Float(String),
     String(String),
+    Template(Vec<TemplatePart>),
 
     // operators
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
     Plus,
     Minus,
     Bang,
     Asterisk,
     Slash,
+    Percent,
+    Power,
     Lt,
     Gt,
+    LtEq,
+    GtEq,
     Eq,
     Noteq,
     Or,
     And,
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
+    DotDot,
+    Ellipsis,
+    FatArrow,
 
     // delimiters
     Comma,
@@ -41,9 +62,24 @@ pub enum Token {
     Else,
     Return,
     While,
+    For,
+    In,
+    Break,
+    Continue,
+    Match,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Macro,
+    Import,
+    Export,
+    As,
 
     // misc
-    Illegal,
+    /// A character that cannot start a token.
+    Illegal(char),
+    Error(LexerError),
     Eof,
 }
 
@@ -55,19 +91,37 @@ impl Display for Token {
             match self {
                 Token::Identifier(id) => id.to_string(),
                 Token::Int(val) => val.to_string(),
+                Token::Float(val) => val.to_string(),
                 Token::String(string) => string.to_string(),
+                Token::Template(parts) => parts.iter().map(|part| part.to_string()).collect(),
                 Token::Assign => String::from("="),
+                Token::PlusAssign => String::from("+="),
+                Token::MinusAssign => String::from("-="),
+                Token::AsteriskAssign => String::from("*="),
+                Token::SlashAssign => String::from("/="),
                 Token::Plus => String::from("+"),
                 Token::Minus => String::from("-"),
                 Token::Bang => String::from("!"),
                 Token::Asterisk => String::from("*"),
                 Token::Slash => String::from("/"),
+                Token::Percent => String::from("%"),
+                Token::Power => String::from("**"),
                 Token::Lt => String::from("<"),
                 Token::Gt => String::from(">"),
+                Token::LtEq => String::from("<="),
+                Token::GtEq => String::from(">="),
                 Token::Eq => String::from("=="),
                 Token::Noteq => String::from("!="),
                 Token::Or => String::from("||"),
                 Token::And => String::from("&&"),
+                Token::Ampersand => String::from("&"),
+                Token::Pipe => String::from("|"),
+                Token::Caret => String::from("^"),
+                Token::ShiftLeft => String::from("<<"),
+                Token::ShiftRight => String::from(">>"),
+                Token::DotDot => String::from(".."),
+                Token::Ellipsis => String::from("..."),
+                Token::FatArrow => String::from("=>"),
                 Token::Comma => String::from(","),
                 Token::Semicolon => String::from(";"),
                 Token::Colon => String::from(":"),
@@ -85,9 +139,48 @@ impl Display for Token {
                 Token::Else => String::from("else"),
                 Token::Return => String::from("return"),
                 Token::While => String::from("while"),
-                Token::Illegal => String::from("illegal token"),
+                Token::For => String::from("for"),
+                Token::In => String::from("in"),
+                Token::Break => String::from("break"),
+                Token::Continue => String::from("continue"),
+                Token::Match => String::from("match"),
+                Token::Throw => String::from("throw"),
+                Token::Try => String::from("try"),
+                Token::Catch => String::from("catch"),
+                Token::Finally => String::from("finally"),
+                Token::Macro => String::from("macro"),
+                Token::Import => String::from("import"),
+                Token::Export => String::from("export"),
+                Token::As => String::from("as"),
+                Token::Illegal(ch) => ch.to_string(),
+                Token::Error(error) => error.to_string(),
                 Token::Eof => String::from("EOF"),
             }
         )
     }
 }
+
+/// A piece of a string containing `${...}`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    Literal(String),
+    /// The tokens of an embedded expression, and the span of the '}' ending it.
+    Expression(Vec<(Token, Span)>, Span),
+}
+
+impl Display for TemplatePart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplatePart::Literal(string) => write!(f, "{string}"),
+            TemplatePart::Expression(tokens, _) => write!(
+                f,
+                "${{{}}}",
+                tokens
+                    .iter()
+                    .map(|(token, _)| token.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
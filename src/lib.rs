@@ -0,0 +1,18 @@
+//! The Monkey interpreter, as a library a host can embed: programs are parsed
+//! with `parser`, and run either by `evaluator` or by `compiler` and `vm`,
+//! with the builtins of an `object::builtins::Builtins` registry.
+
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod diagnostics;
+mod difftest;
+pub mod evaluator;
+pub mod lexer;
+pub mod module;
+pub mod object;
+pub mod parser;
+pub mod repl;
+pub mod span;
+pub mod symtab;
+pub mod vm;
@@ -1,17 +1,86 @@
-use std::io;
+use std::io::{self, IsTerminal, Read};
+use std::process::ExitCode;
+use std::rc::Rc;
 
-use crate::repl::Repl;
+use rust_monkey::cli::{self, Command, Failure, Options, Source, USAGE};
+use rust_monkey::evaluator::STACK_SIZE;
+use rust_monkey::object::builtins::Builtins;
+use rust_monkey::object::Object;
+use rust_monkey::repl::Repl;
 
-mod code;
-mod compiler;
-mod evaluator;
-pub mod lexer;
-mod object;
-mod parser;
-pub mod repl;
-mod symtab;
-mod vm;
+fn main() -> ExitCode {
+    // the evaluator nests Rust calls for the calls in a program, so needs a
+    // larger stack than the main thread's
+    let main = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command);
+    match main.map(|thread| thread.join()) {
+        Ok(Ok(code)) => code,
+        Ok(Err(panic)) => std::panic::resume_unwind(panic),
+        Err(e) => {
+            eprintln!("error: cannot start the interpreter: {e}");
+            Failure::Io.into()
+        }
+    }
+}
+
+fn run_command() -> ExitCode {
+    let options = match cli::parse_args(std::env::args().skip(1), io::stdin().is_terminal()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return Failure::Usage.into();
+        }
+    };
+
+    let Options {
+        engine,
+        lib,
+        command,
+    } = options;
+
+    let builtins = Rc::new(Builtins::new());
+    let (source, args) = match command {
+        Command::Help => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Command::Repl => {
+            return match Repl::start(engine, lib, builtins) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    Failure::Io.into()
+                }
+            };
+        }
+        Command::Run { source, args } => (source, args),
+    };
+
+    let input = match &source {
+        Source::File(path) => std::fs::read_to_string(path),
+        Source::Expression(code) => Ok(code.clone()),
+        Source::Stdin => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {e}", source.name());
+            return Failure::Io.into();
+        }
+    };
 
-fn main() -> io::Result<()> {
-    Repl::start()
+    match cli::run(&source, &input, &args, engine, &lib, builtins) {
+        Ok(Some(object)) if matches!(source, Source::Expression(_)) => {
+            if *object != Object::Null {
+                println!("{object}");
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(failure) => failure.into(),
+    }
 }
@@ -0,0 +1,230 @@
+use crate::diagnostics::Diagnostic;
+use crate::evaluator::macros::{MacroError, Macros};
+use crate::object::builtins::Builtins;
+use crate::parser::ast::{Expression, Import, Module, Program, Statement};
+use crate::parser::{Parser, ParsingError};
+use crate::span::{Located, Sources, Span, MAIN_SOURCE};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+mod tests;
+
+/// Reads the modules a program imports, and the modules those import in turn.
+///
+/// `import "lib/strings.mk" as strings;` looks for the file relative to the
+/// directory of the importing file first, and then relative to each search
+/// path in order. Each file is loaded once, however many imports name it, and
+/// a module that imports itself, directly or through others, is an error.
+pub struct Loader {
+    search_paths: Vec<PathBuf>,
+    /// The program and the modules loaded so far, for diagnostics to quote.
+    pub sources: Sources,
+    // the modules loaded so far, by their canonical path
+    modules: HashMap<PathBuf, Rc<Module>>,
+    // the canonical path and name of each module being loaded, importers first
+    loading: Vec<(PathBuf, String)>,
+    // the builtins the macros of the modules call
+    builtins: Rc<Builtins>,
+}
+
+impl Loader {
+    /// A loader for the program `text`, named `name` in diagnostics, whose
+    /// modules are run with the builtins of `builtins`.
+    pub fn new(name: &str, text: &str, search_paths: Vec<PathBuf>, builtins: Rc<Builtins>) -> Self {
+        Loader {
+            search_paths,
+            sources: Sources::new(name, text),
+            modules: HashMap::new(),
+            loading: vec![],
+            builtins,
+        }
+    }
+
+    /// Loads the modules `program` imports into its `import` statements.
+    /// `file` is the program's file, if it has one, which relative paths are
+    /// resolved from; otherwise they are resolved from the current directory.
+    pub fn load(&mut self, program: Program, file: Option<&Path>) -> Result<Program, LoadError> {
+        let dir = file.and_then(Path::parent).unwrap_or(Path::new(""));
+        let canonical = file.and_then(|file| file.canonicalize().ok());
+
+        if let Some(canonical) = canonical.clone() {
+            let (name, _) = self.sources.get(MAIN_SOURCE);
+            self.loading.push((canonical, name.to_string()));
+        }
+        let program = self.load_imports(program, dir);
+        if canonical.is_some() {
+            self.loading.pop();
+        }
+        program
+    }
+
+    fn load_imports(&mut self, program: Program, dir: &Path) -> Result<Program, LoadError> {
+        let Program(statements) = program;
+        let statements = statements
+            .into_iter()
+            .map(|statement| match statement {
+                Statement::Import(import, span) => {
+                    let module = self.load_module(&import.path, dir, span)?;
+                    let import = Import {
+                        module: Some(module),
+                        ..import
+                    };
+                    Ok(Statement::Import(import, span))
+                }
+                statement => Ok(statement),
+            })
+            .collect::<Result<_, LoadError>>()?;
+        Ok(Program(statements))
+    }
+
+    /// Loads the module at `path`, imported from a file in `dir` by the
+    /// statement at `span`, unless it has been loaded already.
+    fn load_module(&mut self, path: &str, dir: &Path, span: Span) -> Result<Rc<Module>, LoadError> {
+        let Some((found, file)) = self.resolve(path, dir) else {
+            return Err(Located::new(ImportError::NotFound(path.to_string()), span).into());
+        };
+        let name = found.display().to_string();
+
+        if let Some(idx) = self
+            .loading
+            .iter()
+            .position(|(loading, _)| *loading == file)
+        {
+            let mut cycle: Vec<_> = self.loading[idx..]
+                .iter()
+                .map(|(_, name)| name.clone())
+                .collect();
+            cycle.push(name);
+            return Err(Located::new(ImportError::Cycle(cycle), span).into());
+        }
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(Rc::clone(module));
+        }
+
+        let text = std::fs::read_to_string(&file).map_err(|e| {
+            Located::new(ImportError::Unreadable(name.clone(), e.to_string()), span)
+        })?;
+        let source = self.sources.add(&name, &text);
+        let program = Parser::parse_source(&text, source).map_err(LoadError::Syntax)?;
+        let program = Macros::new_with_builtins(Rc::clone(&self.builtins))
+            .expand(program)
+            .map_err(LoadError::Macro)?;
+
+        self.loading.push((file.clone(), name));
+        let program = self.load_imports(program, found.parent().unwrap_or(Path::new("")));
+        self.loading.pop();
+        let program = program?;
+
+        let mut exports = vec![];
+        for statement in program.0.iter() {
+            if let Statement::Export(statement, _) = statement {
+                if let Statement::Let(Expression::Identifier(name), ..) = &**statement {
+                    if !exports.contains(name) {
+                        exports.push(name.clone());
+                    }
+                }
+            }
+        }
+
+        let module = Rc::new(Module {
+            source,
+            program,
+            exports,
+        });
+        self.modules.insert(file, Rc::clone(&module));
+        Ok(module)
+    }
+
+    /// Finds the file `path` names, returning the path it was found at, which
+    /// names the module in diagnostics, and its canonical path, which
+    /// identifies it.
+    fn resolve(&self, path: &str, dir: &Path) -> Option<(PathBuf, PathBuf)> {
+        std::iter::once(dir)
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .and_then(|found| {
+                let canonical = found.canonicalize().ok()?;
+                Some((found, canonical))
+            })
+    }
+}
+
+/// A directory of files for a test to import, removed when dropped.
+#[cfg(test)]
+pub struct TestDir(pub PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Writes each `(path, text)` of `files` to a directory unique to `test`.
+    pub fn new(test: &str, files: &[(&str, &str)]) -> Self {
+        let dir = std::env::temp_dir().join(format!("rust-monkey-{}-{test}", std::process::id()));
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        TestDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Why the modules of a program could not be loaded: an error in a module's
+/// code, or in an import.
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    Syntax(Vec<Located<ParsingError>>),
+    Macro(Located<MacroError>),
+    Import(Located<ImportError>),
+}
+
+impl LoadError {
+    /// Renders the diagnostics for the error against `sources`.
+    pub fn render(&self, sources: &Sources) -> String {
+        match self {
+            LoadError::Syntax(errors) => errors
+                .iter()
+                .map(|e| Diagnostic::from(e).render_in(sources))
+                .collect(),
+            LoadError::Macro(e) => Diagnostic::from(e).render_in(sources),
+            LoadError::Import(e) => Diagnostic::from(e).render_in(sources),
+        }
+    }
+}
+
+impl From<Located<ImportError>> for LoadError {
+    fn from(error: Located<ImportError>) -> Self {
+        LoadError::Import(error)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    /// The path as written in the `import`.
+    NotFound(String),
+    /// The names of the modules importing each other, starting and ending
+    /// with the same module.
+    Cycle(Vec<String>),
+    /// The name of the module, and why it could not be read.
+    Unreadable(String, String),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::NotFound(path) => write!(f, "cannot find module '{path}'"),
+            ImportError::Cycle(names) => write!(f, "import cycle: {}", names.join(" -> ")),
+            ImportError::Unreadable(name, reason) => {
+                write!(f, "cannot read module '{name}': {reason}")
+            }
+        }
+    }
+}
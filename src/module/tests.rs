@@ -0,0 +1,148 @@
+#![cfg(test)]
+
+use crate::module::{ImportError, LoadError, Loader, TestDir};
+use crate::object::builtins::Builtins;
+use crate::parser::ast::{Module, Program, Statement};
+use crate::parser::Parser;
+use crate::span::Position;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Loads the modules imported by the file `main` in `dir`.
+fn load(dir: &TestDir, main: &str, search_paths: &[&str]) -> Result<Program, LoadError> {
+    let file = dir.0.join(main);
+    let text = std::fs::read_to_string(&file).unwrap();
+    let search_paths = search_paths.iter().map(|path| dir.0.join(path)).collect();
+    let mut loader = Loader::new(main, &text, search_paths, Rc::new(Builtins::new()));
+    loader.load(Parser::parse_program(&text).unwrap(), Some(&file))
+}
+
+fn imported(program: &Program) -> Vec<Rc<Module>> {
+    program
+        .0
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Import(import, _) => import.module.clone(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn import_error(result: Result<Program, LoadError>) -> (ImportError, Option<Position>) {
+    match result {
+        Err(LoadError::Import(e)) => (e.error, e.span.map(|span| span.start)),
+        result => panic!("expected an import error, got {result:?}"),
+    }
+}
+
+#[test]
+fn test_load_modules_and_exports() {
+    let dir = TestDir::new(
+        "exports",
+        &[
+            ("main.mk", "import \"lib/strings.mk\" as strings;"),
+            (
+                "lib/strings.mk",
+                "export let upper = fn(s) { s }; let hidden = 1; export let pad = 2;",
+            ),
+        ],
+    );
+    let program = load(&dir, "main.mk", &[]).unwrap();
+    let modules = imported(&program);
+    assert_eq!(modules.len(), 1);
+    assert_eq!(modules[0].source, 1);
+    assert_eq!(modules[0].exports, vec!["upper", "pad"]);
+    assert_eq!(modules[0].program.0[0].span().source, 1);
+}
+
+#[test]
+fn test_load_resolves_against_importer_then_search_paths() {
+    let dir = TestDir::new(
+        "search",
+        &[
+            ("main.mk", "import \"a.mk\" as a; import \"b.mk\" as b;"),
+            ("a.mk", "export let from = \"main directory\";"),
+            ("first/a.mk", "export let from = \"first\";"),
+            ("first/b.mk", "import \"c.mk\" as c;"),
+            ("first/c.mk", "export let from = \"beside b\";"),
+            ("second/b.mk", "export let from = \"second\";"),
+        ],
+    );
+    let program = load(&dir, "main.mk", &["first", "second"]).unwrap();
+    let modules = imported(&program);
+    let c = imported(&modules[1].program);
+    assert_eq!(modules[0].exports, vec!["from"]);
+    assert_eq!(modules[1].exports, Vec::<String>::new());
+    assert_eq!(c.len(), 1);
+}
+
+#[test]
+fn test_load_caches_modules() {
+    let dir = TestDir::new(
+        "cache",
+        &[
+            (
+                "main.mk",
+                "import \"a.mk\" as a; import \"lib/b.mk\" as b; import \"./a.mk\" as again;",
+            ),
+            ("a.mk", "export let x = 1;"),
+            ("lib/b.mk", "import \"../a.mk\" as a;"),
+        ],
+    );
+    let program = load(&dir, "main.mk", &[]).unwrap();
+    let modules = imported(&program);
+    let from_b = imported(&modules[1].program);
+    assert!(Rc::ptr_eq(&modules[0], &modules[2]));
+    assert!(Rc::ptr_eq(&modules[0], &from_b[0]));
+}
+
+#[test]
+fn test_load_errors() {
+    let dir = TestDir::new(
+        "errors",
+        &[
+            ("missing.mk", "let x = 1;\nimport \"nowhere.mk\" as n;"),
+            ("self.mk", "import \"self.mk\" as me;"),
+            ("a.mk", "import \"b.mk\" as b;"),
+            ("b.mk", "\n  import \"a.mk\" as a;"),
+            ("syntax.mk", "import \"bad.mk\" as bad;"),
+            ("bad.mk", "let = 1;"),
+            ("macro.mk", "import \"quoted.mk\" as q;"),
+            ("quoted.mk", "quote(1);"),
+        ],
+    );
+    assert_eq!(
+        import_error(load(&dir, "missing.mk", &[])),
+        (
+            ImportError::NotFound("nowhere.mk".to_string()),
+            Some(Position::new(2, 1))
+        )
+    );
+
+    let (error, _) = import_error(load(&dir, "self.mk", &[]));
+    let ImportError::Cycle(names) = error else {
+        panic!("expected a cycle, got {error:?}");
+    };
+    assert_eq!(names.len(), 2);
+    assert!(names[1].ends_with("self.mk"));
+
+    let (error, position) = import_error(load(&dir, "a.mk", &[]));
+    let ImportError::Cycle(names) = error else {
+        panic!("expected a cycle, got {error:?}");
+    };
+    let names: Vec<_> = names
+        .iter()
+        .map(|name| PathBuf::from(name).file_name().unwrap().to_owned())
+        .collect();
+    assert_eq!(names, vec!["a.mk", "b.mk", "a.mk"]);
+    assert_eq!(position, Some(Position::new(2, 3)));
+
+    match load(&dir, "syntax.mk", &[]) {
+        Err(LoadError::Syntax(errors)) => assert_eq!(errors[0].span.unwrap().source, 1),
+        result => panic!("expected a syntax error, got {result:?}"),
+    }
+    assert!(matches!(
+        load(&dir, "macro.mk", &[]),
+        Err(LoadError::Macro(_))
+    ));
+}
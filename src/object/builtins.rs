@@ -1,130 +1,310 @@
-use crate::object::Object;
+use crate::diagnostics::describe_thrown;
+use crate::object::{ErrorObject, Hashable, Object};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
-pub const NUM_BUILTINS: usize = 6;
+thread_local! {
+    /// Collects what `puts` prints while output is being captured.
+    static CAPTURED_OUTPUT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, collecting what `puts` prints instead of writing it to stdout.
+#[cfg(test)]
+pub fn capture_output<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let outer = CAPTURED_OUTPUT.with(|output| output.replace(Some(String::new())));
+    let result = f();
+    let captured = CAPTURED_OUTPUT.with(|output| output.replace(outer));
+    (result, captured.unwrap_or_default())
+}
+
+fn print_line(line: String) {
+    CAPTURED_OUTPUT.with(|output| match &mut *output.borrow_mut() {
+        Some(captured) => {
+            captured.push_str(&line);
+            captured.push('\n');
+        }
+        None => println!("{line}"),
+    })
+}
+
+/// How many builtins and namespaces a registry can hold, as `OpGetBuiltin`
+/// has a one-byte operand.
+const MAX_BUILTINS: usize = 256;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum Builtin {
-    Len,
-    First,
-    Last,
-    Rest,
-    Push,
-    Puts,
+/// The Rust function behind a builtin. It is only called with a number of
+/// arguments its builtin's arity accepts.
+pub type NativeFunction = dyn Fn(&[Rc<Object>]) -> Result<Rc<Object>, BuiltinError>;
+
+/// The numbers of arguments a builtin can be called with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    /// At least the first number and at most the second.
+    Between(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn accepts(self, num_args: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => num_args == n,
+            Arity::Between(min, max) => (min..=max).contains(&num_args),
+            Arity::AtLeast(min) => num_args >= min,
+        }
+    }
+}
+
+/// A function provided by the host rather than written in Monkey.
+#[derive(Clone)]
+pub struct Builtin {
+    /// The name it is registered under, after its namespace if it has one,
+    /// e.g. `len` or `math.sqrt`.
+    pub name: String,
+    arity: Arity,
+    function: Rc<NativeFunction>,
 }
 
 impl Builtin {
-    pub fn get(id: &str) -> Option<Rc<Object>> {
-        Some(match id {
-            "len" => Rc::new(Object::Builtin(Builtin::Len)),
-            "first" => Rc::new(Object::Builtin(Builtin::First)),
-            "last" => Rc::new(Object::Builtin(Builtin::Last)),
-            "rest" => Rc::new(Object::Builtin(Builtin::Rest)),
-            "push" => Rc::new(Object::Builtin(Builtin::Push)),
-            "puts" => Rc::new(Object::Builtin(Builtin::Puts)),
-            _ => return None,
-        })
-    }
-
-    pub fn get_by_idx(id: usize) -> Option<Rc<Object>> {
-        Some(match id {
-            0 => Rc::new(Object::Builtin(Builtin::Len)),
-            1 => Rc::new(Object::Builtin(Builtin::First)),
-            2 => Rc::new(Object::Builtin(Builtin::Last)),
-            3 => Rc::new(Object::Builtin(Builtin::Rest)),
-            4 => Rc::new(Object::Builtin(Builtin::Push)),
-            5 => Rc::new(Object::Builtin(Builtin::Puts)),
-            _ => return None,
-        })
+    pub fn new(
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> + 'static,
+    ) -> Self {
+        Builtin {
+            name: name.to_string(),
+            arity,
+            function: Rc::new(function),
+        }
     }
 
     pub fn apply(&self, args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
-        Ok(match self {
-            Builtin::Len => {
-                if args.len() != 1 {
-                    return Err(BuiltinError::IncorrectNumberOfArgs);
-                }
-
-                // safe to unwrap as the length of args is 1
-                let result = match &**args.first().unwrap() {
-                    Object::String(string) => string.len() as i64,
-                    Object::Array(array) => array.len() as i64,
-                    _ => return Err(BuiltinError::IncompatibleTypes),
-                };
-
-                Rc::new(Object::Integer(result))
-            }
-            Builtin::First => {
-                if args.len() != 1 {
-                    return Err(BuiltinError::IncorrectNumberOfArgs);
-                }
-
-                if let Object::Array(array) = &**args.first().unwrap() {
-                    match array.first() {
-                        Some(element) => Rc::clone(element),
-                        None => Rc::new(Object::Null),
-                    }
-                } else {
-                    return Err(BuiltinError::IncompatibleTypes);
-                }
-            }
-            Builtin::Last => {
-                if args.len() != 1 {
-                    return Err(BuiltinError::IncorrectNumberOfArgs);
-                }
-
-                if let Object::Array(array) = &**args.first().unwrap() {
-                    match array.last() {
-                        Some(object) => Rc::clone(object),
-                        None => Rc::new(Object::Null),
-                    }
-                } else {
-                    return Err(BuiltinError::IncompatibleTypes);
-                }
-            }
-            Builtin::Rest => {
-                if args.len() != 1 {
-                    return Err(BuiltinError::IncorrectNumberOfArgs);
-                }
-
-                if let Object::Array(array) = &**args.first().unwrap() {
-                    if array.is_empty() {
-                        Rc::new(Object::Null)
-                    } else {
-                        Rc::new(Object::Array(array[1..].to_vec()))
-                    }
-                } else {
-                    return Err(BuiltinError::IncompatibleTypes);
-                }
-            }
-            Builtin::Push => {
-                if args.len() != 2 {
-                    return Err(BuiltinError::IncorrectNumberOfArgs);
-                }
-
-                if let Object::Array(array) = &**args.first().unwrap() {
-                    let mut new_array = vec![];
-                    array
-                        .iter()
-                        .for_each(|element| new_array.push(element.clone()));
-                    let element = Rc::clone(&args[1].clone());
-                    new_array.push(element);
-                    Rc::new(Object::Array(new_array))
-                } else {
-                    return Err(BuiltinError::IncompatibleTypes);
-                }
-            }
-            Builtin::Puts => {
-                for arg in args {
-                    println!("{arg}");
-                }
-                Rc::new(Object::Null)
-            }
-        })
+        if !self.arity.accepts(args.len()) {
+            return Err(BuiltinError::IncorrectNumberOfArgs);
+        }
+        (self.function)(args)
+    }
+}
+
+impl Debug for Builtin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builtins registered under the same name are taken to be the same, as their
+/// functions cannot be compared.
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arity == other.arity
+    }
+}
+
+/// The builtins programs can call, which the symbol table, the evaluator and
+/// the VM share, so that a host can add functions of its own to them.
+///
+/// A builtin is registered either under a name of its own or in a namespace,
+/// which programs see as a hash of its builtins, e.g. `math["sqrt"](2.0)`.
+/// Each name keeps the index it was first registered at, which is how the
+/// compiled code refers to it.
+#[derive(Debug, PartialEq)]
+pub struct Builtins {
+    // the builtins, and the hashes of the namespaces, with their names
+    entries: Vec<(String, Rc<Object>)>,
+}
+
+impl Builtins {
+    /// A registry of the standard builtins: `len`, `first`, `last`, `rest`,
+    /// `push`, `puts` and `error`.
+    pub fn new() -> Self {
+        let entry = |name: &str, arity, function: fn(&[Rc<Object>]) -> _| {
+            let builtin = Builtin::new(name, arity, function);
+            (name.to_string(), Rc::new(Object::Builtin(builtin)))
+        };
+        let entries = vec![
+            entry("len", Arity::Exactly(1), len),
+            entry("first", Arity::Exactly(1), first),
+            entry("last", Arity::Exactly(1), last),
+            entry("rest", Arity::Exactly(1), rest),
+            entry("push", Arity::Exactly(2), push),
+            entry("puts", Arity::AtLeast(0), puts),
+            entry("error", Arity::Between(1, 2), error),
+        ];
+        Builtins { entries }
+    }
+
+    /// Registers `function` as the builtin `name`, replacing any builtin or
+    /// namespace already registered under it. A new name cannot be registered
+    /// once there are as many builtins and namespaces as compiled code can
+    /// refer to.
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> + 'static,
+    ) -> Result<(), RegistryFull> {
+        let builtin = Builtin::new(name, arity, function);
+        self.set(name, Rc::new(Object::Builtin(builtin)))
+    }
+
+    /// Registers `function` as the builtin `name` in `namespace`, replacing
+    /// any builtin of that name in it. The namespace is created, replacing any
+    /// builtin registered under its name, if it does not exist yet, which it
+    /// cannot be once the registry is full.
+    pub fn register_in(
+        &mut self,
+        namespace: &str,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> + 'static,
+    ) -> Result<(), RegistryFull> {
+        let mut members = match self.get(namespace).as_deref() {
+            Some(Object::Hash(members)) => members.clone(),
+            _ => HashMap::new(),
+        };
+        let builtin = Builtin::new(&format!("{namespace}.{name}"), arity, function);
+        members.insert(
+            Hashable::String(name.to_string()),
+            Rc::new(Object::Builtin(builtin)),
+        );
+        self.set(namespace, Rc::new(Object::Hash(members)))
+    }
+
+    fn set(&mut self, name: &str, object: Rc<Object>) -> Result<(), RegistryFull> {
+        let full = self.entries.len() == MAX_BUILTINS;
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some((_, entry)) => *entry = object,
+            None if full => return Err(RegistryFull),
+            None => self.entries.push((name.to_string(), object)),
+        }
+        Ok(())
+    }
+
+    /// The builtin or namespace registered under `name`.
+    pub fn get(&self, name: &str) -> Option<Rc<Object>> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, object)| Rc::clone(object))
+    }
+
+    pub fn get_by_idx(&self, idx: usize) -> Option<Rc<Object>> {
+        self.entries.get(idx).map(|(_, object)| Rc::clone(object))
+    }
+
+    /// The names of the builtins and namespaces, in the order of their indices.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Default for Builtins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn len(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    let result = match &*args[0] {
+        Object::String(string) => string.len() as i64,
+        Object::Array(array) => array.len() as i64,
+        _ => return Err(BuiltinError::IncompatibleTypes),
+    };
+    Ok(Rc::new(Object::Integer(result)))
+}
+
+fn first(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    let Object::Array(array) = &*args[0] else {
+        return Err(BuiltinError::IncompatibleTypes);
+    };
+    Ok(match array.first() {
+        Some(element) => Rc::clone(element),
+        None => Rc::new(Object::Null),
+    })
+}
+
+fn last(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    let Object::Array(array) = &*args[0] else {
+        return Err(BuiltinError::IncompatibleTypes);
+    };
+    Ok(match array.last() {
+        Some(element) => Rc::clone(element),
+        None => Rc::new(Object::Null),
+    })
+}
+
+fn rest(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    let Object::Array(array) = &*args[0] else {
+        return Err(BuiltinError::IncompatibleTypes);
+    };
+    Ok(match array.is_empty() {
+        true => Rc::new(Object::Null),
+        false => Rc::new(Object::Array(array[1..].to_vec())),
+    })
+}
+
+fn push(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    let Object::Array(array) = &*args[0] else {
+        return Err(BuiltinError::IncompatibleTypes);
+    };
+    let mut new_array = array.clone();
+    new_array.push(Rc::clone(&args[1]));
+    Ok(Rc::new(Object::Array(new_array)))
+}
+
+fn puts(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    for arg in args {
+        print_line(arg.to_string());
     }
+    Ok(Rc::new(Object::Null))
+}
+
+fn error(args: &[Rc<Object>]) -> Result<Rc<Object>, BuiltinError> {
+    // the message, and optionally the kind
+    let kind = match args.get(1).map(|kind| &**kind) {
+        None => "Error",
+        Some(Object::String(kind)) => kind.as_str(),
+        Some(_) => return Err(BuiltinError::IncompatibleTypes),
+    };
+    let Object::String(message) = &*args[0] else {
+        return Err(BuiltinError::IncompatibleTypes);
+    };
+    Ok(Rc::new(Object::Error(ErrorObject::new(kind, message))))
 }
 
+#[derive(Debug, PartialEq)]
 pub enum BuiltinError {
     IncompatibleTypes,
     IncorrectNumberOfArgs,
+    /// A value to raise as if by `throw`, e.g. an error made with
+    /// `ErrorObject::new`.
+    Thrown(Rc<Object>),
+}
+
+impl Display for BuiltinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuiltinError::IncompatibleTypes => write!(f, "incompatible types"),
+            BuiltinError::IncorrectNumberOfArgs => write!(f, "wrong number of arguments"),
+            BuiltinError::Thrown(object) => write!(f, "uncaught {}", describe_thrown(object)),
+        }
+    }
+}
+
+/// The error registering a builtin or namespace under a new name in a
+/// registry that already holds as many as compiled code can refer to.
+#[derive(Debug, PartialEq)]
+pub struct RegistryFull;
+
+impl Display for RegistryFull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot register more than {MAX_BUILTINS} builtins and namespaces"
+        )
+    }
 }
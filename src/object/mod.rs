@@ -1,7 +1,8 @@
-use crate::code::Instructions;
+use crate::code::{Instructions, LineTable};
 use crate::evaluator::environment::Environment;
-use crate::parser::ast::Statement;
+use crate::parser::ast::{Expression, Statement};
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
@@ -9,20 +10,42 @@ use std::rc::Rc;
 use self::builtins::Builtin;
 
 pub mod builtins;
+mod tests;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Object {
     Null,
     Integer(i64),
+    Float(f64),
     Boolean(bool),
     String(String),
     Return(Rc<Object>),
+    /// Unwinds to the innermost loop, ending it.
+    Break,
+    /// Unwinds to the innermost loop, which goes on with its next iteration.
+    Continue,
     Function(Function),
     Builtin(Builtin),
     Array(Vec<Rc<Object>>),
     Hash(HashMap<Hashable, Rc<Object>>),
     CompiledFunc(Rc<CompiledFunction>),
     Closure(Rc<Closure>),
+    Upvalue(Upvalue),
+    /// The integers from the first bound up to but excluding the second.
+    Range(i64, i64),
+    /// The state of a `for` loop in the VM; never seen by programs.
+    Iterator(ForIterator),
+    /// The pattern of a `match` arm, as a constant of compiled code; never
+    /// seen by programs.
+    Pattern(Rc<MatchPattern>),
+    /// An error raised by `throw` or by a runtime error, as a `catch` block
+    /// receives it.
+    Error(ErrorObject),
+    /// Code quoted with `quote(...)` in a macro, as the value the macro
+    /// expands to.
+    Quote(Expression),
+    /// A macro, defined when a program is expanded; never seen by programs.
+    Macro(Function),
 }
 
 impl Display for Object {
@@ -33,9 +56,11 @@ impl Display for Object {
             match self {
                 Object::Null => "Null".to_string(),
                 Object::Integer(int) => int.to_string(),
+                Object::Float(float) => format_float(*float),
                 Object::Boolean(bool) => bool.to_string(),
                 Object::String(string) => string.to_string(),
                 Object::Return(object) => object.to_string(),
+                Object::Break | Object::Continue => "".to_string(),
                 Object::Function(_) => "".to_string(),
                 Object::Builtin(_) => "".to_string(),
                 Object::Array(elements) => format!(
@@ -56,23 +81,426 @@ impl Display for Object {
                 ),
                 Object::CompiledFunc(_) => "".to_string(),
                 Object::Closure(_) => "".to_string(),
+                Object::Upvalue(cell) => cell.borrow().to_string(),
+                Object::Range(start, end) => format!("{start}..{end}"),
+                Object::Iterator(_) => "".to_string(),
+                Object::Pattern(_) => "".to_string(),
+                Object::Error(error) => format!("{}: {}", error.kind, error.message),
+                Object::Quote(_) => "".to_string(),
+                Object::Macro(_) => "".to_string(),
             }
         )
     }
 }
 
+impl Object {
+    /// The name of this object's type as shown to users, e.g. in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "NULL",
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Return(object) => object.type_name(),
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Function(_) | Object::CompiledFunc(_) | Object::Closure(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Upvalue(cell) => cell.borrow().type_name(),
+            Object::Range(..) => "RANGE",
+            Object::Iterator(_) => "ITERATOR",
+            Object::Pattern(_) => "PATTERN",
+            Object::Error(_) => "ERROR",
+            Object::Quote(_) => "QUOTE",
+            Object::Macro(_) => "MACRO",
+        }
+    }
+
+    /// The value of a number as a float, or `None` if this is not a number.
+    /// Arithmetic mixing integers and floats is done in floats.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Object::Integer(int) => Some(*int as f64),
+            Object::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Function {
-    pub parameters: Vec<String>,
+    /// The pattern each argument is bound to.
+    pub parameters: Vec<Expression>,
     pub body: Statement,
     pub env: Rc<RefCell<Environment>>,
 }
 
+/// An error as a value. Runtime errors have the kind of error they are, e.g.
+/// `TypeError`; the `error` builtin makes errors of kind `Error` by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorObject {
+    pub kind: String,
+    pub message: String,
+}
+
+impl ErrorObject {
+    pub fn new(kind: &str, message: &str) -> Self {
+        ErrorObject {
+            kind: kind.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The value of `error[index]`: the error's kind for `"kind"`, its
+    /// message for `"message"` and `Null` for anything else, as for a hash.
+    pub fn field(&self, index: &Object) -> Rc<Object> {
+        Rc::new(match index {
+            Object::String(name) if name == "kind" => Object::String(self.kind.clone()),
+            Object::String(name) if name == "message" => Object::String(self.message.clone()),
+            _ => Object::Null,
+        })
+    }
+}
+
+/// Formats a float in the shortest form that reads back as the same value.
+/// There is always a decimal point or an exponent, so floats cannot be
+/// mistaken for integers: `3.0`, `0.1`, `1e100`. `NaN` and the infinities,
+/// which have no literals, are `NaN`, `inf` and `-inf`.
+pub fn format_float(float: f64) -> String {
+    if float.is_nan() {
+        String::from("NaN")
+    } else if float.is_infinite() {
+        String::from(if float > 0.0 { "inf" } else { "-inf" })
+    } else {
+        // `Debug` switches to an exponent for very large and small magnitudes
+        format!("{float:?}")
+    }
+}
+
+/// A hash key. A float with an integral value is the same key as the integer
+/// with that value, e.g. `1.0` and `1`; other floats, including integral ones
+/// beyond the range of integers, are keys of their own.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Hashable {
     String(String),
     Integer(i64),
     Boolean(bool),
+    /// The bits of a float that is not integral. `NaN` cannot be a key, as it
+    /// is not equal to itself.
+    Float(u64),
+}
+
+impl Hashable {
+    /// The key for `object`, or `None` if it cannot be used as one.
+    pub fn from_object(object: &Object) -> Option<Self> {
+        Some(match object {
+            Object::String(string) => Hashable::String(string.clone()),
+            Object::Integer(int) => Hashable::Integer(*int),
+            Object::Boolean(bool) => Hashable::Boolean(*bool),
+            Object::Float(float) if float.is_nan() => return None,
+            // -2^63 is exact as a float, so this is precisely the range of i64;
+            // `0.0` and `-0.0` both become 0
+            Object::Float(float)
+                if float.fract() == 0.0
+                    && *float >= i64::MIN as f64
+                    && *float < -(i64::MIN as f64) =>
+            {
+                Hashable::Integer(*float as i64)
+            }
+            Object::Float(float) => Hashable::Float(float.to_bits()),
+            _ => return None,
+        })
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            Hashable::String(string) => Object::String(string.clone()),
+            Hashable::Integer(int) => Object::Integer(*int),
+            Hashable::Boolean(bool) => Object::Boolean(*bool),
+            Hashable::Float(bits) => Object::Float(f64::from_bits(*bits)),
+        }
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        match self {
+            Hashable::Integer(int) => Some(*int as f64),
+            Hashable::Float(bits) => Some(f64::from_bits(*bits)),
+            _ => None,
+        }
+    }
+}
+
+/// The order a `for` loop visits the keys of a hash in: booleans, then numbers
+/// by value, then strings.
+impl Ord for Hashable {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn rank(key: &Hashable) -> u8 {
+            match key {
+                Hashable::Boolean(_) => 0,
+                Hashable::Integer(_) | Hashable::Float(_) => 1,
+                Hashable::String(_) => 2,
+            }
+        }
+
+        match (self, other) {
+            (Hashable::Boolean(a), Hashable::Boolean(b)) => a.cmp(b),
+            (Hashable::Integer(a), Hashable::Integer(b)) => a.cmp(b),
+            (Hashable::String(a), Hashable::String(b)) => a.cmp(b),
+            // floats that are keys are neither NaN nor integral, so they never
+            // equal another key
+            (a, b) => match (a.as_float(), b.as_float()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                _ => rank(a).cmp(&rank(b)),
+            },
+        }
+    }
+}
+
+impl PartialOrd for Hashable {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The keys and values a `for` loop visits. Arrays, strings and ranges are keyed
+/// by index; a string's values are its characters. Collections are iterated as
+/// they were when the loop started.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForIterator {
+    Elements(Rc<[Rc<Object>]>, usize),
+    /// The entries of a hash, in key order.
+    Entries(Rc<[(Rc<Object>, Rc<Object>)]>, usize),
+    Range {
+        next: i64,
+        end: i64,
+        index: i64,
+    },
+}
+
+impl ForIterator {
+    /// An iterator over `object`, or `None` if it cannot be iterated over.
+    pub fn new(object: &Object) -> Option<Self> {
+        Some(match object {
+            Object::Array(elements) => ForIterator::Elements(elements.as_slice().into(), 0),
+            Object::String(string) => ForIterator::Elements(
+                string
+                    .chars()
+                    .map(|ch| Rc::new(Object::String(ch.to_string())))
+                    .collect(),
+                0,
+            ),
+            Object::Hash(pairs) => {
+                let mut entries: Vec<_> = pairs.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                ForIterator::Entries(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (Rc::new(key.to_object()), Rc::clone(value)))
+                        .collect(),
+                    0,
+                )
+            }
+            Object::Range(start, end) => ForIterator::Range {
+                next: *start,
+                end: *end,
+                index: 0,
+            },
+            _ => return None,
+        })
+    }
+
+    /// What a loop with a single variable binds from a key and value: the key
+    /// of a hash entry, and the value of anything else.
+    pub fn single(&self, (key, value): (Rc<Object>, Rc<Object>)) -> Rc<Object> {
+        match self {
+            ForIterator::Entries(..) => key,
+            _ => value,
+        }
+    }
+}
+
+impl Iterator for ForIterator {
+    type Item = (Rc<Object>, Rc<Object>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ForIterator::Elements(elements, idx) => {
+                let element = elements.get(*idx)?;
+                let item = (Rc::new(Object::Integer(*idx as i64)), Rc::clone(element));
+                *idx += 1;
+                Some(item)
+            }
+            ForIterator::Entries(entries, idx) => {
+                let (key, value) = entries.get(*idx)?;
+                let item = (Rc::clone(key), Rc::clone(value));
+                *idx += 1;
+                Some(item)
+            }
+            ForIterator::Range { next, end, index } => {
+                if next >= end {
+                    return None;
+                }
+                let item = (
+                    Rc::new(Object::Integer(*index)),
+                    Rc::new(Object::Integer(*next)),
+                );
+                *next += 1;
+                *index += 1;
+                Some(item)
+            }
+        }
+    }
+}
+
+/// The pattern of a `match` arm, as it is matched against values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchPattern {
+    /// `_`, which matches anything.
+    Wildcard,
+    /// A variable, which matches anything and is bound to it.
+    Binding(String),
+    /// A number, string or boolean, which matches values equal to it.
+    Literal(Hashable),
+    /// Matches arrays with as many elements as there are patterns, or at least
+    /// as many if there is a rest, which is a binding or a wildcard.
+    Array(Vec<MatchPattern>, Option<Box<MatchPattern>>),
+    /// Matches hashes with all of the keys, whose values match the patterns.
+    Hash(Vec<(Hashable, MatchPattern)>),
+}
+
+impl MatchPattern {
+    /// The pattern parsed as `pattern`, or `None` if it is not a refutable
+    /// pattern.
+    pub fn new(pattern: &Expression) -> Option<Self> {
+        Some(match pattern {
+            Expression::Identifier(name) | Expression::Rest(name) if name == "_" => {
+                MatchPattern::Wildcard
+            }
+            Expression::Identifier(name) | Expression::Rest(name) => {
+                MatchPattern::Binding(name.clone())
+            }
+            Expression::Array(patterns) => match patterns.split_last() {
+                Some((rest @ Expression::Rest(_), patterns)) => MatchPattern::Array(
+                    patterns.iter().map(Self::new).collect::<Option<_>>()?,
+                    Some(Box::new(Self::new(rest)?)),
+                ),
+                _ => MatchPattern::Array(
+                    patterns.iter().map(Self::new).collect::<Option<_>>()?,
+                    None,
+                ),
+            },
+            Expression::Hash(pairs) => MatchPattern::Hash(
+                pairs
+                    .iter()
+                    .map(|(key, pattern)| Some((Self::literal(key)?, Self::new(pattern)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            literal => MatchPattern::Literal(Self::literal(literal)?),
+        })
+    }
+
+    fn literal(expression: &Expression) -> Option<Hashable> {
+        Hashable::from_object(&match expression {
+            Expression::Integer(int) => Object::Integer(*int),
+            Expression::Float(float) => Object::Float(*float),
+            Expression::String(string) => Object::String(string.clone()),
+            Expression::Boolean(bool) => Object::Boolean(*bool),
+            _ => return None,
+        })
+    }
+
+    /// The names of the variables the pattern binds, in the order their
+    /// values are bound by [`MatchPattern::bind`].
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            MatchPattern::Wildcard | MatchPattern::Literal(_) => vec![],
+            MatchPattern::Binding(name) => vec![name],
+            MatchPattern::Array(patterns, rest) => patterns
+                .iter()
+                .chain(rest.as_deref())
+                .flat_map(|pattern| pattern.names())
+                .collect(),
+            MatchPattern::Hash(pairs) => pairs
+                .iter()
+                .flat_map(|(_, pattern)| pattern.names())
+                .collect(),
+        }
+    }
+
+    /// The values the variables of the pattern are bound to if it matches
+    /// `value`, or `None` if it does not.
+    pub fn bind(&self, value: &Rc<Object>) -> Option<Vec<Rc<Object>>> {
+        let mut bound = vec![];
+        self.bind_into(value, &mut bound).then_some(bound)
+    }
+
+    fn bind_into(&self, value: &Rc<Object>, bound: &mut Vec<Rc<Object>>) -> bool {
+        match (self, &**value) {
+            (MatchPattern::Wildcard, _) => true,
+            (MatchPattern::Binding(_), _) => {
+                bound.push(Rc::clone(value));
+                true
+            }
+            (MatchPattern::Literal(literal), value) => {
+                Hashable::from_object(value).as_ref() == Some(literal)
+            }
+            (MatchPattern::Array(patterns, rest), Object::Array(elements)) => {
+                let length_matches = match rest {
+                    Some(_) => elements.len() >= patterns.len(),
+                    None => elements.len() == patterns.len(),
+                };
+                length_matches
+                    && patterns
+                        .iter()
+                        .zip(elements.iter())
+                        .all(|(pattern, element)| pattern.bind_into(element, bound))
+                    && rest.as_ref().is_none_or(|rest| {
+                        let rest_elements = elements[patterns.len()..].to_vec();
+                        rest.bind_into(&Rc::new(Object::Array(rest_elements)), bound)
+                    })
+            }
+            (MatchPattern::Hash(pairs), Object::Hash(table)) => {
+                pairs.iter().all(|(key, pattern)| match table.get(key) {
+                    Some(value) => pattern.bind_into(value, bound),
+                    None => false,
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether every value this pattern could match is also matched by
+    /// `other`, so that an arm with `other` and no guard makes an arm with
+    /// this pattern after it unreachable.
+    pub fn is_covered_by(&self, other: &MatchPattern) -> bool {
+        match (self, other) {
+            (_, MatchPattern::Wildcard | MatchPattern::Binding(_)) => true,
+            (MatchPattern::Literal(a), MatchPattern::Literal(b)) => a == b,
+            (MatchPattern::Array(patterns, rest), MatchPattern::Array(others, other_rest)) => {
+                let lengths_covered = match (rest, other_rest) {
+                    (_, Some(_)) => patterns.len() >= others.len(),
+                    (None, None) => patterns.len() == others.len(),
+                    (Some(_), None) => false,
+                };
+                lengths_covered
+                    && patterns
+                        .iter()
+                        .zip(others.iter())
+                        .all(|(pattern, other)| pattern.is_covered_by(other))
+            }
+            (MatchPattern::Hash(pairs), MatchPattern::Hash(others)) => {
+                others.iter().all(|(other_key, other)| {
+                    pairs
+                        .iter()
+                        .any(|(key, pattern)| key == other_key && pattern.is_covered_by(other))
+                })
+            }
+            _ => false,
+        }
+    }
 }
 
 impl Display for Hashable {
@@ -84,6 +512,7 @@ impl Display for Hashable {
                 Hashable::String(key) => format!("\"{}\"", key),
                 Hashable::Integer(key) => key.to_string(),
                 Hashable::Boolean(key) => key.to_string(),
+                Hashable::Float(bits) => format_float(f64::from_bits(*bits)),
             }
         )
     }
@@ -93,7 +522,16 @@ impl Display for Hashable {
 pub struct CompiledFunction {
     pub instructions: Rc<Instructions>,
     pub num_locals: u32,
+    /// The number of parameters, other than a rest parameter.
     pub num_params: u32,
+    /// How many of the last parameters have defaults, so need no argument.
+    pub num_defaults: u32,
+    /// Whether the arguments after the parameters are collected into an array
+    /// in the slot after theirs.
+    pub has_rest: bool,
+    pub lines: Rc<LineTable>,
+    /// The name the function was bound to with `let`, or empty if anonymous.
+    pub name: String,
 }
 
 impl CompiledFunction {
@@ -102,18 +540,47 @@ impl CompiledFunction {
             instructions: Rc::new(instructions),
             num_locals,
             num_params,
+            num_defaults: 0,
+            has_rest: false,
+            lines: Rc::new(LineTable::default()),
+            name: String::new(),
         }
     }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_defaults(mut self, num_defaults: u32) -> Self {
+        self.num_defaults = num_defaults;
+        self
+    }
+
+    pub fn with_rest(mut self, has_rest: bool) -> Self {
+        self.has_rest = has_rest;
+        self
+    }
+
+    pub fn with_lines(mut self, lines: LineTable) -> Self {
+        self.lines = Rc::new(lines);
+        self
+    }
 }
 
+/// A shared, mutable cell holding a variable captured by a closure. Every closure
+/// capturing the same variable (and the frame that defined it) shares the cell,
+/// so an assignment through any of them is seen by all.
+pub type Upvalue = Rc<RefCell<Rc<Object>>>;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Closure {
     pub function: Rc<CompiledFunction>,
-    pub free: Vec<Rc<Object>>,
+    pub free: Vec<Upvalue>,
 }
 
 impl Closure {
-    pub fn new(function: CompiledFunction, free: Vec<Rc<Object>>) -> Self {
+    pub fn new(function: CompiledFunction, free: Vec<Upvalue>) -> Self {
         Closure {
             function: Rc::new(function),
             free,
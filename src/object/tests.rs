@@ -0,0 +1,358 @@
+#![cfg(test)]
+
+use super::builtins::{Arity, Builtin, BuiltinError, Builtins, RegistryFull};
+use super::{ErrorObject, Hashable, MatchPattern, Object};
+use crate::parser::ast::{Expression, Program, Statement};
+use crate::parser::Parser;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn test_float_display() {
+    let tests = vec![
+        (3.0, "3.0"),
+        (0.1, "0.1"),
+        (-2.5, "-2.5"),
+        (1e300, "1e300"),
+        (1e22, "1e22"),
+        (1e-7, "1e-7"),
+        (f64::NAN, "NaN"),
+        (f64::INFINITY, "inf"),
+        (f64::NEG_INFINITY, "-inf"),
+    ];
+
+    for (float, expected) in tests {
+        assert_eq!(Object::Float(float).to_string(), expected);
+    }
+}
+
+#[test]
+fn test_float_display_reads_back() {
+    for float in [3.0, 0.1, 1e22, 1e-7, 2.5e-300, 9223372036854775808.0] {
+        let printed = Object::Float(float).to_string();
+        let Program(statements) = Parser::parse_program(&printed).unwrap();
+        match &statements[..] {
+            [Statement::Expression(Expression::Float(read), _)] => assert_eq!(*read, float),
+            _ => panic!("'{printed}' does not read back as a float"),
+        }
+    }
+}
+
+#[test]
+fn test_hashable_from_object() {
+    let tests = vec![
+        (Object::Integer(1), Some(Hashable::Integer(1))),
+        (Object::Float(1.0), Some(Hashable::Integer(1))),
+        (Object::Float(-0.0), Some(Hashable::Integer(0))),
+        (Object::Float(1.5), Some(Hashable::Float(1.5f64.to_bits()))),
+        (
+            Object::Float(1e300),
+            Some(Hashable::Float(1e300f64.to_bits())),
+        ),
+        (Object::Float(f64::NAN), None),
+        (Object::Boolean(true), Some(Hashable::Boolean(true))),
+        (
+            Object::String("a".to_string()),
+            Some(Hashable::String("a".to_string())),
+        ),
+        (Object::Array(vec![]), None),
+    ];
+
+    for (object, expected) in tests {
+        assert_eq!(Hashable::from_object(&object), expected, "{object:?}");
+    }
+}
+
+#[test]
+fn test_hashable_order() {
+    let mut keys = vec![
+        Hashable::String("b".to_string()),
+        Hashable::Integer(2),
+        Hashable::Float(1.5f64.to_bits()),
+        Hashable::Boolean(true),
+        Hashable::String("a".to_string()),
+        Hashable::Integer(-3),
+        Hashable::Float((-1e300f64).to_bits()),
+        Hashable::Boolean(false),
+    ];
+    keys.sort();
+    let expected = vec![
+        Hashable::Boolean(false),
+        Hashable::Boolean(true),
+        Hashable::Float((-1e300f64).to_bits()),
+        Hashable::Integer(-3),
+        Hashable::Float(1.5f64.to_bits()),
+        Hashable::Integer(2),
+        Hashable::String("a".to_string()),
+        Hashable::String("b".to_string()),
+    ];
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn test_match_pattern_bind() {
+    let pattern = MatchPattern::Array(
+        vec![
+            MatchPattern::Literal(Hashable::Integer(1)),
+            MatchPattern::Hash(vec![(
+                Hashable::String("k".to_string()),
+                MatchPattern::Binding("v".to_string()),
+            )]),
+        ],
+        Some(Box::new(MatchPattern::Binding("rest".to_string()))),
+    );
+    let hash = Rc::new(Object::Hash(HashMap::from([(
+        Hashable::String("k".to_string()),
+        Rc::new(Object::Boolean(true)),
+    )])));
+    let tests = vec![
+        (
+            vec![Rc::new(Object::Float(1.0)), Rc::clone(&hash)],
+            Some(vec![
+                Rc::new(Object::Boolean(true)),
+                Rc::new(Object::Array(vec![])),
+            ]),
+        ),
+        (
+            vec![
+                Rc::new(Object::Integer(1)),
+                Rc::clone(&hash),
+                Rc::new(Object::Null),
+            ],
+            Some(vec![
+                Rc::new(Object::Boolean(true)),
+                Rc::new(Object::Array(vec![Rc::new(Object::Null)])),
+            ]),
+        ),
+        (vec![Rc::new(Object::Integer(1))], None),
+        (vec![Rc::new(Object::Integer(2)), Rc::clone(&hash)], None),
+        (
+            vec![
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Hash(HashMap::new())),
+            ],
+            None,
+        ),
+    ];
+
+    assert_eq!(pattern.names(), vec!["v", "rest"]);
+    for (elements, expected) in tests {
+        let value = Rc::new(Object::Array(elements));
+        assert_eq!(pattern.bind(&value), expected, "{value}");
+    }
+}
+
+#[test]
+fn test_match_pattern_is_covered_by() {
+    let one = MatchPattern::Literal(Hashable::Integer(1));
+    let x = MatchPattern::Binding("x".to_string());
+    let pair = MatchPattern::Array(vec![one.clone(), x.clone()], None);
+    let tests = vec![
+        (one.clone(), MatchPattern::Wildcard, true),
+        (MatchPattern::Wildcard, one.clone(), false),
+        (
+            one.clone(),
+            MatchPattern::Literal(Hashable::Integer(2)),
+            false,
+        ),
+        (
+            pair.clone(),
+            MatchPattern::Array(vec![x.clone(), x.clone()], None),
+            true,
+        ),
+        (
+            pair.clone(),
+            MatchPattern::Array(vec![x.clone()], None),
+            false,
+        ),
+        (
+            pair.clone(),
+            MatchPattern::Array(vec![one.clone()], Some(Box::new(MatchPattern::Wildcard))),
+            true,
+        ),
+        (
+            MatchPattern::Array(vec![], Some(Box::new(x.clone()))),
+            MatchPattern::Array(vec![], None),
+            false,
+        ),
+        (
+            MatchPattern::Hash(vec![
+                (Hashable::Boolean(true), one.clone()),
+                (Hashable::Integer(0), x.clone()),
+            ]),
+            MatchPattern::Hash(vec![(Hashable::Boolean(true), x.clone())]),
+            true,
+        ),
+        (
+            MatchPattern::Hash(vec![(Hashable::Boolean(true), x.clone())]),
+            MatchPattern::Hash(vec![(Hashable::Boolean(true), one.clone())]),
+            false,
+        ),
+    ];
+
+    for (pattern, earlier, expected) in tests {
+        assert_eq!(pattern.is_covered_by(&earlier), expected, "{pattern:?}");
+    }
+}
+
+#[test]
+fn test_error_object_field() {
+    let error = ErrorObject::new("TypeError", "cannot add INTEGER and BOOLEAN");
+    let tests = vec![
+        (
+            Object::String(String::from("kind")),
+            Object::String(String::from("TypeError")),
+        ),
+        (
+            Object::String(String::from("message")),
+            Object::String(String::from("cannot add INTEGER and BOOLEAN")),
+        ),
+        (Object::String(String::from("other")), Object::Null),
+        (Object::Integer(0), Object::Null),
+    ];
+    for (index, expected) in tests {
+        assert_eq!(*error.field(&index), expected, "{index}");
+    }
+    assert_eq!(
+        Object::Error(error).to_string(),
+        "TypeError: cannot add INTEGER and BOOLEAN"
+    );
+}
+
+#[test]
+fn test_builtins_register() {
+    let mut builtins = Builtins::new();
+    let standard = ["len", "first", "last", "rest", "push", "puts", "error"];
+    assert_eq!(builtins.names().collect::<Vec<_>>(), standard);
+
+    let constant = |value| move |_: &[Rc<Object>]| Ok(Rc::new(Object::Integer(value)));
+    assert_eq!(
+        builtins.register("answer", Arity::Exactly(0), constant(42)),
+        Ok(())
+    );
+    assert_eq!(
+        builtins.register_in("math", "one", Arity::Exactly(0), constant(1)),
+        Ok(())
+    );
+    assert_eq!(
+        builtins.register_in("math", "two", Arity::Exactly(0), constant(2)),
+        Ok(())
+    );
+    // replacing a builtin keeps its index
+    assert_eq!(
+        builtins.register("len", Arity::Exactly(0), constant(0)),
+        Ok(())
+    );
+    let names = builtins.names().collect::<Vec<_>>();
+    assert_eq!(names[..7], standard);
+    assert_eq!(names[7..], ["answer", "math"]);
+
+    let call = |builtin: Option<Rc<Object>>| match builtin.as_deref() {
+        Some(Object::Builtin(builtin)) => builtin.apply(&[]).ok(),
+        _ => None,
+    };
+    assert_eq!(call(builtins.get("len")), Some(Rc::new(Object::Integer(0))));
+    assert_eq!(
+        call(builtins.get_by_idx(7)),
+        Some(Rc::new(Object::Integer(42)))
+    );
+    assert_eq!(builtins.get("one"), None);
+
+    let Some(Object::Hash(math)) = builtins.get("math").as_deref().cloned() else {
+        panic!("expected the namespace to be a hash");
+    };
+    let two = math.get(&Hashable::String(String::from("two"))).cloned();
+    assert_eq!(call(two), Some(Rc::new(Object::Integer(2))));
+    assert_eq!(math.len(), 2);
+}
+
+#[test]
+fn test_builtins_registry_full() {
+    let mut builtins = Builtins::new();
+    let constant = |_: &[Rc<Object>]| Ok(Rc::new(Object::Null));
+    builtins
+        .register_in("math", "one", Arity::Exactly(0), constant)
+        .unwrap();
+    // compiled code refers to a builtin by a one-byte index
+    for idx in builtins.names().count()..256 {
+        let name = format!("f{idx}");
+        assert_eq!(
+            builtins.register(&name, Arity::Exactly(0), constant),
+            Ok(())
+        );
+    }
+
+    let full = Err(RegistryFull);
+    assert_eq!(builtins.register("new", Arity::Exactly(0), constant), full);
+    assert_eq!(
+        builtins.register_in("new", "one", Arity::Exactly(0), constant),
+        full
+    );
+    assert_eq!(builtins.names().count(), 256);
+    assert_eq!(builtins.get("new"), None);
+    // names already registered can still be replaced
+    assert_eq!(
+        builtins.register("len", Arity::Exactly(0), constant),
+        Ok(())
+    );
+    assert_eq!(
+        builtins.register_in("math", "two", Arity::Exactly(0), constant),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_builtin_arity() {
+    let tests = [
+        (Arity::Exactly(1), vec![false, true, false]),
+        (Arity::Between(1, 2), vec![false, true, true]),
+        (Arity::AtLeast(1), vec![false, true, true]),
+    ];
+    for (arity, expected) in tests {
+        let builtin = Builtin::new("f", arity, |_| Ok(Rc::new(Object::Null)));
+        let null = Rc::new(Object::Null);
+        let accepted = (0..3)
+            .map(|num_args| builtin.apply(&vec![Rc::clone(&null); num_args]).is_ok())
+            .collect::<Vec<_>>();
+        assert_eq!(accepted, expected, "{arity:?}");
+    }
+}
+
+#[test]
+fn test_builtin_error_display() {
+    let builtin = Builtin::new("f", Arity::Exactly(1), |args| match &*args[0] {
+        Object::Integer(_) => Err(BuiltinError::IncompatibleTypes),
+        _ => Err(BuiltinError::Thrown(Rc::clone(&args[0]))),
+    });
+    let error = ErrorObject::new("HostError", "failed");
+    let tests = [
+        (
+            vec![],
+            BuiltinError::IncorrectNumberOfArgs,
+            "wrong number of arguments",
+        ),
+        (
+            vec![Rc::new(Object::Integer(1))],
+            BuiltinError::IncompatibleTypes,
+            "incompatible types",
+        ),
+        (
+            vec![Rc::new(Object::String(String::from("x")))],
+            BuiltinError::Thrown(Rc::new(Object::String(String::from("x")))),
+            "uncaught value x",
+        ),
+        (
+            vec![Rc::new(Object::Error(error.clone()))],
+            BuiltinError::Thrown(Rc::new(Object::Error(error))),
+            "uncaught HostError: failed",
+        ),
+    ];
+    for (args, expected, message) in tests {
+        let result = builtin.apply(&args);
+        assert_eq!(
+            result.as_ref().map_err(|e| e.to_string()),
+            Err(message.to_string())
+        );
+        assert_eq!(result, Err(expected));
+    }
+}
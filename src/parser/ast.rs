@@ -1,30 +1,638 @@
+use crate::span::Span;
+use std::fmt::{Display, Formatter};
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
 #[derive(Debug, PartialEq)]
 pub struct Program(pub Vec<Statement>);
 
+/// Every statement carries the span of source it was parsed from.
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
-    Let(Expression, Expression),
-    Return(Expression),
-    Expression(Expression),
-    BlockStatement(Vec<Statement>),
-    Assignment(Expression, Expression),
+    /// `let pattern = value;`, where the pattern is an identifier or an array
+    /// or hash of patterns, e.g. `[a, {"key": b}, ...rest]`.
+    Let(Expression, Expression, Span),
+    Return(Expression, Span),
+    Expression(Expression, Span),
+    BlockStatement(Vec<Statement>, Span),
+    /// `target = value`, where the target is a variable or an element of one,
+    /// e.g. `a[0]["key"]`.
+    Assignment(Expression, Expression, Span),
+    /// `target += value` and the like, with the operator applied.
+    CompoundAssignment(Expression, Infix, Expression, Span),
+    Break(Span),
+    Continue(Span),
+    /// `throw value;`, which raises the value as an error.
+    Throw(Expression, Span),
+    /// `import "path" as name;`, at the top level of a file.
+    Import(Import, Span),
+    /// `export let name = value;`, at the top level of a file: the `let`,
+    /// whose name the module exports.
+    Export(Box<Statement>, Span),
 }
 
+/// What an `import` statement loads, and the name it binds the module's
+/// exports to, as a hash from their names to their values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    /// The path as written, relative to the importing file or a search path.
+    pub path: String,
+    pub name: String,
+    /// The module, once the loader has read it. A module imported more than
+    /// once is shared by its imports.
+    pub module: Option<Rc<Module>>,
+}
+
+/// A file loaded by an `import`, parsed and with its macros expanded.
+#[derive(Debug, PartialEq)]
+pub struct Module {
+    /// The number of the module's source, which also identifies the module.
+    pub source: usize,
+    pub program: Program,
+    /// The names of the module's exports, in the order they are defined.
+    pub exports: Vec<String>,
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(_, _, span)
+            | Statement::Return(_, span)
+            | Statement::Expression(_, span)
+            | Statement::BlockStatement(_, span)
+            | Statement::Assignment(_, _, span)
+            | Statement::CompoundAssignment(_, _, _, span)
+            | Statement::Break(span)
+            | Statement::Continue(span)
+            | Statement::Throw(_, span)
+            | Statement::Import(_, span)
+            | Statement::Export(_, span) => *span,
+        }
+    }
+
+    /// Whether this statement, or any code within it, assigns to the variable
+    /// `name` or to an element of it, e.g. `name = x;` or `name[0] += 1;`.
+    pub fn assigns_to(&self, name: &str) -> bool {
+        match self {
+            Statement::Assignment(target, value, _)
+            | Statement::CompoundAssignment(target, _, value, _) => {
+                target.as_place().is_some_and(|(id, _)| id == name) || value.assigns_to(name)
+            }
+            Statement::Let(_, value, _)
+            | Statement::Return(value, _)
+            | Statement::Expression(value, _)
+            | Statement::Throw(value, _) => value.assigns_to(name),
+            Statement::BlockStatement(statements, _) => statements
+                .iter()
+                .any(|statement| statement.assigns_to(name)),
+            Statement::Export(statement, _) => statement.assigns_to(name),
+            Statement::Break(_) | Statement::Continue(_) | Statement::Import(..) => false,
+        }
+    }
+
+    /// Whether the code in this statement uses the variable `name` bound
+    /// outside it, rather than one it binds itself.
+    pub fn refers_to(&self, name: &str) -> bool {
+        match self {
+            Statement::Let(_, value, _)
+            | Statement::Return(value, _)
+            | Statement::Expression(value, _)
+            | Statement::Throw(value, _) => value.refers_to(name),
+            Statement::Assignment(target, value, _)
+            | Statement::CompoundAssignment(target, _, value, _) => {
+                target.refers_to(name) || value.refers_to(name)
+            }
+            Statement::BlockStatement(statements, _) => {
+                for statement in statements {
+                    if statement.refers_to(name) {
+                        return true;
+                    }
+                    // the statements after a `let` binding `name` use its variable
+                    if let Statement::Let(pattern, ..) = statement {
+                        if pattern.bound_names().contains(&name) {
+                            return false;
+                        }
+                    }
+                }
+                false
+            }
+            Statement::Export(statement, _) => statement.refers_to(name),
+            Statement::Break(_) | Statement::Continue(_) | Statement::Import(..) => false,
+        }
+    }
+
+    /// Rewrites the expressions in this statement with `f`, as
+    /// [`Expression::modify`] does.
+    pub fn modify<E>(
+        self,
+        f: &mut impl FnMut(Expression) -> Result<Rewrite, E>,
+    ) -> Result<Statement, E> {
+        Ok(match self {
+            Statement::Let(pattern, value, span) => {
+                Statement::Let(pattern.modify(f)?, value.modify(f)?, span)
+            }
+            Statement::Return(value, span) => Statement::Return(value.modify(f)?, span),
+            Statement::Expression(exp, span) => Statement::Expression(exp.modify(f)?, span),
+            Statement::BlockStatement(statements, span) => Statement::BlockStatement(
+                statements
+                    .into_iter()
+                    .map(|statement| statement.modify(f))
+                    .collect::<Result<_, E>>()?,
+                span,
+            ),
+            Statement::Assignment(target, value, span) => {
+                Statement::Assignment(target.modify(f)?, value.modify(f)?, span)
+            }
+            Statement::CompoundAssignment(target, infix, value, span) => {
+                Statement::CompoundAssignment(target.modify(f)?, infix, value.modify(f)?, span)
+            }
+            Statement::Throw(value, span) => Statement::Throw(value.modify(f)?, span),
+            Statement::Export(statement, span) => {
+                Statement::Export(Box::new(statement.modify(f)?), span)
+            }
+            statement @ (Statement::Break(_) | Statement::Continue(_) | Statement::Import(..)) => {
+                statement
+            }
+        })
+    }
+}
+
+/// What a function rewriting the AST with [`Expression::modify`] does with an
+/// expression: `Continue` puts the expression it holds in its place and goes
+/// on to rewrite the parts of that, `Break` puts it in place as it is.
+pub type Rewrite = ControlFlow<Expression, Expression>;
+
+/// The expressions that can raise an error when run carry the span of source
+/// they were parsed from, so the error can be reported there.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     Identifier(String),
     Integer(i64),
-    Prefix(Prefix, Box<Expression>),
-    Infix(Box<Expression>, Infix, Box<Expression>),
+    Float(f64),
+    /// A string with embedded expressions, e.g. `"Hello ${name}"`, as the
+    /// parts to be converted to strings and concatenated.
+    Interpolation(Vec<Expression>),
+    Prefix(Prefix, Box<Expression>, Span),
+    Infix(Box<Expression>, Infix, Box<Expression>, Span),
     Boolean(bool),
     If(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
+    /// The parameter patterns, the body and the name the function is bound to.
     Function(Vec<Expression>, Box<Statement>, String),
-    Call(Box<Expression>, Vec<Expression>),
+    Call(Box<Expression>, Vec<Expression>, Span),
     String(String),
     Array(Vec<Expression>),
-    Index(Box<Expression>, Box<Expression>),
+    Index(Box<Expression>, Box<Expression>, Span),
     Hash(Vec<(Expression, Expression)>),
     While(Box<Expression>, Box<Statement>),
+    /// `for (x in xs) { ... }` or `for (k, v in xs) { ... }`: the one or two
+    /// identifiers bound on each iteration, the iterable and the body.
+    For(Vec<Expression>, Box<Expression>, Box<Statement>),
+    /// `start..end`, the integers from `start` up to but excluding `end`.
+    Range(Box<Expression>, Box<Expression>),
+    /// `...name`, the last element of an array pattern, which binds the
+    /// elements not bound by the others; as the last parameter, it binds the
+    /// arguments after the other parameters' in an array.
+    Rest(String),
+    /// `pattern = value`, a parameter with a default, evaluated on each call
+    /// the parameter is not given an argument in.
+    Default(Box<Expression>, Box<Expression>),
+    /// `match (value) { pattern => result, ... }`: the value and the arms,
+    /// tried in order.
+    Match(Box<Expression>, Vec<MatchArm>),
+    /// `try { ... } catch (e) { ... } finally { ... }`: the block, the name
+    /// an error raised in it is bound to and the block handling it, and the
+    /// block run however the others end. At least one of the last two is
+    /// present.
+    Try(
+        Box<Statement>,
+        Option<(String, Box<Statement>)>,
+        Option<Box<Statement>>,
+    ),
+    /// `macro(x, y) { ... }`: the parameter patterns and the body. Macros are
+    /// bound by top-level `let` statements, which macro expansion removes.
+    Macro(Vec<Expression>, Box<Statement>),
+}
+
+/// An arm of a `match`. Its pattern is an identifier, `_`, a number, string or
+/// boolean, or an array or hash of patterns; hash keys are literals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Expression,
+    /// Checked after the pattern matches, with its variables bound.
+    pub guard: Option<Expression>,
+    /// A block, or an expression statement.
+    pub body: Statement,
+    /// The span of the pattern and guard.
+    pub span: Span,
+}
+
+impl Expression {
+    /// The variable and the index expressions of an assignment target, e.g.
+    /// `a` and `[0, "key"]` for `a[0]["key"]`, or `None` if this expression
+    /// cannot be assigned to.
+    pub fn as_place(&self) -> Option<(&str, Vec<&Expression>)> {
+        match self {
+            Expression::Identifier(name) => Some((name, vec![])),
+            Expression::Index(collection, index, _) => {
+                let (name, mut indices) = collection.as_place()?;
+                indices.push(index);
+                Some((name, indices))
+            }
+            _ => None,
+        }
+    }
+
+    /// The pattern of a parameter, and its default if it has one.
+    pub fn parameter_parts(&self) -> (&Expression, Option<&Expression>) {
+        match self {
+            Expression::Default(pattern, default) => (pattern, Some(default)),
+            pattern => (pattern, None),
+        }
+    }
+
+    /// Whether any code within this expression assigns to the variable `name`
+    /// or to an element of it.
+    pub fn assigns_to(&self, name: &str) -> bool {
+        let any = |expressions: &[Expression]| expressions.iter().any(|e| e.assigns_to(name));
+        match self {
+            Expression::Interpolation(expressions) | Expression::Array(expressions) => {
+                any(expressions)
+            }
+            Expression::Prefix(_, operand, _) => operand.assigns_to(name),
+            Expression::Infix(left, _, right, _)
+            | Expression::Index(left, right, _)
+            | Expression::Range(left, right)
+            | Expression::Default(left, right) => left.assigns_to(name) || right.assigns_to(name),
+            Expression::If(condition, consequence, alternative) => {
+                condition.assigns_to(name)
+                    || consequence.assigns_to(name)
+                    || alternative
+                        .as_ref()
+                        .is_some_and(|block| block.assigns_to(name))
+            }
+            Expression::Function(parameters, body, _) | Expression::Macro(parameters, body) => {
+                any(parameters) || body.assigns_to(name)
+            }
+            Expression::Call(function, arguments, _) => function.assigns_to(name) || any(arguments),
+            Expression::Hash(pairs) => pairs
+                .iter()
+                .any(|(key, value)| key.assigns_to(name) || value.assigns_to(name)),
+            Expression::While(condition, body) => {
+                condition.assigns_to(name) || body.assigns_to(name)
+            }
+            Expression::For(_, iterable, body) => {
+                iterable.assigns_to(name) || body.assigns_to(name)
+            }
+            Expression::Match(value, arms) => {
+                value.assigns_to(name)
+                    || arms.iter().any(|arm| {
+                        arm.guard
+                            .as_ref()
+                            .is_some_and(|guard| guard.assigns_to(name))
+                            || arm.body.assigns_to(name)
+                    })
+            }
+            Expression::Try(block, catch, finally) => {
+                block.assigns_to(name)
+                    || catch
+                        .as_ref()
+                        .is_some_and(|(_, handler)| handler.assigns_to(name))
+                    || finally.as_ref().is_some_and(|block| block.assigns_to(name))
+            }
+            Expression::Identifier(_)
+            | Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::Rest(_) => false,
+        }
+    }
+
+    /// The names of the variables this pattern binds, in order; `_` binds
+    /// nothing.
+    pub fn bound_names(&self) -> Vec<&str> {
+        match self {
+            Expression::Identifier(name) | Expression::Rest(name) if name != "_" => vec![name],
+            Expression::Array(patterns) => patterns
+                .iter()
+                .flat_map(|pattern| pattern.bound_names())
+                .collect(),
+            Expression::Hash(pairs) => pairs
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bound_names())
+                .collect(),
+            Expression::Default(pattern, _) => pattern.bound_names(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether the code in this expression uses the variable `name` bound
+    /// outside it, rather than one it binds itself.
+    pub fn refers_to(&self, name: &str) -> bool {
+        let any = |expressions: &[Expression]| expressions.iter().any(|e| e.refers_to(name));
+        let binds = |patterns: &[Expression]| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.bound_names().contains(&name))
+        };
+        match self {
+            Expression::Identifier(id) => id == name,
+            Expression::Interpolation(expressions) | Expression::Array(expressions) => {
+                any(expressions)
+            }
+            Expression::Prefix(_, operand, _) => operand.refers_to(name),
+            Expression::Infix(left, _, right, _)
+            | Expression::Index(left, right, _)
+            | Expression::Range(left, right) => left.refers_to(name) || right.refers_to(name),
+            // only the default of a parameter is code
+            Expression::Default(_, default) => default.refers_to(name),
+            Expression::If(condition, consequence, alternative) => {
+                condition.refers_to(name)
+                    || consequence.refers_to(name)
+                    || alternative
+                        .as_ref()
+                        .is_some_and(|block| block.refers_to(name))
+            }
+            // a function's parameters, and the name it is bound to, are
+            // variables of its own; the defaults of parameters binding `name`
+            // are checked when the function is parsed
+            Expression::Function(parameters, body, function_name) => {
+                !binds(parameters)
+                    && function_name != name
+                    && (any(parameters) || body.refers_to(name))
+            }
+            Expression::Macro(parameters, body) => {
+                !binds(parameters) && (any(parameters) || body.refers_to(name))
+            }
+            Expression::Call(function, arguments, _) => function.refers_to(name) || any(arguments),
+            Expression::Hash(pairs) => pairs
+                .iter()
+                .any(|(key, value)| key.refers_to(name) || value.refers_to(name)),
+            Expression::While(condition, body) => condition.refers_to(name) || body.refers_to(name),
+            Expression::For(variables, iterable, body) => {
+                iterable.refers_to(name) || (!binds(variables) && body.refers_to(name))
+            }
+            Expression::Match(value, arms) => {
+                value.refers_to(name)
+                    || arms.iter().any(|arm| {
+                        !arm.pattern.bound_names().contains(&name)
+                            && (arm
+                                .guard
+                                .as_ref()
+                                .is_some_and(|guard| guard.refers_to(name))
+                                || arm.body.refers_to(name))
+                    })
+            }
+            Expression::Try(block, catch, finally) => {
+                block.refers_to(name)
+                    || catch
+                        .as_ref()
+                        .is_some_and(|(error, handler)| error != name && handler.refers_to(name))
+                    || finally.as_ref().is_some_and(|block| block.refers_to(name))
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::Rest(_) => false,
+        }
+    }
+
+    /// Rewrites this expression from the top down: `f` is given this
+    /// expression, then each expression within what it puts in its place, and
+    /// so on, unless it returns `Rewrite::Break`.
+    pub fn modify<E>(
+        self,
+        f: &mut impl FnMut(Expression) -> Result<Rewrite, E>,
+    ) -> Result<Expression, E> {
+        let expression = match f(self)? {
+            ControlFlow::Break(expression) => return Ok(expression),
+            ControlFlow::Continue(expression) => expression,
+        };
+
+        Ok(match expression {
+            Expression::Interpolation(parts) => Expression::Interpolation(modify_all(parts, f)?),
+            Expression::Prefix(prefix, operand, span) => {
+                Expression::Prefix(prefix, Box::new(operand.modify(f)?), span)
+            }
+            Expression::Infix(left, infix, right, span) => Expression::Infix(
+                Box::new(left.modify(f)?),
+                infix,
+                Box::new(right.modify(f)?),
+                span,
+            ),
+            Expression::If(condition, consequence, alternative) => Expression::If(
+                Box::new(condition.modify(f)?),
+                Box::new(consequence.modify(f)?),
+                match alternative {
+                    Some(alternative) => Some(Box::new(alternative.modify(f)?)),
+                    None => None,
+                },
+            ),
+            Expression::Function(parameters, body, name) => {
+                Expression::Function(modify_all(parameters, f)?, Box::new(body.modify(f)?), name)
+            }
+            Expression::Macro(parameters, body) => {
+                Expression::Macro(modify_all(parameters, f)?, Box::new(body.modify(f)?))
+            }
+            Expression::Call(function, arguments, span) => Expression::Call(
+                Box::new(function.modify(f)?),
+                modify_all(arguments, f)?,
+                span,
+            ),
+            Expression::Array(elements) => Expression::Array(modify_all(elements, f)?),
+            Expression::Index(collection, index, span) => Expression::Index(
+                Box::new(collection.modify(f)?),
+                Box::new(index.modify(f)?),
+                span,
+            ),
+            Expression::Hash(pairs) => Expression::Hash(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.modify(f)?, value.modify(f)?)))
+                    .collect::<Result<_, E>>()?,
+            ),
+            Expression::While(condition, body) => {
+                Expression::While(Box::new(condition.modify(f)?), Box::new(body.modify(f)?))
+            }
+            Expression::For(variables, iterable, body) => Expression::For(
+                modify_all(variables, f)?,
+                Box::new(iterable.modify(f)?),
+                Box::new(body.modify(f)?),
+            ),
+            Expression::Range(start, end) => {
+                Expression::Range(Box::new(start.modify(f)?), Box::new(end.modify(f)?))
+            }
+            Expression::Default(pattern, default) => {
+                Expression::Default(Box::new(pattern.modify(f)?), Box::new(default.modify(f)?))
+            }
+            Expression::Match(value, arms) => Expression::Match(
+                Box::new(value.modify(f)?),
+                arms.into_iter()
+                    .map(|arm| {
+                        Ok(MatchArm {
+                            pattern: arm.pattern.modify(f)?,
+                            guard: match arm.guard {
+                                Some(guard) => Some(guard.modify(f)?),
+                                None => None,
+                            },
+                            body: arm.body.modify(f)?,
+                            span: arm.span,
+                        })
+                    })
+                    .collect::<Result<_, E>>()?,
+            ),
+            Expression::Try(block, catch, finally) => Expression::Try(
+                Box::new(block.modify(f)?),
+                match catch {
+                    Some((name, handler)) => Some((name, Box::new(handler.modify(f)?))),
+                    None => None,
+                },
+                match finally {
+                    Some(finally) => Some(Box::new(finally.modify(f)?)),
+                    None => None,
+                },
+            ),
+            leaf @ (Expression::Identifier(_)
+            | Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::Rest(_)) => leaf,
+        })
+    }
+}
+
+fn modify_all<E>(
+    expressions: Vec<Expression>,
+    f: &mut impl FnMut(Expression) -> Result<Rewrite, E>,
+) -> Result<Vec<Expression>, E> {
+    expressions
+        .into_iter()
+        .map(|expression| expression.modify(f))
+        .collect()
+}
+
+/// Spans differ with the layout of the source, so tests compare ASTs with
+/// their spans replaced by the default one.
+#[cfg(test)]
+impl Program {
+    pub fn without_spans(self) -> Program {
+        Program(self.0.into_iter().map(Statement::without_spans).collect())
+    }
+}
+
+#[cfg(test)]
+impl Statement {
+    pub fn without_spans(self) -> Statement {
+        let span = Span::default();
+        match self {
+            Statement::Let(pattern, value, _) => {
+                Statement::Let(pattern.without_spans(), value.without_spans(), span)
+            }
+            Statement::Return(value, _) => Statement::Return(value.without_spans(), span),
+            Statement::Expression(exp, _) => Statement::Expression(exp.without_spans(), span),
+            Statement::BlockStatement(statements, _) => Statement::BlockStatement(
+                statements
+                    .into_iter()
+                    .map(Statement::without_spans)
+                    .collect(),
+                span,
+            ),
+            Statement::Assignment(target, value, _) => {
+                Statement::Assignment(target.without_spans(), value.without_spans(), span)
+            }
+            Statement::CompoundAssignment(target, infix, value, _) => {
+                Statement::CompoundAssignment(
+                    target.without_spans(),
+                    infix,
+                    value.without_spans(),
+                    span,
+                )
+            }
+            Statement::Break(_) => Statement::Break(span),
+            Statement::Continue(_) => Statement::Continue(span),
+            Statement::Throw(value, _) => Statement::Throw(value.without_spans(), span),
+            Statement::Import(import, _) => Statement::Import(import, span),
+            Statement::Export(statement, _) => {
+                Statement::Export(Box::new(statement.without_spans()), span)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Expression {
+    pub fn without_spans(self) -> Expression {
+        let span = Span::default();
+        let block = |block: Box<Statement>| Box::new(block.without_spans());
+        let all = |expressions: Vec<Expression>| {
+            expressions
+                .into_iter()
+                .map(Expression::without_spans)
+                .collect()
+        };
+        // `modify` does not reach the spans of statements, so the expressions
+        // containing statements are rebuilt here
+        let stripped = self.modify(&mut |expression| {
+            Ok::<_, std::convert::Infallible>(match expression {
+                Expression::Prefix(prefix, operand, _) => {
+                    Rewrite::Continue(Expression::Prefix(prefix, operand, span))
+                }
+                Expression::Infix(left, infix, right, _) => {
+                    Rewrite::Continue(Expression::Infix(left, infix, right, span))
+                }
+                Expression::Call(function, arguments, _) => {
+                    Rewrite::Continue(Expression::Call(function, arguments, span))
+                }
+                Expression::Index(collection, index, _) => {
+                    Rewrite::Continue(Expression::Index(collection, index, span))
+                }
+                Expression::If(condition, consequence, alternative) => {
+                    Rewrite::Break(Expression::If(
+                        Box::new(condition.without_spans()),
+                        block(consequence),
+                        alternative.map(block),
+                    ))
+                }
+                Expression::Function(parameters, body, name) => {
+                    Rewrite::Break(Expression::Function(all(parameters), block(body), name))
+                }
+                Expression::Macro(parameters, body) => {
+                    Rewrite::Break(Expression::Macro(all(parameters), block(body)))
+                }
+                Expression::While(condition, body) => Rewrite::Break(Expression::While(
+                    Box::new(condition.without_spans()),
+                    block(body),
+                )),
+                Expression::For(variables, iterable, body) => Rewrite::Break(Expression::For(
+                    variables,
+                    Box::new(iterable.without_spans()),
+                    block(body),
+                )),
+                Expression::Match(value, arms) => Rewrite::Break(Expression::Match(
+                    Box::new(value.without_spans()),
+                    arms.into_iter()
+                        .map(|arm| MatchArm {
+                            pattern: arm.pattern.without_spans(),
+                            guard: arm.guard.map(Expression::without_spans),
+                            body: arm.body.without_spans(),
+                            span,
+                        })
+                        .collect(),
+                )),
+                Expression::Try(try_block, catch, finally) => Rewrite::Break(Expression::Try(
+                    block(try_block),
+                    catch.map(|(name, handler)| (name, block(handler))),
+                    finally.map(block),
+                )),
+                expression => Rewrite::Continue(expression),
+            })
+        });
+        let Ok(stripped) = stripped;
+        stripped
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -39,10 +647,74 @@ pub enum Infix {
     Minus,
     Multiply,
     Divide,
+    Modulo,
+    Power,
     GreaterThan,
     LessThan,
+    GreaterEqual,
+    LessEqual,
     Equal,
     NotEqual,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     And,
     Or,
 }
+
+impl Prefix {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Prefix::Minus => "-",
+            Prefix::Bang => "!",
+        }
+    }
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl Infix {
+    /// Bitwise operators are only defined on integers.
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            Infix::BitAnd | Infix::BitOr | Infix::BitXor | Infix::ShiftLeft | Infix::ShiftRight
+        )
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Infix::Plus => "+",
+            Infix::Minus => "-",
+            Infix::Multiply => "*",
+            Infix::Divide => "/",
+            Infix::Modulo => "%",
+            Infix::Power => "**",
+            Infix::GreaterThan => ">",
+            Infix::LessThan => "<",
+            Infix::GreaterEqual => ">=",
+            Infix::LessEqual => "<=",
+            Infix::BitAnd => "&",
+            Infix::BitOr => "|",
+            Infix::BitXor => "^",
+            Infix::ShiftLeft => "<<",
+            Infix::ShiftRight => ">>",
+            Infix::Equal => "==",
+            Infix::NotEqual => "!=",
+            Infix::And => "&&",
+            Infix::Or => "||",
+        }
+    }
+}
+
+impl Display for Infix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
@@ -1,23 +1,64 @@
+use std::collections::HashSet;
 use std::fmt::Formatter;
 use std::iter::Peekable;
 
-use self::ast::{Expression, Infix, Prefix, Statement};
-use crate::lexer::{token::Token, Lexer, LexerIter};
+use self::ast::{Expression, Import, Infix, MatchArm, Prefix, Statement};
+use crate::lexer::token::{TemplatePart, Token};
+use crate::lexer::{Lexer, LexerError};
 use crate::parser::ast::Program;
 use crate::parser::precedence::Precedence;
+use crate::span::{Located, Position, Span, MAIN_SOURCE};
 
 pub mod ast;
 mod precedence;
 mod tests;
 
+/// The tokens a parser reads, each with its span.
+type Tokens<'a> = Box<dyn Iterator<Item = (Token, Span)> + 'a>;
+
 pub struct Parser<'a> {
-    iter: Peekable<LexerIter<'a>>,
+    iter: Peekable<Tokens<'a>>,
+    // span of the token most recently peeked at or consumed, which is the
+    // token any parsing error refers to
+    span: Span,
+    // span of the token most recently consumed
+    consumed: Span,
+    eof: Span,
+    // number of loops around the code being parsed, within the innermost function
+    loop_depth: usize,
+    // the number of the source being parsed, which the spans are given
+    source: usize,
+    // the variables used in the statement being parsed, with their spans,
+    // which errors about them refer to
+    identifiers: Vec<(String, Span)>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn parse_program(program: &str) -> Result<Program, Vec<ParsingError>> {
+    pub fn parse_program(program: &str) -> Result<Program, Vec<Located<ParsingError>>> {
+        Self::parse_source(program, MAIN_SOURCE)
+    }
+
+    /// Parses `program` as the source numbered `source`, e.g. a module.
+    pub fn parse_source(
+        program: &str,
+        source: usize,
+    ) -> Result<Program, Vec<Located<ParsingError>>> {
+        let end = Position::new(
+            program.matches('\n').count() as u32 + 1,
+            program.rsplit('\n').next().unwrap_or("").chars().count() as u32 + 1,
+        );
+        let tokens = program
+            .tokens()
+            .spanned()
+            .map(move |(token, span)| (token, span.in_source(source)));
         let mut parser = Parser {
-            iter: program.tokens().peekable(),
+            iter: (Box::new(tokens) as Tokens).peekable(),
+            span: Span::default().in_source(source),
+            consumed: Span::default().in_source(source),
+            eof: Span::new(end, end).in_source(source),
+            loop_depth: 0,
+            source,
+            identifiers: vec![],
         };
 
         let mut program = vec![];
@@ -25,17 +66,18 @@ impl<'a> Parser<'a> {
 
         loop {
             let token = match parser.iter.peek() {
-                Some(Token::Semicolon) => {
+                Some((Token::Semicolon, _)) => {
                     parser.iter.next();
                     continue;
                 }
-                Some(tok) => tok.clone(),
+                Some((tok, _)) => tok.clone(),
                 None => break,
             };
 
-            match parser.parse_statement(&token) {
+            parser.identifiers.clear();
+            match parser.parse_top_level_statement(&token) {
                 Ok(statement) => program.push(statement),
-                Err(error) => errors.push(error),
+                Err(error) => errors.push(Located::new(error, parser.span)),
             }
         }
 
@@ -46,84 +88,127 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a statement outside any block, where modules are imported and
+    /// exports defined.
+    fn parse_top_level_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
+        let parse = match token {
+            Token::Import => Self::parse_import,
+            Token::Export => Self::parse_export,
+            token => return self.parse_statement(token),
+        };
+        self.next_token();
+        let start = self.span;
+        let r = parse(self, start);
+        self.skip_to_semicolon();
+        r
+    }
+
     fn parse_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
-        self.iter.next();
+        self.next_token();
+        let start = self.span;
         match token {
+            Token::Import | Token::Export => {
+                self.skip_to_semicolon();
+                Err(ParsingError::NotTopLevel(token.clone()))
+            }
             Token::Let => {
-                let r = self.parse_let();
+                let r = self.parse_let(start);
                 self.skip_to_semicolon();
                 r
             }
             Token::Return => {
-                let r = self.parse_return();
+                let r = self.parse_return(start);
+                self.skip_to_semicolon();
+                r
+            }
+            Token::Throw => {
+                let r = self.parse_throw(start);
+                self.skip_to_semicolon();
+                r
+            }
+            Token::Break | Token::Continue => {
+                let r = self.parse_loop_control(token, start);
                 self.skip_to_semicolon();
                 r
             }
             t => {
-                if let Some(Token::Assign) = self.iter.peek() {
-                    let r = self.parse_assignment(t);
-                    self.skip_to_semicolon();
-                    r
-                } else {
-                    match self.parse_expression_statement(t) {
-                        Ok(s) => Ok(s),
-                        Err(e) => {
-                            self.skip_to_semicolon();
-                            Err(e)
-                        }
+                // peeking moved `span` on; an error in `t` itself should point at `t`
+                self.span = start;
+                let r = match self.parse_expression_statement(t, start) {
+                    Ok(Statement::Expression(target, _)) if self.peek_assignment().is_some() => {
+                        self.parse_assignment(target, start)
                     }
+                    r => r,
+                };
+                if r.is_err() {
+                    self.skip_to_semicolon();
                 }
+                r
             }
         }
     }
 
-    fn parse_assignment(&mut self, token: &Token) -> Result<Statement, ParsingError> {
-        // first token in assignment statement has to be an identifier
-        let identifier = Expression::Identifier(match token {
-            Token::Identifier(id) => id.clone(),
-            token => return Err(ParsingError::UnexpectedToken(token.clone())),
-        });
+    /// The operator applied by the assignment operator at the next token, or
+    /// `None` for a plain `=`. The outer `None` means there is no assignment.
+    fn peek_assignment(&mut self) -> Option<Option<Infix>> {
+        Some(match self.peek_token()? {
+            Token::Assign => None,
+            Token::PlusAssign => Some(Infix::Plus),
+            Token::MinusAssign => Some(Infix::Minus),
+            Token::AsteriskAssign => Some(Infix::Multiply),
+            Token::SlashAssign => Some(Infix::Divide),
+            _ => return None,
+        })
+    }
 
-        // after identifier next token should be '='
-        match self.next_token_or_end()? {
-            Token::Assign => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
-        };
+    fn parse_assignment(
+        &mut self,
+        target: Expression,
+        start: Span,
+    ) -> Result<Statement, ParsingError> {
+        // only a variable or an element of one can be assigned to
+        if target.as_place().is_none() {
+            self.span = start.to(self.consumed);
+            return Err(ParsingError::InvalidAssignmentTarget);
+        }
 
-        // after '=' next token should be the start of an expression, which
-        // means it should not be ';' or EOF
+        // the caller has peeked an assignment operator
+        let operator = self.peek_assignment().flatten();
+        self.next_token();
+
+        // after the operator next token should be the start of an expression,
+        // which means it should not be ';' or EOF
         let token = self.next_token_or_end()?;
 
-        let expression = match self.parse_expression(&token, Precedence::Lowest) {
-            Ok(exp) => exp,
-            Err(e) => return Err(e),
-        };
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
 
         // after expression next token should be ';'
-        match self.iter.peek() {
+        match self.peek_token() {
             Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
             None => return Err(ParsingError::UnexpectedEof),
         }
 
-        Ok(Statement::Assignment(identifier, expression))
+        let span = start.to(self.consumed);
+        Ok(match operator {
+            None => Statement::Assignment(target, expression, span),
+            Some(infix) => Statement::CompoundAssignment(target, infix, expression, span),
+        })
     }
 
-    fn parse_let(&mut self) -> Result<Statement, ParsingError> {
-        // after 'let' next token should be an identifier
-        let name: String;
-        let identifier = Expression::Identifier(match self.next_token_or_end()? {
-            Token::Identifier(id) => {
-                name = id.clone();
-                id
-            }
-            token => return Err(ParsingError::UnexpectedToken(token)),
-        });
+    fn parse_let(&mut self, start: Span) -> Result<Statement, ParsingError> {
+        // after 'let' next token should start a pattern
+        let token = self.next_token_or_end()?;
+        let pattern = self.parse_pattern(token, false, &mut HashSet::new())?;
+        let name = match &pattern {
+            Expression::Identifier(id) => id.clone(),
+            _ => String::new(),
+        };
 
-        // after identifier next token should be '='
+        // after pattern next token should be '='
         match self.next_token_or_end()? {
             Token::Assign => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         };
 
         // after '=' next token should be the start of an expression, which
@@ -142,57 +227,149 @@ impl<'a> Parser<'a> {
         };
 
         // after expression next token should be ';'
-        match self.iter.peek() {
+        match self.peek_token() {
             Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
             None => return Err(ParsingError::UnexpectedEof),
         }
 
-        Ok(Statement::Let(identifier, expression))
+        Ok(Statement::Let(pattern, expression, start.to(self.consumed)))
     }
 
-    fn parse_return(&mut self) -> Result<Statement, ParsingError> {
+    fn parse_return(&mut self, start: Span) -> Result<Statement, ParsingError> {
         // after 'let' next token should be beginning of expression, which
         // means it should not be ';' or EOF
         let token = self.next_token_or_end()?;
 
-        let expression = match self.parse_expression(&token, Precedence::Lowest) {
-            Ok(exp) => exp,
-            Err(e) => return Err(e),
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
+
+        // after expression next token should be ';'
+        match self.peek_token() {
+            Some(Token::Semicolon) => {}
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
+            None => return Err(ParsingError::UnexpectedEof),
         };
 
+        Ok(Statement::Return(expression, start.to(self.consumed)))
+    }
+
+    fn parse_throw(&mut self, start: Span) -> Result<Statement, ParsingError> {
+        let token = self.next_token_or_end()?;
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
+
         // after expression next token should be ';'
-        match self.iter.peek() {
+        match self.peek_token() {
             Some(Token::Semicolon) => {}
-            Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
             None => return Err(ParsingError::UnexpectedEof),
         };
 
-        Ok(Statement::Return(expression))
+        Ok(Statement::Throw(expression, start.to(self.consumed)))
     }
 
-    fn parse_expression_statement(&mut self, token: &Token) -> Result<Statement, ParsingError> {
-        let expression = match self.parse_expression(token, Precedence::Lowest) {
-            Ok(s) => s,
-            Err(e) => return Err(e),
+    fn parse_import(&mut self, start: Span) -> Result<Statement, ParsingError> {
+        let path = match self.next_token_or_end()? {
+            Token::String(path) => path,
+            token => return Err(ParsingError::unexpected(token)),
+        };
+        match self.next_token_or_end()? {
+            Token::As => {}
+            token => return Err(ParsingError::unexpected(token)),
+        }
+        let name = match self.next_token_or_end()? {
+            Token::Identifier(name) => name,
+            token => return Err(ParsingError::unexpected(token)),
+        };
+
+        // after the name next token should be ';'
+        match self.peek_token() {
+            Some(Token::Semicolon) => {}
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
+            None => return Err(ParsingError::UnexpectedEof),
+        };
+
+        let import = Import {
+            path,
+            name,
+            module: None,
+        };
+        Ok(Statement::Import(import, start.to(self.consumed)))
+    }
+
+    fn parse_export(&mut self, start: Span) -> Result<Statement, ParsingError> {
+        match self.next_token_or_end()? {
+            Token::Let => {}
+            token => return Err(ParsingError::unexpected(token)),
+        }
+        let statement = self.parse_let(self.span)?;
+        if !matches!(statement, Statement::Let(Expression::Identifier(_), ..)) {
+            return Err(ParsingError::ExportPattern);
+        }
+        Ok(Statement::Export(
+            Box::new(statement),
+            start.to(self.consumed),
+        ))
+    }
+
+    fn parse_loop_control(
+        &mut self,
+        token: &Token,
+        start: Span,
+    ) -> Result<Statement, ParsingError> {
+        if self.loop_depth == 0 {
+            return Err(ParsingError::OutsideLoop(token.clone()));
+        }
+
+        // after 'break' or 'continue' next token should be ';'
+        match self.peek_token() {
+            Some(Token::Semicolon) => {}
+            Some(token) => return Err(ParsingError::unexpected(token.clone())),
+            None => return Err(ParsingError::UnexpectedEof),
         };
 
-        Ok(Statement::Expression(expression))
+        Ok(match token {
+            Token::Break => Statement::Break(start),
+            _ => Statement::Continue(start),
+        })
+    }
+
+    /// Parses the body of a loop, in which `break` and `continue` may be used.
+    fn parse_loop_block(&mut self) -> Result<Statement, ParsingError> {
+        self.loop_depth += 1;
+        let block = self.parse_block_statement();
+        self.loop_depth -= 1;
+        let block = block?;
+        if let Some((token, span)) = misplaced_loop_control(&block, false) {
+            self.span = span;
+            return Err(ParsingError::LoopControlInExpression(token));
+        }
+        Ok(block)
+    }
+
+    fn parse_expression_statement(
+        &mut self,
+        token: &Token,
+        start: Span,
+    ) -> Result<Statement, ParsingError> {
+        let expression = self.parse_expression(token, Precedence::Lowest)?;
+
+        Ok(Statement::Expression(expression, start.to(self.consumed)))
     }
 
     fn parse_block_statement(&mut self) -> Result<Statement, ParsingError> {
         // expect first token of block to be '{'
         match self.next_token_or_end()? {
             Token::Lbrace => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         }
+        let start = self.span;
 
         let mut block = vec![];
 
         loop {
-            let token = match self.iter.peek() {
+            let token = match self.peek_token() {
                 Some(Token::Semicolon) => {
-                    self.iter.next();
+                    self.next_token();
                     continue;
                 }
                 Some(tok) => tok.clone(),
@@ -210,22 +387,44 @@ impl<'a> Parser<'a> {
         // expect last token of block to be '}'
         match self.next_token_or_end()? {
             Token::Rbrace => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         }
 
-        Ok(Statement::BlockStatement(block))
+        Ok(Statement::BlockStatement(block, start.to(self.consumed)))
     }
 
-    fn next_token_or_end(&mut self) -> Result<Token, ParsingError> {
+    fn peek_token(&mut self) -> Option<&Token> {
         match self.iter.peek() {
+            Some((token, span)) => {
+                self.span = *span;
+                Some(token)
+            }
+            None => {
+                self.span = self.eof;
+                None
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let (token, span) = self.iter.next()?;
+        self.span = span;
+        self.consumed = span;
+        Some(token)
+    }
+
+    fn next_token_or_end(&mut self) -> Result<Token, ParsingError> {
+        match self.peek_token() {
             Some(Token::Semicolon) => Err(ParsingError::UnexpectedSemicolon),
-            Some(_) => Ok(self.iter.next().unwrap()), // unwrap safe since peeked value is Some
+            Some(_) => Ok(self.next_token().unwrap()), // unwrap safe since peeked value is Some
             None => Err(ParsingError::UnexpectedEof),
         }
     }
 
     fn skip_to_semicolon(&mut self) {
-        while let Some(token) = self.iter.peek() {
+        // reads the raw token stream so that `span` keeps pointing at the
+        // token that caused an error
+        while let Some((token, _)) = self.iter.peek() {
             if *token != Token::Semicolon {
                 self.iter.next();
             } else {
@@ -239,25 +438,39 @@ impl<'a> Parser<'a> {
         token: &Token,
         precedence: Precedence,
     ) -> Result<Expression, ParsingError> {
+        // the span of the expression so far, which starts at `token`
+        let start = self.consumed;
+
         // prefix parse functions
         let mut left_expression = match token {
-            Token::Identifier(id) => Self::parse_identifier(id),
+            Token::Identifier(id) => {
+                self.identifiers.push((id.clone(), self.consumed));
+                Self::parse_identifier(id)
+            }
             Token::Int(int) => Self::parse_integer(int),
+            Token::Float(float) => Self::parse_float(float),
             Token::Bang | Token::Minus => self.parse_prefix_expression(token),
             Token::True => Parser::parse_boolean(true),
             Token::False => Parser::parse_boolean(false),
             Token::Lparen => self.parse_grouped_expression(),
             Token::If => self.parse_if_expression(),
             Token::Function => self.parse_function_literal(),
+            Token::Macro => self.parse_macro_literal(),
             Token::String(string) => Self::parse_string(string),
+            Token::Template(parts) => self.parse_template(parts),
+            Token::Error(error) => return Err(ParsingError::Lexer(error.clone())),
+            Token::Illegal(_) => return Err(ParsingError::UnexpectedToken(token.clone())),
             Token::Lbracket => self.parse_array_literal(),
             Token::Lbrace => self.parse_hash_literal(),
             Token::While => self.parse_while_expression(),
+            Token::For => self.parse_for_expression(),
+            Token::Match => self.parse_match_expression(),
+            Token::Try => self.parse_try_expression(),
             _ => return Err(ParsingError::InvalidPrefixOperator(token.clone())),
         }?;
 
         loop {
-            let right = match self.iter.peek() {
+            let right = match self.peek_token() {
                 Some(Token::Semicolon) | None => break,
                 Some(tok) => tok.clone(),
             };
@@ -270,14 +483,26 @@ impl<'a> Parser<'a> {
                     | Token::Minus
                     | Token::Asterisk
                     | Token::Slash
+                    | Token::Percent
+                    | Token::Power
                     | Token::Lt
                     | Token::Gt
+                    | Token::LtEq
+                    | Token::GtEq
+                    | Token::Ampersand
+                    | Token::Pipe
+                    | Token::Caret
+                    | Token::ShiftLeft
+                    | Token::ShiftRight
                     | Token::Eq
                     | Token::Noteq
                     | Token::And
-                    | Token::Or => self.parse_infix_expression(left_expression, &operator)?,
-                    Token::Lparen => self.parse_call_expression(left_expression)?,
-                    Token::Lbracket => self.parse_index_expression(left_expression)?,
+                    | Token::Or => {
+                        self.parse_infix_expression(left_expression, &operator, start)?
+                    }
+                    Token::Lparen => self.parse_call_expression(left_expression, start)?,
+                    Token::Lbracket => self.parse_index_expression(left_expression, start)?,
+                    Token::DotDot => self.parse_range_expression(left_expression)?,
                     _ => break,
                 }
             } else {
@@ -292,17 +517,61 @@ impl<'a> Parser<'a> {
         // get and expect next token to be '(' after 'if'
         let token = match self.next_token_or_end()? {
             Token::Lparen => Token::Lparen,
-            t => return Err(ParsingError::UnexpectedToken(t)),
+            t => return Err(ParsingError::unexpected(t)),
         };
 
         // expect grouped expression after 'if' token
         let condition = self.parse_expression(&token, Precedence::Lowest)?;
 
-        let loop_block = Box::new(self.parse_block_statement()?);
+        let loop_block = Box::new(self.parse_loop_block()?);
 
         Ok(Expression::While(Box::new(condition), loop_block))
     }
 
+    fn parse_for_expression(&mut self) -> Result<Expression, ParsingError> {
+        match self.next_token_or_end()? {
+            Token::Lparen => {}
+            t => return Err(ParsingError::unexpected(t)),
+        }
+
+        // one identifier for the values, or two for the keys and values
+        let mut variables = vec![];
+        let mut bound = HashSet::new();
+        loop {
+            match self.next_token_or_end()? {
+                Token::Identifier(id) => {
+                    self.bind(&id, &mut bound)?;
+                    variables.push(Expression::Identifier(id));
+                }
+                t => return Err(ParsingError::unexpected(t)),
+            }
+            match self.next_token_or_end()? {
+                Token::Comma if variables.len() == 1 => {}
+                Token::In => break,
+                t => return Err(ParsingError::unexpected(t)),
+            }
+        }
+
+        let token = self.next_token_or_end()?;
+        let iterable = self.parse_expression(&token, Precedence::Lowest)?;
+
+        match self.next_token_or_end()? {
+            Token::Rparen => {}
+            t => return Err(ParsingError::unexpected(t)),
+        }
+
+        let loop_block = Box::new(self.parse_loop_block()?);
+
+        Ok(Expression::For(variables, Box::new(iterable), loop_block))
+    }
+
+    fn parse_range_expression(&mut self, start: Expression) -> Result<Expression, ParsingError> {
+        let next_token = self.next_token_or_end()?;
+        let end = self.parse_expression(&next_token, Precedence::Range)?;
+
+        Ok(Expression::Range(Box::new(start), Box::new(end)))
+    }
+
     fn parse_hash_literal(&mut self) -> Result<Expression, ParsingError> {
         let mut hash = vec![];
 
@@ -316,7 +585,7 @@ impl<'a> Parser<'a> {
 
             curr_token = self.next_token_or_end()?;
             if curr_token != Token::Colon {
-                return Err(ParsingError::UnexpectedToken(curr_token));
+                return Err(ParsingError::unexpected(curr_token));
             }
 
             curr_token = self.next_token_or_end()?;
@@ -324,12 +593,12 @@ impl<'a> Parser<'a> {
 
             hash.push((key, value));
 
-            match self.iter.peek() {
+            match self.peek_token() {
                 Some(Token::Comma) => {
                     self.next_token_or_end()?;
                 }
                 Some(Token::Rbrace) => continue,
-                Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+                Some(token) => return Err(ParsingError::unexpected(token.clone())),
                 None => return Err(ParsingError::UnexpectedEof),
             }
         }
@@ -337,9 +606,13 @@ impl<'a> Parser<'a> {
         Ok(Expression::Hash(hash))
     }
 
-    fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, ParsingError> {
-        if let Some(Token::Rbracket) = self.iter.peek() {
-            return Err(ParsingError::UnexpectedToken(Token::Rbracket));
+    fn parse_index_expression(
+        &mut self,
+        left: Expression,
+        start: Span,
+    ) -> Result<Expression, ParsingError> {
+        if let Some(Token::Rbracket) = self.peek_token() {
+            return Err(ParsingError::unexpected(Token::Rbracket));
         }
 
         let next_token = self.next_token_or_end()?;
@@ -347,14 +620,18 @@ impl<'a> Parser<'a> {
 
         match self.next_token_or_end()? {
             Token::Rbracket => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         }
 
-        Ok(Expression::Index(Box::new(left), Box::new(right)))
+        Ok(Expression::Index(
+            Box::new(left),
+            Box::new(right),
+            start.to(self.consumed),
+        ))
     }
 
     fn parse_array_literal(&mut self) -> Result<Expression, ParsingError> {
-        if let Some(Token::Rbracket) = self.iter.peek() {
+        if let Some(Token::Rbracket) = self.peek_token() {
             self.next_token_or_end()?;
             return Ok(Expression::Array(vec![]));
         }
@@ -363,7 +640,7 @@ impl<'a> Parser<'a> {
 
         match self.next_token_or_end()? {
             Token::Rbracket => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         }
 
         Ok(Expression::Array(array))
@@ -375,7 +652,7 @@ impl<'a> Parser<'a> {
         let next_token = self.next_token_or_end()?;
         array.push(self.parse_expression(&next_token, Precedence::Lowest)?);
 
-        while let Some(Token::Comma) = self.iter.peek() {
+        while let Some(Token::Comma) = self.peek_token() {
             self.next_token_or_end()?;
             let next_token = self.next_token_or_end()?;
             array.push(self.parse_expression(&next_token, Precedence::Lowest)?);
@@ -394,6 +671,63 @@ impl<'a> Parser<'a> {
             .map_err(|_| ParsingError::InvalidInteger(int.to_string()))
     }
 
+    fn parse_float(float: &str) -> Result<Expression, ParsingError> {
+        // a literal too large for a float, e.g. `1e400`, is an error rather
+        // than infinity
+        match float.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(Expression::Float(value)),
+            _ => Err(ParsingError::InvalidFloat(float.to_string())),
+        }
+    }
+
+    fn parse_template(&mut self, parts: &[TemplatePart]) -> Result<Expression, ParsingError> {
+        let mut expressions = vec![];
+        for part in parts {
+            match part {
+                TemplatePart::Literal(string) => {
+                    expressions.push(Expression::String(string.clone()))
+                }
+                TemplatePart::Expression(tokens, end) => {
+                    let source = self.source;
+                    let tokens = tokens
+                        .clone()
+                        .into_iter()
+                        .map(move |(token, span)| (token, span.in_source(source)));
+                    let mut parser = Parser {
+                        iter: (Box::new(tokens) as Tokens).peekable(),
+                        span: self.span,
+                        consumed: self.consumed,
+                        eof: end.in_source(source),
+                        loop_depth: self.loop_depth,
+                        source: self.source,
+                        identifiers: std::mem::take(&mut self.identifiers),
+                    };
+                    let parsed = parser.parse_embedded_expression();
+                    self.identifiers = parser.identifiers;
+                    match parsed {
+                        Ok(expression) => expressions.push(expression),
+                        Err(error) => {
+                            // point at the error within the string
+                            self.span = parser.span;
+                            return Err(error);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Expression::Interpolation(expressions))
+    }
+
+    /// Parses the expression inside a `${...}`, which must be all its tokens.
+    fn parse_embedded_expression(&mut self) -> Result<Expression, ParsingError> {
+        let token = self.next_token_or_end()?;
+        let expression = self.parse_expression(&token, Precedence::Lowest)?;
+        match self.peek_token() {
+            Some(token) => Err(ParsingError::unexpected(token.clone())),
+            None => Ok(expression),
+        }
+    }
+
     fn parse_string(string: &str) -> Result<Expression, ParsingError> {
         Ok(Expression::String(string.to_string()))
     }
@@ -405,9 +739,9 @@ impl<'a> Parser<'a> {
     fn parse_grouped_expression(&mut self) -> Result<Expression, ParsingError> {
         let next_token = self.next_token_or_end()?;
         let exp = self.parse_expression(&next_token, Precedence::Lowest)?;
-        if let Some(token) = self.iter.peek() {
+        if let Some(token) = self.peek_token() {
             if *token != Token::Rparen {
-                return Err(ParsingError::UnexpectedToken(token.clone()));
+                return Err(ParsingError::unexpected(token.clone()));
             } else {
                 self.next_token_or_end()?;
             }
@@ -419,7 +753,7 @@ impl<'a> Parser<'a> {
         // get and expect next token to be '(' after 'if'
         let token = match self.next_token_or_end()? {
             Token::Lparen => Token::Lparen,
-            t => return Err(ParsingError::UnexpectedToken(t)),
+            t => return Err(ParsingError::unexpected(t)),
         };
 
         // expect grouped expression after 'if' token
@@ -427,7 +761,7 @@ impl<'a> Parser<'a> {
 
         let consequence = Box::new(self.parse_block_statement()?);
 
-        let alternative = match self.iter.peek() {
+        let alternative = match self.peek_token() {
             Some(Token::Else) => {
                 self.next_token_or_end()?;
 
@@ -447,34 +781,82 @@ impl<'a> Parser<'a> {
         // expect parameter list after 'fn' keyword
         let parameters = self.parse_function_parameters()?;
 
-        // expect block statement after parameter list
-        let body = Box::new(self.parse_block_statement()?);
+        // expect block statement after parameter list; loops around the
+        // function cannot be broken out of from inside it
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = Box::new(body?);
 
         Ok(Expression::Function(parameters, body, String::new()))
     }
 
+    fn parse_macro_literal(&mut self) -> Result<Expression, ParsingError> {
+        // a macro takes parameters as a function does; its body is run when
+        // the program is expanded, outside of any loop
+        let parameters = self.parse_function_parameters()?;
+
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = Box::new(body?);
+
+        Ok(Expression::Macro(parameters, body))
+    }
+
     fn parse_function_parameters(&mut self) -> Result<Vec<Expression>, ParsingError> {
         // expect first token of parameter list to be '('
         match self.next_token_or_end()? {
             Token::Lparen => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
         }
 
         let mut parameters = vec![];
+        // the names the parameters bind, and the variables used by each
+        // parameter's default, as a range of `self.identifiers`
+        let mut bound = HashSet::new();
+        let mut uses = vec![];
 
         // handle case where there are no parameters
-        if let Some(Token::Rparen) = self.iter.peek() {
+        if let Some(Token::Rparen) = self.peek_token() {
             self.next_token_or_end()?;
             return Ok(parameters);
         }
 
         loop {
-            match self.next_token_or_end()? {
-                Token::Identifier(id) => parameters.push(Expression::Identifier(id)),
-                t => return Err(ParsingError::UnexpectedToken(t)),
+            let token = self.next_token_or_end()?;
+            if token == Token::Ellipsis {
+                // the rest parameter comes last
+                match self.next_token_or_end()? {
+                    Token::Identifier(id) => {
+                        self.bind(&id, &mut bound)?;
+                        parameters.push(Expression::Rest(id));
+                        uses.push(0..0);
+                    }
+                    token => return Err(ParsingError::unexpected(token)),
+                }
+                match self.next_token_or_end()? {
+                    Token::Rparen => break,
+                    token => return Err(ParsingError::unexpected(token)),
+                }
             }
 
-            match self.iter.peek() {
+            let pattern = self.parse_pattern(token, false, &mut bound)?;
+            if let Some(Token::Assign) = self.peek_token() {
+                self.next_token_or_end()?;
+                let token = self.next_token_or_end()?;
+                let first = self.identifiers.len();
+                let default = self.parse_expression(&token, Precedence::Lowest)?;
+                parameters.push(Expression::Default(Box::new(pattern), Box::new(default)));
+                uses.push(first..self.identifiers.len());
+            } else if let Some(Expression::Default(..)) = parameters.last() {
+                return Err(ParsingError::RequiredAfterDefault);
+            } else {
+                parameters.push(pattern);
+                uses.push(0..0);
+            }
+
+            match self.peek_token() {
                 Some(Token::Comma) => {
                     self.next_token_or_end()?;
                 }
@@ -482,15 +864,228 @@ impl<'a> Parser<'a> {
                     self.next_token_or_end()?;
                     break;
                 }
-                Some(t) => return Err(ParsingError::UnexpectedToken(t.clone())),
+                Some(t) => return Err(ParsingError::unexpected(t.clone())),
                 None => return Err(ParsingError::UnexpectedEof),
             }
         }
 
+        if let Some((idx, name)) = unbound_parameter(&parameters) {
+            // point at the first use of it in the default
+            let used = self.identifiers[uses[idx].clone()]
+                .iter()
+                .find(|(id, _)| id == name);
+            if let Some((_, span)) = used {
+                self.span = *span;
+            }
+            return Err(ParsingError::UnboundParameter(name.to_string()));
+        }
         Ok(parameters)
     }
 
+    /// Adds a name a pattern binds to `bound`, the names bound by the patterns
+    /// parsed with it so far; `_` binds nothing, so may be repeated.
+    fn bind(&mut self, name: &str, bound: &mut HashSet<String>) -> Result<(), ParsingError> {
+        if name != "_" && !bound.insert(name.to_string()) {
+            self.span = self.consumed;
+            return Err(ParsingError::DuplicateBinding(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Parses what a `let` or a parameter binds: an identifier, an array of
+    /// patterns ending with an optional `...rest`, or a hash of keys and
+    /// patterns. A refutable pattern, as in a `match`, may also be a literal
+    /// that values are compared to, and its hash keys must be literals.
+    fn parse_pattern(
+        &mut self,
+        token: Token,
+        refutable: bool,
+        bound: &mut HashSet<String>,
+    ) -> Result<Expression, ParsingError> {
+        match token {
+            Token::Identifier(id) => {
+                self.bind(&id, bound)?;
+                Ok(Expression::Identifier(id))
+            }
+            Token::Lbracket => {
+                let mut elements = vec![];
+                loop {
+                    match self.next_token_or_end()? {
+                        Token::Rbracket if elements.is_empty() => break,
+                        Token::Ellipsis => {
+                            match self.next_token_or_end()? {
+                                Token::Identifier(id) => {
+                                    self.bind(&id, bound)?;
+                                    elements.push(Expression::Rest(id));
+                                }
+                                token => return Err(ParsingError::unexpected(token)),
+                            }
+                            // the rest binds everything after the others
+                            match self.next_token_or_end()? {
+                                Token::Rbracket => break,
+                                token => return Err(ParsingError::unexpected(token)),
+                            }
+                        }
+                        token => elements.push(self.parse_pattern(token, refutable, bound)?),
+                    }
+                    match self.next_token_or_end()? {
+                        Token::Comma => {}
+                        Token::Rbracket => break,
+                        token => return Err(ParsingError::unexpected(token)),
+                    }
+                }
+                Ok(Expression::Array(elements))
+            }
+            Token::Lbrace => {
+                let mut pairs = vec![];
+                loop {
+                    let token = self.next_token_or_end()?;
+                    if token == Token::Rbrace && pairs.is_empty() {
+                        break;
+                    }
+                    let key = if refutable {
+                        self.parse_literal_pattern(token)?
+                    } else {
+                        self.parse_expression(&token, Precedence::Lowest)?
+                    };
+                    match self.next_token_or_end()? {
+                        Token::Colon => {}
+                        token => return Err(ParsingError::unexpected(token)),
+                    }
+                    let token = self.next_token_or_end()?;
+                    pairs.push((key, self.parse_pattern(token, refutable, bound)?));
+                    match self.next_token_or_end()? {
+                        Token::Comma => {}
+                        Token::Rbrace => break,
+                        token => return Err(ParsingError::unexpected(token)),
+                    }
+                }
+                Ok(Expression::Hash(pairs))
+            }
+            token if refutable => self.parse_literal_pattern(token),
+            token => Err(ParsingError::unexpected(token)),
+        }
+    }
+
+    /// Parses a number, which may be negative, a string or a boolean.
+    fn parse_literal_pattern(&mut self, token: Token) -> Result<Expression, ParsingError> {
+        match token {
+            Token::Int(int) => Self::parse_integer(&int),
+            Token::Float(float) => Self::parse_float(&float),
+            Token::Minus => match self.next_token_or_end()? {
+                Token::Int(int) => Self::parse_integer(&format!("-{int}")),
+                Token::Float(float) => Self::parse_float(&format!("-{float}")),
+                token => Err(ParsingError::unexpected(token)),
+            },
+            Token::String(string) => Self::parse_string(&string),
+            Token::True => Parser::parse_boolean(true),
+            Token::False => Parser::parse_boolean(false),
+            token => Err(ParsingError::unexpected(token)),
+        }
+    }
+
+    fn parse_match_expression(&mut self) -> Result<Expression, ParsingError> {
+        // expect the value in parentheses after 'match'
+        let token = match self.next_token_or_end()? {
+            Token::Lparen => Token::Lparen,
+            token => return Err(ParsingError::unexpected(token)),
+        };
+        let value = self.parse_expression(&token, Precedence::Lowest)?;
+
+        match self.next_token_or_end()? {
+            Token::Lbrace => {}
+            token => return Err(ParsingError::unexpected(token)),
+        }
+
+        // arms are separated by commas, and the last may be followed by one
+        let mut arms = vec![];
+        loop {
+            let token = self.next_token_or_end()?;
+            if token == Token::Rbrace {
+                break;
+            }
+            let start = self.consumed;
+            let pattern = self.parse_pattern(token, true, &mut HashSet::new())?;
+            let guard = match self.peek_token() {
+                Some(Token::If) => {
+                    self.next_token_or_end()?;
+                    let token = self.next_token_or_end()?;
+                    Some(self.parse_expression(&token, Precedence::Lowest)?)
+                }
+                _ => None,
+            };
+            let span = start.to(self.consumed);
+
+            match self.next_token_or_end()? {
+                Token::FatArrow => {}
+                token => return Err(ParsingError::unexpected(token)),
+            }
+            let body = match self.peek_token() {
+                Some(Token::Lbrace) => self.parse_block_statement()?,
+                _ => {
+                    let token = self.next_token_or_end()?;
+                    let start = self.consumed;
+                    let expression = self.parse_expression(&token, Precedence::Lowest)?;
+                    Statement::Expression(expression, start.to(self.consumed))
+                }
+            };
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+                span,
+            });
+
+            match self.next_token_or_end()? {
+                Token::Comma => {}
+                Token::Rbrace => break,
+                token => return Err(ParsingError::unexpected(token)),
+            }
+        }
+
+        Ok(Expression::Match(Box::new(value), arms))
+    }
+
+    fn parse_try_expression(&mut self) -> Result<Expression, ParsingError> {
+        let block = Box::new(self.parse_block_statement()?);
+
+        let catch = match self.peek_token() {
+            Some(Token::Catch) => {
+                self.next_token_or_end()?;
+                // expect the name the error is bound to in parentheses
+                match self.next_token_or_end()? {
+                    Token::Lparen => {}
+                    token => return Err(ParsingError::unexpected(token)),
+                }
+                let name = match self.next_token_or_end()? {
+                    Token::Identifier(name) => name,
+                    token => return Err(ParsingError::unexpected(token)),
+                };
+                match self.next_token_or_end()? {
+                    Token::Rparen => {}
+                    token => return Err(ParsingError::unexpected(token)),
+                }
+                Some((name, Box::new(self.parse_block_statement()?)))
+            }
+            _ => None,
+        };
+
+        let finally = match self.peek_token() {
+            Some(Token::Finally) => {
+                self.next_token_or_end()?;
+                Some(Box::new(self.parse_block_statement()?))
+            }
+            _ => None,
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(ParsingError::TryWithoutHandler);
+        }
+        Ok(Expression::Try(block, catch, finally))
+    }
+
     fn parse_prefix_expression(&mut self, token: &Token) -> Result<Expression, ParsingError> {
+        let start = self.consumed;
         let prefix = match token {
             Token::Bang => Prefix::Bang,
             Token::Minus => Prefix::Minus,
@@ -505,21 +1100,35 @@ impl<'a> Parser<'a> {
 
         let right_expression = self.parse_expression(&next_token, Precedence::Prefix)?;
 
-        Ok(Expression::Prefix(prefix, Box::new(right_expression)))
+        Ok(Expression::Prefix(
+            prefix,
+            Box::new(right_expression),
+            start.to(self.consumed),
+        ))
     }
 
     fn parse_infix_expression(
         &mut self,
         left_expression: Expression,
         operator: &Token,
+        start: Span,
     ) -> Result<Expression, ParsingError> {
         let infix = match operator {
             Token::Plus => Infix::Plus,
             Token::Minus => Infix::Minus,
             Token::Asterisk => Infix::Multiply,
             Token::Slash => Infix::Divide,
+            Token::Percent => Infix::Modulo,
+            Token::Power => Infix::Power,
             Token::Lt => Infix::LessThan,
             Token::Gt => Infix::GreaterThan,
+            Token::LtEq => Infix::LessEqual,
+            Token::GtEq => Infix::GreaterEqual,
+            Token::Ampersand => Infix::BitAnd,
+            Token::Pipe => Infix::BitOr,
+            Token::Caret => Infix::BitXor,
+            Token::ShiftLeft => Infix::ShiftLeft,
+            Token::ShiftRight => Infix::ShiftRight,
             Token::Eq => Infix::Equal,
             Token::Noteq => Infix::NotEqual,
             Token::And => Infix::And,
@@ -531,7 +1140,11 @@ impl<'a> Parser<'a> {
             }
         };
 
-        let precedence = Precedence::get_precedence(operator);
+        let precedence = match Precedence::get_precedence(operator) {
+            // `**` is right associative: `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+            Precedence::Power => Precedence::Prefix,
+            precedence => precedence,
+        };
 
         let next_token = self.next_token_or_end()?;
 
@@ -541,26 +1154,124 @@ impl<'a> Parser<'a> {
             Box::new(left_expression),
             infix,
             Box::new(right_expression),
+            start.to(self.consumed),
         ))
     }
 
     fn parse_call_expression(
         &mut self,
         left_expression: Expression,
+        start: Span,
     ) -> Result<Expression, ParsingError> {
-        if let Some(Token::Rparen) = self.iter.peek() {
+        if let Some(Token::Rparen) = self.peek_token() {
             self.next_token_or_end()?;
-            return Ok(Expression::Call(Box::new(left_expression), vec![]));
+            return Ok(Expression::Call(
+                Box::new(left_expression),
+                vec![],
+                start.to(self.consumed),
+            ));
         }
 
         let arguments = self.parse_expression_list()?;
 
         match self.next_token_or_end()? {
             Token::Rparen => {}
-            token => return Err(ParsingError::UnexpectedToken(token)),
+            token => return Err(ParsingError::unexpected(token)),
+        }
+
+        Ok(Expression::Call(
+            Box::new(left_expression),
+            arguments,
+            start.to(self.consumed),
+        ))
+    }
+}
+
+/// The first parameter whose default uses a parameter after it, or itself,
+/// e.g. `y` in `fn(x = y, y = 2)`, whichever variable named `y` is bound
+/// outside the function, with the name of the parameter it uses.
+fn unbound_parameter(parameters: &[Expression]) -> Option<(usize, &str)> {
+    parameters.iter().enumerate().find_map(|(idx, parameter)| {
+        let default = parameter.parameter_parts().1?;
+        let unbound = parameters[idx..]
+            .iter()
+            .flat_map(|parameter| parameter.bound_names())
+            .find(|name| default.refers_to(name))?;
+        Some((idx, unbound))
+    })
+}
+
+/// The first `break` or `continue` in `statement` that would leave an
+/// expression part way through computing its value, e.g. the `continue` in
+/// `x += if (c) { continue; } else { 1 };`. `in_value` is whether the value of
+/// `statement` is used by such an expression. Loops nested in `statement` are
+/// checked when they are parsed.
+fn misplaced_loop_control(statement: &Statement, in_value: bool) -> Option<(Token, Span)> {
+    match statement {
+        Statement::Break(span) if in_value => Some((Token::Break, *span)),
+        Statement::Continue(span) if in_value => Some((Token::Continue, *span)),
+        Statement::Expression(expression, _) => loop_control_in(expression, in_value),
+        Statement::BlockStatement(statements, _) => statements
+            .iter()
+            .find_map(|statement| misplaced_loop_control(statement, in_value)),
+        Statement::Let(_, value, _) | Statement::Return(value, _) | Statement::Throw(value, _) => {
+            loop_control_in(value, true)
+        }
+        Statement::Assignment(target, value, _)
+        | Statement::CompoundAssignment(target, _, value, _) => {
+            loop_control_in(target, true).or_else(|| loop_control_in(value, true))
         }
+        Statement::Export(statement, _) => misplaced_loop_control(statement, in_value),
+        Statement::Break(_) | Statement::Continue(_) | Statement::Import(..) => None,
+    }
+}
 
-        Ok(Expression::Call(Box::new(left_expression), arguments))
+/// As [`misplaced_loop_control`], for the code in `expression`. The branches
+/// of an `if`, `match` or `try` are used as its value, so only their own
+/// values may be, and the operands of anything else are.
+fn loop_control_in(expression: &Expression, in_value: bool) -> Option<(Token, Span)> {
+    let branch = |statement: &Statement| misplaced_loop_control(statement, in_value);
+    let value = |expression: &Expression| loop_control_in(expression, true);
+    let values = |expressions: &[Expression]| expressions.iter().find_map(value);
+    match expression {
+        Expression::If(condition, consequence, alternative) => value(condition)
+            .or_else(|| branch(consequence))
+            .or_else(|| alternative.as_deref().and_then(branch)),
+        Expression::Match(subject, arms) => value(subject).or_else(|| {
+            arms.iter().find_map(|arm| {
+                arm.guard
+                    .as_ref()
+                    .and_then(value)
+                    .or_else(|| branch(&arm.body))
+            })
+        }),
+        Expression::Try(block, catch, finally) => branch(block)
+            .or_else(|| catch.as_ref().and_then(|(_, handler)| branch(handler)))
+            .or_else(|| finally.as_deref().and_then(branch)),
+        // the body of a function cannot break out of the loops around it
+        Expression::While(condition, _) => value(condition),
+        Expression::For(_, iterable, _) => value(iterable),
+        Expression::Function(parameters, ..) | Expression::Macro(parameters, _) => {
+            values(parameters)
+        }
+        Expression::Prefix(_, operand, _) => value(operand),
+        Expression::Infix(left, _, right, _)
+        | Expression::Index(left, right, _)
+        | Expression::Range(left, right)
+        | Expression::Default(left, right) => value(left).or_else(|| value(right)),
+        Expression::Call(function, arguments, _) => value(function).or_else(|| values(arguments)),
+        Expression::Interpolation(expressions) | Expression::Array(expressions) => {
+            values(expressions)
+        }
+        Expression::Hash(pairs) => pairs
+            .iter()
+            .find_map(|(key, val)| value(key).or_else(|| value(val))),
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::String(_)
+        | Expression::Rest(_) => None,
     }
 }
 
@@ -571,22 +1282,70 @@ pub enum ParsingError {
     UnexpectedSemicolon,
     InvalidPrefixOperator(Token),
     InvalidInteger(String),
+    InvalidFloat(String),
+    OutsideLoop(Token),
+    /// `break` or `continue` in an expression whose value is used, e.g.
+    /// `f(if (c) { break; } else { 1 })`.
+    LoopControlInExpression(Token),
+    InvalidAssignmentTarget,
+    RequiredAfterDefault,
+    TryWithoutHandler,
+    /// `import` or `export` inside a block or function.
+    NotTopLevel(Token),
+    ExportPattern,
+    /// A name bound more than once by the parameters of a function or by a
+    /// pattern.
+    DuplicateBinding(String),
+    /// A parameter used by the default of an earlier parameter, or its own.
+    UnboundParameter(String),
+    Lexer(LexerError),
     Generic(String),
 }
 
+impl ParsingError {
+    /// The error for finding `token` where it does not belong. A token the
+    /// lexer could not read is reported as the lexer's error instead.
+    fn unexpected(token: Token) -> Self {
+        match token {
+            Token::Error(error) => ParsingError::Lexer(error),
+            token => ParsingError::UnexpectedToken(token),
+        }
+    }
+}
+
 impl std::fmt::Display for ParsingError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
             match self {
-                ParsingError::UnexpectedToken(token) => format!("Unexpected token: '{token}'"),
-                ParsingError::UnexpectedEof => "Unexpected EOF".to_string(),
-                ParsingError::UnexpectedSemicolon => "Unexpected end of statement: ';'".to_string(),
+                ParsingError::UnexpectedToken(Token::Illegal(ch)) =>
+                    format!("unexpected character: '{ch}'"),
+                ParsingError::UnexpectedToken(token) => format!("unexpected token: '{token}'"),
+                ParsingError::UnexpectedEof => "unexpected end of input".to_string(),
+                ParsingError::UnexpectedSemicolon => "unexpected end of statement: ';'".to_string(),
                 ParsingError::InvalidPrefixOperator(token) =>
                     format!("'{token}' is not a valid prefix operator"),
                 ParsingError::InvalidInteger(string) =>
-                    format!("Cannot parse '{}' as a valid integer", *string),
+                    format!("cannot parse '{}' as a valid integer", *string),
+                ParsingError::InvalidFloat(string) =>
+                    format!("cannot parse '{}' as a valid float", *string),
+                ParsingError::OutsideLoop(token) => format!("'{token}' outside of a loop"),
+                ParsingError::LoopControlInExpression(token) =>
+                    format!("'{token}' inside an expression whose value is used"),
+                ParsingError::InvalidAssignmentTarget =>
+                    "cannot assign to this expression".to_string(),
+                ParsingError::RequiredAfterDefault =>
+                    "a parameter without a default cannot follow one with a default".to_string(),
+                ParsingError::TryWithoutHandler => "'try' without 'catch' or 'finally'".to_string(),
+                ParsingError::NotTopLevel(token) =>
+                    format!("'{token}' is only allowed at the top level of a file"),
+                ParsingError::ExportPattern =>
+                    "only a 'let' binding a single name can be exported".to_string(),
+                ParsingError::DuplicateBinding(name) => format!("'{name}' is bound more than once"),
+                ParsingError::UnboundParameter(name) =>
+                    format!("parameter '{name}' is used by a default before it is bound"),
+                ParsingError::Lexer(error) => error.to_string(),
                 ParsingError::Generic(string) => string.to_string(),
             }
         )
@@ -3,12 +3,20 @@ use crate::lexer::token::Token;
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum Precedence {
     Lowest = 0,
+    // binds loosest of all, so `0..n + 1` is `0..(n + 1)`
+    Range,
     Logical,
     Equals,
     LessGreater,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Sum,
     Product,
     Prefix,
+    // binds tighter than a prefix operator on its left, so `-2 ** 2` is -4
+    Power,
     Call,
     Index,
 }
@@ -16,11 +24,17 @@ pub enum Precedence {
 impl Precedence {
     pub fn get_precedence(token: &Token) -> Precedence {
         match token {
+            Token::DotDot => Precedence::Range,
             Token::And | Token::Or => Precedence::Logical,
             Token::Eq | Token::Noteq => Precedence::Equals,
-            Token::Lt | Token::Gt => Precedence::LessGreater,
+            Token::Lt | Token::Gt | Token::LtEq | Token::GtEq => Precedence::LessGreater,
+            Token::Pipe => Precedence::BitOr,
+            Token::Caret => Precedence::BitXor,
+            Token::Ampersand => Precedence::BitAnd,
+            Token::ShiftLeft | Token::ShiftRight => Precedence::Shift,
             Token::Plus | Token::Minus => Precedence::Sum,
-            Token::Asterisk | Token::Slash => Precedence::Product,
+            Token::Asterisk | Token::Slash | Token::Percent => Precedence::Product,
+            Token::Power => Precedence::Power,
             Token::Lparen => Precedence::Call,
             Token::Lbracket => Precedence::Index,
             _ => Precedence::Lowest,
@@ -1,12 +1,23 @@
 #![cfg(test)]
 
+use crate::lexer::LexerError;
 use crate::parser::{Parser, ParsingError};
 use crate::{
     lexer::token::Token,
     parser::ast::{Expression, Statement},
 };
 
-use super::ast::{Infix, Prefix, Program};
+use super::ast::{Import, Infix, MatchArm, Prefix, Program};
+use crate::span::{Position, Span};
+
+fn parse_errors(input: &str) -> Vec<ParsingError> {
+    Parser::parse_program(input)
+        .err()
+        .unwrap()
+        .into_iter()
+        .map(|e| e.error)
+        .collect()
+}
 
 #[test]
 fn test_let_statement() {
@@ -14,16 +25,449 @@ fn test_let_statement() {
     let expected = Program(vec![Statement::Let(
         Expression::Identifier(String::from("x")),
         Expression::Integer(5),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_let_destructuring() {
+    let input = "let [a, {\"k\": b, 1: [c]}, ...d] = x;";
+    let expected = Program(vec![Statement::Let(
+        Expression::Array(vec![
+            Expression::Identifier(String::from("a")),
+            Expression::Hash(vec![
+                (
+                    Expression::String(String::from("k")),
+                    Expression::Identifier(String::from("b")),
+                ),
+                (
+                    Expression::Integer(1),
+                    Expression::Array(vec![Expression::Identifier(String::from("c"))]),
+                ),
+            ]),
+            Expression::Rest(String::from("d")),
+        ]),
+        Expression::Identifier(String::from("x")),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_function_parameter_patterns() {
+    let input = "fn([a, ...b], {}) { a };";
+    let expected = Program(vec![Statement::Expression(
+        Expression::Function(
+            vec![
+                Expression::Array(vec![
+                    Expression::Identifier(String::from("a")),
+                    Expression::Rest(String::from("b")),
+                ]),
+                Expression::Hash(vec![]),
+            ],
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Identifier(String::from("a")),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            String::new(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_default_and_rest_parameters() {
+    let input = "fn(a, [b] = [a], ...c) { c };";
+    let expected = Program(vec![Statement::Expression(
+        Expression::Function(
+            vec![
+                Expression::Identifier(String::from("a")),
+                Expression::Default(
+                    Box::new(Expression::Array(vec![Expression::Identifier(
+                        String::from("b"),
+                    )])),
+                    Box::new(Expression::Array(vec![Expression::Identifier(
+                        String::from("a"),
+                    )])),
+                ),
+                Expression::Rest(String::from("c")),
+            ],
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Identifier(String::from("c")),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            String::new(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_parameter_parse_errors() {
+    let tests = vec![
+        ("fn(a = 1, b) { a };", ParsingError::RequiredAfterDefault),
+        (
+            "fn(...a, b) { a };",
+            ParsingError::UnexpectedToken(Token::Comma),
+        ),
+        (
+            "fn(...[a]) { a };",
+            ParsingError::UnexpectedToken(Token::Lbracket),
+        ),
+        (
+            "fn(...a = 1) { a };",
+            ParsingError::UnexpectedToken(Token::Assign),
+        ),
+        (
+            "fn(a = ) { a };",
+            ParsingError::InvalidPrefixOperator(Token::Rparen),
+        ),
+        (
+            "fn(a, a) { a };",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "fn(a, {\"k\": [b, a]}) { a };",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "fn(a, b = 1, ...a) { a };",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "macro(x, x) { x };",
+            ParsingError::DuplicateBinding(String::from("x")),
+        ),
+        (
+            "fn(x = y, y = 2) { x };",
+            ParsingError::UnboundParameter(String::from("y")),
+        ),
+        (
+            "let y = 5; fn(x = y, y = 2) { x };",
+            ParsingError::UnboundParameter(String::from("y")),
+        ),
+        (
+            "fn(x = x) { x };",
+            ParsingError::UnboundParameter(String::from("x")),
+        ),
+        (
+            "fn(x = [y][0], [y] = [1]) { x };",
+            ParsingError::UnboundParameter(String::from("y")),
+        ),
+    ];
+    // `_` binds nothing, so it can be given for any number of parameters
+    assert!(Parser::parse_program("fn(_, [_, a], _) { a };").is_ok());
+    // a default can use the parameters before it, and a nested function
+    // can bind the name of a later parameter for itself
+    assert!(Parser::parse_program("fn(a = 1, b = a + 1) { b };").is_ok());
+    assert!(Parser::parse_program("fn(f = fn(y) { y }, y = 2) { f };").is_ok());
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
+#[test]
+fn test_match_expression() {
+    let input = "match (x) { -1 => a, [n, ..._] if n => { b }, {\"k\": 2.5, true: _} => c, }";
+    let expected = Program(vec![Statement::Expression(
+        Expression::Match(
+            Box::new(Expression::Identifier(String::from("x"))),
+            vec![
+                MatchArm {
+                    pattern: Expression::Integer(-1),
+                    guard: None,
+                    body: Statement::Expression(
+                        Expression::Identifier(String::from("a")),
+                        Span::default(),
+                    ),
+                    span: Span::default(),
+                },
+                MatchArm {
+                    pattern: Expression::Array(vec![
+                        Expression::Identifier(String::from("n")),
+                        Expression::Rest(String::from("_")),
+                    ]),
+                    guard: Some(Expression::Identifier(String::from("n"))),
+                    body: Statement::BlockStatement(
+                        vec![Statement::Expression(
+                            Expression::Identifier(String::from("b")),
+                            Span::default(),
+                        )],
+                        Span::default(),
+                    ),
+                    span: Span::default(),
+                },
+                MatchArm {
+                    pattern: Expression::Hash(vec![
+                        (
+                            Expression::String(String::from("k")),
+                            Expression::Float(2.5),
+                        ),
+                        (
+                            Expression::Boolean(true),
+                            Expression::Identifier(String::from("_")),
+                        ),
+                    ]),
+                    guard: None,
+                    body: Statement::Expression(
+                        Expression::Identifier(String::from("c")),
+                        Span::default(),
+                    ),
+                    span: Span::default(),
+                },
+            ],
+        ),
+        Span::default(),
     )]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_try_expression_and_throw_statement() {
+    let block = |name: &str| {
+        Box::new(Statement::BlockStatement(
+            vec![Statement::Expression(
+                Expression::Identifier(String::from(name)),
+                Span::default(),
+            )],
+            Span::default(),
+        ))
+    };
+    let tests = vec![
+        (
+            "try { a } catch (e) { b } finally { c }",
+            Statement::Expression(
+                Expression::Try(
+                    block("a"),
+                    Some((String::from("e"), block("b"))),
+                    Some(block("c")),
+                ),
+                Span::default(),
+            ),
+        ),
+        (
+            "try { a } finally { c }",
+            Statement::Expression(
+                Expression::Try(block("a"), None, Some(block("c"))),
+                Span::default(),
+            ),
+        ),
+        (
+            "throw error(a);",
+            Statement::Throw(
+                Expression::Call(
+                    Box::new(Expression::Identifier(String::from("error"))),
+                    vec![Expression::Identifier(String::from("a"))],
+                    Span::default(),
+                ),
+                Span::default(),
+            ),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = Parser::parse_program(input).ok().unwrap().without_spans();
+        assert_eq!(program, Program(vec![expected]), "{input}");
+    }
+}
+
+#[test]
+fn test_try_parse_errors() {
+    let tests = vec![
+        ("try { a }", ParsingError::TryWithoutHandler),
+        (
+            "try { a } catch e { b }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("e"))),
+        ),
+        (
+            "try { a } catch ([e]) { b }",
+            ParsingError::UnexpectedToken(Token::Lbracket),
+        ),
+        (
+            "try a catch (e) { b }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("a"))),
+        ),
+        (
+            "throw 1 2;",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
+#[test]
+fn test_import_and_export_statements() {
+    let input = "import \"lib/strings.mk\" as strings; export let x = 1;";
+    let expected = Program(vec![
+        Statement::Import(
+            Import {
+                path: String::from("lib/strings.mk"),
+                name: String::from("strings"),
+                module: None,
+            },
+            Span::default(),
+        ),
+        Statement::Export(
+            Box::new(Statement::Let(
+                Expression::Identifier(String::from("x")),
+                Expression::Integer(1),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+    ]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_import_and_export_parse_errors() {
+    let tests = vec![
+        (
+            "import strings as s;",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("strings"))),
+        ),
+        (
+            "import \"a.mk\" s;",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("s"))),
+        ),
+        ("import \"a.mk\" as;", ParsingError::UnexpectedSemicolon),
+        (
+            "export fn() {};",
+            ParsingError::UnexpectedToken(Token::Function),
+        ),
+        ("export let [a, b] = c;", ParsingError::ExportPattern),
+        (
+            "if (a) { import \"a.mk\" as b; }",
+            ParsingError::NotTopLevel(Token::Import),
+        ),
+        (
+            "let f = fn() { export let x = 1; };",
+            ParsingError::NotTopLevel(Token::Export),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input).first(), Some(&expected), "{input}");
+    }
+}
+
+#[test]
+fn test_parse_source_numbers_spans() {
+    let program = Parser::parse_source("let x = \"${y}\";", 2).unwrap();
+    assert_eq!(program.0[0].span().source, 2);
+
+    let errors = Parser::parse_source("\"${1 +}\"", 3).err().unwrap();
+    assert_eq!(errors[0].span.map(|span| span.source), Some(3));
+}
+
+#[test]
+fn test_match_parse_errors() {
+    let tests = vec![
+        (
+            "match x { _ => 1 }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("x"))),
+        ),
+        (
+            "match (x) { 1 + 1 => 1 }",
+            ParsingError::UnexpectedToken(Token::Plus),
+        ),
+        (
+            "match (x) { {k: v} => 1 }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("k"))),
+        ),
+        (
+            "match (x) { 1 => 1 2 => 2 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+        (
+            "match (x) { -a => 1 }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("a"))),
+        ),
+        (
+            "let [1] = x;",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
+#[test]
+fn test_pattern_parse_errors() {
+    let tests = vec![
+        (
+            "let [a, ...b, c] = x;",
+            ParsingError::UnexpectedToken(Token::Comma),
+        ),
+        (
+            "let [a,] = x;",
+            ParsingError::UnexpectedToken(Token::Rbracket),
+        ),
+        (
+            "let [...] = x;",
+            ParsingError::UnexpectedToken(Token::Rbracket),
+        ),
+        (
+            "let {\"k\"} = x;",
+            ParsingError::UnexpectedToken(Token::Rbrace),
+        ),
+        (
+            "let {\"k\": 1} = x;",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+        (
+            "let {...a} = x;",
+            ParsingError::InvalidPrefixOperator(Token::Ellipsis),
+        ),
+        (
+            "fn(a, [1]) { a };",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+        (
+            "let [a, a] = x;",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "let {\"k\": [a], \"j\": a} = x;",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "match (x) { [a, a] => a }",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+        (
+            "match (x) { [1, a, ...a] => a }",
+            ParsingError::DuplicateBinding(String::from("a")),
+        ),
+    ];
+    // a name can be bound again by another arm, and `_` any number of times
+    assert!(Parser::parse_program("match (x) { [a, _] => a, [_, a, _] => a }").is_ok());
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
 #[test]
 fn test_let_parse_error_if_no_identifier() {
     let input = "let = 5;";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Assign)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -31,7 +475,7 @@ fn test_let_parse_error_if_no_identifier() {
 fn test_let_parse_error_if_no_assign() {
     let input = "let x 5;";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Int(String::from("5")))];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -39,7 +483,7 @@ fn test_let_parse_error_if_no_assign() {
 fn test_let_statement_parse_error_if_no_semicolon() {
     let input = "let x = 5";
     let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -50,7 +494,7 @@ fn test_let_statement_parse_error_if_no_rhs_expression() {
         ParsingError::UnexpectedSemicolon,
         ParsingError::UnexpectedEof,
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -65,15 +509,18 @@ let y 3;";
         ParsingError::UnexpectedSemicolon,
         ParsingError::UnexpectedToken(Token::Int(String::from("3"))),
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_return_statement() {
     let input = "return 10;";
-    let expected = Program(vec![Statement::Return(Expression::Integer(10))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Return(
+        Expression::Integer(10),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -81,7 +528,7 @@ fn test_return_statement() {
 fn test_return_statement_parse_error_if_no_expression() {
     let input = "return ;";
     let expected_errors = vec![ParsingError::UnexpectedSemicolon];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -91,7 +538,7 @@ fn test_return_statement_parse_error_if_no_semicolon() {
 let x = 5;
 ";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Let)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -104,10 +551,11 @@ return 10;
         Statement::Let(
             Expression::Identifier(String::from("x")),
             Expression::Integer(5),
+            Span::default(),
         ),
-        Statement::Return(Expression::Integer(10)),
+        Statement::Return(Expression::Integer(10), Span::default()),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -117,10 +565,10 @@ fn test_identifier_expression_statement() {
 return 10;
 ";
     let expected = Program(vec![
-        Statement::Expression(Expression::Identifier(String::from("foo"))),
-        Statement::Return(Expression::Integer(10)),
+        Statement::Expression(Expression::Identifier(String::from("foo")), Span::default()),
+        Statement::Return(Expression::Integer(10), Span::default()),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -130,29 +578,97 @@ fn test_expression_statement_parses_without_semicolon() {
 foo
 ";
     let expected = Program(vec![
-        Statement::Return(Expression::Integer(10)),
-        Statement::Expression(Expression::Identifier(String::from("foo"))),
+        Statement::Return(Expression::Integer(10), Span::default()),
+        Statement::Expression(Expression::Identifier(String::from("foo")), Span::default()),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_integer_expression_statement() {
     let input = "5;";
-    let expected = Program(vec![Statement::Expression(Expression::Integer(5))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Integer(5),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_float_expression_statement() {
+    let input = "2.5;";
+    let expected = Program(vec![Statement::Expression(
+        Expression::Float(2.5),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_float_exponent_expression_statement() {
+    let input = "1e3; 2.5e-1;";
+    let expected = Program(vec![
+        Statement::Expression(Expression::Float(1000.0), Span::default()),
+        Statement::Expression(Expression::Float(0.25), Span::default()),
+    ]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+
+    let expected_errors = vec![ParsingError::InvalidFloat(String::from("1e400"))];
+    assert_eq!(parse_errors("1e400;"), expected_errors);
+}
+
+#[test]
+fn test_string_interpolation() {
+    let input = r#""sum: ${a + 1}!""#;
+    let expected = Program(vec![Statement::Expression(
+        Expression::Interpolation(vec![
+            Expression::String(String::from("sum: ")),
+            Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Plus,
+                Box::new(Expression::Integer(1)),
+                Span::default(),
+            ),
+            Expression::String(String::from("!")),
+        ]),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
+#[test]
+fn test_string_interpolation_errors() {
+    let input = r#"
+"${1 2}";
+"${}";
+let x = "abc"#;
+    let expected_errors = vec![
+        ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ParsingError::UnexpectedEof,
+        ParsingError::Lexer(LexerError::UnterminatedString),
+    ];
+    let errors = parse_errors(input);
+    assert_eq!(errors, expected_errors);
+}
+
 #[test]
 fn test_parsing_bang_prefix_expressions() {
     let input = "let x = !5;";
     let expected = Program(vec![Statement::Let(
         Expression::Identifier(String::from("x")),
-        Expression::Prefix(Prefix::Bang, Box::new(Expression::Integer(5))),
+        Expression::Prefix(
+            Prefix::Bang,
+            Box::new(Expression::Integer(5)),
+            Span::default(),
+        ),
+        Span::default(),
     )]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -164,14 +680,24 @@ let y = !10;
     let expected = Program(vec![
         Statement::Let(
             Expression::Identifier(String::from("x")),
-            Expression::Prefix(Prefix::Minus, Box::new(Expression::Integer(5))),
+            Expression::Prefix(
+                Prefix::Minus,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
         ),
         Statement::Let(
             Expression::Identifier(String::from("y")),
-            Expression::Prefix(Prefix::Bang, Box::new(Expression::Integer(10))),
+            Expression::Prefix(
+                Prefix::Bang,
+                Box::new(Expression::Integer(10)),
+                Span::default(),
+            ),
+            Span::default(),
         ),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -184,7 +710,7 @@ fn test_prefix_expressions_error_if_no_right_expression() {
         ParsingError::UnexpectedSemicolon,
         ParsingError::UnexpectedEof,
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -200,185 +726,260 @@ fn test_infix_expressions() {
 5 != 5;
 ";
     let expected = Program(vec![
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Plus,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Minus,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Multiply,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Divide,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::GreaterThan,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::LessThan,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Equal,
-            Box::new(Expression::Integer(5)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::NotEqual,
-            Box::new(Expression::Integer(5)),
-        )),
-    ]);
-    let program = Parser::parse_program(input).ok().unwrap();
-    assert_eq!(program, expected);
-}
-
-#[test]
-fn test_operator_precedence_one() {
-    let input = "-a * b"; // ((-a) * b)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Prefix(
-            Prefix::Minus,
-            Box::new(Expression::Identifier(String::from("a"))),
-        )),
-        Infix::Multiply,
-        Box::new(Expression::Identifier(String::from("b"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
-    assert_eq!(program, expected);
-}
-
-#[test]
-fn test_operator_precedence_two() {
-    let input = "!-a"; // (!(-a))
-    let expected = Program(vec![Statement::Expression(Expression::Prefix(
-        Prefix::Bang,
-        Box::new(Expression::Prefix(
-            Prefix::Minus,
-            Box::new(Expression::Identifier(String::from("a"))),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
-    assert_eq!(program, expected);
-}
-
-#[test]
-fn test_operator_precedence_three() {
-    let input = "a + b + c"; // ((a + b) + c)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
-            Infix::Plus,
-            Box::new(Expression::Identifier(String::from("b"))),
-        )),
-        Infix::Plus,
-        Box::new(Expression::Identifier(String::from("c"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
-    assert_eq!(program, expected);
-}
-
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Plus,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Minus,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Multiply,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Divide,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::GreaterThan,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::LessThan,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Equal,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::NotEqual,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+    ]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_one() {
+    let input = "-a * b"; // ((-a) * b)
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Prefix(
+                Prefix::Minus,
+                Box::new(Expression::Identifier(String::from("a"))),
+                Span::default(),
+            )),
+            Infix::Multiply,
+            Box::new(Expression::Identifier(String::from("b"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_two() {
+    let input = "!-a"; // (!(-a))
+    let expected = Program(vec![Statement::Expression(
+        Expression::Prefix(
+            Prefix::Bang,
+            Box::new(Expression::Prefix(
+                Prefix::Minus,
+                Box::new(Expression::Identifier(String::from("a"))),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_three() {
+    let input = "a + b + c"; // ((a + b) + c)
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Plus,
+                Box::new(Expression::Identifier(String::from("b"))),
+                Span::default(),
+            )),
+            Infix::Plus,
+            Box::new(Expression::Identifier(String::from("c"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
 #[test]
 fn test_operator_precedence_four() {
     let input = "a + b - c"; // ((a + b) - c)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
-            Infix::Plus,
-            Box::new(Expression::Identifier(String::from("b"))),
-        )),
-        Infix::Minus,
-        Box::new(Expression::Identifier(String::from("c"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Plus,
+                Box::new(Expression::Identifier(String::from("b"))),
+                Span::default(),
+            )),
+            Infix::Minus,
+            Box::new(Expression::Identifier(String::from("c"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_five() {
     let input = "a * b * c"; // ((a * b) * c)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Multiply,
+                Box::new(Expression::Identifier(String::from("b"))),
+                Span::default(),
+            )),
             Infix::Multiply,
-            Box::new(Expression::Identifier(String::from("b"))),
-        )),
-        Infix::Multiply,
-        Box::new(Expression::Identifier(String::from("c"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            Box::new(Expression::Identifier(String::from("c"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_six() {
     let input = "a * b / c"; // ((a * b) / c)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
-            Infix::Multiply,
-            Box::new(Expression::Identifier(String::from("b"))),
-        )),
-        Infix::Divide,
-        Box::new(Expression::Identifier(String::from("c"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Multiply,
+                Box::new(Expression::Identifier(String::from("b"))),
+                Span::default(),
+            )),
+            Infix::Divide,
+            Box::new(Expression::Identifier(String::from("c"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_seven() {
     let input = "a + b / c"; // (a + (b / c))
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Identifier(String::from("a"))),
-        Infix::Plus,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("b"))),
-            Infix::Divide,
-            Box::new(Expression::Identifier(String::from("c"))),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Identifier(String::from("a"))),
+            Infix::Plus,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("b"))),
+                Infix::Divide,
+                Box::new(Expression::Identifier(String::from("c"))),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_eight() {
     let input = "a + b * c + d / e - f"; // (((a + (b * c)) + (d / e)) - f)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
             Box::new(Expression::Infix(
-                Box::new(Expression::Identifier(String::from("a"))),
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Identifier(String::from("a"))),
+                    Infix::Plus,
+                    Box::new(Expression::Infix(
+                        Box::new(Expression::Identifier(String::from("b"))),
+                        Infix::Multiply,
+                        Box::new(Expression::Identifier(String::from("c"))),
+                        Span::default(),
+                    )),
+                    Span::default(),
+                )),
                 Infix::Plus,
                 Box::new(Expression::Infix(
-                    Box::new(Expression::Identifier(String::from("b"))),
-                    Infix::Multiply,
-                    Box::new(Expression::Identifier(String::from("c"))),
+                    Box::new(Expression::Identifier(String::from("d"))),
+                    Infix::Divide,
+                    Box::new(Expression::Identifier(String::from("e"))),
+                    Span::default(),
                 )),
+                Span::default(),
             )),
-            Infix::Plus,
-            Box::new(Expression::Infix(
-                Box::new(Expression::Identifier(String::from("d"))),
-                Infix::Divide,
-                Box::new(Expression::Identifier(String::from("e"))),
-            )),
-        )),
-        Infix::Minus,
-        Box::new(Expression::Identifier(String::from("f"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            Infix::Minus,
+            Box::new(Expression::Identifier(String::from("f"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -386,93 +987,123 @@ fn test_operator_precedence_eight() {
 fn test_operator_precedence_nine() {
     let input = "3 + 4; -5 * 5"; // (3 + 4)((-5) * 5)
     let expected = Program(vec![
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::Plus,
-            Box::new(Expression::Integer(4)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Prefix(
-                Prefix::Minus,
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::Plus,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Prefix(
+                    Prefix::Minus,
+                    Box::new(Expression::Integer(5)),
+                    Span::default(),
+                )),
+                Infix::Multiply,
                 Box::new(Expression::Integer(5)),
-            )),
-            Infix::Multiply,
-            Box::new(Expression::Integer(5)),
-        )),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_ten() {
     let input = "5 > 4 == 3 < 4"; // ((5 > 4) == (3 < 4))
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::GreaterThan,
-            Box::new(Expression::Integer(4)),
-        )),
-        Infix::Equal,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::LessThan,
-            Box::new(Expression::Integer(4)),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::GreaterThan,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            )),
+            Infix::Equal,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::LessThan,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_eleven() {
     let input = "5 < 4 != 3 > 4"; // ((5 < 4) != (3 > 4))
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::LessThan,
-            Box::new(Expression::Integer(4)),
-        )),
-        Infix::NotEqual,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::GreaterThan,
-            Box::new(Expression::Integer(4)),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::LessThan,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            )),
+            Infix::NotEqual,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::GreaterThan,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_twelve() {
     let input = "3 + 4 * 5 == 3 * 1 + 4 * 5"; // ((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::Plus,
-            Box::new(Expression::Infix(
-                Box::new(Expression::Integer(4)),
-                Infix::Multiply,
-                Box::new(Expression::Integer(5)),
-            )),
-        )),
-        Infix::Equal,
-        Box::new(Expression::Infix(
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
             Box::new(Expression::Infix(
                 Box::new(Expression::Integer(3)),
-                Infix::Multiply,
-                Box::new(Expression::Integer(1)),
+                Infix::Plus,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(4)),
+                    Infix::Multiply,
+                    Box::new(Expression::Integer(5)),
+                    Span::default(),
+                )),
+                Span::default(),
             )),
-            Infix::Plus,
+            Infix::Equal,
             Box::new(Expression::Infix(
-                Box::new(Expression::Integer(4)),
-                Infix::Multiply,
-                Box::new(Expression::Integer(5)),
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(3)),
+                    Infix::Multiply,
+                    Box::new(Expression::Integer(1)),
+                    Span::default(),
+                )),
+                Infix::Plus,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(4)),
+                    Infix::Multiply,
+                    Box::new(Expression::Integer(5)),
+                    Span::default(),
+                )),
+                Span::default(),
             )),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -480,7 +1111,7 @@ fn test_operator_precedence_twelve() {
 fn test_prefix_expression_parse_error_if_invalid_prefix() {
     let input = "+4;";
     let expected_errors = vec![ParsingError::InvalidPrefixOperator(Token::Plus)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -488,7 +1119,7 @@ fn test_prefix_expression_parse_error_if_invalid_prefix() {
 fn test_expression_parse_error_if_invalid_prefix_placement() {
     let input = "6!";
     let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -496,13 +1127,17 @@ fn test_expression_parse_error_if_invalid_prefix_placement() {
 fn test_adjacent_expressions_parse() {
     let input = "4!4;";
     let expected = Program(vec![
-        Statement::Expression(Expression::Integer(4)),
-        Statement::Expression(Expression::Prefix(
-            Prefix::Bang,
-            Box::new(Expression::Integer(4)),
-        )),
+        Statement::Expression(Expression::Integer(4), Span::default()),
+        Statement::Expression(
+            Expression::Prefix(
+                Prefix::Bang,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -513,7 +1148,7 @@ fn test_infix_expression_parse_error_if_no_rhs_expression() {
         ParsingError::UnexpectedSemicolon,
         ParsingError::UnexpectedEof,
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -524,115 +1159,257 @@ fn test_boolean_expression() {
         Statement::Let(
             Expression::Identifier(String::from("a")),
             Expression::Boolean(true),
+            Span::default(),
+        ),
+        Statement::Return(Expression::Boolean(false), Span::default()),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Boolean(true)),
+                Infix::Equal,
+                Box::new(Expression::Boolean(false)),
+                Span::default(),
+            ),
+            Span::default(),
         ),
-        Statement::Return(Expression::Boolean(false)),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Boolean(true)),
-            Infix::Equal,
-            Box::new(Expression::Boolean(false)),
-        )),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_operator_precedence_thirteen() {
     let input = "3 > 5 == false"; // ((3 > 5) == false)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::GreaterThan,
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::GreaterThan,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            )),
+            Infix::Equal,
+            Box::new(Expression::Boolean(false)),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_fourteen() {
+    let input = "1 | 2 ^ 3 & 4 << 5 + 6"; // (1 | (2 ^ (3 & (4 << (5 + 6)))))
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Integer(1)),
+            Infix::BitOr,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(2)),
+                Infix::BitXor,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(3)),
+                    Infix::BitAnd,
+                    Box::new(Expression::Infix(
+                        Box::new(Expression::Integer(4)),
+                        Infix::ShiftLeft,
+                        Box::new(Expression::Infix(
+                            Box::new(Expression::Integer(5)),
+                            Infix::Plus,
+                            Box::new(Expression::Integer(6)),
+                            Span::default(),
+                        )),
+                        Span::default(),
+                    )),
+                    Span::default(),
+                )),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_fifteen() {
+    let input = "-2 ** 3 ** 2 % 5"; // ((-(2 ** (3 ** 2))) % 5)
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Prefix(
+                Prefix::Minus,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(2)),
+                    Infix::Power,
+                    Box::new(Expression::Infix(
+                        Box::new(Expression::Integer(3)),
+                        Infix::Power,
+                        Box::new(Expression::Integer(2)),
+                        Span::default(),
+                    )),
+                    Span::default(),
+                )),
+                Span::default(),
+            )),
+            Infix::Modulo,
             Box::new(Expression::Integer(5)),
-        )),
-        Infix::Equal,
-        Box::new(Expression::Boolean(false)),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_operator_precedence_sixteen() {
+    let input = "1 <= 2 == 3 >= 4 & 5"; // ((1 <= 2) == (3 >= (4 & 5)))
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(1)),
+                Infix::LessEqual,
+                Box::new(Expression::Integer(2)),
+                Span::default(),
+            )),
+            Infix::Equal,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::GreaterEqual,
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(4)),
+                    Infix::BitAnd,
+                    Box::new(Expression::Integer(5)),
+                    Span::default(),
+                )),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_precedence_promotion_with_parentheses_one() {
     let input = "(5 + 5) * 2;";
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Plus,
-            Box::new(Expression::Integer(5)),
-        )),
-        Infix::Multiply,
-        Box::new(Expression::Integer(2)),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Plus,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            )),
+            Infix::Multiply,
+            Box::new(Expression::Integer(2)),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_precedence_promotion_with_parentheses_two() {
     let input = "-(5 + 5)";
-    let expected = Program(vec![Statement::Expression(Expression::Prefix(
-        Prefix::Minus,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(5)),
-            Infix::Plus,
-            Box::new(Expression::Integer(5)),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Prefix(
+            Prefix::Minus,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(5)),
+                Infix::Plus,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_precedence_promotion_with_parentheses_three() {
     let input = "!(true == false)";
-    let expected = Program(vec![Statement::Expression(Expression::Prefix(
-        Prefix::Bang,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Boolean(true)),
-            Infix::Equal,
-            Box::new(Expression::Boolean(false)),
-        )),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Prefix(
+            Prefix::Bang,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Boolean(true)),
+                Infix::Equal,
+                Box::new(Expression::Boolean(false)),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_if_expression() {
     let input = "if (x < y) { x }";
-    let expected = Program(vec![Statement::Expression(Expression::If(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("x"))),
-            Infix::LessThan,
-            Box::new(Expression::Identifier(String::from("y"))),
-        )),
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
-            Expression::Identifier(String::from("x")),
-        )])),
-        None,
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::If(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("x"))),
+                Infix::LessThan,
+                Box::new(Expression::Identifier(String::from("y"))),
+                Span::default(),
+            )),
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Identifier(String::from("x")),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            None,
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_if_expression_with_else() {
     let input = "if (x < y) { x } else { y }";
-    let expected = Program(vec![Statement::Expression(Expression::If(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("x"))),
-            Infix::LessThan,
-            Box::new(Expression::Identifier(String::from("y"))),
-        )),
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
-            Expression::Identifier(String::from("x")),
-        )])),
-        Some(Box::new(Statement::BlockStatement(vec![
-            Statement::Expression(Expression::Identifier(String::from("y"))),
-        ]))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::If(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("x"))),
+                Infix::LessThan,
+                Box::new(Expression::Identifier(String::from("y"))),
+                Span::default(),
+            )),
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Identifier(String::from("x")),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            Some(Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Identifier(String::from("y")),
+                    Span::default(),
+                )],
+                Span::default(),
+            ))),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -640,42 +1417,89 @@ fn test_if_expression_with_else() {
 fn test_if_expression_error_if_missing_brace() {
     let input = "if (x < y) { x  else { y }";
     let expected_errors = vec![ParsingError::InvalidPrefixOperator(Token::Else)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_function_literal() {
     let input = "fn(x, y) { x + y; };";
-    let expected = Program(vec![Statement::Expression(Expression::Function(
-        vec![
-            Expression::Identifier(String::from("x")),
-            Expression::Identifier(String::from("y")),
-        ],
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
-            Expression::Infix(
-                Box::new(Expression::Identifier(String::from("x"))),
-                Infix::Plus,
-                Box::new(Expression::Identifier(String::from("y"))),
-            ),
-        )])),
-        String::new(),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Function(
+            vec![
+                Expression::Identifier(String::from("x")),
+                Expression::Identifier(String::from("y")),
+            ],
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Infix(
+                        Box::new(Expression::Identifier(String::from("x"))),
+                        Infix::Plus,
+                        Box::new(Expression::Identifier(String::from("y"))),
+                        Span::default(),
+                    ),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            String::new(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_macro_literal() {
+    let input = "let unless = macro(x, ...ys) { quote(unquote(x)) };";
+    let expected = Program(vec![Statement::Let(
+        Expression::Identifier(String::from("unless")),
+        Expression::Macro(
+            vec![
+                Expression::Identifier(String::from("x")),
+                Expression::Rest(String::from("ys")),
+            ],
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Call(
+                        Box::new(Expression::Identifier(String::from("quote"))),
+                        vec![Expression::Call(
+                            Box::new(Expression::Identifier(String::from("unquote"))),
+                            vec![Expression::Identifier(String::from("x"))],
+                            Span::default(),
+                        )],
+                        Span::default(),
+                    ),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_function_literal_no_parameters() {
     let input = "fn() { 1 };";
-    let expected = Program(vec![Statement::Expression(Expression::Function(
-        vec![],
-        Box::new(Statement::BlockStatement(vec![Statement::Expression(
-            Expression::Integer(1),
-        )])),
-        String::new(),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Function(
+            vec![],
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Expression(
+                    Expression::Integer(1),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+            String::new(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -683,7 +1507,7 @@ fn test_function_literal_no_parameters() {
 fn test_function_literal_error_if_missing_brace() {
     let input = "fn(x, y) { x + y; ";
     let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -691,88 +1515,123 @@ fn test_function_literal_error_if_missing_brace() {
 fn test_function_literal_error_if_misplaced_comma() {
     let input = "fn(x, y,) { x + y }";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Rparen)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_block_statement_with_multiple_statements() {
     let input = "if (x < y) { x; x + y; 5 }";
-    let expected = Program(vec![Statement::Expression(Expression::If(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("x"))),
-            Infix::LessThan,
-            Box::new(Expression::Identifier(String::from("y"))),
-        )),
-        Box::new(Statement::BlockStatement(vec![
-            Statement::Expression(Expression::Identifier(String::from("x"))),
-            Statement::Expression(Expression::Infix(
+    let expected = Program(vec![Statement::Expression(
+        Expression::If(
+            Box::new(Expression::Infix(
                 Box::new(Expression::Identifier(String::from("x"))),
-                Infix::Plus,
+                Infix::LessThan,
                 Box::new(Expression::Identifier(String::from("y"))),
+                Span::default(),
+            )),
+            Box::new(Statement::BlockStatement(
+                vec![
+                    Statement::Expression(
+                        Expression::Identifier(String::from("x")),
+                        Span::default(),
+                    ),
+                    Statement::Expression(
+                        Expression::Infix(
+                            Box::new(Expression::Identifier(String::from("x"))),
+                            Infix::Plus,
+                            Box::new(Expression::Identifier(String::from("y"))),
+                            Span::default(),
+                        ),
+                        Span::default(),
+                    ),
+                    Statement::Expression(Expression::Integer(5), Span::default()),
+                ],
+                Span::default(),
             )),
-            Statement::Expression(Expression::Integer(5)),
-        ])),
-        None,
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            None,
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_call_expression_basic() {
     let input = "add(2, 3);";
-    let expected = Program(vec![Statement::Expression(Expression::Call(
-        Box::new(Expression::Identifier(String::from("add"))),
-        vec![Expression::Integer(2), Expression::Integer(3)],
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Call(
+            Box::new(Expression::Identifier(String::from("add"))),
+            vec![Expression::Integer(2), Expression::Integer(3)],
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_call_expression_no_arguments() {
     let input = "add();";
-    let expected = Program(vec![Statement::Expression(Expression::Call(
-        Box::new(Expression::Identifier(String::from("add"))),
-        vec![],
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Call(
+            Box::new(Expression::Identifier(String::from("add"))),
+            vec![],
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_call_expression_one_arguments() {
     let input = "add(1);";
-    let expected = Program(vec![Statement::Expression(Expression::Call(
-        Box::new(Expression::Identifier(String::from("add"))),
-        vec![Expression::Integer(1)],
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Call(
+            Box::new(Expression::Identifier(String::from("add"))),
+            vec![Expression::Integer(1)],
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_call_expression_inlined_function() {
     let input = "fn(x, y) { x + y }(2, 3);";
-    let expected = Program(vec![Statement::Expression(Expression::Call(
-        Box::new(Expression::Function(
-            vec![
-                Expression::Identifier(String::from("x")),
-                Expression::Identifier(String::from("y")),
-            ],
-            Box::new(Statement::BlockStatement(vec![Statement::Expression(
-                Expression::Infix(
-                    Box::new(Expression::Identifier(String::from("x"))),
-                    Infix::Plus,
-                    Box::new(Expression::Identifier(String::from("y"))),
-                ),
-            )])),
-            String::new(),
-        )),
-        vec![Expression::Integer(2), Expression::Integer(3)],
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Call(
+            Box::new(Expression::Function(
+                vec![
+                    Expression::Identifier(String::from("x")),
+                    Expression::Identifier(String::from("y")),
+                ],
+                Box::new(Statement::BlockStatement(
+                    vec![Statement::Expression(
+                        Expression::Infix(
+                            Box::new(Expression::Identifier(String::from("x"))),
+                            Infix::Plus,
+                            Box::new(Expression::Identifier(String::from("y"))),
+                            Span::default(),
+                        ),
+                        Span::default(),
+                    )],
+                    Span::default(),
+                )),
+                String::new(),
+            )),
+            vec![Expression::Integer(2), Expression::Integer(3)],
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -780,7 +1639,7 @@ fn test_call_expression_inlined_function() {
 fn test_call_expression_error_if_missing_paren() {
     let input = "add(2, 3";
     let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -788,46 +1647,55 @@ fn test_call_expression_error_if_missing_paren() {
 fn test_call_expression_error_if_extra_comma() {
     let input = "add(2, 3,)";
     let expected_errors = vec![ParsingError::InvalidPrefixOperator(Token::Rparen)];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_string_expression() {
     let input = "\"hello world\"";
-    let expected = Program(vec![Statement::Expression(Expression::String(
-        String::from("hello world"),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::String(String::from("hello world")),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_array_expression() {
     let input = "[1, 2, 3 * 4, 1 + 1]";
-    let expected = Program(vec![Statement::Expression(Expression::Array(vec![
-        Expression::Integer(1),
-        Expression::Integer(2),
-        Expression::Infix(
-            Box::new(Expression::Integer(3)),
-            Infix::Multiply,
-            Box::new(Expression::Integer(4)),
-        ),
-        Expression::Infix(
-            Box::new(Expression::Integer(1)),
-            Infix::Plus,
-            Box::new(Expression::Integer(1)),
-        ),
-    ]))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Array(vec![
+            Expression::Integer(1),
+            Expression::Integer(2),
+            Expression::Infix(
+                Box::new(Expression::Integer(3)),
+                Infix::Multiply,
+                Box::new(Expression::Integer(4)),
+                Span::default(),
+            ),
+            Expression::Infix(
+                Box::new(Expression::Integer(1)),
+                Infix::Plus,
+                Box::new(Expression::Integer(1)),
+                Span::default(),
+            ),
+        ]),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_array_expression_empty() {
     let input = "[]";
-    let expected = Program(vec![Statement::Expression(Expression::Array(vec![]))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Array(vec![]),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -838,7 +1706,7 @@ fn test_array_expression_errors() {
         ParsingError::InvalidPrefixOperator(Token::Rbracket),
         ParsingError::UnexpectedEof,
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -846,46 +1714,62 @@ fn test_array_expression_errors() {
 fn test_array_index_operator_expression() {
     let input = "myArray[1 + 1]; [1, 2][0];";
     let expected = Program(vec![
-        Statement::Expression(Expression::Index(
-            Box::new(Expression::Identifier(String::from("myArray"))),
-            Box::new(Expression::Infix(
-                Box::new(Expression::Integer(1)),
-                Infix::Plus,
-                Box::new(Expression::Integer(1)),
-            )),
-        )),
-        Statement::Expression(Expression::Index(
-            Box::new(Expression::Array(vec![
-                Expression::Integer(1),
-                Expression::Integer(2),
-            ])),
-            Box::new(Expression::Integer(0)),
-        )),
+        Statement::Expression(
+            Expression::Index(
+                Box::new(Expression::Identifier(String::from("myArray"))),
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Integer(1)),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(1)),
+                    Span::default(),
+                )),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Index(
+                Box::new(Expression::Array(vec![
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                ])),
+                Box::new(Expression::Integer(0)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
     ]);
-    let program = Parser::parse_program(input).ok().unwrap();
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
 #[test]
 fn test_array_index_operator_precedence() {
     let input = "a * myArray[1 + 1] + b"; // ((a * (myArray[(1 + 1)])) + b)
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
-            Infix::Multiply,
-            Box::new(Expression::Index(
-                Box::new(Expression::Identifier(String::from("myArray"))),
-                Box::new(Expression::Infix(
-                    Box::new(Expression::Integer(1)),
-                    Infix::Plus,
-                    Box::new(Expression::Integer(1)),
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::Multiply,
+                Box::new(Expression::Index(
+                    Box::new(Expression::Identifier(String::from("myArray"))),
+                    Box::new(Expression::Infix(
+                        Box::new(Expression::Integer(1)),
+                        Infix::Plus,
+                        Box::new(Expression::Integer(1)),
+                        Span::default(),
+                    )),
+                    Span::default(),
                 )),
+                Span::default(),
             )),
-        )),
-        Infix::Plus,
-        Box::new(Expression::Identifier(String::from("b"))),
-    ))]);
-    let program = Parser::parse_program(input).ok().unwrap();
+            Infix::Plus,
+            Box::new(Expression::Identifier(String::from("b"))),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let program = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(program, expected);
 }
 
@@ -896,88 +1780,105 @@ fn test_index_expression_errors() {
         ParsingError::UnexpectedSemicolon,
         ParsingError::UnexpectedToken(Token::Comma),
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_hash_literal() {
     let input = "{\"one\": 1, \"two\": 2, \"three\": 3}";
-    let expected = Program(vec![Statement::Expression(Expression::Hash(vec![
-        (
-            Expression::String(String::from("one")),
-            Expression::Integer(1),
-        ),
-        (
-            Expression::String(String::from("two")),
-            Expression::Integer(2),
-        ),
-        (
-            Expression::String(String::from("three")),
-            Expression::Integer(3),
-        ),
-    ]))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Hash(vec![
+            (
+                Expression::String(String::from("one")),
+                Expression::Integer(1),
+            ),
+            (
+                Expression::String(String::from("two")),
+                Expression::Integer(2),
+            ),
+            (
+                Expression::String(String::from("three")),
+                Expression::Integer(3),
+            ),
+        ]),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
 #[test]
 fn test_hash_literal_empty() {
     let input = "{}";
-    let expected = Program(vec![Statement::Expression(Expression::Hash(vec![]))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Hash(vec![]),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
 #[test]
 fn test_hash_literal_with_expressions() {
     let input = "{\"one\": 0 + 1, \"two\": 10 - 8, \"three\": 15 / 5}";
-    let expected = Program(vec![Statement::Expression(Expression::Hash(vec![
-        (
-            Expression::String(String::from("one")),
-            Expression::Infix(
-                Box::new(Expression::Integer(0)),
-                Infix::Plus,
-                Box::new(Expression::Integer(1)),
+    let expected = Program(vec![Statement::Expression(
+        Expression::Hash(vec![
+            (
+                Expression::String(String::from("one")),
+                Expression::Infix(
+                    Box::new(Expression::Integer(0)),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(1)),
+                    Span::default(),
+                ),
             ),
-        ),
-        (
-            Expression::String(String::from("two")),
-            Expression::Infix(
-                Box::new(Expression::Integer(10)),
-                Infix::Minus,
-                Box::new(Expression::Integer(8)),
+            (
+                Expression::String(String::from("two")),
+                Expression::Infix(
+                    Box::new(Expression::Integer(10)),
+                    Infix::Minus,
+                    Box::new(Expression::Integer(8)),
+                    Span::default(),
+                ),
             ),
-        ),
-        (
-            Expression::String(String::from("three")),
-            Expression::Infix(
-                Box::new(Expression::Integer(15)),
-                Infix::Divide,
-                Box::new(Expression::Integer(5)),
+            (
+                Expression::String(String::from("three")),
+                Expression::Infix(
+                    Box::new(Expression::Integer(15)),
+                    Infix::Divide,
+                    Box::new(Expression::Integer(5)),
+                    Span::default(),
+                ),
             ),
-        ),
-    ]))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+        ]),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
 #[test]
 fn test_hash_literal_with_infix_expression_keys() {
     let input = "{2 + 2: 15 / 5}";
-    let expected = Program(vec![Statement::Expression(Expression::Hash(vec![(
-        Expression::Infix(
-            Box::new(Expression::Integer(2)),
-            Infix::Plus,
-            Box::new(Expression::Integer(2)),
-        ),
-        Expression::Infix(
-            Box::new(Expression::Integer(15)),
-            Infix::Divide,
-            Box::new(Expression::Integer(5)),
-        ),
-    )]))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Hash(vec![(
+            Expression::Infix(
+                Box::new(Expression::Integer(2)),
+                Infix::Plus,
+                Box::new(Expression::Integer(2)),
+                Span::default(),
+            ),
+            Expression::Infix(
+                Box::new(Expression::Integer(15)),
+                Infix::Divide,
+                Box::new(Expression::Integer(5)),
+                Span::default(),
+            ),
+        )]),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
@@ -985,7 +1886,7 @@ fn test_hash_literal_with_infix_expression_keys() {
 fn test_hash_literal_error_if_colon_missing() {
     let input = "{ 1: 2, 3: 4, 5 6}";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Int(String::from("6")))];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -993,7 +1894,7 @@ fn test_hash_literal_error_if_colon_missing() {
 fn test_hash_literal_error_if_comma_missing() {
     let input = "{ 1: 2, 3: 4 5: 6}";
     let expected_errors = vec![ParsingError::UnexpectedToken(Token::Int(String::from("5")))];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -1001,7 +1902,7 @@ fn test_hash_literal_error_if_comma_missing() {
 fn test_hash_literal_error_if_closing_brace_missing() {
     let input = "{ 1: 2, 3: 4, 5: 6";
     let expected_errors = vec![ParsingError::UnexpectedEof];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
@@ -1009,18 +1910,26 @@ fn test_hash_literal_error_if_closing_brace_missing() {
 fn test_logical_infix_operators() {
     let input = "true || true; a && b";
     let expected = Program(vec![
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Boolean(true)),
-            Infix::Or,
-            Box::new(Expression::Boolean(true)),
-        )),
-        Statement::Expression(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("a"))),
-            Infix::And,
-            Box::new(Expression::Identifier(String::from("b"))),
-        )),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Boolean(true)),
+                Infix::Or,
+                Box::new(Expression::Boolean(true)),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Infix(
+                Box::new(Expression::Identifier(String::from("a"))),
+                Infix::And,
+                Box::new(Expression::Identifier(String::from("b"))),
+                Span::default(),
+            ),
+            Span::default(),
+        ),
     ]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
@@ -1031,49 +1940,257 @@ fn test_logical_infix_operator_error_if_missing_operand() {
         ParsingError::InvalidPrefixOperator(Token::And),
         ParsingError::UnexpectedSemicolon,
     ];
-    let errors = Parser::parse_program(input).err().unwrap();
+    let errors = parse_errors(input);
     assert_eq!(errors, expected_errors);
 }
 
 #[test]
 fn test_logical_infix_operator_precedence() {
     let input = "1 == 0 || 2 < 9";
-    let expected = Program(vec![Statement::Expression(Expression::Infix(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(1)),
-            Infix::Equal,
-            Box::new(Expression::Integer(0)),
-        )),
-        Infix::Or,
-        Box::new(Expression::Infix(
-            Box::new(Expression::Integer(2)),
-            Infix::LessThan,
-            Box::new(Expression::Integer(9)),
-        )),
-    ))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let expected = Program(vec![Statement::Expression(
+        Expression::Infix(
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(1)),
+                Infix::Equal,
+                Box::new(Expression::Integer(0)),
+                Span::default(),
+            )),
+            Infix::Or,
+            Box::new(Expression::Infix(
+                Box::new(Expression::Integer(2)),
+                Infix::LessThan,
+                Box::new(Expression::Integer(9)),
+                Span::default(),
+            )),
+            Span::default(),
+        ),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
 #[test]
 fn test_while_expression_parses() {
     let input = "while (i < 10) { let i = i + 1; }";
-    let expected = Program(vec![Statement::Expression(Expression::While(
-        Box::new(Expression::Infix(
-            Box::new(Expression::Identifier(String::from("i"))),
-            Infix::LessThan,
-            Box::new(Expression::Integer(10)),
-        )),
-        Box::new(Statement::BlockStatement(vec![Statement::Let(
-            Expression::Identifier(String::from("i")),
-            Expression::Infix(
+    let expected = Program(vec![Statement::Expression(
+        Expression::While(
+            Box::new(Expression::Infix(
                 Box::new(Expression::Identifier(String::from("i"))),
-                Infix::Plus,
-                Box::new(Expression::Integer(1)),
+                Infix::LessThan,
+                Box::new(Expression::Integer(10)),
+                Span::default(),
+            )),
+            Box::new(Statement::BlockStatement(
+                vec![Statement::Let(
+                    Expression::Identifier(String::from("i")),
+                    Expression::Infix(
+                        Box::new(Expression::Identifier(String::from("i"))),
+                        Infix::Plus,
+                        Box::new(Expression::Integer(1)),
+                        Span::default(),
+                    ),
+                    Span::default(),
+                )],
+                Span::default(),
+            )),
+        ),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_for_expression_parses() {
+    let input = "for (x in xs) { x } for (k, v in h) { v }";
+    let expected = Program(vec![
+        Statement::Expression(
+            Expression::For(
+                vec![Expression::Identifier(String::from("x"))],
+                Box::new(Expression::Identifier(String::from("xs"))),
+                Box::new(Statement::BlockStatement(
+                    vec![Statement::Expression(
+                        Expression::Identifier(String::from("x")),
+                        Span::default(),
+                    )],
+                    Span::default(),
+                )),
+            ),
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::For(
+                vec![
+                    Expression::Identifier(String::from("k")),
+                    Expression::Identifier(String::from("v")),
+                ],
+                Box::new(Expression::Identifier(String::from("h"))),
+                Box::new(Statement::BlockStatement(
+                    vec![Statement::Expression(
+                        Expression::Identifier(String::from("v")),
+                        Span::default(),
+                    )],
+                    Span::default(),
+                )),
+            ),
+            Span::default(),
+        ),
+    ]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_for_expression_errors() {
+    let tests = vec![
+        (
+            "for x in xs { x }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("x"))),
+        ),
+        (
+            "for (1 in xs) { 1 }",
+            ParsingError::UnexpectedToken(Token::Int(String::from("1"))),
+        ),
+        (
+            "for (a, b, c in xs) { a }",
+            ParsingError::UnexpectedToken(Token::Comma),
+        ),
+        (
+            "for (x xs) { x }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("xs"))),
+        ),
+        (
+            "for (x in xs { x }",
+            ParsingError::UnexpectedToken(Token::Lbrace),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
+#[test]
+fn test_break_and_continue_parse() {
+    let input = "while (true) { continue; break; }";
+    let expected = Program(vec![Statement::Expression(
+        Expression::While(
+            Box::new(Expression::Boolean(true)),
+            Box::new(Statement::BlockStatement(
+                vec![
+                    Statement::Continue(Span::default()),
+                    Statement::Break(Span::default()),
+                ],
+                Span::default(),
+            )),
+        ),
+        Span::default(),
+    )]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_break_and_continue_errors() {
+    let tests = vec![
+        ("break;", ParsingError::OutsideLoop(Token::Break)),
+        (
+            "if (true) { continue; }",
+            ParsingError::OutsideLoop(Token::Continue),
+        ),
+        (
+            "while (true) { let f = fn() { break; }; }",
+            ParsingError::OutsideLoop(Token::Break),
+        ),
+        (
+            "for (x in xs) { break x; }",
+            ParsingError::UnexpectedToken(Token::Identifier(String::from("x"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input).first(), Some(&expected), "{input}");
+    }
+    // a loop inside a function inside a loop can be broken out of
+    assert!(Parser::parse_program("while (true) { fn() { for (x in xs) { break; } }; }").is_ok());
+}
+
+#[test]
+fn test_loop_control_in_expression_errors() {
+    let tests = vec![
+        (
+            "for (x in xs) { r = push(r, if (x == 1) { continue; } else { x }); }",
+            Token::Continue,
+        ),
+        (
+            "while (c) { s += 1 + if (c) { continue; } else { 0 }; }",
+            Token::Continue,
+        ),
+        (
+            "while (c) { let y = match (x) { 1 => { break; }, _ => x }; }",
+            Token::Break,
+        ),
+        ("while (c) { return if (c) { break; }; }", Token::Break),
+        (
+            "while (c) { [1, try { break; } finally { 2 }]; }",
+            Token::Break,
+        ),
+        (
+            "while (c) { fn(x = if (c) { break; }) { x }; }",
+            Token::Break,
+        ),
+        (
+            r#"while (c) { "${if (c) { continue; }}"; }"#,
+            Token::Continue,
+        ),
+    ];
+    for (input, token) in tests {
+        let expected = ParsingError::LoopControlInExpression(token);
+        assert_eq!(parse_errors(input).first(), Some(&expected), "{input}");
+    }
+
+    // the branches of an `if`, `match` or `try` whose value is not used by
+    // another expression, and loops whose values are, may use them
+    let valid = vec![
+        "while (c) { if (a) { break; } else { if (b) { continue; } } }",
+        "for (x in xs) { match (x) { 1 => { continue; }, _ => { try { break; } finally { f(); } } } }",
+        "let v = while (c) { if (a) { break; } };",
+        "for (x in xs) { f(while (c) { if (a) { break; } }); }",
+    ];
+    for input in valid {
+        assert!(Parser::parse_program(input).is_ok(), "{input}");
+    }
+}
+
+#[test]
+fn test_range_expression_precedence() {
+    let input = "0..n + 1; a == b..c";
+    let expected = Program(vec![
+        Statement::Expression(
+            Expression::Range(
+                Box::new(Expression::Integer(0)),
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Identifier(String::from("n"))),
+                    Infix::Plus,
+                    Box::new(Expression::Integer(1)),
+                    Span::default(),
+                )),
             ),
-        )])),
-    ))]);
-    let result = Parser::parse_program(input).ok().unwrap();
+            Span::default(),
+        ),
+        Statement::Expression(
+            Expression::Range(
+                Box::new(Expression::Infix(
+                    Box::new(Expression::Identifier(String::from("a"))),
+                    Infix::Equal,
+                    Box::new(Expression::Identifier(String::from("b"))),
+                    Span::default(),
+                )),
+                Box::new(Expression::Identifier(String::from("c"))),
+            ),
+            Span::default(),
+        ),
+    ]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
 
@@ -1086,8 +2203,202 @@ fn test_assignment_expression_parses() {
             Box::new(Expression::Identifier(String::from("i"))),
             Infix::Plus,
             Box::new(Expression::Integer(1)),
+            Span::default(),
         ),
+        Span::default(),
     )]);
-    let result = Parser::parse_program(input).ok().unwrap();
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_index_assignment_parses() {
+    let input = "a[0][\"k\"] = 1; x += 2; h[k] /= 3;";
+    let expected = Program(vec![
+        Statement::Assignment(
+            Expression::Index(
+                Box::new(Expression::Index(
+                    Box::new(Expression::Identifier(String::from("a"))),
+                    Box::new(Expression::Integer(0)),
+                    Span::default(),
+                )),
+                Box::new(Expression::String(String::from("k"))),
+                Span::default(),
+            ),
+            Expression::Integer(1),
+            Span::default(),
+        ),
+        Statement::CompoundAssignment(
+            Expression::Identifier(String::from("x")),
+            Infix::Plus,
+            Expression::Integer(2),
+            Span::default(),
+        ),
+        Statement::CompoundAssignment(
+            Expression::Index(
+                Box::new(Expression::Identifier(String::from("h"))),
+                Box::new(Expression::Identifier(String::from("k"))),
+                Span::default(),
+            ),
+            Infix::Divide,
+            Expression::Integer(3),
+            Span::default(),
+        ),
+    ]);
+    let result = Parser::parse_program(input).ok().unwrap().without_spans();
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_assignment_errors() {
+    let tests = vec![
+        ("5 = 1;", ParsingError::InvalidAssignmentTarget),
+        ("f(x)[0] += 1;", ParsingError::InvalidAssignmentTarget),
+        ("[1][0] = 2;", ParsingError::InvalidAssignmentTarget),
+        ("x -= ;", ParsingError::UnexpectedSemicolon),
+        (
+            "a[0] = 1 2;",
+            ParsingError::UnexpectedToken(Token::Int(String::from("2"))),
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(parse_errors(input), vec![expected], "{input}");
+    }
+}
+
+#[test]
+fn test_parse_error_positions() {
+    let input = "let x = 5;
+let = 5;
+let y = (1 + 2;";
+    let errors: Vec<_> = Parser::parse_program(input)
+        .err()
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.error, e.span.map(|span| span.start)))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                ParsingError::UnexpectedToken(Token::Assign),
+                Some(Position::new(2, 5))
+            ),
+            (
+                ParsingError::UnexpectedToken(Token::Semicolon),
+                Some(Position::new(3, 15))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_error_position_at_eof() {
+    let input = "let x = 5";
+    let error = Parser::parse_program(input).err().unwrap().remove(0);
+    assert_eq!(error.error, ParsingError::UnexpectedEof);
+    assert_eq!(
+        error.span.map(|span| span.start),
+        Some(Position::new(1, 10))
+    );
+}
+
+#[test]
+fn test_binding_error_positions() {
+    let tests = [
+        ("let f = fn(a, a) { a };", "a", (1, 15)),
+        ("match (x) { [a, a] => a }", "a", (1, 17)),
+        ("let {\"a\": [x], \"b\": x} = h;", "x", (1, 21)),
+        ("for (k, k in {}) { k }", "k", (1, 9)),
+    ];
+    for (input, name, (line, column)) in tests {
+        let error = Parser::parse_program(input).err().unwrap().remove(0);
+        assert_eq!(
+            (error.error, error.span.map(|span| span.start)),
+            (
+                ParsingError::DuplicateBinding(name.to_string()),
+                Some(Position::new(line, column))
+            ),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_unbound_parameter_positions() {
+    // the error points at the use of the later parameter in the default
+    let tests = [
+        ("fn(a = b, b = 1) { a }", "b", (1, 8)),
+        ("fn(x = \"${y}\", y = 2) { x }", "y", (1, 11)),
+        ("fn(a,\n  b = [1, c],\n  c = 2) { b }", "c", (2, 11)),
+    ];
+    for (input, name, (line, column)) in tests {
+        let error = Parser::parse_program(input).err().unwrap().remove(0);
+        assert_eq!(
+            (error.error, error.span.map(|span| span.start)),
+            (
+                ParsingError::UnboundParameter(name.to_string()),
+                Some(Position::new(line, column))
+            ),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_statement_spans() {
+    let input = "let x = 5;
+if (x) {
+    x + 1
+}";
+    let Program(statements) = Parser::parse_program(input).ok().unwrap();
+    assert_eq!(statements[0].span().start, Position::new(1, 1));
+    assert_eq!(statements[0].span().end, Position::new(1, 10));
+    assert_eq!(statements[1].span().start, Position::new(2, 1));
+    assert_eq!(statements[1].span().end, Position::new(4, 2));
+
+    let Statement::Expression(Expression::If(_, consequence, _), _) = &statements[1] else {
+        panic!("expected an if expression");
+    };
+    let Statement::BlockStatement(block, span) = consequence.as_ref() else {
+        panic!("expected a block statement");
+    };
+    assert_eq!(span.start, Position::new(2, 8));
+    assert_eq!(block[0].span().start, Position::new(3, 5));
+    assert_eq!(block[0].span().end, Position::new(3, 10));
+}
+
+#[test]
+fn test_expression_spans() {
+    let input = "let y = f(a)[0] +
+  -x;";
+    let Program(statements) = Parser::parse_program(input).ok().unwrap();
+    let Statement::Let(_, Expression::Infix(left, _, right, span), _) = &statements[0] else {
+        panic!("expected an infix expression");
+    };
+    assert_eq!(
+        (span.start, span.end),
+        (Position::new(1, 9), Position::new(2, 5))
+    );
+    let Expression::Index(call, _, span) = left.as_ref() else {
+        panic!("expected an index expression");
+    };
+    assert_eq!(
+        (span.start, span.end),
+        (Position::new(1, 9), Position::new(1, 16))
+    );
+    let Expression::Call(_, _, span) = call.as_ref() else {
+        panic!("expected a call expression");
+    };
+    assert_eq!(
+        (span.start, span.end),
+        (Position::new(1, 9), Position::new(1, 13))
+    );
+    let Expression::Prefix(_, _, span) = right.as_ref() else {
+        panic!("expected a prefix expression");
+    };
+    assert_eq!(
+        (span.start, span.end),
+        (Position::new(2, 3), Position::new(2, 5))
+    );
+}
@@ -1,23 +1,39 @@
+use crate::cli::Engine;
+use crate::diagnostics::Diagnostic;
+use crate::evaluator::environment::Environment;
+use crate::evaluator::eval;
+use crate::evaluator::macros::Macros;
+use crate::module::Loader;
+use crate::object::builtins::Builtins;
+use crate::parser::ast::{Program, Statement};
 use crate::parser::Parser;
+use crate::span::Sources;
 use crate::symtab::SymbolTable;
-use crate::vm::{VirtualMachine, GLOBAL_SIZE};
+use crate::vm::{VirtualMachine, VmError, GLOBAL_SIZE};
 use crate::{compiler::Compiler, object::Object};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 
 pub struct Repl;
 
 const PROMPT: &str = ">> ";
+const SOURCE_NAME: &str = "<repl>";
 
 impl Repl {
-    pub fn start() -> io::Result<()> {
+    /// Starts a session on `engine` with the builtins of `builtins`, looking
+    /// for imported modules in the current directory and then the directories
+    /// of `lib`.
+    pub fn start(engine: Engine, lib: Vec<PathBuf>, builtins: Rc<Builtins>) -> io::Result<()> {
         let reader = io::stdin();
         let mut writer = io::stdout();
 
-        let mut symtab = SymbolTable::new();
-        symtab.define_all_builtins();
-        let mut constants = vec![];
-        let mut globals = vec![Rc::new(Object::Null); GLOBAL_SIZE];
+        let mut macros = Macros::new_with_builtins(Rc::clone(&builtins));
+        // modules are loaded once per session, however many lines import them
+        let mut loader = Loader::new(SOURCE_NAME, "", lib, Rc::clone(&builtins));
+        let mut session = Session::new(engine, builtins);
 
         loop {
             writer.write_all(PROMPT.as_bytes())?;
@@ -31,39 +47,141 @@ impl Repl {
                 break;
             }
 
+            loader.sources.set_main(SOURCE_NAME, &buffer);
             let parsing_result = Parser::parse_program(buffer.as_str());
             let program = match parsing_result {
                 Ok(program) => program,
                 Err(errors) => {
-                    errors.iter().for_each(|e| println!("{e:?}"));
+                    errors
+                        .iter()
+                        .for_each(|e| print!("{}", Diagnostic::from(e).render_in(&loader.sources)));
                     continue;
                 }
             };
-
-            let mut compiler = Compiler::new_with_state(symtab, constants);
-
-            let byte_code = match compiler.compile(program) {
-                Ok(res) => res,
+            let program = match macros.expand(program) {
+                Ok(program) => program,
+                Err(e) => {
+                    print!("{}", Diagnostic::from(&e).render_in(&loader.sources));
+                    continue;
+                }
+            };
+            let program = match loader.load(program, None) {
+                Ok(program) => program,
                 Err(e) => {
-                    println!("{e:?}");
-                    symtab = compiler.symbol_table;
-                    constants = compiler.constants;
+                    print!("{}", e.render(&loader.sources));
                     continue;
                 }
             };
 
-            let mut vm = VirtualMachine::new_with_global_state(byte_code, globals);
+            session.run(program, &loader.sources);
+        }
 
-            match vm.run() {
-                Ok(obj) => println!("{obj}"),
-                Err(e) => println!("{e:?}"),
-            }
+        Ok(())
+    }
+}
+
+/// The state kept between lines, which depends on the engine.
+enum Session {
+    Eval(Rc<RefCell<Environment>>),
+    Vm {
+        symtab: SymbolTable,
+        constants: Vec<Rc<Object>>,
+        globals: Vec<Rc<Object>>,
+        /// The globals holding the exports of the modules imported so far.
+        modules: HashMap<usize, u32>,
+        builtins: Rc<Builtins>,
+    },
+}
 
-            symtab = compiler.symbol_table;
-            constants = compiler.constants;
-            globals = vm.globals;
+impl Session {
+    fn new(engine: Engine, builtins: Rc<Builtins>) -> Self {
+        match engine {
+            Engine::Eval => Session::Eval(Rc::new(RefCell::new(Environment::new_with_builtins(
+                builtins,
+            )))),
+            Engine::Vm => {
+                let mut symtab = SymbolTable::new();
+                symtab.define_builtins(&builtins);
+                let null = Rc::new(Object::Null);
+                Session::Vm {
+                    symtab,
+                    constants: vec![],
+                    globals: vec![null; GLOBAL_SIZE],
+                    modules: HashMap::new(),
+                    builtins,
+                }
+            }
         }
+    }
 
-        Ok(())
+    /// Runs `program` and prints its value or error, quoting `sources`.
+    fn run(&mut self, program: Program, sources: &Sources) {
+        match self {
+            Session::Eval(env) => {
+                // statements such as `let` have no value worth printing
+                let has_value = matches!(program.0.last(), Some(Statement::Expression(..)));
+                match eval(program, Rc::clone(env)) {
+                    Ok(obj) if has_value => println!("{obj}"),
+                    Ok(_) => {}
+                    Err(e) => print!("{}", Diagnostic::from(&e).render_in(sources)),
+                }
+            }
+            Session::Vm {
+                symtab,
+                constants,
+                globals,
+                modules,
+                builtins,
+            } => {
+                let mut compiler = Compiler::new_with_state(
+                    std::mem::replace(symtab, SymbolTable::new()),
+                    std::mem::take(constants),
+                    Rc::clone(builtins),
+                );
+                compiler.modules = modules.clone();
+
+                let result = compiler.compile(program);
+                compiler
+                    .warnings
+                    .iter()
+                    .for_each(|w| print!("{}", Diagnostic::from(w).render_in(sources)));
+                *symtab = compiler.symbol_table;
+                *constants = compiler.constants;
+
+                let byte_code = match result {
+                    Ok(res) => res,
+                    Err(e) => {
+                        print!("{}", Diagnostic::from(&e).render_in(sources));
+                        return;
+                    }
+                };
+
+                let mut vm = VirtualMachine::new_with_global_state(
+                    byte_code,
+                    std::mem::take(globals),
+                    Rc::clone(builtins),
+                );
+
+                let ran = match vm.run() {
+                    Ok(obj) => {
+                        println!("{obj}");
+                        true
+                    }
+                    // statements such as `let` leave nothing behind to print
+                    Err(e) if e.error == VmError::EmptyStack => true,
+                    Err(e) => {
+                        print!("{}", Diagnostic::from(&e).render_in(sources));
+                        false
+                    }
+                };
+
+                *globals = vm.globals;
+                // the modules the line imports may not have run, in which case
+                // they are compiled again when next imported
+                if ran {
+                    *modules = compiler.modules;
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+/// A 1-based line and column in the source text. They are kept in 32 bits, as
+/// every statement and error carries two of them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    pub fn new(line: u32, column: u32) -> Self {
+        Position { line, column }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new(1, 1)
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The region of source text a token or statement was read from. `end` is the
+/// position just past the last character, and `source` the number of the
+/// source in [`Sources`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+    pub source: usize,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span {
+            start,
+            end,
+            source: MAIN_SOURCE,
+        }
+    }
+
+    /// Returns this span as one of the source numbered `source`.
+    pub fn in_source(self, source: usize) -> Span {
+        Span { source, ..self }
+    }
+
+    /// Returns the span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end)).in_source(self.source)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
+
+/// The number of the source of the program being run; the modules it imports
+/// are numbered after it.
+pub const MAIN_SOURCE: usize = 0;
+
+/// The name and text of each source spans can refer to, by number.
+#[derive(Debug)]
+pub struct Sources(Vec<(String, String)>);
+
+impl Sources {
+    /// The sources of a program named `name` that imports no modules.
+    pub fn new(name: &str, text: &str) -> Self {
+        Sources(vec![(name.to_string(), text.to_string())])
+    }
+
+    /// Adds a source, returning its number.
+    pub fn add(&mut self, name: &str, text: &str) -> usize {
+        self.0.push((name.to_string(), text.to_string()));
+        self.0.len() - 1
+    }
+
+    /// Replaces the program being run, e.g. with the next line in a REPL.
+    pub fn set_main(&mut self, name: &str, text: &str) {
+        self.0[MAIN_SOURCE] = (name.to_string(), text.to_string());
+    }
+
+    /// The name and text of the source numbered `source`.
+    pub fn get(&self, source: usize) -> (&str, &str) {
+        let (name, text) = &self.0[source];
+        (name, text)
+    }
+}
+
+/// An error together with the span of source it was raised at, if known.
+#[derive(Debug, PartialEq)]
+pub struct Located<E> {
+    pub error: E,
+    pub span: Option<Span>,
+}
+
+impl<E> Located<E> {
+    pub fn new(error: E, span: Span) -> Self {
+        Located {
+            error,
+            span: Some(span),
+        }
+    }
+
+    /// Attaches `span` unless a (more precise) span is already known.
+    pub fn or_at(mut self, span: Span) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span);
+        }
+        self
+    }
+}
+
+impl<E> From<E> for Located<E> {
+    fn from(error: E) -> Self {
+        Located { error, span: None }
+    }
+}
+
+impl<E: Display> Display for Located<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{}: {}", span, self.error),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::object::builtins::Builtins;
 use std::{collections::HashMap, rc::Rc};
 
 mod tests;
@@ -13,7 +14,7 @@ pub enum SymbolScope {
 
 #[derive(Debug, PartialEq)]
 pub struct Symbol {
-    name: String,
+    pub name: String,
     pub scope: SymbolScope,
     pub index: u32,
 }
@@ -58,13 +59,11 @@ impl SymbolTable {
         symbol
     }
 
-    pub fn define_all_builtins(&mut self) {
-        self.define_builtin(0, "len".to_string());
-        self.define_builtin(1, "first".to_string());
-        self.define_builtin(2, "last".to_string());
-        self.define_builtin(3, "rest".to_string());
-        self.define_builtin(4, "push".to_string());
-        self.define_builtin(5, "puts".to_string());
+    /// Defines each builtin and namespace of `builtins` at its index.
+    pub fn define_builtins(&mut self, builtins: &Builtins) {
+        for (idx, name) in builtins.names().enumerate() {
+            self.define_builtin(idx as u32, name.to_string());
+        }
     }
 
     pub fn define_function_name(&mut self, name: String) -> Rc<Symbol> {
@@ -74,6 +73,13 @@ impl SymbolTable {
     }
 
     pub fn define(&mut self, name: String) -> Rc<Symbol> {
+        // redefining a name in the same scope reuses its slot, so that a `let`
+        // inside a loop body rebinds the variable the loop condition reads
+        if let Some(symbol) = self.store.get(&name) {
+            if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) {
+                return Rc::clone(symbol);
+            }
+        }
         let scope = match &self.outer {
             Some(_) => SymbolScope::Local,
             None => SymbolScope::Global,
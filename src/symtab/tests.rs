@@ -227,3 +227,19 @@ fn test_shadowing_function_name() {
     let a = global.resolve("a".to_string()).unwrap();
     assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Global, 0)));
 }
+
+#[test]
+fn test_redefine_reuses_index() {
+    let mut global = SymbolTable::new();
+    global.define("a".to_string());
+    global.define("b".to_string());
+    let a = global.define("a".to_string());
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Global, 0)));
+    assert_eq!(global.num_definitions, 2);
+
+    let mut local = SymbolTable::new_enclosed(global.clone());
+    local.define("a".to_string());
+    let a = local.define("a".to_string());
+    assert_eq!(a, Rc::new(Symbol::new("a", SymbolScope::Local, 0)));
+    assert_eq!(local.num_definitions, 1);
+}
@@ -7,11 +7,18 @@ pub struct Frame {
     pub closure: Closure,
     pub ip: usize,
     pub bp: usize,
+    /// The number of arguments the function was called with.
+    pub num_args: usize,
 }
 
 impl Frame {
-    pub fn new(closure: Closure, bp: usize) -> Self {
-        Frame { closure, ip: 0, bp }
+    pub fn new(closure: Closure, bp: usize, num_args: usize) -> Self {
+        Frame {
+            closure,
+            ip: 0,
+            bp,
+            num_args,
+        }
     }
 
     pub fn instructions(&self) -> &Rc<Instructions> {
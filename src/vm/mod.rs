@@ -1,9 +1,12 @@
 use self::frame::Frame;
 use crate::code::{read_u16, OpCode, WORD_SIZE};
 use crate::compiler::ByteCode;
-use crate::object::builtins::{Builtin, BuiltinError};
-use crate::object::{Closure, CompiledFunction, Hashable, Object};
-use std::collections::HashMap;
+use crate::diagnostics::{describe_operand, describe_operands, describe_thrown};
+use crate::object::builtins::{BuiltinError, Builtins};
+use crate::object::{Closure, CompiledFunction, ErrorObject, ForIterator, Hashable, Object};
+use crate::span::Span;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -25,31 +28,174 @@ pub struct VirtualMachine {
     pub globals: Vec<Rc<Object>>,
     frames: Vec<Frame>,
     frames_idx: usize,
+    // the `try` blocks being run, innermost last
+    handlers: Vec<Handler>,
+    // the errors being handled by `finally` blocks, innermost last
+    raised: Vec<Raised>,
+    builtins: Rc<Builtins>,
+}
+
+/// Where an error raised in a `try` block is handled: the frame running the
+/// block, the height of the stack when the block was entered, the address
+/// of the handler in the frame's function, and whether the handler runs a
+/// `finally` block and then raises the error again.
+#[derive(Debug, PartialEq)]
+struct Handler {
+    frame_idx: usize,
+    sp: usize,
+    ip: usize,
+    rethrows: bool,
+}
+
+/// An error a handler that raises it again was jumped to with, where it was
+/// handled and where it was first raised.
+#[derive(Debug, PartialEq)]
+struct Raised {
+    frame_idx: usize,
+    sp: usize,
+    object: Rc<Object>,
+    span: Option<Span>,
+    trace: Vec<TraceFrame>,
 }
 
 impl VirtualMachine {
     pub fn new(bytecode: ByteCode) -> Self {
-        let ByteCode(instructions, constants) = bytecode;
-        let main_fn = CompiledFunction::new(instructions, 0, 0);
+        VirtualMachine::new_with_builtins(bytecode, Rc::new(Builtins::new()))
+    }
+
+    /// A VM running `bytecode`, compiled for the builtins of `builtins`.
+    pub fn new_with_builtins(bytecode: ByteCode, builtins: Rc<Builtins>) -> Self {
+        let ByteCode(instructions, constants, lines) = bytecode;
+        let main_fn = CompiledFunction::new(instructions, 0, 0).with_lines(lines);
         let main_closure = Closure::new(main_fn, vec![]);
-        let main_frame = Frame::new(main_closure, 0);
+        let main_frame = Frame::new(main_closure, 0, 0);
+        let null = Rc::new(Object::Null);
         VirtualMachine {
             constants,
             stack: Vec::with_capacity(STACK_SIZE),
-            globals: vec![Rc::new(Object::Null); GLOBAL_SIZE],
+            globals: vec![null; GLOBAL_SIZE],
             frames: vec![main_frame],
             frames_idx: 0,
+            handlers: vec![],
+            raised: vec![],
+            builtins,
         }
     }
 
-    pub fn new_with_global_state(bytecode: ByteCode, globals: Vec<Rc<Object>>) -> VirtualMachine {
-        let mut vm = VirtualMachine::new(bytecode);
+    pub fn new_with_global_state(
+        bytecode: ByteCode,
+        globals: Vec<Rc<Object>>,
+        builtins: Rc<Builtins>,
+    ) -> VirtualMachine {
+        let mut vm = VirtualMachine::new_with_builtins(bytecode, builtins);
         vm.globals = globals;
         vm
     }
 
-    pub fn run(&mut self) -> Result<Rc<Object>, VmError> {
+    pub fn run(&mut self) -> Result<Rc<Object>, RuntimeError> {
+        self.execute()
+    }
+
+    /// Where `error` was raised: at the instruction being executed, or, for
+    /// an error OpRethrow raises again after a `finally` block, where it was
+    /// first raised.
+    fn locate(&mut self, error: VmError) -> RuntimeError {
+        if let VmError::Thrown(object) = &error {
+            let frame = &self.frames[self.frames_idx];
+            if frame.instructions()[frame.ip] == u8::from(OpCode::Rethrow) {
+                let handled_at = (self.frames_idx, self.stack.len());
+                let found = self.raised.iter().rposition(|raised| {
+                    (raised.frame_idx, raised.sp) == handled_at
+                        && Rc::ptr_eq(&raised.object, object)
+                });
+                if let Some(idx) = found {
+                    let raised = self.raised.remove(idx);
+                    return RuntimeError {
+                        error,
+                        span: raised.span,
+                        trace: raised.trace,
+                    };
+                }
+            }
+        }
+        let trace = self.stack_trace();
+        RuntimeError {
+            error,
+            span: trace.first().and_then(|frame| frame.span),
+            trace,
+        }
+    }
+
+    /// The active call frames, innermost first.
+    fn stack_trace(&self) -> Vec<TraceFrame> {
+        self.frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(idx, frame)| {
+                let function = &frame.closure.function;
+                TraceFrame {
+                    function: match function.name.as_str() {
+                        _ if idx == 0 => "<main>".to_string(),
+                        "" => "<anonymous>".to_string(),
+                        name => name.to_string(),
+                    },
+                    offset: frame.ip,
+                    span: function.lines.lookup(frame.ip),
+                }
+            })
+            .collect()
+    }
+
+    fn execute(&mut self) -> Result<Rc<Object>, RuntimeError> {
         let mut last_popped = None;
+        loop {
+            match self.execute_until_error(&mut last_popped) {
+                Ok(object) => return Ok(object),
+                Err(error) => {
+                    let error = self.locate(error);
+                    self.unwind(error)?;
+                }
+            }
+        }
+    }
+
+    /// Unwinds the frames and the stack to the innermost `try` block being
+    /// run, and jumps to its handler with the error's value on top of the
+    /// stack. Errors outside any `try` block, and errors in the VM itself, are
+    /// returned instead.
+    fn unwind(&mut self, error: RuntimeError) -> Result<(), RuntimeError> {
+        let Some(object) = error.error.to_object() else {
+            return Err(error);
+        };
+        let Some(handler) = self.handlers.pop() else {
+            return Err(error);
+        };
+        // the `finally` blocks left behind, as by a `return`, will not raise
+        // their errors again
+        self.raised
+            .retain(|raised| (raised.frame_idx, raised.sp) < (handler.frame_idx, handler.sp));
+        if handler.rethrows {
+            self.raised.push(Raised {
+                frame_idx: handler.frame_idx,
+                sp: handler.sp,
+                object: Rc::clone(&object),
+                span: error.span,
+                trace: error.trace,
+            });
+        }
+        self.frames.truncate(handler.frame_idx + 1);
+        self.frames_idx = handler.frame_idx;
+        self.stack.truncate(handler.sp);
+        self.push(&object).map_err(|error| self.locate(error))?;
+        self.frames[self.frames_idx].ip = handler.ip;
+        Ok(())
+    }
+
+    fn execute_until_error(
+        &mut self,
+        last_popped: &mut Option<Rc<Object>>,
+    ) -> Result<Rc<Object>, VmError> {
         let mut ip: usize;
 
         while self.frames[self.frames_idx].ip < self.frames[self.frames_idx].instructions().len() {
@@ -75,8 +221,16 @@ impl VirtualMachine {
                 | OpCode::Equal
                 | OpCode::NotEqual
                 | OpCode::GreaterThan
-                | OpCode::And
-                | OpCode::Or => {
+                | OpCode::LessThan
+                | OpCode::GreaterEqual
+                | OpCode::LessEqual
+                | OpCode::Modulo
+                | OpCode::Power
+                | OpCode::BitAnd
+                | OpCode::BitOr
+                | OpCode::BitXor
+                | OpCode::ShiftLeft
+                | OpCode::ShiftRight => {
                     self.execute_binary_expression(op)?;
                 }
                 OpCode::True => {
@@ -91,8 +245,12 @@ impl VirtualMachine {
                 OpCode::Bang => {
                     self.execute_bang_expression()?;
                 }
+                OpCode::Stringify => {
+                    let object = self.pop()?;
+                    self.push(&Rc::new(Object::String(object.to_string())))?;
+                }
                 OpCode::Pop => {
-                    last_popped = Some(self.pop()?);
+                    *last_popped = Some(self.pop()?);
                 }
                 OpCode::Jump => {
                     let pos = read_u16(&word[1..=2]) as usize;
@@ -102,7 +260,7 @@ impl VirtualMachine {
                 OpCode::JumpNotTruthy => {
                     let pos = read_u16(&word[1..=2]) as usize;
                     let condition = self.pop()?;
-                    if !VirtualMachine::is_truthy(&*condition) {
+                    if !VirtualMachine::is_truthy(&condition) {
                         self.frames[self.frames_idx].ip = pos;
                         continue;
                     }
@@ -131,29 +289,29 @@ impl VirtualMachine {
                 OpCode::Index => {
                     self.execute_index_expression()?;
                 }
-                OpCode::Call => {
+                OpCode::Call | OpCode::TailCall => {
                     let num_args = word[1] as usize;
                     match &*self.stack[self.stack.len() - 1 - num_args] {
                         Object::Closure(closure) => {
-                            let num_locals = closure.function.num_locals;
-                            if closure.function.num_params != num_args as u32 {
-                                return Err(VmError::WrongArguments);
-                            }
-                            let frame =
-                                Frame::new(closure.deref().clone(), self.stack.len() - num_args);
-                            self.push_frame(frame)?;
-                            for _ in 0..(num_locals - (num_args as u32)) {
-                                self.push(&Rc::new(NULL))?;
+                            let closure = closure.deref().clone();
+                            if op == OpCode::TailCall {
+                                check_arguments(&closure.function, num_args)?;
+                                self.leave_frame_for_tail_call(num_args)?;
                             }
+                            self.call_closure(closure, num_args)?;
                             continue; // don't want to increment ip
                         }
+                        // a builtin's value is returned by the `ReturnValue`
+                        // after a tail call
                         Object::Builtin(builtin) => {
                             let args = &self.stack[self.stack.len() - num_args..];
                             let result = builtin.apply(args).map_err(|e| match e {
                                 BuiltinError::IncompatibleTypes => VmError::IncompatibleTypes,
                                 BuiltinError::IncorrectNumberOfArgs => VmError::WrongArguments,
+                                BuiltinError::Thrown(object) => VmError::Thrown(object),
                             })?;
-                            for _ in 0..num_args {
+                            // pop the arguments and the builtin itself
+                            for _ in 0..=num_args {
                                 self.pop()?;
                             }
                             self.push(&result)?;
@@ -165,6 +323,10 @@ impl VirtualMachine {
                 }
                 OpCode::ReturnValue => {
                     let return_val = self.pop()?;
+                    if self.frames_idx == 0 {
+                        // returning from the main frame ends the program
+                        return Ok(return_val);
+                    }
                     let frame = self.pop_frame()?;
                     // pop local bindings off stack
                     while self.stack.len() >= frame.bp {
@@ -183,20 +345,31 @@ impl VirtualMachine {
                 OpCode::SetLocal => {
                     let local_idx = word[1] as usize;
                     self.frames[self.frames_idx].ip += WORD_SIZE;
-                    self.stack[self.frames[self.frames_idx].bp + local_idx] = self.pop()?;
+                    let slot = self.frames[self.frames_idx].bp + local_idx;
+                    let value = self.pop()?;
+                    // locals captured by a closure live in an upvalue cell
+                    if let Object::Upvalue(cell) = &*self.stack[slot] {
+                        *cell.borrow_mut() = value;
+                    } else {
+                        self.stack[slot] = value;
+                    }
                     continue;
                 }
                 OpCode::GetLocal => {
                     let local_idx = word[1] as usize;
                     self.frames[self.frames_idx].ip += WORD_SIZE;
-                    let obj = self.stack[self.frames[self.frames_idx].bp + local_idx].clone();
+                    let obj = match &*self.stack[self.frames[self.frames_idx].bp + local_idx] {
+                        Object::Upvalue(cell) => cell.borrow().clone(),
+                        _ => self.stack[self.frames[self.frames_idx].bp + local_idx].clone(),
+                    };
                     self.push(&obj)?;
                     continue;
                 }
                 OpCode::GetBuiltin => {
                     let builtin_idx = word[1] as usize;
-                    if let Some(builtin) = Builtin::get_by_idx(builtin_idx) {
-                        self.push(&Rc::clone(&builtin))?;
+                    match self.builtins.get_by_idx(builtin_idx) {
+                        Some(builtin) => self.push(&builtin)?,
+                        None => return Err(VmError::UnknownBuiltin(builtin_idx)),
                     }
                 }
                 OpCode::Closure => {
@@ -206,9 +379,134 @@ impl VirtualMachine {
                 }
                 OpCode::GetFree => {
                     let free_idx = word[1] as usize;
-                    let free = self.frames[self.frames_idx].closure.free[free_idx].clone();
+                    let free = self.frames[self.frames_idx].closure.free[free_idx]
+                        .borrow()
+                        .clone();
                     self.push(&free)?;
                 }
+                OpCode::SetFree => {
+                    let free_idx = word[1] as usize;
+                    let value = self.pop()?;
+                    *self.frames[self.frames_idx].closure.free[free_idx].borrow_mut() = value;
+                }
+                OpCode::CaptureLocal => {
+                    let local_idx = word[1] as usize;
+                    let upvalue = self.capture_local(self.frames[self.frames_idx].bp + local_idx);
+                    self.push(&upvalue)?;
+                }
+                OpCode::CaptureFree => {
+                    let free_idx = word[1] as usize;
+                    let cell = Rc::clone(&self.frames[self.frames_idx].closure.free[free_idx]);
+                    self.push(&Rc::new(Object::Upvalue(cell)))?;
+                }
+                OpCode::Range => {
+                    let end = self.pop()?;
+                    let start = self.pop()?;
+                    let range = match (&*start, &*end) {
+                        (Object::Integer(start), Object::Integer(end)) => {
+                            Object::Range(*start, *end)
+                        }
+                        (start, end) => {
+                            return Err(VmError::InvalidOperands(
+                                "..",
+                                start.type_name(),
+                                end.type_name(),
+                            ))
+                        }
+                    };
+                    self.push(&Rc::new(range))?;
+                }
+                OpCode::IterStart => {
+                    let iterable = self.pop()?;
+                    let iterator = ForIterator::new(&iterable)
+                        .ok_or(VmError::NotIterable(iterable.type_name()))?;
+                    self.push(&Rc::new(Object::Iterator(iterator)))?;
+                }
+                OpCode::IterNext => {
+                    let pos = read_u16(&word[1..=2]) as usize;
+                    let count = word[3] as usize;
+                    if self.execute_iter_next(count)? {
+                        self.frames[self.frames_idx].ip = pos;
+                        continue;
+                    }
+                }
+                OpCode::GetIndexPath => {
+                    let depth = word[1] as usize;
+                    let path = &self.stack[self.stack.len() - depth - 1..];
+                    let mut element = Rc::clone(&path[0]);
+                    for index in path[1..].iter() {
+                        element = element_at(&element, index)?;
+                    }
+                    self.push(&element)?;
+                }
+                OpCode::SetIndexPath => {
+                    let depth = word[1] as usize;
+                    let value = self.pop()?;
+                    let indices = self.stack.split_off(self.stack.len() - depth);
+                    let collection = self.pop()?;
+                    let updated = set_index_path(&collection, &indices, value)?;
+                    self.push(&updated)?;
+                }
+                OpCode::Match => {
+                    let const_index = read_u16(&word[1..=2]) as usize;
+                    let Object::Pattern(pattern) = &*self.constants[const_index] else {
+                        return Err(VmError::IncompatibleTypes);
+                    };
+                    let value = Rc::clone(self.stack.last().ok_or(VmError::StackUnderflow)?);
+                    match pattern.bind(&value) {
+                        Some(bound) => {
+                            for object in bound.iter() {
+                                self.push(object)?;
+                            }
+                            self.push(&Rc::new(TRUE))?;
+                        }
+                        None => self.push(&Rc::new(FALSE))?,
+                    }
+                }
+                OpCode::NoMatch => {
+                    let value = self.stack.last().ok_or(VmError::StackUnderflow)?;
+                    return Err(VmError::NoMatchingArm(value.type_name()));
+                }
+                OpCode::JumpIfArgGiven => {
+                    let pos = read_u16(&word[1..=2]) as usize;
+                    let param_idx = word[3] as usize;
+                    if param_idx < self.frames[self.frames_idx].num_args {
+                        self.frames[self.frames_idx].ip = pos;
+                        continue;
+                    }
+                }
+                OpCode::UnpackArray => {
+                    let count = read_u16(&word[1..=2]) as usize;
+                    let has_rest = word[3] != 0;
+                    self.execute_unpack_array(count, has_rest)?;
+                }
+                OpCode::UnpackHash => {
+                    let count = read_u16(&word[1..=2]) as usize;
+                    let keys = self.stack.split_off(self.stack.len() - count);
+                    let hash = self.pop()?;
+                    if !matches!(&*hash, Object::Hash(_)) {
+                        return Err(VmError::CannotDestructure("a hash", hash.type_name()));
+                    }
+                    for key in keys.iter().rev() {
+                        self.push(&element_at(&hash, key)?)?;
+                    }
+                }
+                OpCode::SetupTry => {
+                    let pos = read_u16(&word[1..=2]) as usize;
+                    self.handlers.push(Handler {
+                        frame_idx: self.frames_idx,
+                        sp: self.stack.len(),
+                        ip: pos,
+                        rethrows: word[3] != 0,
+                    });
+                }
+                OpCode::PopTry => {
+                    self.handlers.pop();
+                }
+                OpCode::Throw | OpCode::Rethrow => {
+                    let object = self.pop()?;
+                    return Err(VmError::Thrown(object));
+                }
                 OpCode::CurrentClosure => {
                     let current_closure = self.frames[self.frames_idx].closure.clone();
                     self.push(&Rc::new(Object::Closure(Rc::new(current_closure))))?;
@@ -219,17 +517,32 @@ impl VirtualMachine {
         }
 
         match last_popped {
-            Some(obj) => Ok(obj),
+            Some(obj) => Ok(Rc::clone(obj)),
             None => Err(VmError::EmptyStack),
         }
     }
 
+    fn capture_local(&mut self, slot: usize) -> Rc<Object> {
+        // the first capture of a local moves its value into an upvalue cell,
+        // which then replaces the value in the local's stack slot
+        if let Object::Upvalue(_) = &*self.stack[slot] {
+            return Rc::clone(&self.stack[slot]);
+        }
+        let cell = Rc::new(RefCell::new(Rc::clone(&self.stack[slot])));
+        self.stack[slot] = Rc::new(Object::Upvalue(cell));
+        Rc::clone(&self.stack[slot])
+    }
+
     fn push_closure(&mut self, idx: usize, num_free: usize) -> Result<(), VmError> {
         match &*self.constants[idx] {
             Object::CompiledFunc(func) => {
                 let mut free = Vec::with_capacity(num_free);
                 for i in 0..num_free {
-                    free.push(self.stack[self.stack.len() - num_free + i].clone());
+                    let object = &self.stack[self.stack.len() - num_free + i];
+                    free.push(match &**object {
+                        Object::Upvalue(cell) => Rc::clone(cell),
+                        _ => Rc::new(RefCell::new(Rc::clone(object))),
+                    });
                 }
                 let closure = Object::Closure(Rc::new(Closure::new(func.deref().clone(), free)));
                 for _ in 0..num_free {
@@ -244,8 +557,90 @@ impl VirtualMachine {
         Ok(())
     }
 
+    /// Advances the iterator below the loop's value on top of the stack. The
+    /// value is replaced by the next `count` loop variables, or, if there are
+    /// none left, the iterator is removed and `true` returned.
+    fn execute_iter_next(&mut self, count: usize) -> Result<bool, VmError> {
+        let result = self.pop()?;
+        let mut iterator = match &*self.pop()? {
+            Object::Iterator(iterator) => iterator.clone(),
+            _ => return Err(VmError::IncompatibleTypes),
+        };
+
+        let Some((key, value)) = iterator.next() else {
+            self.push(&result)?;
+            return Ok(true);
+        };
+        let variables = if count == 1 {
+            vec![iterator.single((key, value))]
+        } else {
+            vec![key, value]
+        };
+        self.push(&Rc::new(Object::Iterator(iterator)))?;
+        for variable in variables.iter() {
+            self.push(variable)?;
+        }
+        Ok(false)
+    }
+
+    /// Pops the current frame, moving the function on top of the stack and its
+    /// `num_args` arguments down to where the frame's own function was, so that
+    /// a tail call takes the frame's place rather than adding one.
+    fn leave_frame_for_tail_call(&mut self, num_args: usize) -> Result<(), VmError> {
+        let frame = self.pop_frame()?;
+        let call = self.stack.split_off(self.stack.len() - 1 - num_args);
+        self.stack.truncate(frame.bp - 1);
+        self.stack.extend(call);
+        Ok(())
+    }
+
+    /// Enters `closure` with the `num_args` arguments on top of the stack. The
+    /// slots of parameters without an argument and of the other locals start
+    /// as `Null`; the function itself fills in the defaults.
+    fn call_closure(&mut self, closure: Closure, num_args: usize) -> Result<(), VmError> {
+        let function = Rc::clone(&closure.function);
+        let num_params = function.num_params as usize;
+        check_arguments(&function, num_args)?;
+
+        let bp = self.stack.len() - num_args;
+        if function.has_rest {
+            let rest = self.stack.split_off(bp + num_args.min(num_params));
+            while self.stack.len() < bp + num_params {
+                self.push(&Rc::new(NULL))?;
+            }
+            self.push(&Rc::new(Object::Array(rest)))?;
+        }
+        self.push_frame(Frame::new(closure, bp, num_args))?;
+        while self.stack.len() < bp + function.num_locals as usize {
+            self.push(&Rc::new(NULL))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the array on top of the stack by its first `count` elements,
+    /// the first on top, with `Null` for any it does not have. With a rest,
+    /// the elements after those are pushed first, as an array.
+    fn execute_unpack_array(&mut self, count: usize, has_rest: bool) -> Result<(), VmError> {
+        let array = self.pop()?;
+        let Object::Array(elements) = &*array else {
+            return Err(VmError::CannotDestructure("an array", array.type_name()));
+        };
+        if has_rest {
+            let rest = elements.get(count..).unwrap_or_default().to_vec();
+            self.push(&Rc::new(Object::Array(rest)))?;
+        }
+        for idx in (0..count).rev() {
+            match elements.get(idx) {
+                Some(element) => self.push(element)?,
+                None => self.push(&Rc::new(NULL))?,
+            }
+        }
+        Ok(())
+    }
+
     fn build_array(&mut self, length: usize) -> Result<Rc<Object>, VmError> {
-        let mut elements = vec![Rc::new(Object::Null); length];
+        let null = Rc::new(Object::Null);
+        let mut elements = vec![null; length];
         for i in 1..=length {
             elements[length - i] = self.pop()?;
         }
@@ -253,20 +648,15 @@ impl VirtualMachine {
     }
 
     fn build_hash(&mut self, length: usize) -> Result<Rc<Object>, VmError> {
-        let mut table = HashMap::new();
+        let mut pairs = Vec::with_capacity(length / 2);
         for _ in (0..length).step_by(2) {
             let val = self.pop()?;
-            let key = match &*self.pop()? {
-                Object::Integer(i) => Hashable::Integer(*i),
-                Object::Boolean(b) => Hashable::Boolean(*b),
-                Object::String(s) => Hashable::String(s.clone()),
-                _ => {
-                    return Err(VmError::UnhashableKey);
-                }
-            };
-            table.insert(key, val);
+            let key = Hashable::from_object(&*self.pop()?).ok_or(VmError::UnhashableKey)?;
+            pairs.push((key, val));
         }
-        Ok(Rc::new(Object::Hash(table)))
+        // the pairs were popped last first; insert them in source order so
+        // that a repeated key keeps its last value
+        Ok(Rc::new(Object::Hash(pairs.into_iter().rev().collect())))
     }
 
     fn execute_index_expression(&mut self) -> Result<(), VmError> {
@@ -282,34 +672,28 @@ impl VirtualMachine {
                 }
             }
             (Object::Hash(table), index) => {
-                let idx = match index {
-                    Object::Integer(i) => Hashable::Integer(*i),
-                    Object::Boolean(b) => Hashable::Boolean(*b),
-                    Object::String(s) => Hashable::String(s.clone()),
-                    _ => {
-                        return Err(VmError::UnhashableKey);
-                    }
-                };
+                let idx = Hashable::from_object(index).ok_or(VmError::UnhashableKey)?;
 
                 match table.get(&idx) {
                     Some(val) => self.push(val),
                     None => self.push(&Rc::new(NULL)),
                 }
             }
-            _ => {
-                return Err(VmError::IndexNotSupported);
-            }
+            (Object::Error(error), index) => self.push(&error.field(index)),
+            _ => Err(VmError::IndexNotSupported),
         }
     }
 
     fn execute_minus_expression(&mut self) -> Result<(), VmError> {
         let right = self.pop()?;
-        if let Object::Integer(int) = &*right {
-            self.push(&Rc::new(Object::Integer(-int)))?;
-        } else {
-            return Err(VmError::IncompatibleTypes);
-        }
-        Ok(())
+        let result = match &*right {
+            Object::Integer(int) => {
+                Object::Integer(int.checked_neg().ok_or(VmError::IntegerOverflow)?)
+            }
+            Object::Float(float) => Object::Float(-float),
+            _ => return Err(VmError::InvalidOperand("-", right.type_name())),
+        };
+        self.push(&Rc::new(result))
     }
 
     fn execute_bang_expression(&mut self) -> Result<(), VmError> {
@@ -323,7 +707,7 @@ impl VirtualMachine {
                 }
             }
             Object::Null => TRUE,
-            _ => return Err(VmError::IncompatibleTypes),
+            object => return Err(VmError::InvalidOperand("!", object.type_name())),
         };
 
         self.push(&Rc::new(result))?;
@@ -350,19 +734,22 @@ impl VirtualMachine {
                 let result = if left_val > right_val { TRUE } else { FALSE };
                 self.push(&Rc::new(result))?;
             }
-            (Object::Boolean(left_val), OpCode::And, Object::Boolean(right_val)) => {
-                let result = if *left_val && *right_val { TRUE } else { FALSE };
-                self.push(&Rc::new(result))?;
-            }
-            (Object::Boolean(left_val), OpCode::Or, Object::Boolean(right_val)) => {
-                let result = if *left_val || *right_val { TRUE } else { FALSE };
-                self.push(&Rc::new(result))?;
-            }
             (Object::String(left_val), OpCode::Add, Object::String(right_val)) => {
                 let result = Object::String(left_val.to_owned() + right_val);
                 self.push(&Rc::new(result))?;
             }
-            _ => return Err(VmError::IncompatibleTypes),
+            (left, op, right) => match (left.as_float(), right.as_float()) {
+                (Some(left_val), Some(right_val)) if !is_bitwise(op) => {
+                    self.execute_float_operation(left_val, op, right_val)?;
+                }
+                _ => {
+                    return Err(VmError::InvalidOperands(
+                        operator_symbol(op),
+                        left.type_name(),
+                        right.type_name(),
+                    ))
+                }
+            },
         }
         Ok(())
     }
@@ -374,10 +761,19 @@ impl VirtualMachine {
         right: i64,
     ) -> Result<(), VmError> {
         let result = match op_code {
-            OpCode::Add => Object::Integer(left + right),
-            OpCode::Subtract => Object::Integer(left - right),
-            OpCode::Multiply => Object::Integer(left * right),
-            OpCode::Divide => Object::Integer(left / right),
+            OpCode::Add => {
+                Object::Integer(left.checked_add(right).ok_or(VmError::IntegerOverflow)?)
+            }
+            OpCode::Subtract => {
+                Object::Integer(left.checked_sub(right).ok_or(VmError::IntegerOverflow)?)
+            }
+            OpCode::Multiply => {
+                Object::Integer(left.checked_mul(right).ok_or(VmError::IntegerOverflow)?)
+            }
+            OpCode::Divide if right == 0 => return Err(VmError::DivisionByZero),
+            OpCode::Divide => {
+                Object::Integer(left.checked_div(right).ok_or(VmError::IntegerOverflow)?)
+            }
             OpCode::Equal => {
                 if left == right {
                     TRUE
@@ -399,7 +795,60 @@ impl VirtualMachine {
                     FALSE
                 }
             }
-            _ => return Err(VmError::IncompatibleTypes),
+            OpCode::LessThan => Object::Boolean(left < right),
+            OpCode::GreaterEqual => Object::Boolean(left >= right),
+            OpCode::LessEqual => Object::Boolean(left <= right),
+            OpCode::Modulo if right == 0 => return Err(VmError::DivisionByZero),
+            // `i64::MIN % -1` overflows in Rust, but the remainder is 0
+            OpCode::Modulo => Object::Integer(left.wrapping_rem(right)),
+            OpCode::Power if right < 0 => Object::Float((left as f64).powf(right as f64)),
+            OpCode::Power => Object::Integer(
+                u32::try_from(right)
+                    .ok()
+                    .and_then(|exponent| left.checked_pow(exponent))
+                    .ok_or(VmError::IntegerOverflow)?,
+            ),
+            OpCode::BitAnd => Object::Integer(left & right),
+            OpCode::BitOr => Object::Integer(left | right),
+            OpCode::BitXor => Object::Integer(left ^ right),
+            OpCode::ShiftLeft | OpCode::ShiftRight if !(0..64).contains(&right) => {
+                return Err(VmError::InvalidShiftAmount(right))
+            }
+            OpCode::ShiftLeft => Object::Integer(left << right),
+            OpCode::ShiftRight => Object::Integer(left >> right),
+            op => {
+                return Err(VmError::InvalidOperands(
+                    operator_symbol(&op),
+                    "INTEGER",
+                    "INTEGER",
+                ))
+            }
+        };
+        self.push(&Rc::new(result))
+    }
+
+    fn execute_float_operation(
+        &mut self,
+        left: f64,
+        op_code: &OpCode,
+        right: f64,
+    ) -> Result<(), VmError> {
+        let result = match op_code {
+            OpCode::Add => Object::Float(left + right),
+            OpCode::Subtract => Object::Float(left - right),
+            OpCode::Multiply => Object::Float(left * right),
+            OpCode::Divide if right == 0.0 => return Err(VmError::DivisionByZero),
+            OpCode::Divide => Object::Float(left / right),
+            OpCode::Equal => Object::Boolean(left == right),
+            OpCode::NotEqual => Object::Boolean(left != right),
+            OpCode::GreaterThan => Object::Boolean(left > right),
+            OpCode::LessThan => Object::Boolean(left < right),
+            OpCode::GreaterEqual => Object::Boolean(left >= right),
+            OpCode::LessEqual => Object::Boolean(left <= right),
+            OpCode::Modulo if right == 0.0 => return Err(VmError::DivisionByZero),
+            OpCode::Modulo => Object::Float(left % right),
+            OpCode::Power => Object::Float(left.powf(right)),
+            op => unreachable!("{op} is not defined on floats"),
         };
         self.push(&Rc::new(result))
     }
@@ -408,6 +857,7 @@ impl VirtualMachine {
         match object {
             Object::Null => false,
             Object::Integer(val) => *val != 0,
+            Object::Float(val) => *val != 0.0,
             Object::Boolean(val) => *val,
             _ => true,
         }
@@ -448,6 +898,123 @@ impl VirtualMachine {
     }
 }
 
+/// Checks that `function` can be called with `num_args` arguments.
+fn check_arguments(function: &CompiledFunction, num_args: usize) -> Result<(), VmError> {
+    let num_params = function.num_params as usize;
+    let num_required = num_params - function.num_defaults as usize;
+    if num_args < num_required || (num_args > num_params && !function.has_rest) {
+        return Err(VmError::WrongArguments);
+    }
+    Ok(())
+}
+
+/// The element of an array or hash at `index`. Unlike `OpIndex`, indexing past
+/// the end of an array is an error, as it is when assigning to the element.
+fn element_at(collection: &Object, index: &Object) -> Result<Rc<Object>, VmError> {
+    match (collection, index) {
+        (Object::Array(array), Object::Integer(i)) => {
+            if *i < 0 || *i as usize >= array.len() {
+                return Err(VmError::IndexOutOfBounds);
+            }
+            Ok(Rc::clone(&array[*i as usize]))
+        }
+        (Object::Hash(table), index) => {
+            let key = Hashable::from_object(index).ok_or(VmError::UnhashableKey)?;
+            Ok(match table.get(&key) {
+                Some(val) => Rc::clone(val),
+                None => Rc::new(NULL),
+            })
+        }
+        _ => Err(VmError::IndexNotSupported),
+    }
+}
+
+/// A copy of `collection` with the element at the path of `indices` replaced by
+/// `value`. The elements along the path are copied; all others are shared, so
+/// other variables holding the collection do not see the change.
+fn set_index_path(
+    collection: &Object,
+    indices: &[Rc<Object>],
+    value: Rc<Object>,
+) -> Result<Rc<Object>, VmError> {
+    let Some((index, rest)) = indices.split_first() else {
+        return Ok(value);
+    };
+
+    match (collection, &**index) {
+        (Object::Array(array), Object::Integer(i)) => {
+            if *i < 0 || *i as usize >= array.len() {
+                return Err(VmError::IndexOutOfBounds);
+            }
+            let mut array = array.clone();
+            let i = *i as usize;
+            array[i] = set_index_path(&array[i], rest, value)?;
+            Ok(Rc::new(Object::Array(array)))
+        }
+        (Object::Hash(table), index) => {
+            let key = Hashable::from_object(index).ok_or(VmError::UnhashableKey)?;
+            let mut table = table.clone();
+            let element = match table.get(&key) {
+                Some(element) => set_index_path(element, rest, value)?,
+                None => set_index_path(&NULL, rest, value)?,
+            };
+            table.insert(key, element);
+            Ok(Rc::new(Object::Hash(table)))
+        }
+        _ => Err(VmError::IndexNotSupported),
+    }
+}
+
+/// The source operator a binary opcode was compiled from.
+fn operator_symbol(op: &OpCode) -> &'static str {
+    match op {
+        OpCode::Add => "+",
+        OpCode::Subtract => "-",
+        OpCode::Multiply => "*",
+        OpCode::Divide => "/",
+        OpCode::Modulo => "%",
+        OpCode::Power => "**",
+        OpCode::Equal => "==",
+        OpCode::NotEqual => "!=",
+        OpCode::GreaterThan => ">",
+        OpCode::LessThan => "<",
+        OpCode::GreaterEqual => ">=",
+        OpCode::LessEqual => "<=",
+        OpCode::BitAnd => "&",
+        OpCode::BitOr => "|",
+        OpCode::BitXor => "^",
+        OpCode::ShiftLeft => "<<",
+        OpCode::ShiftRight => ">>",
+        _ => "?",
+    }
+}
+
+/// Bitwise operators are only defined on integers.
+fn is_bitwise(op: &OpCode) -> bool {
+    matches!(
+        op,
+        OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor | OpCode::ShiftLeft | OpCode::ShiftRight
+    )
+}
+
+/// A call that was in progress when a runtime error was raised.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    /// The offset of the instruction the frame was executing.
+    pub offset: usize,
+    pub span: Option<Span>,
+}
+
+/// A [`VmError`] together with where it was raised and the calls that led
+/// there, innermost first.
+#[derive(Debug, PartialEq)]
+pub struct RuntimeError {
+    pub error: VmError,
+    pub span: Option<Span>,
+    pub trace: Vec<TraceFrame>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum VmError {
     UnknownOpCode,
@@ -455,10 +1022,96 @@ pub enum VmError {
     StackUnderflow,
     EmptyStack,
     IncompatibleTypes,
+    InvalidOperands(&'static str, &'static str, &'static str),
+    InvalidOperand(&'static str, &'static str),
     UnhashableKey,
     IndexNotSupported,
+    IndexOutOfBounds,
     FrameStackUnderflow,
     FrameStackOverflow,
     CallingNonFunction,
     WrongArguments,
+    DivisionByZero,
+    IntegerOverflow,
+    InvalidShiftAmount(i64),
+    NotIterable(&'static str),
+    /// The kind of pattern, and the type of the value it did not match.
+    CannotDestructure(&'static str, &'static str),
+    /// The type of the value no arm of a `match` matched.
+    NoMatchingArm(&'static str),
+    /// A value raised by `throw` that no `catch` block handled.
+    Thrown(Rc<Object>),
+    /// The index of a builtin missing from the VM's registry, as when the
+    /// code was compiled with another one.
+    UnknownBuiltin(usize),
+}
+
+impl VmError {
+    /// The value a `catch` block receives for this error: the value thrown,
+    /// or an error of the kind matching the runtime error. Errors in the VM
+    /// itself cannot be caught.
+    fn to_object(&self) -> Option<Rc<Object>> {
+        let kind = match self {
+            VmError::Thrown(object) => return Some(Rc::clone(object)),
+            VmError::IncompatibleTypes
+            | VmError::InvalidOperands(..)
+            | VmError::InvalidOperand(..)
+            | VmError::UnhashableKey
+            | VmError::IndexNotSupported
+            | VmError::CallingNonFunction
+            | VmError::NotIterable(_)
+            | VmError::CannotDestructure(..) => "TypeError",
+            VmError::WrongArguments => "ArgumentError",
+            VmError::IndexOutOfBounds => "IndexError",
+            VmError::DivisionByZero | VmError::IntegerOverflow | VmError::InvalidShiftAmount(_) => {
+                "ArithmeticError"
+            }
+            VmError::NoMatchingArm(_) => "MatchError",
+            VmError::StackOverflow | VmError::FrameStackOverflow => "StackOverflowError",
+            VmError::UnknownOpCode
+            | VmError::StackUnderflow
+            | VmError::EmptyStack
+            | VmError::FrameStackUnderflow
+            | VmError::UnknownBuiltin(_) => return None,
+        };
+        Some(Rc::new(Object::Error(ErrorObject::new(
+            kind,
+            &self.to_string(),
+        ))))
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::UnknownOpCode => write!(f, "unknown opcode"),
+            VmError::StackOverflow => write!(f, "stack overflow"),
+            VmError::StackUnderflow => write!(f, "stack underflow"),
+            VmError::EmptyStack => write!(f, "no value on the stack"),
+            VmError::IncompatibleTypes => write!(f, "incompatible types"),
+            VmError::InvalidOperands(op, left, right) => {
+                write!(f, "{}", describe_operands(op, left, right))
+            }
+            VmError::InvalidOperand(op, operand) => write!(f, "{}", describe_operand(op, operand)),
+            VmError::UnhashableKey => write!(f, "unusable as hash key"),
+            VmError::IndexNotSupported => write!(f, "index operator not supported"),
+            VmError::IndexOutOfBounds => write!(f, "index out of bounds"),
+            VmError::FrameStackUnderflow => write!(f, "frame stack underflow"),
+            VmError::FrameStackOverflow => write!(f, "maximum call depth exceeded"),
+            VmError::CallingNonFunction => write!(f, "not a function"),
+            VmError::WrongArguments => write!(f, "wrong number of arguments"),
+            VmError::DivisionByZero => write!(f, "division by zero"),
+            VmError::IntegerOverflow => write!(f, "integer overflow"),
+            VmError::InvalidShiftAmount(amount) => write!(f, "cannot shift by {amount} bits"),
+            VmError::NotIterable(type_name) => write!(f, "cannot iterate over {type_name}"),
+            VmError::CannotDestructure(pattern, type_name) => {
+                write!(f, "cannot destructure {type_name} with {pattern} pattern")
+            }
+            VmError::NoMatchingArm(type_name) => {
+                write!(f, "no arm of the match matches the {type_name}")
+            }
+            VmError::Thrown(object) => write!(f, "uncaught {}", describe_thrown(object)),
+            VmError::UnknownBuiltin(idx) => write!(f, "no builtin at index {idx}"),
+        }
+    }
 }
@@ -1,12 +1,15 @@
+#![cfg(test)]
+
 use crate::compiler::Compiler;
-use crate::object::{Hashable, Object};
+use crate::object::builtins::{capture_output, Arity, BuiltinError, Builtins};
+use crate::object::{ErrorObject, Hashable, Object};
 use crate::parser::Parser;
+use crate::span::Position;
 use crate::vm::{VirtualMachine, VmError, STACK_SIZE};
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-#[cfg(test)]
-
 fn compile_and_run(input: &str) -> (Option<Rc<Object>>, Option<VmError>) {
     let mut result = None;
     let mut error = None;
@@ -16,7 +19,7 @@ fn compile_and_run(input: &str) -> (Option<Rc<Object>>, Option<VmError>) {
     let mut vm = VirtualMachine::new(byte_code);
     match vm.run() {
         Ok(object) => result = Some(object),
-        Err(err) => error = Some(err),
+        Err(err) => error = Some(err.error),
     }
     (result, error)
 }
@@ -111,6 +114,306 @@ fn test_vm_integer_arithmetic_eight() {
     assert_eq!(result, Some(expected));
 }
 
+#[test]
+fn test_vm_integer_division_by_zero() {
+    let input = "let zero = 0; 1 / zero";
+    let expected_error = VmError::DivisionByZero;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_addition_overflow() {
+    let input = "9223372036854775807 + 1";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_subtraction_overflow() {
+    let input = "-9223372036854775807 - 2";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_multiplication_overflow() {
+    let input = "4611686018427387904 * 2";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_division_overflow() {
+    let input = "let min = -9223372036854775807 - 1; min / -1";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_negation_overflow() {
+    let input = "let min = -9223372036854775807 - 1; -min";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_integer_arithmetic_at_bounds() {
+    let input = "(-9223372036854775807 - 1) + 9223372036854775807";
+    let expected = Rc::new(Object::Integer(-1));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_float_arithmetic() {
+    let input = "1.5 + 2.25 * 2.0 - 1.0 / 4.0";
+    let expected = Rc::new(Object::Float(5.75));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_mixed_integer_and_float_arithmetic() {
+    let input = "1 + 0.5 * 3";
+    let expected = Rc::new(Object::Float(2.5));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_float_comparison() {
+    let input = "0.1 + 0.2 > 0.3";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_integer_equals_integral_float() {
+    let input = "1 == 1.0";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_float_negation() {
+    let input = "-(2.5 - 5)";
+    let expected = Rc::new(Object::Float(2.5));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_zero_float_is_falsy() {
+    let input = "if (0.0) { 1 } else { 2 }";
+    let expected = Rc::new(Object::Integer(2));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_integral_float_is_integer_hash_key() {
+    let input = "{1: \"one\", 2.5: \"two and a half\"}[1.0]";
+    let expected = Rc::new(Object::String(String::from("one")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_float_division_by_zero() {
+    let input = "1.5 / 0";
+    let expected_error = VmError::DivisionByZero;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_modulo() {
+    let input = "[7 % 3, -7 % 3, 7.5 % 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(-1)),
+        Rc::new(Object::Float(1.5)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_modulo_of_minimum_integer() {
+    let input = "let min = -9223372036854775807 - 1; min % -1";
+    let expected = Rc::new(Object::Integer(0));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_power() {
+    let input = "[2 ** 10, 2 ** 3 ** 2, -2 ** 2, 2 ** -1, 4.0 ** 0.5]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1024)),
+        Rc::new(Object::Integer(512)),
+        Rc::new(Object::Integer(-4)),
+        Rc::new(Object::Float(0.5)),
+        Rc::new(Object::Float(2.0)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_less_and_greater_or_equal() {
+    let input = "[1 <= 1, 2 <= 1, 1 >= 1, 1 >= 2, 1.5 <= 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+        Rc::new(Object::Boolean(false)),
+        Rc::new(Object::Boolean(true)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_bitwise_operators() {
+    let input = "[6 & 3, 6 | 3, 6 ^ 3, 1 << 62, -16 >> 2]";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(7)),
+        Rc::new(Object::Integer(5)),
+        Rc::new(Object::Integer(4611686018427387904)),
+        Rc::new(Object::Integer(-4)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_less_than_evaluates_left_operand_first() {
+    let input =
+        "let x = 1; let f = fn() { x = x * 10; x }; let g = fn() { x = x + 1; x }; f() < g()";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_modulo_by_zero() {
+    let input = "let zero = 0; 1 % zero";
+    let expected_error = VmError::DivisionByZero;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_power_overflow() {
+    let input = "2 ** 63";
+    let expected_error = VmError::IntegerOverflow;
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_shift_amount_out_of_range() {
+    let input = "1 << 64";
+    let expected_error = VmError::InvalidShiftAmount(64);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_negative_shift_amount() {
+    let input = "1 >> -1";
+    let expected_error = VmError::InvalidShiftAmount(-1);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_bitwise_operator_on_float() {
+    let input = "1.5 & 1";
+    let expected_error = VmError::InvalidOperands("&", "FLOAT", "INTEGER");
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_vm_string_escapes() {
+    let input = r#""\"a\"\tb\\\u{e9}""#;
+    let expected = Rc::new(Object::String(String::from("\"a\"\tb\\é")));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_raw_strings() {
+    let input = r###"let x = 1; [r"a\n${x}", r#"say "hi""#, len(r"\t")]"###;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from(r"a\n${x}"))),
+        Rc::new(Object::String(String::from(r#"say "hi""#))),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_string_interpolation() {
+    let input = r#"let name = "Monkey"; let xs = [1, 2.5];
+"${name} has ${len(xs)} items: ${xs}, ${ "n${1 + 1}" }""#;
+    let expected = Rc::new(Object::String(String::from(
+        "Monkey has 2 items: [1, 2.5], n2",
+    )));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_vm_string_interpolation_evaluates_left_to_right() {
+    let input = r#"let log = fn(x) { puts(x); x }; "${log(1)}${log(2)}""#;
+    let ((result, error), output) = capture_output(|| compile_and_run(input));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(Rc::new(Object::String(String::from("12")))));
+    assert_eq!(output, "1\n2\n");
+}
+
 #[test]
 fn test_vm_boolean_true() {
     let input = "true";
@@ -240,7 +543,7 @@ fn test_vm_bang_in_infix_expression() {
 #[test]
 fn test_vm_bang_expression_error_if_used_on_integer() {
     let input = "!5";
-    let expected_error = VmError::IncompatibleTypes;
+    let expected_error = VmError::InvalidOperand("!", "INTEGER");
     let (result, error) = compile_and_run(input);
     assert_eq!(error, Some(expected_error));
     assert_eq!(result, None);
@@ -249,7 +552,7 @@ fn test_vm_bang_expression_error_if_used_on_integer() {
 #[test]
 fn test_vm_minus_expression_error_if_used_on_boolean() {
     let input = "-true";
-    let expected_error = VmError::IncompatibleTypes;
+    let expected_error = VmError::InvalidOperand("-", "BOOLEAN");
     let (result, error) = compile_and_run(input);
     assert_eq!(error, Some(expected_error));
     assert_eq!(result, None);
@@ -995,6 +1298,15 @@ fn test_builtin_eighteen() {
     assert_eq!(result, None);
 }
 
+#[test]
+fn test_builtin_nested_calls() {
+    let input = "first(rest(push([1, 2], len(\"abc\"))));";
+    let expected = Rc::new(Object::Integer(2));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
 #[test]
 fn test_closure_one() {
     let input = "
@@ -1181,3 +1493,1256 @@ false || (1 < 0)
     assert_eq!(error, None);
     assert_eq!(result, Some(expected));
 }
+
+#[test]
+fn test_logical_operator_short_circuits_and() {
+    let input = "false && (1 + true)";
+    let expected = Rc::new(Object::Boolean(false));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_short_circuits_or() {
+    let input = "true || (1 + true)";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_guard() {
+    let input = "
+let arr = [1, 2];
+let i = 2;
+i < len(arr) && arr[i] == 1
+";
+    let expected = Rc::new(Object::Boolean(false));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_skips_side_effects() {
+    let input = "
+let x = 0;
+let f = fn() { x = x + 1; true };
+false && f();
+true || f();
+true && f();
+x
+";
+    let expected = Rc::new(Object::Integer(1));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_with_any_type() {
+    let input = "\"a\" && [1] && (0 || fn() {})";
+    let expected = Rc::new(Object::Boolean(true));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_logical_operator_with_falsy_values() {
+    let input = "1 && 0";
+    let expected = Rc::new(Object::Boolean(false));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop() {
+    let input = "
+let i = 0;
+while (i < 3) {
+    let i = i + 1;
+}
+return i;
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_early_return() {
+    let input = "
+let i = 0;
+while (true) {
+    let i = i + 1;
+    if (i == 3) {
+        return i;
+    }
+}
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_value() {
+    let input = "
+let i = 0;
+while (i < 3) {
+    let i = i + 1;
+    i * 10
+}
+";
+    let expected = Rc::new(Object::Integer(30));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_never_runs() {
+    let input = "while (false) { 10 }";
+    let expected = Rc::new(Object::Null);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_while_loop_in_function() {
+    let input = "
+let sum = fn(n) {
+    let total = 0;
+    let i = 0;
+    while (i < n) {
+        let i = i + 1;
+        let total = total + i;
+    }
+    total
+};
+sum(4)
+";
+    let expected = Rc::new(Object::Integer(10));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_over_array() {
+    let input = "
+let sum = 0;
+for (x in [1, 2, 3]) {
+    sum = sum + x;
+}
+sum
+";
+    let expected = Rc::new(Object::Integer(6));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_over_hash_in_key_order() {
+    let input = "
+let keys = \"\";
+let total = 0;
+for (k, v in {\"b\": 2, 10: 3, \"a\": 1, 2.5: 4, true: 5}) {
+    keys = \"${keys}${k} \";
+    total = total + v;
+};
+[keys, total]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from("true 2.5 10 a b "))),
+        Rc::new(Object::Integer(15)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_over_string_and_range_with_index() {
+    let input = "
+let pairs = [];
+for (i, c in \"ab\") { pairs = push(pairs, [i, c]); }
+for (i, n in 5..7) { pairs = push(pairs, [i, n]); }
+pairs
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::String(String::from("a"))),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::String(String::from("b"))),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(5)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(6)),
+        ])),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_value() {
+    let input = "for (i in 0..3) { i * 10 }";
+    let expected = Rc::new(Object::Integer(20));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_over_empty_range() {
+    let input = "for (i in 3..0) { i }";
+    let expected = Rc::new(Object::Null);
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_early_return_in_function() {
+    let input = "
+let find = fn(xs, target) {
+    for (i, x in xs) {
+        if (x == target) {
+            return i;
+        }
+    };
+    -1
+};
+[find([4, 5, 6], 6), find([4, 5, 6], 7)]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(-1)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_for_loop_error_if_not_iterable() {
+    let input = "for (x in 5) { x }";
+    let expected_error = VmError::NotIterable("INTEGER");
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_range_error_if_bounds_not_integers() {
+    let input = "0..1.5";
+    let expected_error = VmError::InvalidOperands("..", "INTEGER", "FLOAT");
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, Some(expected_error));
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_break_ends_loop_with_null() {
+    let input = "
+let i = 0;
+let value = while (true) {
+    i = i + 1;
+    if (i == 3) { break; }
+    i
+};
+[i, value]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Null),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_continue_skips_rest_of_iteration() {
+    let input = "
+let odd = [];
+for (x in 0..6) {
+    if (x % 2 == 0) { continue; }
+    odd = push(odd, x);
+};
+odd
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(5)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_break_and_continue_in_nested_loops() {
+    let input = "
+let pairs = 0;
+let i = 0;
+while (i < 4) {
+    i = i + 1;
+    for (j in 0..4) {
+        if (j == 1) { continue; }
+        if (j >= i) { break; }
+        pairs = pairs + 1;
+    }
+    if (i == 3) { break; }
+};
+[i, pairs]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(4)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_break_in_loop_in_function() {
+    let input = "
+let first_over = fn(xs, limit) {
+    let found = -1;
+    for (x in xs) {
+        if (x > limit) {
+            found = x;
+            break;
+        }
+    };
+    found
+};
+[first_over([1, 5, 9], 4), first_over([1], 4)]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(5)),
+        Rc::new(Object::Integer(-1)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_break_and_continue_in_branches() {
+    let input = "
+let evens = [];
+let last = for (x in 0..10) {
+    match (x % 2) {
+        1 => { continue; },
+        _ => {
+            try {
+                if (x > 6) { break; }
+                evens = push(evens, x);
+            } finally {
+                evens = evens;
+            }
+        }
+    }
+};
+[evens, last]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(0)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Integer(4)),
+            Rc::new(Object::Integer(6)),
+        ])),
+        Rc::new(Object::Null),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_index_assignment_nested() {
+    let input = "
+let a = [1, [2, 3], {\"k\": 1}];
+a[0] = 10;
+a[1][0] = 20;
+a[2][\"k\"] = 30;
+a[2][\"new\"] = 40;
+[a[0], a[1], a[2][\"k\"], a[2][\"new\"]]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(10)),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(20)),
+            Rc::new(Object::Integer(3)),
+        ])),
+        Rc::new(Object::Integer(30)),
+        Rc::new(Object::Integer(40)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_index_assignment_copies_collection() {
+    let input = "
+let a = [[1], 2];
+let b = a;
+let inner = a[0];
+a[0][0] = 5;
+let set = fn(arr) { arr[1] = 6; arr };
+[a, b, inner, set(a), a]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(6)),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Array(vec![Rc::new(Object::Integer(5))])),
+            Rc::new(Object::Integer(2)),
+        ])),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_compound_assignment() {
+    let input = "
+let x = 10;
+x += 5;
+x -= 3;
+x *= 2;
+x /= 4;
+let h = {\"n\": [1]};
+h[\"n\"][0] += 41;
+let counter = fn() {
+    let c = [0];
+    fn() { c[0] += 1; c[0] }
+};
+let next = counter();
+next();
+[x, h[\"n\"][0], next()]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_index_assignment_errors() {
+    let tests = vec![
+        ("let a = [1]; a[1] = 2;", VmError::IndexOutOfBounds),
+        ("let a = [1]; a[-1] += 2;", VmError::IndexOutOfBounds),
+        ("let h = {}; h[[1]] = 2;", VmError::UnhashableKey),
+        (
+            "let h = {}; h[\"a\"][\"b\"] = 2;",
+            VmError::IndexNotSupported,
+        ),
+        ("let n = 1; n[0] = 2;", VmError::IndexNotSupported),
+        (
+            "let h = {}; h[\"a\"] += 1;",
+            VmError::InvalidOperands("+", "NULL", "INTEGER"),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_let_destructuring() {
+    let input = "
+let [a, [b, c], ...rest] = [1, [2], 3, 4];
+let {\"name\": name, 1 + 1: two, \"missing\": missing} = {\"name\": \"n\", 2: [a]};
+[a, b, c, rest, name, two, missing]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Null),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(3)),
+            Rc::new(Object::Integer(4)),
+        ])),
+        Rc::new(Object::String(String::from("n"))),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(1))])),
+        Rc::new(Object::Null),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_destructuring_parameters() {
+    let input = "
+let sum = fn([first, ...others], {\"scale\": scale}) {
+    let total = first;
+    for (x in others) { total += x; };
+    let inner = fn() { total * scale };
+    inner()
+};
+let [x, ...empty] = [5];
+[sum([1, 2, 3], {\"scale\": 10}), sum([x], {\"scale\": 2}), empty]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(60)),
+        Rc::new(Object::Integer(10)),
+        Rc::new(Object::Array(vec![])),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_destructuring_errors() {
+    let tests = vec![
+        (
+            "let [a] = 1;",
+            VmError::CannotDestructure("an array", "INTEGER"),
+        ),
+        (
+            "let {\"a\": a} = [1];",
+            VmError::CannotDestructure("a hash", "ARRAY"),
+        ),
+        (
+            "let f = fn([a]) { a }; f({});",
+            VmError::CannotDestructure("an array", "HASH"),
+        ),
+        (
+            "let [[a]] = [\"s\"];",
+            VmError::CannotDestructure("an array", "STRING"),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_default_and_rest_parameters() {
+    let input = "
+let f = fn(a, b = a * 10, ...rest) { [a, b, rest] };
+let calls = 0;
+let count = fn() { calls += 1; calls };
+let g = fn(n = count()) { n };
+[f(1), f(1, 2), f(1, 2, 3, 4), g(), g(), g(7), calls]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(10)),
+            Rc::new(Object::Array(vec![])),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Array(vec![])),
+        ])),
+        Rc::new(Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Integer(2)),
+            Rc::new(Object::Array(vec![
+                Rc::new(Object::Integer(3)),
+                Rc::new(Object::Integer(4)),
+            ])),
+        ])),
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::Integer(7)),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_variadic_function() {
+    let input = "
+let sum = fn(...xs) { let total = 0; for (x in xs) { total += x; }; total };
+let apply = fn(f, ...args) { f(args[0], args[1]) };
+[sum(), sum(1, 2, 3), apply(fn(a, [b] = [5]) { a + b }, 1, [2])]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(0)),
+        Rc::new(Object::Integer(6)),
+        Rc::new(Object::Integer(3)),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_default_parameter_arity() {
+    let tests = vec![
+        "let f = fn(a, b = 1) { a }; f();",
+        "let f = fn(a, b = 1) { a }; f(1, 2, 3);",
+        "let f = fn(a, ...b) { a }; f();",
+    ];
+    for input in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::WrongArguments), "{input}");
+    }
+}
+
+#[test]
+fn test_match_expression() {
+    let input = "
+let describe = fn(v) {
+    match (v) {
+        0 => \"zero\",
+        -1.5 => \"minus one and a half\",
+        \"hi\" => \"greeting\",
+        false => \"no\",
+        [] => \"empty\",
+        [x] => \"one ${x}\",
+        [x, ...xs] if len(xs) > 1 => \"many\",
+        [x, y] => x + y,
+        {\"type\": \"add\", \"lhs\": l, \"rhs\": r} => l + r,
+        {\"type\": t} => { let s = \"type \"; s + t },
+        _ => \"other\",
+    }
+};
+[describe(0), describe(0.0), describe(-1.5), describe(\"hi\"), describe(false), describe([]),
+ describe([1]), describe([1, 2, 3]), describe([1, 2]), describe({\"type\": \"add\", \"lhs\": 2, \"rhs\": 3}),
+ describe({\"type\": \"t\"}), describe({}), describe(true)]
+";
+    let expected = Rc::new(Object::Array(
+        vec![
+            "zero",
+            "zero",
+            "minus one and a half",
+            "greeting",
+            "no",
+            "empty",
+            "one 1",
+            "many",
+        ]
+        .into_iter()
+        .map(|string| Rc::new(Object::String(string.to_string())))
+        .chain([Rc::new(Object::Integer(3)), Rc::new(Object::Integer(5))])
+        .chain(
+            ["type t", "other", "other"]
+                .into_iter()
+                .map(|string| Rc::new(Object::String(string.to_string()))),
+        )
+        .collect(),
+    ));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_match_bindings_and_guards() {
+    let input = "
+let classify = fn(pair) {
+    match (pair) {
+        [a, b] if a > b => \"desc\",
+        [a, b] if a == b => \"same\",
+        _ => \"asc\",
+    }
+};
+let total = 0;
+for (i in 0..6) {
+    match (i % 3) {
+        0 => { continue; },
+        n => { total += n * i; },
+    };
+};
+let last = match ([7, 8]) { [x, ...rest] => rest };
+[classify([2, 1]), classify([1, 1]), classify([1, 2]), total, last]
+";
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from("desc"))),
+        Rc::new(Object::String(String::from("same"))),
+        Rc::new(Object::String(String::from("asc"))),
+        Rc::new(Object::Integer(1 + 4 + 4 + 10)),
+        Rc::new(Object::Array(vec![Rc::new(Object::Integer(8))])),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_match_without_matching_arm() {
+    let input = "match ([1, 2]) { [a] => a, {\"k\": v} => v, x if false => x }";
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(VmError::NoMatchingArm("ARRAY")));
+}
+
+#[test]
+fn test_try_catch_runtime_errors() {
+    let input = r#"let kind = fn(f) { try { f(); "none" } catch (e) { e["kind"] } };
+[
+    kind(fn() { 1 + true }),
+    kind(fn() { 5() }),
+    kind(fn() { let g = fn(a) { a }; g() }),
+    kind(fn() { 1 / 0 }),
+    kind(fn() { match (1) { 2 => 2 } }),
+    kind(fn() { throw error("out of range", "RangeError"); }),
+    kind(fn() { throw {"kind": "thrown hash"}; }),
+    kind(fn() { 1 })
+]"#;
+    let expected = Rc::new(Object::Array(
+        [
+            "TypeError",
+            "TypeError",
+            "ArgumentError",
+            "ArithmeticError",
+            "MatchError",
+            "RangeError",
+            "thrown hash",
+            "none",
+        ]
+        .into_iter()
+        .map(|kind| Rc::new(Object::String(String::from(kind))))
+        .collect(),
+    ));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_try_finally_control_flow() {
+    let input = r#"let log = fn(x) { puts(x); x };
+let f = fn() { try { return log(1); } finally { log(2); } };
+let g = fn() { try { throw "oops"; } catch (e) { return e; } finally { log(3); } };
+let total = 0;
+for (i in 0..4) {
+    try {
+        if (i == 1) { continue; };
+        if (i == 3) { break; };
+        total += i;
+    } finally { log(i); }
+};
+let nested = try { try { throw "inner"; } finally { log(4); } } catch (e) { e };
+[f(), g(), total, nested]"#;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::String(String::from("oops"))),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::String(String::from("inner"))),
+    ]));
+    let ((result, error), output) = capture_output(|| compile_and_run(input));
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+    assert_eq!(output, "0\n1\n2\n3\n4\n1\n2\n3\n");
+}
+
+#[test]
+fn test_uncaught_throw() {
+    let tests = vec![
+        (
+            r#"throw error("bye");"#,
+            Object::Error(ErrorObject::new("Error", "bye")),
+        ),
+        (r#"try { throw 1; } finally { 2 }"#, Object::Integer(1)),
+        (
+            r#"try { 1 } catch (e) { 2 } finally { throw "late"; }"#,
+            Object::String(String::from("late")),
+        ),
+        (
+            r#"try { 1 + true } finally { 2 }"#,
+            Object::Error(ErrorObject::new(
+                "TypeError",
+                "cannot add INTEGER and BOOLEAN",
+            )),
+        ),
+        (
+            r#"try { 1 + true } catch (e) { throw e; }"#,
+            Object::Error(ErrorObject::new(
+                "TypeError",
+                "cannot add INTEGER and BOOLEAN",
+            )),
+        ),
+    ];
+    for (input, expected) in tests {
+        let (_, error) = compile_and_run(input);
+        assert_eq!(error, Some(VmError::Thrown(Rc::new(expected))), "{input}");
+    }
+}
+
+#[test]
+fn test_catch_unwinds_frames_and_stack() {
+    let input = r#"let deep = fn(n) { if (n == 0) { throw n; }; 1 + deep(n - 1) };
+let overflow = fn(n) { 1 + overflow(n + 1) };
+[1, try { deep(50) } catch (e) { e }, 2, try { overflow(0) } catch (e) { e["kind"] }]"#;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(0)),
+        Rc::new(Object::Integer(2)),
+        Rc::new(Object::String(String::from("StackOverflowError"))),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_return_from_try_removes_handler() {
+    let input = "let f = fn() { try { return 1; } catch (e) { 2 } }; f(); -true";
+    let (_, error) = compile_and_run(input);
+    assert_eq!(error, Some(VmError::InvalidOperand("-", "BOOLEAN")));
+}
+
+#[test]
+fn test_assignment_global() {
+    let input = "
+let i = 0;
+i = i + 1;
+i
+";
+    let expected = Rc::new(Object::Integer(1));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_local() {
+    let input = "
+let f = fn(x) {
+    let y = 1;
+    y = y + x;
+    x = 0;
+    x + y
+};
+f(5)
+";
+    let expected = Rc::new(Object::Integer(6));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_closure() {
+    let input = "
+let count = 0;
+let counter = fn() { count = count + 1; count };
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(2));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_closure_and_hof() {
+    let input = "
+let makeCounter = fn() {
+    let count = 0;
+    return fn() {
+        count = count + 1;
+        return count;
+    };
+};
+let counter = makeCounter();
+counter();
+counter();
+counter();
+";
+    let expected = Rc::new(Object::Integer(3));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_counters_are_independent() {
+    let input = "
+let makeCounter = fn() { let count = 0; fn() { count = count + 1; count } };
+let a = makeCounter();
+let b = makeCounter();
+a();
+a();
+b();
+a() * 10 + b()
+";
+    let expected = Rc::new(Object::Integer(32));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_free_shared_with_enclosing_scope() {
+    let input = "
+let f = fn() {
+    let total = 0;
+    let add = fn(x) { total = total + x; };
+    add(3);
+    add(4);
+    total
+};
+f()
+";
+    let expected = Rc::new(Object::Integer(7));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_nested_free() {
+    let input = "
+let outer = fn() {
+    let n = 0;
+    let middle = fn() {
+        let inner = fn() { n = n + 1; };
+        inner();
+        inner();
+    };
+    middle();
+    n
+};
+outer()
+";
+    let expected = Rc::new(Object::Integer(2));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+#[test]
+fn test_assignment_to_own_binding() {
+    let tests = vec![
+        ("let f = fn() { f = 1; }; f(); f", 1),
+        ("let g = fn() { let f = fn() { f = 2; }; f(); f }; g()", 2),
+    ];
+    for (input, expected) in tests {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None);
+        assert_eq!(result, Some(Rc::new(Object::Integer(expected))));
+    }
+}
+
+#[test]
+fn test_runtime_error_position() {
+    let input = "let f = fn(x) {
+    let y = 1;
+    x + true
+};
+f(1);";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(
+        error.error,
+        VmError::InvalidOperands("+", "INTEGER", "BOOLEAN")
+    );
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(3, 5)));
+}
+
+#[test]
+fn test_runtime_error_position_in_main() {
+    let input = "let a = [1, 2];
+a(1);";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(error.error, VmError::CallingNonFunction);
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(2, 1)));
+}
+
+#[test]
+fn test_runtime_error_position_in_expression() {
+    let input = "let a = 1;
+let b = [a,
+    a + \"x\"];";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(error.span.map(|span| span.start), Some(Position::new(3, 5)));
+}
+
+#[test]
+fn test_runtime_error_stack_trace() {
+    let input = "let check = fn(x) { x + true };
+let outer = fn() {
+    let inner = fn() { let result = check(1); result };
+    let result = inner();
+    result
+};
+outer();";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    let trace = error
+        .trace
+        .iter()
+        .map(|frame| {
+            let line = frame.span.map(|span| span.start.line);
+            (frame.function.as_str(), line)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        trace,
+        vec![
+            ("check", Some(1)),
+            ("inner", Some(3)),
+            ("outer", Some(4)),
+            ("<main>", Some(7)),
+        ]
+    );
+}
+
+#[test]
+fn test_runtime_error_stack_trace_wrong_arguments() {
+    let input = "let add = fn(a, b) { a + b };
+let apply = fn() { fn() { add(1) }() };
+apply();";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(error.error, VmError::WrongArguments);
+    let functions = error
+        .trace
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect::<Vec<_>>();
+    // the call is rejected before a frame for `add` is pushed; the tail call
+    // to the anonymous function took the place of `apply`'s frame
+    assert_eq!(functions, vec!["<anonymous>", "<main>"]);
+    assert_eq!(error.trace[0].offset, 8);
+}
+
+#[test]
+fn test_runtime_error_position_after_finally() {
+    // an error a `finally` block was run for is raised again from where it
+    // was raised, through any number of them, and also after a function left
+    // a `finally` block early with `return`
+    let tests = [
+        (
+            "try {
+  throw \"x\";
+} finally { 1 }",
+            (2, 3),
+        ),
+        (
+            "let f = fn() { try { throw 1; } finally { return 2; } };
+let g = fn() {
+  throw \"x\";
+};
+try { f(); g() } finally { 3 }",
+            (3, 3),
+        ),
+        (
+            "let g = fn() { throw \"x\"; };
+let f = fn() { try { g() } finally { 1 } };
+try { f() } catch (e) {
+  e + true
+} finally { 2 }",
+            (4, 3),
+        ),
+    ];
+    for (input, (line, column)) in tests {
+        let ast = Parser::parse_program(input).expect("got a parsing error");
+        let byte_code = Compiler::new().compile(ast).expect("got a compiler error");
+        let error = VirtualMachine::new(byte_code).run().err().unwrap();
+        assert_eq!(
+            error.span.map(|span| span.start),
+            Some(Position::new(line, column)),
+            "{input}"
+        );
+    }
+
+    let input = "let g = fn() { throw \"x\"; };
+let f = fn() { try { g() } finally { 1 } };
+try { f() } finally { 2 }";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let byte_code = Compiler::new().compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(
+        error.error,
+        VmError::Thrown(Rc::new(Object::String(String::from("x"))))
+    );
+    let functions = error
+        .trace
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(functions, vec!["g", "f", "<main>"]);
+}
+
+#[test]
+fn test_tail_calls_reuse_frames() {
+    let inputs = [
+        "let sum = fn(n, acc) { if (n == 0) { acc } else { sum(n - 1, acc + n) } }; sum(5000, 0)",
+        "let sum = fn(n, acc) { if (n == 0) { return acc; }; return sum(n - 1, acc + n); }; sum(5000, 0)",
+        "let sum = fn(n, acc = 0) { match (n) { 0 => acc, _ => sum(n - 1, acc + n) } }; sum(5000)",
+        "let odd = fn(n, even) { if (n == 0) { false } else { even(n - 1) } };
+let even = fn(n) { if (n == 0) { true } else { odd(n - 1, even) } };
+if (even(5001)) { 0 } else { 12502500 }",
+    ];
+    for input in inputs {
+        let (result, error) = compile_and_run(input);
+        assert_eq!(error, None, "{input}");
+        assert_eq!(result, Some(Rc::new(Object::Integer(12502500))), "{input}");
+    }
+}
+
+#[test]
+fn test_tail_calls_run_in_constant_frame_space() {
+    let input = "let count = fn(n) { if (n == 0) { -true } else { count(n - 1) } };
+count(5000);";
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new();
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run().err().unwrap();
+    assert_eq!(error.error, VmError::InvalidOperand("-", "BOOLEAN"));
+    let functions = error
+        .trace
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(functions, vec!["count", "<main>"]);
+}
+
+#[test]
+fn test_calls_in_try_are_not_tail_calls() {
+    let input = r#"let fail = fn() { throw "failed"; };
+let f = fn() { try { return fail(); } catch (e) { "caught " + e } };
+let g = fn(n) { if (n == 0) { fail() } else { g(n - 1) } };
+[f(), try { g(5000) } catch (e) { e }]"#;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::String(String::from("caught failed"))),
+        Rc::new(Object::String(String::from("failed"))),
+    ]));
+    let (result, error) = compile_and_run(input);
+    assert_eq!(error, None);
+    assert_eq!(result, Some(expected));
+}
+
+/// Builtins a host registers: `count`, which counts its calls in `calls`, and
+/// `double` and `fail`, in the namespace `host`.
+fn host_builtins(calls: &Rc<Cell<i64>>) -> Rc<Builtins> {
+    let mut builtins = Builtins::new();
+    let counter = Rc::clone(calls);
+    builtins
+        .register("count", Arity::Exactly(0), move |_| {
+            counter.set(counter.get() + 1);
+            Ok(Rc::new(Object::Integer(counter.get())))
+        })
+        .unwrap();
+    builtins
+        .register_in("host", "double", Arity::Exactly(1), |args| {
+            match &*args[0] {
+                Object::Integer(int) => Ok(Rc::new(Object::Integer(int * 2))),
+                _ => Err(BuiltinError::IncompatibleTypes),
+            }
+        })
+        .unwrap();
+    builtins
+        .register_in("host", "fail", Arity::AtLeast(0), |_| {
+            let error = ErrorObject::new("HostError", "failed");
+            Err(BuiltinError::Thrown(Rc::new(Object::Error(error))))
+        })
+        .unwrap();
+    Rc::new(builtins)
+}
+
+#[test]
+fn test_host_builtins() {
+    let input = r#"let a = count();
+count();
+[a, count(), host["double"](21), try { host["fail"]() } catch (e) { e["kind"] }, len("ab")]"#;
+    let expected = Rc::new(Object::Array(vec![
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Integer(3)),
+        Rc::new(Object::Integer(42)),
+        Rc::new(Object::String(String::from("HostError"))),
+        Rc::new(Object::Integer(2)),
+    ]));
+    let calls = Rc::new(Cell::new(0));
+    let builtins = host_builtins(&calls);
+    let ast = Parser::parse_program(input).expect("got a parsing error");
+    let mut compiler = Compiler::new_with_builtins(Rc::clone(&builtins));
+    let byte_code = compiler.compile(ast).expect("got a compiler error");
+    let result = VirtualMachine::new_with_builtins(byte_code, builtins).run();
+    assert_eq!(result.map_err(|e| e.error), Ok(expected));
+    assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn test_host_builtins_errors() {
+    let tests = [
+        ("count(1)", VmError::WrongArguments),
+        (r#"host["double"]("a")"#, VmError::IncompatibleTypes),
+        (r#"host["double"]()"#, VmError::WrongArguments),
+    ];
+    for (input, expected) in tests {
+        let builtins = host_builtins(&Rc::new(Cell::new(0)));
+        let ast = Parser::parse_program(input).expect("got a parsing error");
+        let mut compiler = Compiler::new_with_builtins(Rc::clone(&builtins));
+        let byte_code = compiler.compile(ast).expect("got a compiler error");
+        let error = VirtualMachine::new_with_builtins(byte_code, builtins).run();
+        assert_eq!(error.map_err(|e| e.error), Err(expected), "{input}");
+    }
+}
+
+#[test]
+fn test_builtin_missing_from_registry() {
+    // compiled with the host's builtins, but run with the standard ones
+    let builtins = host_builtins(&Rc::new(Cell::new(0)));
+    let ast = Parser::parse_program("count()").expect("got a parsing error");
+    let byte_code = Compiler::new_with_builtins(builtins)
+        .compile(ast)
+        .expect("got a compiler error");
+    let error = VirtualMachine::new(byte_code).run();
+    assert_eq!(error.map_err(|e| e.error), Err(VmError::UnknownBuiltin(7)));
+}
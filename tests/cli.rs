@@ -0,0 +1,41 @@
+//! Runs the interpreter's command the way a user does, to check what only the
+//! process shows: its exit code and what it prints.
+
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-monkey"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .expect("cannot run the interpreter")
+}
+
+#[test]
+fn test_deep_recursion_with_evaluator() {
+    let recursion = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+
+    let output = run(&["--engine=eval", "-e", &format!("{recursion} f(1000)")]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000\n");
+
+    let output = run(&["--engine=eval", "-e", &format!("{recursion} f(5000)")]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.starts_with("runtime error: maximum call depth exceeded"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("hint: this is usually caused by unbounded recursion"),
+        "{stderr}"
+    );
+
+    let caught = format!(r#"{recursion} try {{ f(5000) }} catch (e) {{ e["kind"] }}"#);
+    let output = run(&["--engine=eval", "-e", &caught]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "StackOverflowError\n"
+    );
+}